@@ -0,0 +1,60 @@
+use std::{collections::HashMap, io::Write, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use log::info;
+use reqwest::blocking::Client;
+use serde_json::{json, Value};
+
+/// 令牌文件，按网关地址保存配对得到的令牌
+fn token_file() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME")?;
+    Some(PathBuf::from(home).join(".config/clock-admin-cli/tokens.json"))
+}
+
+fn load_tokens() -> HashMap<String, String> {
+    token_file()
+        .and_then(|x| std::fs::read(x).ok())
+        .and_then(|x| serde_json::from_slice(&x).ok())
+        .unwrap_or_default()
+}
+
+/// 依次使用环境变量CLOCK_TOKEN与令牌文件中保存的令牌
+pub fn load_token(url: &str) -> Option<String> {
+    std::env::var("CLOCK_TOKEN")
+        .ok()
+        .or_else(|| load_tokens().remove(url))
+}
+
+fn save_token(url: &str, token: &str) -> Result<()> {
+    let file = token_file().ok_or(anyhow!("HOME is not set"))?;
+    let mut tokens = load_tokens();
+    tokens.insert(url.into(), token.into());
+    std::fs::create_dir_all(file.parent().unwrap())?;
+    std::fs::write(&file, serde_json::to_vec_pretty(&tokens)?)?;
+    info!("token saved to {}", file.display());
+    Ok(())
+}
+
+/// 请求设备显示配对码，输入配对码后保存令牌
+pub fn pair(url: &str) -> Result<()> {
+    let client = Client::new();
+    let resp = client.post(format!("{url}/pair")).json(&json!({})).send()?;
+    if resp.status().as_u16() != 202 {
+        return Err(anyhow!("pairing request failed: {}", resp.text()?));
+    }
+    print!("pairing code shown on the device: ");
+    std::io::stdout().flush()?;
+    let mut code = String::new();
+    std::io::stdin().read_line(&mut code)?;
+
+    let resp = client
+        .post(format!("{url}/pair"))
+        .json(&json!({"code": code.trim(), "name": "admin-cli"}))
+        .send()?;
+    let status = resp.status().as_u16();
+    let body: Value = resp.json()?;
+    match body["token"].as_str() {
+        Some(token) if status == 200 => save_token(url, token),
+        _ => Err(anyhow!("pairing failed: {}", body["error"])),
+    }
+}
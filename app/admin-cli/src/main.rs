@@ -2,10 +2,10 @@ use std::rc::Rc;
 
 use clap::Parser;
 use log::info;
-use proto::*;
+use proto::{gateway::node_path_name, *};
 use reqwest::blocking::Client;
-use serde_json::json;
 
+mod auth;
 mod subcmds;
 
 #[derive(Parser)]
@@ -14,7 +14,7 @@ struct AdminCli {
     #[clap(long, short = 'D')]
     debug: bool,
 
-    /// url
+    /// 网关地址，如http://127.0.0.1:38080
     #[clap(long)]
     url: Option<String>,
 
@@ -26,23 +26,43 @@ struct AdminCli {
 struct ContextImpl {
     client: Client,
     url: String,
+    token: Option<String>,
 }
 
 impl ContextImpl {
     fn new(url: String) -> Self {
         let client = Client::new();
-        Self { client, url }
+        let token = auth::load_token(&url);
+        Self { client, url, token }
     }
 
     fn send_message(&self, to: MessageTo, body: Message) -> HandleResult {
-        let msg = json!({
-            "to": to,
-            "body": body,
-        });
-        info!("send msg: {}", msg);
-        let req = self.client.post(&self.url).json(&msg).build().unwrap();
+        let path = match &to {
+            MessageTo::Broadcast => "broadcast".into(),
+            MessageTo::Topic(x) => format!("topics/{x:?}"),
+            MessageTo::Point(x) => format!("nodes/{}", node_path_name(x)),
+        };
+        info!(
+            "send msg to {path}: {}",
+            serde_json::to_string(&body).unwrap()
+        );
+        let mut req = self.client.post(format!("{}/{path}", self.url)).json(&body);
+        if let Some(token) = &self.token {
+            req = req.bearer_auth(token);
+        }
+        let req = req.build().unwrap();
         let resp = self.client.execute(req).unwrap();
-        resp.json().unwrap()
+        match resp.status().as_u16() {
+            200 => HandleResult::Finish(resp.json().unwrap()),
+            202 => HandleResult::Finish(Message::Empty),
+            // 节点没有处理该消息
+            422 => HandleResult::Discard,
+            401 => panic!("not paired with {}, run `admin-cli pair` first", self.url),
+            status => panic!(
+                "gateway error {status}: {}",
+                resp.text().unwrap_or_default()
+            ),
+        }
     }
 }
 
@@ -67,6 +87,10 @@ impl Context for ContextImpl {
         unimplemented!()
     }
 
+    fn async_discard(&self, _seq: usize) {
+        unimplemented!()
+    }
+
     fn subscribe_topic(&self, _topic: TopicName) {
         unimplemented!()
     }
@@ -78,6 +102,10 @@ impl Context for ContextImpl {
     fn create_wait_group(&self) -> Rc<dyn WaitGroup> {
         unimplemented!()
     }
+
+    fn node_names(&self) -> Vec<NodeName> {
+        unimplemented!()
+    }
 }
 
 fn main() -> anyhow::Result<()> {
@@ -88,11 +116,20 @@ fn main() -> anyhow::Result<()> {
         std::env::set_var("RUST_LOG", "debug");
     }
 
-    let ctx = Rc::new(ContextImpl::new(
-        cli.url
-            .unwrap_or_else(|| std::env::var("CLOCK_URL").expect("no url")),
-    ));
+    let url = cli
+        .url
+        .unwrap_or_else(|| std::env::var("CLOCK_URL").expect("no url"))
+        .trim_end_matches('/')
+        .to_string();
     env_logger::init();
+    match cli.subcmd {
+        subcmds::SubCommands::Pair => return auth::pair(&url),
+        subcmds::SubCommands::Watch { topics, broadcast } => {
+            return subcmds::watch::run(&url, auth::load_token(&url), topics, broadcast)
+        }
+        _ => {}
+    }
+    let ctx = Rc::new(ContextImpl::new(url));
     cli.subcmd.run(ctx)?;
     anyhow::Ok(())
 }
@@ -1,4 +1,5 @@
 use std::{
+    cell::RefCell,
     io::{stdin, Read, Stdin},
     process::Stdio,
     rc::Rc,
@@ -13,9 +14,11 @@ use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use log::{debug, info};
-use proto::ipc::{StorageClient, SystemClient, WeatherClient};
-use proto::storage::{MusicStorage, WeatherStorage, WiFiStorage};
+use log::{debug, info, warn};
+use proto::ipc::{MidiPlayerClient, StorageClient, SystemClient, WeatherClient};
+use proto::storage::{
+    BackupArchive, MusicStorage, StorageBackup, SystemStorage, WeatherStorage, WiFiStorage,
+};
 use proto::*;
 use tui::{
     backend::CrosstermBackend,
@@ -24,6 +27,7 @@ use tui::{
 };
 
 mod onebutton;
+pub mod watch;
 
 #[derive(Subcommand)]
 pub enum SubCommands {
@@ -31,10 +35,37 @@ pub enum SubCommands {
         #[arg()]
         prefix: Option<String>,
     },
+    /// 显示存储容量以及各命名空间的用量与配额
+    StorageStats,
+    /// 删除给定前缀下的所有key
+    StorageDelete {
+        prefix: String,
+    },
     MusicList,
     MusicUpload {
         mid_file: String,
     },
+    /// 由设备下载MIDI文件，未指定名称时使用URL中的文件名
+    MusicDownload {
+        url: String,
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// 导出设备的全部存储到文件，网关令牌与HTTP缓存不导出
+    Backup {
+        file: String,
+        /// 同时导出解密后的WiFi密码与天气API key，备份文件中为明文
+        #[arg(long)]
+        include_secrets: bool,
+    },
+    /// 从备份文件恢复设备的存储。默认只写入备份中的key，设备上多出的key保持不变；
+    /// 备份中的secret以本设备的密钥重新加密保存
+    Restore {
+        file: String,
+        /// 删除备份中没有的key，使设备与备份一致
+        #[arg(long)]
+        clean: bool,
+    },
     WifiConfig {
         #[arg()]
         ssid: String,
@@ -47,9 +78,49 @@ pub enum SubCommands {
     WeatherSearch {
         query: String,
     },
+    /// 设置首选位置，不在列表中时添加
     WeatherSetLocation {
         location_id: u32,
         location: String,
+        /// 纬度，不设置时首次计算日出日落前通过数据源查询
+        #[arg(long, requires = "lon", allow_hyphen_values = true)]
+        lat: Option<f32>,
+        #[arg(long, requires = "lat", allow_hyphen_values = true)]
+        lon: Option<f32>,
+    },
+    WeatherListLocations,
+    WeatherAddLocation {
+        location_id: u32,
+        location: String,
+        #[arg(long, requires = "lon", allow_hyphen_values = true)]
+        lat: Option<f32>,
+        #[arg(long, requires = "lat", allow_hyphen_values = true)]
+        lon: Option<f32>,
+    },
+    WeatherRemoveLocation {
+        location_id: u32,
+    },
+    /// 调整位置顺序，index为0时设为首选位置
+    WeatherMoveLocation {
+        location_id: u32,
+        index: usize,
+    },
+    /// 首页轮播位置的间隔秒数，0表示只通过长按切换
+    WeatherSetRotation {
+        seconds: u32,
+    },
+    /// 切换天气数据源：qweather、open-meteo或server服务地址，切换后需重新设置位置
+    WeatherSetProvider {
+        provider: String,
+    },
+    /// 显示设置，未指定的项保持不变：温度单位c或f、12或24小时制、语言zh或en
+    DisplaySet {
+        #[arg(long)]
+        unit: Option<String>,
+        #[arg(long)]
+        clock: Option<u8>,
+        #[arg(long)]
+        lang: Option<String>,
     },
     MonitorEnable {
         #[arg()]
@@ -65,6 +136,15 @@ pub enum SubCommands {
     PlayDefaultAlarm,
     AddUserAlarm,
     ListUserAlarm,
+    /// 与设备配对，输入屏幕上显示的配对码后保存令牌
+    Pair,
+    /// 通过WebSocket实时查看话题消息，未指定话题时订阅常用话题
+    Watch {
+        topics: Vec<String>,
+        /// 同时显示全局广播
+        #[clap(long, short)]
+        broadcast: bool,
+    },
 }
 
 impl SubCommands {
@@ -74,10 +154,59 @@ impl SubCommands {
                 let stg = ipc::StorageClient(ctx);
                 let keys = stg.list(prefix.unwrap_or_default()).unwrap();
                 for ref k in keys.into_iter() {
+                    if k.starts_with("secret/") {
+                        println!("{k}\t<redacted>");
+                        continue;
+                    }
                     let v = stg.get(k.into()).unwrap();
                     println!("{k}\t{v:?}");
                 }
             }
+            SubCommands::StorageStats => {
+                let stg = ipc::StorageClient(ctx);
+                let cap = stg.capacity().map_err(|e| anyhow::anyhow!("{e:?}"))?;
+                let fmt = |x: Option<usize>| x.map(|x| x.to_string()).unwrap_or("-".into());
+                println!(
+                    "total: {}\tused: {}\tfree: {}",
+                    fmt(cap.total),
+                    cap.used,
+                    fmt(cap.free)
+                );
+                // 按第一段路径分组统计
+                let mut namespaces: Vec<String> = stg
+                    .list(String::new())
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))?
+                    .into_iter()
+                    .map(|k| match k.find('/') {
+                        Some(i) => k[..=i].to_string(),
+                        None => k,
+                    })
+                    .collect::<std::collections::HashSet<_>>()
+                    .into_iter()
+                    .collect();
+                namespaces.sort();
+                for ns in namespaces {
+                    let usage = stg
+                        .usage(ns.clone())
+                        .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+                    let quota = STORAGE_QUOTAS
+                        .iter()
+                        .find(|(x, _)| *x == ns)
+                        .map(|(_, q)| *q);
+                    println!(
+                        "{ns}\tkeys: {}\tbytes: {}\tquota: {}",
+                        usage.keys,
+                        usage.bytes,
+                        fmt(quota)
+                    );
+                }
+            }
+            SubCommands::StorageDelete { prefix } => {
+                let n = StorageClient(ctx)
+                    .delete_prefix(prefix.clone())
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+                info!("deleted {n} keys under {prefix:?}");
+            }
             SubCommands::MusicUpload { mid_file } => {
                 let mut f = std::fs::File::open(&mid_file).unwrap();
                 let mut buffer = Vec::new();
@@ -85,6 +214,70 @@ impl SubCommands {
                 MusicStorage(StorageClient(ctx)).upload(mid_file, buffer);
                 info!("upload done");
             }
+            SubCommands::MusicDownload { url, name } => {
+                let name = match name {
+                    Some(x) => x,
+                    None => url
+                        .split(['?', '#'])
+                        .next()
+                        .and_then(|x| x.rsplit('/').next())
+                        .filter(|x| !x.is_empty())
+                        .ok_or_else(|| anyhow::anyhow!("no filename in {url}, use --name"))?
+                        .to_string(),
+                };
+                let result = Rc::new(RefCell::new(None));
+                MidiPlayerClient(ctx).download(
+                    name.clone(),
+                    url,
+                    Box::new({
+                        let result = result.clone();
+                        move |r| *result.borrow_mut() = Some(r)
+                    }),
+                );
+                match result.take() {
+                    Some(Ok(())) => info!("downloaded {name}"),
+                    Some(Err(e)) => anyhow::bail!("download error: {e:?}"),
+                    None => anyhow::bail!("no response from device"),
+                }
+            }
+            SubCommands::Backup {
+                file,
+                include_secrets,
+            } => {
+                let backup = StorageBackup(StorageClient(ctx));
+                let archive = if include_secrets {
+                    backup.dump_with_secrets()
+                } else {
+                    backup.dump()
+                }
+                .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+                let f = std::fs::File::create(&file)?;
+                serde_json::to_writer_pretty(f, &archive)?;
+                info!(
+                    "backup {} keys and {} secrets to {file}",
+                    archive.entries.len(),
+                    archive.secrets.len()
+                );
+                info!("skipped: gateway tokens (gateway/auth/), HTTP cache (http_cache/)");
+                if include_secrets {
+                    warn!("{file} contains plaintext secrets, keep it safe");
+                } else {
+                    warn!("skipped: secrets (WiFi password, weather API key), use --include-secrets to export them");
+                }
+            }
+            SubCommands::Restore { file, clean } => {
+                let f = std::fs::File::open(&file)?;
+                let archive: BackupArchive = serde_json::from_reader(f)?;
+                let (n, secrets) = (archive.entries.len(), archive.secrets.len());
+                let backup = StorageBackup(StorageClient(ctx));
+                if clean {
+                    backup.restore_clean(archive)
+                } else {
+                    backup.restore(archive)
+                }
+                .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+                info!("restore {n} keys and {secrets} secrets from {file}");
+            }
             SubCommands::MusicList => {
                 let list = MusicStorage(StorageClient(ctx)).get_list();
                 for e in list.into_iter() {
@@ -160,11 +353,112 @@ impl SubCommands {
             SubCommands::WeatherSetLocation {
                 location_id,
                 location,
+                lat,
+                lon,
             } => {
                 WeatherStorage(StorageClient(ctx))
-                    .set_location(location_id, location)
+                    .set_location(Location {
+                        location_id,
+                        location,
+                        coord: lat.zip(lon).map(|(lat, lon)| Coord { lat, lon }),
+                    })
                     .unwrap();
             }
+            SubCommands::WeatherListLocations => {
+                let locations = WeatherClient(ctx)
+                    .list_locations()
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+                for (i, x) in locations.into_iter().enumerate() {
+                    match x.coord {
+                        Some(c) => println!(
+                            "{i}: {} {} ({}, {})",
+                            x.location_id, x.location, c.lat, c.lon
+                        ),
+                        None => println!("{i}: {} {}", x.location_id, x.location),
+                    }
+                }
+            }
+            SubCommands::WeatherAddLocation {
+                location_id,
+                location,
+                lat,
+                lon,
+            } => {
+                WeatherClient(ctx)
+                    .add_location(Location {
+                        location_id,
+                        location,
+                        coord: lat.zip(lon).map(|(lat, lon)| Coord { lat, lon }),
+                    })
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+            }
+            SubCommands::WeatherRemoveLocation { location_id } => {
+                WeatherClient(ctx)
+                    .remove_location(location_id)
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+            }
+            SubCommands::WeatherMoveLocation { location_id, index } => {
+                WeatherClient(ctx)
+                    .move_location(location_id, index)
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+            }
+            SubCommands::WeatherSetRotation { seconds } => {
+                WeatherStorage(StorageClient(ctx))
+                    .set_rotation_interval(seconds)
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+            }
+            SubCommands::WeatherSetProvider { provider } => {
+                let provider = match provider.as_str() {
+                    "qweather" => WeatherProviderConfig::QWeather,
+                    "open-meteo" => WeatherProviderConfig::OpenMeteo,
+                    x if x.starts_with("http://") || x.starts_with("https://") => {
+                        WeatherProviderConfig::Server(x.trim_end_matches('/').into())
+                    }
+                    x => anyhow::bail!("unknown weather provider {x}"),
+                };
+                let stg = WeatherStorage(StorageClient(ctx));
+                let had_locations = !stg
+                    .get_locations()
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))?
+                    .is_empty();
+                stg.set_provider(provider)
+                    .map_err(|e| anyhow::anyhow!("{e:?}"))?;
+                if had_locations
+                    && stg
+                        .get_locations()
+                        .map_err(|e| anyhow::anyhow!("{e:?}"))?
+                        .is_empty()
+                {
+                    warn!("location ids differ between providers, saved locations were cleared");
+                }
+            }
+            SubCommands::DisplaySet { unit, clock, lang } => {
+                let stg = SystemStorage(StorageClient(ctx));
+                let mut settings = stg.get_display_settings();
+                if let Some(x) = unit {
+                    settings.temp_unit = match x.as_str() {
+                        "c" => TempUnit::Celsius,
+                        "f" => TempUnit::Fahrenheit,
+                        x => anyhow::bail!("unknown temperature unit {x}"),
+                    };
+                }
+                if let Some(x) = clock {
+                    settings.clock = match x {
+                        12 => ClockFormat::H12,
+                        24 => ClockFormat::H24,
+                        x => anyhow::bail!("unknown clock format {x}"),
+                    };
+                }
+                if let Some(x) = lang {
+                    settings.lang = match x.as_str() {
+                        "zh" => Language::Zh,
+                        "en" => Language::En,
+                        x => anyhow::bail!("unknown language {x}"),
+                    };
+                }
+                stg.set_display_settings(&settings);
+                println!("{settings:?}");
+            }
             SubCommands::AlertDialog { text } => ctx.async_call(
                 NodeName::Notifaction,
                 Message::Notifaction(NotifactionMessage::ShowRequest {
@@ -226,6 +520,8 @@ impl SubCommands {
                     println!("{}\t{:?}", id, body)
                 }
             }
+            // 需要网关地址，在main中处理
+            SubCommands::Pair | SubCommands::Watch { .. } => unreachable!(),
         }
         anyhow::Ok(())
     }
@@ -0,0 +1,68 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use proto::{
+    gateway::{GatewayClientFrame, GatewayServerFrame},
+    TopicName,
+};
+use tungstenite::Message as WsMessage;
+
+/// 未指定话题时默认订阅的话题
+const DEFAULT_TOPICS: [TopicName; 5] = [
+    TopicName::OneButton,
+    TopicName::Sntp,
+    TopicName::WiFi,
+    TopicName::Connectivity,
+    TopicName::Sensor,
+];
+
+fn parse_topic(name: &str) -> Result<TopicName> {
+    serde_json::from_value(serde_json::Value::String(name.into()))
+        .map_err(|_| anyhow!("unknown topic {name}"))
+}
+
+/// 连接网关的WebSocket事件流，打印收到的话题消息与广播
+pub fn run(url: &str, token: Option<String>, topics: Vec<String>, broadcast: bool) -> Result<()> {
+    let token = token.ok_or(anyhow!("not paired with {url}, run `admin-cli pair` first"))?;
+    let topics = if topics.is_empty() {
+        DEFAULT_TOPICS.to_vec()
+    } else {
+        topics
+            .iter()
+            .map(|x| parse_topic(x))
+            .collect::<Result<Vec<_>>>()?
+    };
+    let url = format!(
+        "{}/ws",
+        url.replacen("https://", "wss://", 1)
+            .replacen("http://", "ws://", 1)
+    );
+    let (mut ws, _) = tungstenite::connect(&url)?;
+    info!("connected to {url}, watching {topics:?}");
+    for frame in [
+        GatewayClientFrame::Auth(token),
+        GatewayClientFrame::Subscribe(topics),
+        GatewayClientFrame::WatchBroadcast(broadcast),
+    ] {
+        ws.send(WsMessage::Text(serde_json::to_string(&frame)?))?;
+    }
+    loop {
+        let text = match ws.read()? {
+            WsMessage::Text(x) => x,
+            WsMessage::Close(_) => return Ok(()),
+            _ => continue,
+        };
+        match serde_json::from_str::<GatewayServerFrame>(&text) {
+            Ok(GatewayServerFrame::Topic { topic, from, body }) => {
+                println!("[{topic:?}] {from:?}: {body:?}")
+            }
+            Ok(GatewayServerFrame::Broadcast { from, body }) => {
+                println!("[Broadcast] {from:?}: {body:?}")
+            }
+            Ok(GatewayServerFrame::Error { error, .. }) => eprintln!("gateway error: {error}"),
+            Ok(GatewayServerFrame::Unauthorized(error)) => {
+                return Err(anyhow!("gateway unauthorized: {error}"))
+            }
+            _ => println!("{text}"),
+        }
+    }
+}
@@ -1,5 +1,18 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
 use slint_build::CompilerConfiguration;
 
+/// 管理后台的构建产物目录，需先在vue-console中执行pnpm build
+const CONSOLE_DIST: &str = "../../vue-console/dist";
+
+/// 嵌入固件的管理后台压缩后的总大小上限，超出时构建失败
+const CONSOLE_SIZE_BUDGET: usize = 192 * 1024;
+
 fn build_slint() -> Result<(), Box<dyn std::error::Error>> {
     cfg_if::cfg_if! {
         if #[cfg(feature = "software-renderer")] {
@@ -14,7 +27,80 @@ fn build_slint() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("js") => "text/javascript",
+        Some("css") => "text/css",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("ico") => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
+
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            list_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// 压缩vue-console/dist中的文件并生成CONSOLE_ASSETS，未启用web-console特性或没有构建产物时为空
+fn build_console() -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR")?);
+    let dist = Path::new(CONSOLE_DIST);
+    println!("cargo:rerun-if-changed={CONSOLE_DIST}");
+
+    let mut files = Vec::new();
+    if std::env::var("CARGO_FEATURE_WEB_CONSOLE").is_ok() {
+        if dist.join("index.html").exists() {
+            list_files(dist, &mut files)?;
+        } else {
+            println!("cargo:warning=web console not found in {CONSOLE_DIST}, run pnpm build in vue-console first");
+        }
+    }
+    files.sort();
+
+    let mut total = 0;
+    let mut code = String::from("&[\n");
+    for (i, file) in files.iter().enumerate() {
+        let mut gz = GzEncoder::new(Vec::new(), Compression::best());
+        gz.write_all(&fs::read(file)?)?;
+        let gz = gz.finish()?;
+        total += gz.len();
+
+        let out = out_dir.join(format!("console_{i}.gz"));
+        fs::write(&out, gz)?;
+        let path = file
+            .strip_prefix(dist)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        code += &format!(
+            "    proto::gateway::StaticAsset {{ path: \"/{path}\", content_type: \"{}\", gzip: include_bytes!({:?}) }},\n",
+            content_type(file),
+            out,
+        );
+    }
+    code += "]\n";
+    if total > CONSOLE_SIZE_BUDGET {
+        return Err(format!(
+            "web console is {total} bytes after compression, over the budget of {CONSOLE_SIZE_BUDGET} bytes"
+        )
+        .into());
+    }
+    fs::write(out_dir.join("console_assets.rs"), code)?;
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     build_slint()?;
+    build_console()?;
     Ok(())
 }
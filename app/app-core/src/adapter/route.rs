@@ -7,6 +7,8 @@ pub fn proto_route_table_to_slint_route_table(r: RoutePage) -> PageRouteTable {
         RoutePage::Menu => PageRouteTable::Menu,
         RoutePage::Weather => PageRouteTable::Weather,
         RoutePage::Music => PageRouteTable::Music,
+        RoutePage::AirQuality => PageRouteTable::AirQuality,
+        RoutePage::Indoor => PageRouteTable::Indoor,
     }
 }
 
@@ -17,5 +19,7 @@ pub fn slint_route_table_to_proto_route_table(r: PageRouteTable) -> RoutePage {
         PageRouteTable::Menu => RoutePage::Menu,
         PageRouteTable::Weather => RoutePage::Weather,
         PageRouteTable::Music => RoutePage::Music,
+        PageRouteTable::AirQuality => RoutePage::AirQuality,
+        PageRouteTable::Indoor => RoutePage::Indoor,
     }
 }
@@ -7,11 +7,16 @@ mod node;
 mod scheduler;
 mod ui;
 
+pub use node::{MockSensorService, SecretService};
 pub use proto;
 pub use proto::storage;
 pub use scheduler::Scheduler;
 pub use ui::get_app_window;
 
+/// 嵌入的管理后台静态文件，由build.rs生成
+pub static CONSOLE_ASSETS: &[proto::gateway::StaticAsset] =
+    include!(concat!(env!("OUT_DIR"), "/console_assets.rs"));
+
 static mut SCHEDULER: Option<Rc<Scheduler>> = None;
 
 pub fn get_scheduler() -> Rc<Scheduler> {
@@ -33,11 +38,16 @@ fn register_default_nodes(sche: &Scheduler) {
     sche.register_node(BootPage::new());
     sche.register_node(AlertDialog::new());
     sche.register_node(MusicPage::new());
+    sche.register_node(AirQualityPage::new());
+    sche.register_node(IndoorPage::new());
 
     sche.register_node(RouterService::new());
     sche.register_node(TouchOneButtonAdapterService::new());
     sche.register_node(WeatherService::new());
+    sche.register_node(SensorService::new());
     sche.register_node(MockStorageService::new());
+    sche.register_node(StorageMigrationService::new());
+    // SecretService需要设备密钥，由各平台注册，未注册时读写密钥会panic
     sche.register_node(MockSystemService {});
     sche.register_node(TimerService::new());
 
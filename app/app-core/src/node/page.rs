@@ -1,7 +1,12 @@
+mod air_quality;
 mod boot;
 mod home;
+mod indoor;
 mod menu;
 mod music;
 mod weather;
 
-pub use {boot::BootPage, home::HomePage, menu::MenuPage, music::MusicPage, weather::WeatherPage};
+pub use {
+    air_quality::AirQualityPage, boot::BootPage, home::HomePage, indoor::IndoorPage,
+    menu::MenuPage, music::MusicPage, weather::WeatherPage,
+};
@@ -0,0 +1,222 @@
+use std::{cell::Cell, rc::Rc};
+
+use ipc::WeatherClient;
+use log::error;
+use slint::{Color, ComponentHandle, ModelRc, VecModel};
+
+use crate::{proto::*, storage::SystemStorage, ui};
+
+/// 污染物、健康建议与预报
+const PAGES: i32 = 3;
+
+/// 预报所在的页
+const FORECAST_PAGE: i32 = 2;
+
+/// 条形图满格对应的AQI
+const BAR_MAX: f32 = 300.0;
+
+/// 按AQI分级的颜色，中国与美国标准的分级界限相同
+fn aqi_color(value: u16) -> Color {
+    let (r, g, b) = match value {
+        0..=50 => (0, 228, 0),
+        51..=100 => (255, 255, 0),
+        101..=150 => (255, 126, 0),
+        151..=200 => (255, 0, 0),
+        201..=300 => (143, 63, 151),
+        _ => (126, 0, 35),
+    };
+    Color::from_rgb_u8(r, g, b)
+}
+
+fn bar_ratio(value: u16) -> f32 {
+    (value as f32 / BAR_MAX).min(1.0)
+}
+
+pub struct AirQualityPage {
+    page: Cell<i32>,
+    // 显示页面时读取的显示设置
+    display: Cell<DisplaySettings>,
+}
+
+impl AirQualityPage {
+    pub fn new() -> Self {
+        Self {
+            page: Cell::new(0),
+            display: Default::default(),
+        }
+    }
+
+    fn set_page(&self, page: i32) {
+        self.page.set(page);
+        if let Some(ui) = ui::get_app_window().upgrade() {
+            ui.global::<ui::AirQualityViewModel>().set_page(page);
+        }
+    }
+
+    fn set_offline(offline: bool) {
+        if let Some(ui) = ui::get_app_window().upgrade() {
+            ui.global::<ui::AirQualityViewModel>().set_offline(offline);
+        }
+    }
+
+    fn update_now(ctx: Rc<dyn Context>) {
+        WeatherClient(ctx).get_now_air_quality(
+            None,
+            Box::new(|r| {
+                let x = match r {
+                    Ok(x) => x,
+                    Err(WeatherError::Offline) => {
+                        Self::set_offline(true);
+                        return;
+                    }
+                    Err(e) => {
+                        error!("get air quality error: {e:?}");
+                        return;
+                    }
+                };
+                let primary = x
+                    .primary_pollutant
+                    .as_ref()
+                    .and_then(|code| x.pollutants.iter().find(|p| &p.code == code))
+                    .map(|p| p.name.clone())
+                    .unwrap_or_default();
+                let pollutants = x
+                    .pollutants
+                    .iter()
+                    .map(|p| ui::PollutantViewModel {
+                        name: p.name.clone().into(),
+                        value: match p.sub_index {
+                            Some(i) => i.to_string(),
+                            None => format!("{:.0}", p.concentration),
+                        }
+                        .into(),
+                        ratio: p.sub_index.map(bar_ratio).unwrap_or_default(),
+                        color: aqi_color(p.sub_index.unwrap_or_default()),
+                        primary: x.primary_pollutant.as_ref() == Some(&p.code),
+                    })
+                    .collect::<Vec<_>>();
+                let health = x.health.unwrap_or_default();
+                if let Some(ui) = ui::get_app_window().upgrade() {
+                    let vm = ui.global::<ui::AirQualityViewModel>();
+                    let (r, g, b) = x.color;
+                    vm.set_value(x.value as _);
+                    vm.set_category(x.category.into());
+                    vm.set_color(Color::from_rgb_u8(r, g, b));
+                    vm.set_primary(primary.into());
+                    vm.set_pollutants(ModelRc::new(VecModel::from(pollutants)));
+                    vm.set_health(ui::AirQualityHealthViewModel {
+                        effect: health.effect.into(),
+                        general: health.general.into(),
+                        sensitive: health.sensitive.into(),
+                    });
+                }
+            }),
+        );
+    }
+
+    fn update_forecast(ctx: Rc<dyn Context>, display: DisplaySettings) {
+        WeatherClient(ctx).get_air_quality_forecast(
+            None,
+            Box::new(move |r| {
+                let w = match r {
+                    Ok(x) => x,
+                    // 数据源不支持时显示提示
+                    Err(WeatherError::Unsupported) => return,
+                    Err(e) => {
+                        error!("get air quality forecast error: {e:?}");
+                        return;
+                    }
+                };
+                let data = w
+                    .daily
+                    .into_iter()
+                    .take(5)
+                    .map(|x| {
+                        let (r, g, b) = x.color;
+                        ui::AirQualityDayViewModel {
+                            title: display.weekday(x.date.weekday()).into(),
+                            value: x.value as _,
+                            ratio: bar_ratio(x.value),
+                            color: Color::from_rgb_u8(r, g, b),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                if let Some(ui) = ui::get_app_window().upgrade() {
+                    let vm = ui.global::<ui::AirQualityViewModel>();
+                    vm.set_forecast(ModelRc::new(VecModel::from(data)));
+                }
+            }),
+        );
+    }
+}
+
+impl Node for AirQualityPage {
+    fn node_name(&self) -> NodeName {
+        NodeName::AirQualityPage
+    }
+
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        match msg.body {
+            Message::Http(HttpMessage::Connectivity(state)) => {
+                Self::set_offline(state == ConnectivityState::Offline);
+                return HandleResult::Finish(Message::Empty);
+            }
+            Message::OneButton(msg) => match msg {
+                OneButtonMessage::Click => {
+                    let page = (self.page.get() + 1) % PAGES;
+                    // 预报在翻到对应页时加载
+                    if page == FORECAST_PAGE {
+                        Self::update_forecast(ctx.clone(), self.display.get());
+                    }
+                    self.set_page(page);
+                    return HandleResult::Finish(Message::Empty);
+                }
+                OneButtonMessage::LongPressHolding(dur) => {
+                    if dur > 1000 {
+                        ctx.sync_call(
+                            NodeName::Router,
+                            Message::Router(RouterMessage::GotoPage(RoutePage::Home)),
+                        );
+                        return HandleResult::Finish(Message::Empty);
+                    }
+                }
+                _ => {}
+            },
+            Message::Lifecycle(msg) => match msg {
+                LifecycleMessage::Hide => {
+                    self.set_page(0);
+                    ctx.unsubscribe_topic(TopicName::OneButton);
+                    ctx.unsubscribe_topic(TopicName::Connectivity);
+                    if let Some(ui) = ui::get_app_window().upgrade() {
+                        let vm = ui.global::<ui::AirQualityViewModel>();
+                        vm.set_pollutants(Default::default());
+                        vm.set_forecast(Default::default());
+                        vm.set_health(Default::default());
+                        vm.set_value(0);
+                        vm.set_category(Default::default());
+                        vm.set_primary(Default::default());
+                        vm.set_offline(false);
+                    }
+                }
+                LifecycleMessage::Show => {
+                    ctx.subscribe_topic(TopicName::OneButton);
+                    ctx.subscribe_topic(TopicName::Connectivity);
+                    Self::set_offline(
+                        ipc::HttpClient(ctx.clone()).connectivity() == ConnectivityState::Offline,
+                    );
+                    let display =
+                        SystemStorage(ipc::StorageClient(ctx.clone())).get_display_settings();
+                    self.display.set(display);
+                    if let Some(ui) = ui::get_app_window().upgrade() {
+                        ui.global::<ui::AirQualityViewModel>()
+                            .set_zh(display.lang == Language::Zh);
+                    }
+                    Self::update_now(ctx);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        HandleResult::Discard
+    }
+}
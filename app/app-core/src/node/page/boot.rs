@@ -32,7 +32,8 @@ impl BootPage {
         if self.t.borrow().is_some() {
             return;
         }
-        let p = ipc::SystemClient(ctx);
+        let p = ipc::SystemClient(ctx.clone());
+        let stg = StorageClient(ctx);
         self.t
             .borrow_mut()
             .get_or_insert_with(slint::Timer::default)
@@ -46,6 +47,13 @@ impl BootPage {
                         vm.set_largest_free_block(p.get_largeest_free_block() as i32);
                         vm.set_memory(p.get_free_heap_size() as i32);
                         vm.set_fps(p.get_fps() as i32);
+                        if let Ok(cap) = stg.capacity() {
+                            let total = cap
+                                .total
+                                .map(|x| format!("{}K", x / 1024))
+                                .unwrap_or("-".into());
+                            vm.set_storage(format!("{}K/{total}", cap.used / 1024).into());
+                        }
                     }
                 },
             );
@@ -1,19 +1,36 @@
 use crate::get_app_window;
 use crate::proto::*;
+use crate::storage::{SystemStorage, WeatherStorage};
 use crate::ui;
 use log::error;
 use log::info;
 use proto::TopicName;
 use slint::Color;
 use slint::ComponentHandle;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::fmt::Debug;
 use std::{rc::Rc, time::Duration};
 use time::{OffsetDateTime, UtcOffset};
 
+/// 天气数据超过该时长未更新时显示过期标记
+const STALE_AFTER: Duration = Duration::from_secs(60 * 30);
+
+/// 天气区域的状态，在定时器与回调间共享
+#[derive(Default)]
+struct WeatherState {
+    // 当前显示的位置在位置列表中的序号
+    location_index: Cell<usize>,
+    // 本次显示首页期间已提示过错误，避免每次刷新都弹出
+    error_shown: Cell<bool>,
+    // 显示首页时读取的显示设置
+    display: Cell<DisplaySettings>,
+}
+
 pub struct HomePage {
     time_update_timer: RefCell<Option<slint::Timer>>,
     weather_update_timer: RefCell<Option<slint::Timer>>,
+    rotation_timer: RefCell<Option<slint::Timer>>,
+    state: Rc<WeatherState>,
 }
 
 impl HomePage {
@@ -21,15 +38,32 @@ impl HomePage {
         Self {
             time_update_timer: RefCell::new(None),
             weather_update_timer: RefCell::new(None),
+            rotation_timer: RefCell::new(None),
+            state: Default::default(),
         }
     }
 }
 
 impl HomePage {
-    fn update_time() {
+    fn update_time(display: DisplaySettings) {
         let t = OffsetDateTime::now_utc().to_offset(UtcOffset::from_hms(8, 0, 0).unwrap());
         if let Some(ui) = get_app_window().upgrade() {
             let home_app = ui.global::<ui::HomeViewModel>();
+            home_app.set_time_text(
+                display
+                    .format_time_seconds(t.hour(), t.minute(), t.second())
+                    .into(),
+            );
+            home_app.set_date_text(
+                format!(
+                    "{}-{:0>2}-{:0>2}  {}",
+                    t.year(),
+                    t.month() as u8,
+                    t.day(),
+                    display.weekday(t.weekday())
+                )
+                .into(),
+            );
             home_app.set_time(ui::TimeData {
                 day: t.day() as _,
                 hour: t.hour() as _,
@@ -55,29 +89,175 @@ impl HomePage {
         );
     }
 
-    fn update_weather(ctx: Rc<dyn Context>) {
-        let update_ui = |forecast: ForecastWeather,
-                         now_weather: NowWeather,
-                         now_air_quality: NowAirQuality,
-                         location: Location| {
+    /// 恢复正常前只提示一次
+    fn alert_once<T: Debug>(ctx: Rc<dyn Context>, state: &WeatherState, e: T) {
+        if state.error_shown.replace(true) {
+            error!("error: {e:?}");
+            return;
+        }
+        Self::alert_dialog(ctx, e);
+    }
+
+    fn set_offline(offline: bool) {
+        if let Some(ui) = get_app_window().upgrade() {
+            ui.global::<ui::HomeViewModel>().set_offline(offline);
+        }
+    }
+
+    fn set_warnings(warnings: Vec<WeatherWarning>) {
+        let Some(ui) = get_app_window().upgrade() else {
+            return;
+        };
+        let banner = match warnings.first() {
+            Some(x) => {
+                let (r, g, b) = x.color;
+                // 亮色背景上使用黑色文字
+                let bright = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000 > 150;
+                ui::WarningBanner {
+                    text: match warnings.len() {
+                        1 => format!("! {}", x.type_name),
+                        n => format!("! {} +{}", x.type_name, n - 1),
+                    }
+                    .into(),
+                    color: Color::from_rgb_u8(r, g, b),
+                    text_color: if bright {
+                        Color::from_rgb_u8(0, 0, 0)
+                    } else {
+                        Color::from_rgb_u8(0xff, 0xff, 0xff)
+                    },
+                }
+            }
+            None => Default::default(),
+        };
+        ui.global::<ui::HomeViewModel>().set_warning(banner);
+    }
+
+    fn set_indoor(x: Option<SensorReading>, display: DisplaySettings) {
+        if let Some(ui) = get_app_window().upgrade() {
+            let vm = ui.global::<ui::HomeViewModel>();
+            vm.set_indoor(match x {
+                Some(x) => ui::IndoorData {
+                    available: true,
+                    temp: display.format_temp(x.temp).into(),
+                    humi: format!("{:.0}%", x.humidity).into(),
+                },
+                None => Default::default(),
+            });
+        }
+    }
+
+    fn set_astronomy(x: Option<Astronomy>, display: DisplaySettings) {
+        let Some(ui) = get_app_window().upgrade() else {
+            return;
+        };
+        let text = match x {
+            Some(x) => {
+                let hm = |t: OffsetDateTime| display.format_time_compact(t.hour(), t.minute());
+                let zh = display.lang == Language::Zh;
+                let sun = match (x.sunrise, x.sunset) {
+                    (Some(rise), Some(set)) => format!("{}-{}", hm(rise), hm(set)),
+                    _ if x.day_length > 0 => if zh { "极昼" } else { "Polar day" }.into(),
+                    _ => if zh { "极夜" } else { "Polar night" }.into(),
+                };
+                // 交节当天显示节气，其他时间显示月相
+                let today = OffsetDateTime::now_utc()
+                    .to_offset(x.solar_term_time.offset())
+                    .date();
+                let note = if x.solar_term_time.date() == today {
+                    x.solar_term.name(display.lang)
+                } else {
+                    x.moon_phase.name(display.lang)
+                };
+                format!("{sun} {note}")
+            }
+            None => String::new(),
+        };
+        ui.global::<ui::HomeViewModel>().set_astronomy(text.into());
+    }
+
+    fn update_weather(ctx: Rc<dyn Context>, state: Rc<WeatherState>) {
+        let index = state.location_index.get();
+        let location = match ipc::WeatherClient(ctx.clone()).list_locations() {
+            Ok(x) if x.is_empty() => {
+                Self::alert_once(ctx.clone(), &state, WeatherError::MissingLocation);
+                return;
+            }
+            Ok(x) => x[index % x.len()].clone(),
+            Err(e) => {
+                Self::alert_once(ctx.clone(), &state, e);
+                return;
+            }
+        };
+        let location_id = Some(location.location_id);
+        let display = state.display.get();
+        // 切换位置后丢弃之前位置的结果
+        let is_current = {
+            let state = state.clone();
+            move || state.location_index.get() == index
+        };
+
+        // 预警与天气数据分开获取，数据源不支持预警时不影响天气显示
+        ipc::WeatherClient(ctx.clone()).get_warnings(location_id, {
+            let is_current = is_current.clone();
+            Box::new(move |r| {
+                if is_current() {
+                    Self::set_warnings(r.map(|x| x.warnings).unwrap_or_default())
+                }
+            })
+        });
+
+        ipc::WeatherClient(ctx.clone()).get_astronomy(location_id, {
+            let is_current = is_current.clone();
+            Box::new(move |r| {
+                if is_current() {
+                    Self::set_astronomy(r.ok(), display)
+                }
+            })
+        });
+
+        // 数据源不支持的预报与空气质量不显示
+        let update_ui = move |forecast: Option<ForecastWeather>,
+                              now_weather: NowWeather,
+                              now_air_quality: Option<NowAirQuality>,
+                              location: Location| {
             if let Some(ui) = get_app_window().upgrade() {
                 let home_app = ui.global::<ui::HomeViewModel>();
+                let (min_temp, max_temp) = forecast
+                    .and_then(|x| x.daily.first().map(|x| (x.min_temp, x.max_temp)))
+                    .unwrap_or((now_weather.temp, now_weather.temp));
+                let air_quality = now_air_quality.unwrap_or(NowAirQuality {
+                    updated_time: now_weather.updated_time,
+                    value: 0,
+                    category: "-".into(),
+                    color: (0x80, 0x80, 0x80),
+                    primary_pollutant: None,
+                    pollutants: Vec::new(),
+                    health: None,
+                });
                 home_app.set_weather(ui::WeatherData {
                     location: location.location.into(),
                     current_humi: now_weather.humidity as _,
                     current_temp: now_weather.temp as _,
                     weather: now_weather.text.into(),
                     icon: now_weather.icon as _,
-                    min_temp: forecast.daily[0].min_temp as _,
-                    max_temp: forecast.daily[0].max_temp as _,
+                    min_temp: min_temp as _,
+                    max_temp: max_temp as _,
                     air_quality_color: Color::from_rgb_u8(
-                        now_air_quality.color.0,
-                        now_air_quality.color.1,
-                        now_air_quality.color.2,
+                        air_quality.color.0,
+                        air_quality.color.1,
+                        air_quality.color.2,
                     ),
-                    air_quality_index: now_air_quality.value as _,
-                    air_quality_text: now_air_quality.category.into(),
+                    air_quality_index: air_quality.value as _,
+                    air_quality_text: air_quality.category.into(),
                 });
+                // 获取失败时服务返回最后一次成功的数据
+                let updated = now_weather
+                    .obs_time
+                    .unwrap_or(now_weather.updated_time)
+                    .to_offset(UtcOffset::from_hms(8, 0, 0).unwrap());
+                home_app
+                    .set_stale(OffsetDateTime::now_utc() - now_weather.updated_time > STALE_AFTER);
+                home_app.set_updated(display.format_time(updated.hour(), updated.minute()).into());
             }
         };
 
@@ -85,62 +265,107 @@ impl HomePage {
 
         let wg = ctx.create_wait_group();
         let now_weather = RefValue::<NowWeather>::default();
-        ipc::WeatherClient(ctx.clone()).get_now_weather(Box::new({
-            let wg = wg.clone();
-            wg.inc();
-            let now_weather = now_weather.clone();
-            move |r| {
-                *now_weather.borrow_mut() = Some(r);
-                wg.done();
-            }
-        }));
+        ipc::WeatherClient(ctx.clone()).get_now_weather(
+            location_id,
+            Box::new({
+                let wg = wg.clone();
+                wg.inc();
+                let now_weather = now_weather.clone();
+                move |r| {
+                    *now_weather.borrow_mut() = Some(r);
+                    wg.done();
+                }
+            }),
+        );
 
         let forecast_weather = RefValue::<ForecastWeather>::default();
-        ipc::WeatherClient(ctx.clone()).get_forecast_weather(Box::new({
-            let wg = wg.clone();
-            wg.inc();
-            let forecast_weather = forecast_weather.clone();
-            move |r| {
-                *forecast_weather.borrow_mut() = Some(r);
-                wg.done();
-            }
-        }));
+        ipc::WeatherClient(ctx.clone()).get_forecast_weather(
+            location_id,
+            Box::new({
+                let wg = wg.clone();
+                wg.inc();
+                let forecast_weather = forecast_weather.clone();
+                move |r| {
+                    *forecast_weather.borrow_mut() = Some(r);
+                    wg.done();
+                }
+            }),
+        );
 
         let now_air_quality = RefValue::<NowAirQuality>::default();
-        ipc::WeatherClient(ctx.clone()).get_now_air_quality(Box::new({
-            let wg = wg.clone();
-            wg.inc();
-            let now_air_quality = now_air_quality.clone();
-            move |r| {
-                *now_air_quality.borrow_mut() = Some(r);
-                wg.done();
-            }
-        }));
+        ipc::WeatherClient(ctx.clone()).get_now_air_quality(
+            location_id,
+            Box::new({
+                let wg = wg.clone();
+                wg.inc();
+                let now_air_quality = now_air_quality.clone();
+                move |r| {
+                    *now_air_quality.borrow_mut() = Some(r);
+                    wg.done();
+                }
+            }),
+        );
 
-        let location = match ipc::WeatherClient(ctx.clone()).get_location() {
-            Ok(x) => x,
-            Err(e) => {
-                Self::alert_dialog(ctx.clone(), e);
+        wg.wait(Box::new(move || {
+            if !is_current() {
                 return;
             }
-        };
-        wg.wait(Box::new(move || {
             let f = || -> Result<(), WeatherError> {
                 let now_weather = now_weather.borrow_mut().take().unwrap()?;
-                let forecast_weather = forecast_weather.borrow_mut().take().unwrap()?;
-                let now_air_quality = now_air_quality.borrow_mut().take().unwrap()?;
+                // 预报与空气质量获取失败时不影响实时天气的显示
+                let optional = |r: Result<_, WeatherError>| match r {
+                    Ok(x) => Some(x),
+                    Err(WeatherError::Unsupported) => None,
+                    Err(e) => {
+                        error!("update weather err: {e:?}");
+                        None
+                    }
+                };
+                let forecast_weather = optional(forecast_weather.borrow_mut().take().unwrap());
+                let now_air_quality = optional(now_air_quality.borrow_mut().take().unwrap());
                 update_ui(forecast_weather, now_weather, now_air_quality, location);
                 Ok(())
             };
-            if let Err(e) = f() {
-                Self::alert_dialog(ctx, e);
+            match f() {
+                Ok(()) => state.error_shown.set(false),
+                // 离线时只显示标记，避免每次刷新都弹出错误
+                Err(WeatherError::Offline) => Self::set_offline(true),
+                Err(e) => Self::alert_once(ctx, &state, e),
             }
         }));
     }
 
+    /// 切换到下一个位置，只有一个位置时不切换
+    fn next_location(ctx: Rc<dyn Context>, state: Rc<WeatherState>) {
+        let len = match ipc::WeatherClient(ctx.clone()).list_locations() {
+            Ok(x) => x.len(),
+            Err(e) => {
+                error!("list weather locations err: {e:?}");
+                return;
+            }
+        };
+        if len <= 1 {
+            return;
+        }
+        let index = &state.location_index;
+        index.set((index.get() + 1) % len);
+        Self::update_weather(ctx, state);
+    }
+
     fn on_show(&self, ctx: Rc<dyn Context>) {
-        Self::update_time();
-        Self::update_weather(ctx.clone());
+        let display = SystemStorage(ipc::StorageClient(ctx.clone())).get_display_settings();
+        self.state.display.set(display);
+        if let Some(ui) = get_app_window().upgrade() {
+            let vm = ui.global::<ui::HomeViewModel>();
+            vm.set_temp_unit(display.temp_symbol().into());
+            vm.set_zh(display.lang == Language::Zh);
+        }
+        Self::update_time(display);
+        Self::set_indoor(ipc::SensorClient(ctx.clone()).get_latest(), display);
+        Self::set_offline(
+            ipc::HttpClient(ctx.clone()).connectivity() == ConnectivityState::Offline,
+        );
+        Self::update_weather(ctx.clone(), self.state.clone());
         self.time_update_timer
             .borrow_mut()
             .get_or_insert(slint::Timer::default())
@@ -148,28 +373,58 @@ impl HomePage {
                 slint::TimerMode::Repeated,
                 Duration::from_secs(1),
                 move || {
-                    Self::update_time();
+                    Self::update_time(display);
                 },
             );
         self.weather_update_timer
             .borrow_mut()
             .get_or_insert(slint::Timer::default())
-            .start(
-                slint::TimerMode::Repeated,
-                Duration::from_secs(60),
+            .start(slint::TimerMode::Repeated, Duration::from_secs(60), {
+                let ctx = ctx.clone();
+                let state = self.state.clone();
                 move || {
-                    Self::update_weather(ctx.clone());
-                },
-            );
+                    Self::update_weather(ctx.clone(), state.clone());
+                }
+            });
+        let interval = WeatherStorage(ipc::StorageClient(ctx.clone()))
+            .get_rotation_interval()
+            .unwrap_or_else(|e| {
+                error!("get rotation interval err: {e:?}");
+                0
+            });
+        if interval > 0 {
+            let state = self.state.clone();
+            self.rotation_timer
+                .borrow_mut()
+                .get_or_insert(slint::Timer::default())
+                .start(
+                    slint::TimerMode::Repeated,
+                    Duration::from_secs(interval as _),
+                    move || {
+                        Self::next_location(ctx.clone(), state.clone());
+                    },
+                );
+        }
     }
 
     fn on_hide(&self) {
         self.time_update_timer.borrow_mut().take();
         self.weather_update_timer.borrow_mut().take();
+        self.rotation_timer.borrow_mut().take();
+        // 返回首页时从首选位置开始显示
+        self.state.location_index.set(0);
+        self.state.error_shown.set(false);
         if let Some(ui) = ui::get_app_window().upgrade() {
             let vm = ui.global::<ui::HomeViewModel>();
             vm.set_weather(Default::default());
+            vm.set_warning(Default::default());
+            vm.set_astronomy(Default::default());
+            vm.set_indoor(Default::default());
             vm.set_time(Default::default());
+            vm.set_time_text(Default::default());
+            vm.set_date_text(Default::default());
+            vm.set_offline(false);
+            vm.set_stale(false);
         }
     }
 }
@@ -184,16 +439,32 @@ impl Node for HomePage {
             Message::Lifecycle(msg) => match msg {
                 LifecycleMessage::Show => {
                     ctx.subscribe_topic(TopicName::OneButton);
+                    ctx.subscribe_topic(TopicName::Connectivity);
+                    ctx.subscribe_topic(TopicName::Sensor);
                     self.on_show(ctx);
                     return HandleResult::Finish(Message::Empty);
                 }
                 LifecycleMessage::Hide => {
                     ctx.unsubscribe_topic(TopicName::OneButton);
+                    ctx.unsubscribe_topic(TopicName::Connectivity);
+                    ctx.unsubscribe_topic(TopicName::Sensor);
                     self.on_hide();
                     return HandleResult::Finish(Message::Empty);
                 }
                 _ => {}
             },
+            Message::Http(HttpMessage::Connectivity(state)) => {
+                let offline = state == ConnectivityState::Offline;
+                Self::set_offline(offline);
+                if !offline {
+                    // 恢复联网后立即刷新过期数据
+                    Self::update_weather(ctx, self.state.clone());
+                }
+                return HandleResult::Finish(Message::Empty);
+            }
+            Message::Sensor(SensorMessage::ReadingBroadcast(x)) => {
+                Self::set_indoor(Some(x), self.state.display.get());
+            }
             Message::OneButton(msg) => match msg {
                 OneButtonMessage::Click => {
                     ctx.sync_call(
@@ -202,6 +473,11 @@ impl Node for HomePage {
                     );
                     return HandleResult::Finish(Message::Empty);
                 }
+                // 长按松手切换位置
+                OneButtonMessage::LongPressHeld(_) => {
+                    Self::next_location(ctx, self.state.clone());
+                    return HandleResult::Finish(Message::Empty);
+                }
                 OneButtonMessage::Clicks(2) => {
                     static MID: &[u8] = include_bytes!("../../../a.mid");
                     ipc::MidiPlayerClient(ctx.clone()).play(
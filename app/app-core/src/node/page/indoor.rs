@@ -0,0 +1,156 @@
+use std::{cell::Cell, rc::Rc};
+
+use ipc::SensorClient;
+use slint::{ComponentHandle, ModelRc, VecModel};
+use time::UtcOffset;
+
+use crate::{proto::*, storage::SystemStorage, ui};
+
+/// 温度与湿度两页
+const PAGES: i32 = 2;
+
+/// 历史曲线区域的高度
+const CHART_HEIGHT: f32 = 120.0;
+
+/// 曲线的最小纵向范围，避免读数稳定时微小波动被放大
+const MIN_TEMP_SPAN: f32 = 2.0;
+const MIN_HUMI_SPAN: f32 = 10.0;
+
+/// 按最小值与最大值绘制曲线，返回各点距曲线顶部的偏移与最小、最大值
+fn chart(values: &[f32], min_span: f32) -> (Vec<f32>, f32, f32) {
+    let (min, max) = values
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(a, b), x| (a.min(*x), b.max(*x)));
+    let span = (max - min).max(min_span);
+    // 范围不足时上下留出相同的空白
+    let top = (max + min + span) / 2.0;
+    let points = values
+        .iter()
+        .map(|x| (top - x) / span * CHART_HEIGHT)
+        .collect();
+    (points, min, max)
+}
+
+pub struct IndoorPage {
+    page: Cell<i32>,
+    // 显示页面时读取的显示设置
+    display: Cell<DisplaySettings>,
+}
+
+impl IndoorPage {
+    pub fn new() -> Self {
+        Self {
+            page: Cell::new(0),
+            display: Default::default(),
+        }
+    }
+
+    fn set_page(&self, page: i32) {
+        self.page.set(page);
+        if let Some(ui) = ui::get_app_window().upgrade() {
+            ui.global::<ui::IndoorViewModel>().set_page(page);
+        }
+    }
+
+    fn set_current(x: SensorReading, display: DisplaySettings) {
+        if let Some(ui) = ui::get_app_window().upgrade() {
+            let vm = ui.global::<ui::IndoorViewModel>();
+            vm.set_available(true);
+            vm.set_temp(display.format_temp(x.temp).into());
+            vm.set_humi(format!("{:.0}%", x.humidity).into());
+        }
+    }
+
+    fn update_history(ctx: Rc<dyn Context>, display: DisplaySettings) {
+        let history = SensorClient(ctx).get_history();
+        let Some(first) = history.first() else {
+            return;
+        };
+        let t = first.time.to_offset(UtcOffset::from_hms(8, 0, 0).unwrap());
+        let start = display.format_time_compact(t.hour(), t.minute());
+        let temps = history.iter().map(|x| x.temp).collect::<Vec<_>>();
+        let humis = history.iter().map(|x| x.humidity).collect::<Vec<_>>();
+        let (temp_points, temp_min, temp_max) = chart(&temps, MIN_TEMP_SPAN);
+        let (humi_points, humi_min, humi_max) = chart(&humis, MIN_HUMI_SPAN);
+        if let Some(ui) = ui::get_app_window().upgrade() {
+            let vm = ui.global::<ui::IndoorViewModel>();
+            vm.set_start(start.into());
+            vm.set_temp_chart(ui::IndoorChartViewModel {
+                points: ModelRc::new(VecModel::from(temp_points)),
+                min: display.format_temp(temp_min).into(),
+                max: display.format_temp(temp_max).into(),
+            });
+            vm.set_humi_chart(ui::IndoorChartViewModel {
+                points: ModelRc::new(VecModel::from(humi_points)),
+                min: format!("{humi_min:.0}%").into(),
+                max: format!("{humi_max:.0}%").into(),
+            });
+        }
+    }
+}
+
+impl Node for IndoorPage {
+    fn node_name(&self) -> NodeName {
+        NodeName::IndoorPage
+    }
+
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        match msg.body {
+            Message::Sensor(SensorMessage::ReadingBroadcast(x)) => {
+                let display = self.display.get();
+                Self::set_current(x, display);
+                Self::update_history(ctx, display);
+            }
+            Message::OneButton(msg) => match msg {
+                OneButtonMessage::Click => {
+                    self.set_page((self.page.get() + 1) % PAGES);
+                    return HandleResult::Finish(Message::Empty);
+                }
+                OneButtonMessage::LongPressHolding(dur) => {
+                    if dur > 1000 {
+                        ctx.sync_call(
+                            NodeName::Router,
+                            Message::Router(RouterMessage::GotoPage(RoutePage::Home)),
+                        );
+                        return HandleResult::Finish(Message::Empty);
+                    }
+                }
+                _ => {}
+            },
+            Message::Lifecycle(msg) => match msg {
+                LifecycleMessage::Hide => {
+                    self.set_page(0);
+                    ctx.unsubscribe_topic(TopicName::OneButton);
+                    ctx.unsubscribe_topic(TopicName::Sensor);
+                    if let Some(ui) = ui::get_app_window().upgrade() {
+                        let vm = ui.global::<ui::IndoorViewModel>();
+                        vm.set_available(false);
+                        vm.set_temp(Default::default());
+                        vm.set_humi(Default::default());
+                        vm.set_start(Default::default());
+                        vm.set_temp_chart(Default::default());
+                        vm.set_humi_chart(Default::default());
+                    }
+                }
+                LifecycleMessage::Show => {
+                    ctx.subscribe_topic(TopicName::OneButton);
+                    ctx.subscribe_topic(TopicName::Sensor);
+                    let display =
+                        SystemStorage(ipc::StorageClient(ctx.clone())).get_display_settings();
+                    self.display.set(display);
+                    if let Some(ui) = ui::get_app_window().upgrade() {
+                        ui.global::<ui::IndoorViewModel>()
+                            .set_zh(display.lang == Language::Zh);
+                    }
+                    if let Some(x) = SensorClient(ctx.clone()).get_latest() {
+                        Self::set_current(x, display);
+                    }
+                    Self::update_history(ctx, display);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        HandleResult::Discard
+    }
+}
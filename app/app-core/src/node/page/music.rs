@@ -20,14 +20,12 @@ impl MusicPage {
                 vm.set_music_list(mrc);
                 let mpc = ipc::MidiPlayerClient(ctx.clone());
                 let elem = vm.get_music_list().row_data(0).unwrap();
-                let bs = ms.get_data(elem.into());
-                mpc.play(bs, Box::new(|_| {}));
+                mpc.play_music(elem.into(), Box::new(|_| {}));
             }
         }
     }
 
     fn on_click(ctx: Rc<dyn Context>) {
-        let ms = MusicStorage(StorageClient(ctx.clone()));
         if let Some(ui) = get_app_window().upgrade() {
             let vm = ui.global::<ui::MusicPageViewModel>();
             let len = vm.get_music_list().row_count();
@@ -54,8 +52,7 @@ impl MusicPage {
                 // 切换音乐时候先关闭先前的音乐释放内存
                 mpc.off();
                 let elem = vm.get_music_list().row_data(idx as usize).unwrap();
-                let bs = ms.get_data(elem.into());
-                mpc.play(bs, Box::new(|_| {}));
+                mpc.play_music(elem.into(), Box::new(|_| {}));
             }
         }
     }
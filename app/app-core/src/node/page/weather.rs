@@ -1,15 +1,135 @@
-use std::rc::Rc;
+use std::{cell::Cell, rc::Rc};
 
 use ipc::WeatherClient;
+use log::error;
 use slint::{ComponentHandle, ModelRc, VecModel};
+use time::OffsetDateTime;
 
-use crate::{proto::*, ui};
+use crate::{proto::*, storage::SystemStorage, ui};
 
-pub struct WeatherPage {}
+/// 三天预报、3页逐小时预报与天文信息
+const PAGES: i32 = 5;
+
+/// 天文信息所在的页
+const ASTRONOMY_PAGE: i32 = 4;
+
+/// 温度曲线区域的高度
+const CURVE_HEIGHT: f32 = 80.0;
+
+pub struct WeatherPage {
+    page: Cell<i32>,
+    // 显示页面时读取的显示设置
+    display: Cell<DisplaySettings>,
+}
 
 impl WeatherPage {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            page: Cell::new(0),
+            display: Default::default(),
+        }
+    }
+
+    fn set_page(&self, page: i32) {
+        self.page.set(page);
+        if let Some(ui) = ui::get_app_window().upgrade() {
+            ui.global::<ui::WeatherPageViewModel>().set_page(page);
+        }
+    }
+
+    fn update_hourly(ctx: Rc<dyn Context>, display: DisplaySettings) {
+        WeatherClient(ctx).get_hourly_forecast(
+            None,
+            Box::new(move |r| {
+                let w = match r {
+                    Ok(x) => x,
+                    Err(WeatherError::Offline) => {
+                        Self::set_offline(true);
+                        return;
+                    }
+                    Err(e) => {
+                        error!("get hourly forecast error: {e:?}");
+                        return;
+                    }
+                };
+                // 按24小时内的温度范围绘制曲线，保证各页的曲线连续
+                let (min, max) = w.hourly.iter().fold((i16::MAX, i16::MIN), |(a, b), x| {
+                    (a.min(x.temp as i16), b.max(x.temp as i16))
+                });
+                let span = (max - min).max(1) as f32;
+                let data = w
+                    .hourly
+                    .into_iter()
+                    .map(|x| ui::HourlyWeatherViewModel {
+                        hour: display.format_hour(x.time.hour()).into(),
+                        icon: x.icon as _,
+                        temp: x.temp as _,
+                        pop: x.pop.map(|x| format!("{x}%")).unwrap_or_default().into(),
+                        curve_y: (max - x.temp as i16) as f32 / span * CURVE_HEIGHT,
+                    })
+                    .collect::<Vec<_>>();
+                if let Some(ui) = ui::get_app_window().upgrade() {
+                    let vm = ui.global::<ui::WeatherPageViewModel>();
+                    vm.set_hourly(ModelRc::new(VecModel::from(data)));
+                }
+            }),
+        );
+    }
+
+    fn update_astronomy(ctx: Rc<dyn Context>, display: DisplaySettings) {
+        WeatherClient(ctx).get_astronomy(
+            None,
+            Box::new(move |r| {
+                let x = match r {
+                    Ok(x) => x,
+                    Err(e) => {
+                        error!("get astronomy error: {e:?}");
+                        return;
+                    }
+                };
+                let hm = |t: OffsetDateTime| display.format_time(t.hour(), t.minute());
+                let md = |t: OffsetDateTime| format!("{:0>2}-{:0>2}", t.month() as u8, t.day());
+                // 上半月亮部在右侧，被照亮的比例越大偏移越小
+                let offset = 1.0 - x.moon_illumination as f32 / 100.0;
+                let data = ui::AstronomyViewModel {
+                    sunrise: x.sunrise.map(hm).unwrap_or("--:--".into()).into(),
+                    sunset: x.sunset.map(hm).unwrap_or("--:--".into()).into(),
+                    day_length: format!("{}h {:0>2}m", x.day_length / 60, x.day_length % 60).into(),
+                    moon_phase: x.moon_phase.name(display.lang).into(),
+                    moon_illumination: x.moon_illumination as _,
+                    moon_offset: if (x.moon_age as f64) < astronomy::SYNODIC_MONTH / 2.0 {
+                        offset
+                    } else {
+                        -offset
+                    },
+                    solar_term: format!(
+                        "{} {}",
+                        x.solar_term.name(display.lang),
+                        md(x.solar_term_time)
+                    )
+                    .into(),
+                    next_solar_term: format!(
+                        "{} {} {}",
+                        match display.lang {
+                            Language::Zh => "下一节气",
+                            Language::En => "Next",
+                        },
+                        x.next_solar_term.name(display.lang),
+                        md(x.next_solar_term_time)
+                    )
+                    .into(),
+                };
+                if let Some(ui) = ui::get_app_window().upgrade() {
+                    ui.global::<ui::WeatherPageViewModel>().set_astronomy(data);
+                }
+            }),
+        );
+    }
+
+    fn set_offline(offline: bool) {
+        if let Some(ui) = ui::get_app_window().upgrade() {
+            ui.global::<ui::WeatherPageViewModel>().set_offline(offline);
+        }
     }
 }
 
@@ -20,8 +140,23 @@ impl Node for WeatherPage {
 
     fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
         match msg.body {
+            Message::Http(HttpMessage::Connectivity(state)) => {
+                Self::set_offline(state == ConnectivityState::Offline);
+                return HandleResult::Finish(Message::Empty);
+            }
             Message::OneButton(msg) => match msg {
-                OneButtonMessage::Click => {}
+                OneButtonMessage::Click => {
+                    let page = (self.page.get() + 1) % PAGES;
+                    // 逐小时预报与天文信息在翻到对应页时加载
+                    if page == 1 {
+                        Self::update_hourly(ctx.clone(), self.display.get());
+                    }
+                    if page == ASTRONOMY_PAGE {
+                        Self::update_astronomy(ctx.clone(), self.display.get());
+                    }
+                    self.set_page(page);
+                    return HandleResult::Finish(Message::Empty);
+                }
                 OneButtonMessage::LongPressHolding(dur) => {
                     if dur > 1000 {
                         ctx.sync_call(
@@ -36,40 +171,70 @@ impl Node for WeatherPage {
             },
             Message::Lifecycle(msg) => match msg {
                 LifecycleMessage::Hide => {
+                    self.set_page(0);
                     ctx.unsubscribe_topic(TopicName::OneButton);
+                    ctx.unsubscribe_topic(TopicName::Connectivity);
                     if let Some(ui) = ui::get_app_window().upgrade() {
                         let vm = ui.global::<ui::WeatherPageViewModel>();
                         vm.set_data(Default::default()); // 释放内存占用
+                        vm.set_hourly(Default::default());
+                        vm.set_astronomy(Default::default());
+                        vm.set_offline(false);
                     }
                 }
                 LifecycleMessage::Show => {
                     ctx.subscribe_topic(TopicName::OneButton);
-                    WeatherClient(ctx.clone()).get_forecast_weather(Box::new(|w| {
-                        let data = w
-                            .unwrap()
-                            .daily
-                            .into_iter()
-                            .map(|x| ui::OneDayWeatherViewModel {
-                                title: {
-                                    ["Sun.", "Mon.", "Tue.", "Wed.", "Thu.", "Fri.", "Sat."]
-                                        [x.date.weekday().number_days_from_sunday() as usize]
-                                        .into()
-                                },
-                                date: format!("{:0>2}-{:0>2}", x.date.month() as u8, x.date.day())
+                    ctx.subscribe_topic(TopicName::Connectivity);
+                    Self::set_offline(
+                        ipc::HttpClient(ctx.clone()).connectivity() == ConnectivityState::Offline,
+                    );
+                    let display =
+                        SystemStorage(ipc::StorageClient(ctx.clone())).get_display_settings();
+                    self.display.set(display);
+                    if let Some(ui) = ui::get_app_window().upgrade() {
+                        let vm = ui.global::<ui::WeatherPageViewModel>();
+                        vm.set_temp_unit(display.temp_symbol().into());
+                        vm.set_zh(display.lang == Language::Zh);
+                    }
+                    WeatherClient(ctx.clone()).get_forecast_weather(
+                        None,
+                        Box::new(move |w| {
+                            let w = match w {
+                                Ok(x) => x,
+                                Err(WeatherError::Offline) => {
+                                    Self::set_offline(true);
+                                    return;
+                                }
+                                Err(e) => {
+                                    error!("get forecast weather error: {e:?}");
+                                    return;
+                                }
+                            };
+                            let data = w
+                                .daily
+                                .into_iter()
+                                .map(|x| ui::OneDayWeatherViewModel {
+                                    title: display.weekday(x.date.weekday()).into(),
+                                    date: format!(
+                                        "{:0>2}-{:0>2}",
+                                        x.date.month() as u8,
+                                        x.date.day()
+                                    )
                                     .into(),
-                                day_icon: x.icon_day as _,
-                                day_temp: x.max_temp as _,
-                                day_text: x.text_day.into(),
-                                night_icon: x.icon_night as _,
-                                night_temp: x.min_temp as _,
-                                night_text: x.text_night.into(),
-                            })
-                            .collect::<Vec<_>>();
-                        if let Some(ui) = ui::get_app_window().upgrade() {
-                            let vm = ui.global::<ui::WeatherPageViewModel>();
-                            vm.set_data(ModelRc::new(VecModel::from(data)));
-                        }
-                    }));
+                                    day_icon: x.icon_day as _,
+                                    day_temp: x.max_temp as _,
+                                    day_text: x.text_day.into(),
+                                    night_icon: x.icon_night as _,
+                                    night_temp: x.min_temp as _,
+                                    night_text: x.text_night.into(),
+                                })
+                                .collect::<Vec<_>>();
+                            if let Some(ui) = ui::get_app_window().upgrade() {
+                                let vm = ui.global::<ui::WeatherPageViewModel>();
+                                vm.set_data(ModelRc::new(VecModel::from(data)));
+                            }
+                        }),
+                    );
                 }
                 _ => {}
             },
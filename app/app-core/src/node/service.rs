@@ -1,6 +1,9 @@
 mod midiplayer;
+mod migration;
 mod onebutton;
 mod router;
+mod secret;
+mod sensor;
 mod storage;
 mod system;
 mod timer;
@@ -8,7 +11,15 @@ mod weather;
 mod wifi;
 
 pub use {
-    midiplayer::MidiPlayerService, onebutton::TouchOneButtonAdapterService, router::RouterService,
-    storage::MockStorageService, system::MockSystemService, timer::TimerService,
-    weather::WeatherService, wifi::MockWiFiService,
+    midiplayer::MidiPlayerService,
+    migration::StorageMigrationService,
+    onebutton::TouchOneButtonAdapterService,
+    router::RouterService,
+    secret::SecretService,
+    sensor::{MockSensorService, SensorService},
+    storage::MockStorageService,
+    system::MockSystemService,
+    timer::TimerService,
+    weather::WeatherService,
+    wifi::MockWiFiService,
 };
@@ -12,9 +12,9 @@ use embedded_tone::AbsulateNotePitch;
 use log::{error, info};
 use midly::{MetaMessage, Timing, TrackEventKind};
 
-use crate::proto::*;
+use crate::{proto::*, storage::MusicStorage};
 
-use self::ipc::BuzzerClient;
+use self::ipc::{BuzzerClient, StorageClient};
 
 fn living_play_midi(
     midi_content: &[u8],
@@ -161,6 +161,16 @@ impl MidiPlayerService {
     pub fn new() -> Self {
         Self {}
     }
+
+    fn play_series(ctx: Rc<dyn Context>, seq: usize, series: ToneSeries) -> HandleResult {
+        BuzzerClient(ctx.clone()).tone_series(
+            series,
+            Box::new(move |is_finished| {
+                ctx.async_ready(seq, Message::Midi(MidiMessage::PlayResponse(is_finished)));
+            }),
+        );
+        HandleResult::Pending
+    }
 }
 
 impl Node for MidiPlayerService {
@@ -171,30 +181,35 @@ impl Node for MidiPlayerService {
     fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
         let seq = msg.seq;
         match msg.body {
-            Message::Midi(msg) => {
-                let cli = BuzzerClient(ctx.clone());
-                match msg {
-                    MidiMessage::PlayRequest(bs) => {
-                        let series = midi_to_freq_and_dur_series(&bs.0);
-                        drop(bs);
-                        cli.tone_series(
-                            series,
-                            Box::new(move |is_finished| {
-                                ctx.async_ready(
-                                    seq,
-                                    Message::Midi(MidiMessage::PlayResponse(is_finished)),
-                                );
-                            }),
-                        );
-                        return HandleResult::Pending;
-                    }
-                    MidiMessage::Off => {
-                        cli.off();
-                        return HandleResult::Finish(Message::Empty);
-                    }
-                    _ => {}
+            Message::Midi(msg) => match msg {
+                MidiMessage::PlayRequest(bs) => {
+                    let series = midi_to_freq_and_dur_series(&bs.0);
+                    drop(bs);
+                    return Self::play_series(ctx, seq, series);
                 }
-            }
+                MidiMessage::PlayMusicRequest(filename) => {
+                    let bs =
+                        match MusicStorage(StorageClient(ctx.clone())).read_first_track(filename) {
+                            Ok(x) => x,
+                            Err(e) => {
+                                return HandleResult::Finish(Message::Midi(MidiMessage::Error(
+                                    MidiError::Other(format!("{e:?}")),
+                                )))
+                            }
+                        };
+                    let series = midi_to_freq_and_dur_series(&bs);
+                    drop(bs);
+                    return Self::play_series(ctx, seq, series);
+                }
+                MidiMessage::DownloadRequest { filename, url } => {
+                    return MusicStorage(StorageClient(ctx)).handle_download(seq, filename, url);
+                }
+                MidiMessage::Off => {
+                    BuzzerClient(ctx).off();
+                    return HandleResult::Finish(Message::Empty);
+                }
+                _ => {}
+            },
             _ => {}
         }
         HandleResult::Discard
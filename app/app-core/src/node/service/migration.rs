@@ -0,0 +1,36 @@
+use std::rc::Rc;
+
+use log::{error, info};
+
+use crate::proto::*;
+use crate::storage::StorageMigrator;
+
+/// 启动时把存储升级到最新布局，优先级仅次于存储后端，保证其他节点初始化时读到的是新布局
+pub struct StorageMigrationService;
+
+impl StorageMigrationService {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Node for StorageMigrationService {
+    fn priority(&self) -> usize {
+        9000
+    }
+
+    fn node_name(&self) -> NodeName {
+        NodeName::StorageMigration
+    }
+
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        if let Message::Lifecycle(LifecycleMessage::Init) = msg.body {
+            match StorageMigrator(ipc::StorageClient(ctx)).migrate() {
+                Ok((from, to)) if from != to => info!("storage migrated from v{from} to v{to}"),
+                Ok(_) => {}
+                Err(e) => error!("storage migration error: {e:?}"),
+            }
+        }
+        HandleResult::Discard
+    }
+}
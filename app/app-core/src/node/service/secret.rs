@@ -0,0 +1,129 @@
+use std::rc::Rc;
+
+use aes_gcm_siv::{
+    aead::{Aead, KeyInit},
+    Aes256GcmSiv, Nonce,
+};
+
+use log::warn;
+
+use crate::proto::*;
+
+/// secret的所属节点，只有所属节点可以读取，写入不受限制
+fn is_owner(name: &str, node: &NodeName) -> bool {
+    match name {
+        "wifi/password" => matches!(node, NodeName::BootPage | NodeName::WiFi),
+        "weather/key" => matches!(node, NodeName::Weather),
+        _ => false,
+    }
+}
+
+/// 由secret名称派生nonce，GCM-SIV在nonce重复时仅会暴露明文是否相同
+fn nonce_of(name: &str) -> Nonce {
+    let mut nonce = [0u8; 12];
+    for (i, b) in name.bytes().enumerate() {
+        nonce[i % nonce.len()] ^= b;
+    }
+    nonce.into()
+}
+
+/// 使用设备密钥加密后存放在`secret/`命名空间下的敏感信息服务
+pub struct SecretService {
+    cipher: Aes256GcmSiv,
+}
+
+impl SecretService {
+    pub fn new(device_key: [u8; 32]) -> Self {
+        Self {
+            cipher: Aes256GcmSiv::new(&device_key.into()),
+        }
+    }
+
+    fn get(&self, ctx: Rc<dyn Context>, name: String) -> Result<Option<Secret>, SecretError> {
+        let bs = match ipc::StorageClient(ctx)
+            .get(format!("{SECRET_PREFIX}{name}"))
+            .map_err(SecretError::StorageError)?
+        {
+            StorageValue::Bytes(bs) => bs.0,
+            StorageValue::None => return Ok(None),
+            m => {
+                return Err(SecretError::StorageError(StorageError::TypeError(format!(
+                    "unexpected secret value {m:?}"
+                ))))
+            }
+        };
+        let plain = self
+            .cipher
+            .decrypt(&nonce_of(&name), bs.as_slice())
+            .map_err(|e| SecretError::CryptoError(e.to_string()))?;
+        String::from_utf8(plain)
+            .map(|x| Some(Secret(x)))
+            .map_err(|e| SecretError::CryptoError(e.to_string()))
+    }
+
+    fn export(&self, ctx: Rc<dyn Context>) -> Result<Vec<(String, Secret)>, SecretError> {
+        let keys = ipc::StorageClient(ctx.clone())
+            .list(SECRET_PREFIX.into())
+            .map_err(SecretError::StorageError)?;
+        let mut ret = Vec::with_capacity(keys.len());
+        for key in keys.into_iter() {
+            let name = key[SECRET_PREFIX.len()..].to_string();
+            match self.get(ctx.clone(), name.clone()) {
+                Ok(Some(x)) => ret.push((name, x)),
+                Ok(None) => {}
+                Err(SecretError::CryptoError(e)) => warn!("skip undecryptable secret {name}: {e}"),
+                Err(e) => return Err(e),
+            }
+        }
+        ret.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(ret)
+    }
+
+    fn set(
+        &self,
+        ctx: Rc<dyn Context>,
+        name: String,
+        value: Option<Secret>,
+    ) -> Result<(), SecretError> {
+        let value = match value {
+            Some(Secret(x)) => StorageValue::Bytes(Bytes(
+                self.cipher
+                    .encrypt(&nonce_of(&name), x.as_bytes())
+                    .map_err(|e| SecretError::CryptoError(e.to_string()))?,
+            )),
+            None => StorageValue::None,
+        };
+        ipc::StorageClient(ctx)
+            .set(format!("{SECRET_PREFIX}{name}"), value)
+            .map_err(SecretError::StorageError)
+    }
+}
+
+impl Node for SecretService {
+    fn node_name(&self) -> NodeName {
+        NodeName::Secret
+    }
+
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        if let Message::Secret(sm) = msg.body {
+            let resp = match sm {
+                SecretMessage::GetRequest(name) => {
+                    if is_owner(&name, &msg.from) {
+                        self.get(ctx, name).map(SecretMessage::GetResponse)
+                    } else {
+                        Err(SecretError::PermissionDenied(msg.from))
+                    }
+                }
+                SecretMessage::SetRequest(name, value) => self
+                    .set(ctx, name, value)
+                    .map(|_| SecretMessage::SetResponse),
+                SecretMessage::ExportRequest => self.export(ctx).map(SecretMessage::ExportResponse),
+                m => panic!("unexpected message {m:?}"),
+            };
+            return HandleResult::Finish(Message::Secret(
+                resp.unwrap_or_else(SecretMessage::Error),
+            ));
+        }
+        HandleResult::Discard
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    cell::{Cell, RefCell},
+    f32::consts::PI,
+    rc::Rc,
+    time::Duration,
+};
+
+use proto::sensor::SensorHistory;
+use time::OffsetDateTime;
+
+use crate::proto::*;
+
+/// 保存传感器驱动广播的最近读数，并按采样间隔记录历史
+pub struct SensorService {
+    latest: RefCell<Option<SensorReading>>,
+    history: RefCell<SensorHistory>,
+}
+
+impl SensorService {
+    pub fn new() -> Self {
+        Self {
+            latest: RefCell::new(None),
+            history: RefCell::new(SensorHistory::default()),
+        }
+    }
+}
+
+impl Node for SensorService {
+    fn node_name(&self) -> NodeName {
+        NodeName::Sensor
+    }
+
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        match msg.body {
+            Message::Lifecycle(LifecycleMessage::Init) => {
+                ctx.subscribe_topic(TopicName::Sensor);
+            }
+            Message::Sensor(msg) => {
+                return HandleResult::Finish(Message::Sensor(match msg {
+                    SensorMessage::ReadingBroadcast(x) => {
+                        *self.latest.borrow_mut() = Some(x);
+                        self.history.borrow_mut().sample(x);
+                        return HandleResult::Discard;
+                    }
+                    SensorMessage::GetLatestRequest => {
+                        SensorMessage::GetLatestResponse(*self.latest.borrow())
+                    }
+                    SensorMessage::GetHistoryRequest => {
+                        SensorMessage::GetHistoryResponse(self.history.borrow().to_vec())
+                    }
+                    m => panic!("unexpected message {m:?}"),
+                }));
+            }
+            _ => {}
+        }
+        HandleResult::Discard
+    }
+}
+
+/// 模拟读数的间隔
+const MOCK_READ_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 桌面与WASM平台使用的模拟传感器，按天周期变化或循环回放给定的读数
+pub struct MockSensorService {
+    // (温度, 湿度)，为空时按时间模拟
+    replay: Vec<(f32, f32)>,
+    index: Rc<Cell<usize>>,
+    timer: slint::Timer,
+}
+
+impl MockSensorService {
+    pub fn new() -> Self {
+        Self::replay(Vec::new())
+    }
+
+    pub fn replay(readings: Vec<(f32, f32)>) -> Self {
+        Self {
+            replay: readings,
+            index: Default::default(),
+            timer: Default::default(),
+        }
+    }
+
+    fn simulate(t: OffsetDateTime) -> (f32, f32) {
+        // 下午最热、清晨湿度最高
+        let day = (t.unix_timestamp() % 86400) as f32 / 86400.0;
+        let phase = 2.0 * PI * (day - 0.375);
+        (24.0 + 2.0 * phase.sin(), 50.0 - 10.0 * phase.sin())
+    }
+}
+
+impl Node for MockSensorService {
+    fn node_name(&self) -> NodeName {
+        NodeName::Other("MockSensor".into())
+    }
+
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        if let Message::Lifecycle(LifecycleMessage::Init) = msg.body {
+            let replay = self.replay.clone();
+            let index = self.index.clone();
+            self.timer
+                .start(slint::TimerMode::Repeated, MOCK_READ_INTERVAL, move || {
+                    let time = OffsetDateTime::now_utc();
+                    let (temp, humidity) = match replay.get(index.get() % replay.len().max(1)) {
+                        Some(x) => *x,
+                        None => Self::simulate(time),
+                    };
+                    index.set(index.get() + 1);
+                    ctx.broadcast_topic(
+                        TopicName::Sensor,
+                        Message::Sensor(SensorMessage::ReadingBroadcast(SensorReading {
+                            time,
+                            temp,
+                            humidity,
+                        })),
+                    );
+                });
+        }
+        HandleResult::Discard
+    }
+}
@@ -2,14 +2,29 @@ use std::{cell::RefCell, collections::HashMap};
 
 use crate::proto::*;
 
+fn usage(data: &HashMap<String, StorageValue>, prefix: &str) -> StorageUsage {
+    data.iter().filter(|(k, _)| k.starts_with(prefix)).fold(
+        StorageUsage::default(),
+        |acc, (_, v)| StorageUsage {
+            keys: acc.keys + 1,
+            bytes: acc.bytes + v.size(),
+        },
+    )
+}
+
 pub struct MockStorageService {
     data: RefCell<HashMap<String, StorageValue>>,
+    // 未提交的blob写入会话，句柄 -> (key, 已写入内容)
+    blob_sessions: RefCell<HashMap<usize, (String, Vec<u8>)>>,
+    blob_next_handle: RefCell<usize>,
 }
 
 impl MockStorageService {
     pub fn new() -> Self {
         Self {
             data: RefCell::new(HashMap::new()),
+            blob_sessions: RefCell::new(HashMap::new()),
+            blob_next_handle: RefCell::new(0),
         }
     }
 }
@@ -31,15 +46,23 @@ impl Node for MockStorageService {
                     StorageMessage::GetResponse(data.get(&k).cloned().unwrap_or(StorageValue::None))
                 }
                 StorageMessage::SetRequest(k, v) => {
-                    match v {
-                        StorageValue::None => {
-                            data.remove(&k);
-                        }
-                        v => {
-                            data.insert(k, v);
+                    let old_size = data.get(&k).map(StorageValue::size).unwrap_or(0);
+                    match check_storage_quota(&k, old_size, v.size(), |prefix| {
+                        usage(&data, prefix).bytes
+                    }) {
+                        Ok(()) => {
+                            match v {
+                                StorageValue::None => {
+                                    data.remove(&k);
+                                }
+                                v => {
+                                    data.insert(k, v);
+                                }
+                            }
+                            StorageMessage::SetResponse
                         }
+                        Err(e) => StorageMessage::Error(e),
                     }
-                    StorageMessage::SetResponse
                 }
                 StorageMessage::ListKeysRequest(prefix) => StorageMessage::ListKeysResponse(
                     data.keys()
@@ -47,6 +70,81 @@ impl Node for MockStorageService {
                         .map(|x| x.into())
                         .collect(),
                 ),
+                StorageMessage::BlobOpenRequest(k) => {
+                    let mut handle = self.blob_next_handle.borrow_mut();
+                    *handle += 1;
+                    self.blob_sessions
+                        .borrow_mut()
+                        .insert(*handle, (k, Vec::new()));
+                    StorageMessage::BlobOpenResponse(*handle)
+                }
+                StorageMessage::BlobWriteRequest(handle, chunk) => {
+                    match self.blob_sessions.borrow_mut().get_mut(&handle) {
+                        Some((_, buf)) => {
+                            buf.extend(chunk.0);
+                            StorageMessage::BlobWriteResponse
+                        }
+                        None => StorageMessage::Error(StorageError::Other(format!(
+                            "blob session {handle} not found"
+                        ))),
+                    }
+                }
+                StorageMessage::BlobCommitRequest(handle) => {
+                    match self.blob_sessions.borrow_mut().remove(&handle) {
+                        Some((k, buf)) => {
+                            let old_size = data.get(&k).map(StorageValue::size).unwrap_or(0);
+                            match check_storage_quota(&k, old_size, buf.len(), |prefix| {
+                                usage(&data, prefix).bytes
+                            }) {
+                                Ok(()) => {
+                                    data.insert(k, StorageValue::Bytes(Bytes(buf)));
+                                    StorageMessage::BlobCommitResponse
+                                }
+                                Err(e) => StorageMessage::Error(e),
+                            }
+                        }
+                        None => StorageMessage::Error(StorageError::Other(format!(
+                            "blob session {handle} not found"
+                        ))),
+                    }
+                }
+                // 会话可能已被提交或放弃，重复放弃不报错
+                StorageMessage::BlobAbortRequest(handle) => {
+                    self.blob_sessions.borrow_mut().remove(&handle);
+                    StorageMessage::BlobAbortResponse
+                }
+                StorageMessage::BlobReadRequest(k, offset, len) => match data.get(&k) {
+                    Some(StorageValue::Bytes(bs)) => {
+                        let start = offset.min(bs.0.len());
+                        let end = offset.saturating_add(len).min(bs.0.len());
+                        StorageMessage::BlobReadResponse(Bytes(bs.0[start..end].to_vec()))
+                    }
+                    Some(m) => StorageMessage::Error(StorageError::TypeError(format!(
+                        "{k} is not a blob: {m:?}"
+                    ))),
+                    None => StorageMessage::Error(StorageError::Other(format!("{k} not found"))),
+                },
+                StorageMessage::BlobSizeRequest(k) => {
+                    StorageMessage::BlobSizeResponse(match data.get(&k) {
+                        Some(StorageValue::Bytes(bs)) => Some(bs.0.len()),
+                        _ => None,
+                    })
+                }
+                StorageMessage::UsageRequest(prefix) => {
+                    StorageMessage::UsageResponse(usage(&data, &prefix))
+                }
+                StorageMessage::CapacityRequest => {
+                    StorageMessage::CapacityResponse(StorageCapacity {
+                        total: None,
+                        used: usage(&data, "").bytes,
+                        free: None,
+                    })
+                }
+                StorageMessage::DeletePrefixRequest(prefix) => {
+                    let n = data.len();
+                    data.retain(|k, _| !k.starts_with(&prefix));
+                    StorageMessage::DeletePrefixResponse(n - data.len())
+                }
                 m => panic!("unexcepted message {m:?}"),
             }));
         }
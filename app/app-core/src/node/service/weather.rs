@@ -1,54 +1,115 @@
-use std::{rc::Rc, time::Duration};
+use std::{cell::RefCell, rc::Rc, time::Duration};
 
-use time::OffsetDateTime;
+use log::error;
+use serde::{de::DeserializeOwned, Serialize};
+use time::{OffsetDateTime, UtcOffset};
 
-use crate::proto::*;
+use crate::{proto::*, storage::WeatherStorage};
 
+mod backoff;
 mod common;
 mod geo;
+mod open_meteo;
+mod provider;
+mod qweather;
+mod server;
 mod weather;
 
+use backoff::Backoff;
+use common::Callback;
+use provider::{get_provider, WeatherProvider};
+
 type Result<T> = std::result::Result<T, WeatherError>;
 
-pub struct WeatherService {}
+/// 缓存的名称与有效期，存储位置见WeatherStorage::cache_key
+struct Cache<T> {
+    name: &'static str,
+    max_age: Duration,
+    updated_time: fn(&T) -> OffsetDateTime,
+    response: fn(T) -> WeatherMessage,
+}
+
+/// 检查灾害预警的间隔
+const WARNING_POLL_INTERVAL: Duration = Duration::from_secs(60 * 10);
+
+/// 启动后首次检查预警的延迟，等待网络连接
+const WARNING_FIRST_POLL_DELAY: Duration = Duration::from_secs(60);
+
+/// 预警提示音，高低音交替，与闹钟区分
+const WARNING_TONE: [(ToneFrequency, u16); 6] = [
+    (1800, 300),
+    (1200, 300),
+    (1800, 300),
+    (1200, 300),
+    (1800, 300),
+    (1200, 300),
+];
+
+pub struct WeatherService {
+    warning_timer: slint::Timer,
+    backoff: Rc<RefCell<Backoff>>,
+}
 
 impl WeatherService {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            warning_timer: Default::default(),
+            backoff: Default::default(),
+        }
     }
 
-    fn get_key(ctx: Rc<dyn Context>) -> Result<String> {
-        ipc::StorageClient(ctx.clone())
-            .get("weather/key".into())
-            .map_err(WeatherError::StorageError)?
-            .as_str()
-            .ok_or(WeatherError::MissingKey)
+    /// None表示首选位置
+    fn get_location(ctx: Rc<dyn Context>, location_id: Option<u32>) -> Result<Location> {
+        let mut locations = WeatherStorage(ipc::StorageClient(ctx))
+            .get_locations()?
+            .into_iter();
+        match location_id {
+            Some(id) => locations.find(|x| x.location_id == id),
+            None => locations.next(),
+        }
+        .ok_or(WeatherError::MissingLocation)
     }
 
-    fn get_location(ctx: Rc<dyn Context>) -> Result<Location> {
-        let s = ipc::StorageClient(ctx.clone())
-            .get("weather/location".into())
-            .map_err(WeatherError::StorageError)?
-            .as_str()
-            .ok_or(WeatherError::MissingLocation)?;
-        serde_json::from_str(&s).map_err(|e| WeatherError::SerdeError(format!("{e:?}")))
+    fn storage_result(
+        ctx: Rc<dyn Context>,
+        f: impl FnOnce(WeatherStorage) -> Result<WeatherMessage>,
+    ) -> HandleResult {
+        Self::handle_error(
+            f(WeatherStorage(ipc::StorageClient(ctx)))
+                .map(|x| HandleResult::Finish(Message::Weather(x))),
+        )
     }
 
-    fn get_now_weather(seq: usize, ctx: Rc<dyn Context>) -> Result<HandleResult> {
+    /// 缓存未过期时直接返回，否则从数据源获取并更新缓存。
+    /// 获取失败或处于退避期间时返回过期的缓存，由调用方根据更新时间判断是否过期
+    fn get_cached<T, F>(
+        &self,
+        seq: usize,
+        ctx: Rc<dyn Context>,
+        location_id: Option<u32>,
+        cache: Cache<T>,
+        fetch: F,
+    ) -> Result<HandleResult>
+    where
+        T: Serialize + DeserializeOwned + 'static,
+        F: FnOnce(&dyn WeatherProvider, Rc<dyn Context>, &Location, Callback<T>),
+    {
+        let location = Self::get_location(ctx.clone(), location_id)?;
+        let key = WeatherStorage::cache_key(location.location_id, cache.name);
+
         // 探测缓存
+        let mut stale = None;
         if let Some(x) = ipc::StorageClient(ctx.clone())
-            .get("weather/cache/now_weather".into())
+            .get(key.clone())
             .map_err(WeatherError::StorageError)?
             .as_str()
         {
-            match serde_json::from_str::<NowWeather>(&x) {
+            match serde_json::from_str::<T>(&x) {
                 Ok(x) => {
-                    // https://dev.qweather.com/docs/best-practices/cache/ 缓存时间10min
-                    if OffsetDateTime::now_utc() - x.updated_time <= Duration::from_secs(60 * 10) {
-                        return Ok(HandleResult::Finish(Message::Weather(
-                            WeatherMessage::GetNowWeatherResponse(x),
-                        )));
+                    if OffsetDateTime::now_utc() - (cache.updated_time)(&x) <= cache.max_age {
+                        return Ok(HandleResult::Finish(Message::Weather((cache.response)(x))));
                     }
+                    stale = Some(x);
                 }
                 Err(e) => {
                     return Ok(HandleResult::Finish(Message::Weather(
@@ -60,28 +121,49 @@ impl WeatherService {
             };
         }
 
-        // API获取数据
-        weather::WeatherQueryInput {
-            location: Self::get_location(ctx.clone())?.location_id.to_string(),
-            key: Self::get_key(ctx.clone())?,
+        if !self.backoff.borrow().ready() {
+            if let Some(x) = stale {
+                return Ok(HandleResult::Finish(Message::Weather((cache.response)(x))));
+            }
         }
-        .request_now_weather(
+
+        let provider = get_provider(ctx.clone())?;
+        let backoff = self.backoff.clone();
+        fetch(
+            provider.as_ref(),
             ctx.clone(),
+            &location,
             Box::new(move |r| {
                 ctx.async_ready(
                     seq,
-                    Message::Weather(match (move || r?.try_into())() {
+                    Message::Weather(match r {
                         Ok(x) => {
+                            backoff.borrow_mut().succeeded();
                             if let Err(e) = ipc::StorageClient(ctx.clone()).set(
-                                "weather/cache/now_weather".into(),
+                                key,
                                 StorageValue::String(serde_json::to_string(&x).unwrap()),
                             ) {
                                 WeatherMessage::Error(WeatherError::StorageError(e))
                             } else {
-                                WeatherMessage::GetNowWeatherResponse(x)
+                                (cache.response)(x)
+                            }
+                        }
+                        // 数据源不支持时不需要重试
+                        Err(WeatherError::Unsupported) => {
+                            WeatherMessage::Error(WeatherError::Unsupported)
+                        }
+                        Err(e) => {
+                            let mut backoff = backoff.borrow_mut();
+                            backoff.failed();
+                            // 离线已在界面上标记
+                            if !matches!(e, WeatherError::Offline) && backoff.should_notify() {
+                                Self::notify_failure(ctx.clone(), &e);
+                            }
+                            match stale {
+                                Some(x) => (cache.response)(x),
+                                None => WeatherMessage::Error(e),
                             }
                         }
-                        Err(e) => WeatherMessage::Error(e),
                     }),
                 )
             }),
@@ -89,125 +171,278 @@ impl WeatherService {
         Ok(HandleResult::Pending)
     }
 
-    fn get_forecast_weather(seq: usize, ctx: Rc<dyn Context>) -> Result<HandleResult> {
-        // 探测缓存
-        if let Some(x) = ipc::StorageClient(ctx.clone())
-            .get("weather/cache/forecast_weather".into())
-            .map_err(WeatherError::StorageError)?
-            .as_str()
-        {
-            match serde_json::from_str::<ForecastWeather>(&x) {
-                Ok(x) => {
-                    // 缓存时间1h https://dev.qweather.com/docs/best-practices/cache/
-                    if OffsetDateTime::now_utc() - x.updated_time <= Duration::from_secs(60 * 60) {
-                        return Ok(HandleResult::Finish(Message::Weather(
-                            WeatherMessage::GetForecastWeatherResponse(x),
-                        )));
-                    }
-                }
-                Err(e) => {
-                    return Ok(HandleResult::Finish(Message::Weather(
-                        WeatherMessage::Error(WeatherError::SerdeError(format!("{e:?}"))),
-                    )));
-                }
-            };
-        }
+    fn get_now_weather(
+        &self,
+        seq: usize,
+        ctx: Rc<dyn Context>,
+        location_id: Option<u32>,
+    ) -> Result<HandleResult> {
+        // https://dev.qweather.com/docs/best-practices/cache/ 缓存时间10min
+        let cache = Cache {
+            name: "now_weather",
+            max_age: Duration::from_secs(60 * 10),
+            updated_time: |x: &NowWeather| x.updated_time,
+            response: WeatherMessage::GetNowWeatherResponse,
+        };
+        self.get_cached(seq, ctx, location_id, cache, |p, ctx, loc, cb| {
+            p.now_weather(ctx, loc, cb)
+        })
+    }
+
+    fn get_forecast_weather(
+        &self,
+        seq: usize,
+        ctx: Rc<dyn Context>,
+        location_id: Option<u32>,
+    ) -> Result<HandleResult> {
+        // 缓存时间1h https://dev.qweather.com/docs/best-practices/cache/
+        let cache = Cache {
+            name: "forecast_weather",
+            max_age: Duration::from_secs(60 * 60),
+            updated_time: |x: &ForecastWeather| x.updated_time,
+            response: WeatherMessage::GetForecastWeatherResponse,
+        };
+        self.get_cached(seq, ctx, location_id, cache, |p, ctx, loc, cb| {
+            p.forecast_weather(ctx, loc, cb)
+        })
+    }
+
+    fn get_now_air_quality(
+        &self,
+        seq: usize,
+        ctx: Rc<dyn Context>,
+        location_id: Option<u32>,
+    ) -> Result<HandleResult> {
+        // https://dev.qweather.com/docs/best-practices/cache/ 缓存时间30min
+        let cache = Cache {
+            name: "now_air_quality",
+            max_age: Duration::from_secs(60 * 30),
+            updated_time: |x: &NowAirQuality| x.updated_time,
+            response: WeatherMessage::GetNowAirQualityResponse,
+        };
+        self.get_cached(seq, ctx, location_id, cache, |p, ctx, loc, cb| {
+            p.now_air_quality(ctx, loc, cb)
+        })
+    }
+
+    fn get_air_quality_forecast(
+        &self,
+        seq: usize,
+        ctx: Rc<dyn Context>,
+        location_id: Option<u32>,
+    ) -> Result<HandleResult> {
+        // 空气质量预报每天更新数次
+        let cache = Cache {
+            name: "air_quality_forecast",
+            max_age: Duration::from_secs(60 * 60 * 2),
+            updated_time: |x: &AirQualityForecast| x.updated_time,
+            response: WeatherMessage::GetAirQualityForecastResponse,
+        };
+        self.get_cached(seq, ctx, location_id, cache, |p, ctx, loc, cb| {
+            p.air_quality_forecast(ctx, loc, cb)
+        })
+    }
+
+    fn get_hourly_forecast(
+        &self,
+        seq: usize,
+        ctx: Rc<dyn Context>,
+        location_id: Option<u32>,
+    ) -> Result<HandleResult> {
+        // 缓存时间1h https://dev.qweather.com/docs/best-practices/cache/
+        let cache = Cache {
+            name: "hourly_forecast",
+            max_age: Duration::from_secs(60 * 60),
+            updated_time: |x: &HourlyForecast| x.updated_time,
+            response: WeatherMessage::GetHourlyForecastResponse,
+        };
+        self.get_cached(seq, ctx, location_id, cache, |p, ctx, loc, cb| {
+            p.hourly_forecast(ctx, loc, cb)
+        })
+    }
 
-        weather::WeatherQueryInput {
-            location: Self::get_location(ctx.clone())?.location_id.to_string(),
-            key: Self::get_key(ctx.clone())?,
+    fn get_warnings(
+        &self,
+        seq: usize,
+        ctx: Rc<dyn Context>,
+        location_id: Option<u32>,
+    ) -> Result<HandleResult> {
+        // 缓存时间10min https://dev.qweather.com/docs/best-practices/cache/
+        let cache = Cache {
+            name: "warnings",
+            max_age: Duration::from_secs(60 * 10),
+            updated_time: |x: &WeatherWarnings| x.updated_time,
+            response: WeatherMessage::GetWarningsResponse,
+        };
+        self.get_cached(seq, ctx, location_id, cache, |p, ctx, loc, cb| {
+            p.warnings(ctx, loc, cb)
+        })
+    }
+
+    /// 本地计算，旧版保存的位置没有经纬度时先通过数据源查询并保存
+    fn get_astronomy(
+        seq: usize,
+        ctx: Rc<dyn Context>,
+        location_id: Option<u32>,
+    ) -> Result<HandleResult> {
+        let location = Self::get_location(ctx.clone(), location_id)?;
+        // 与首页时钟相同使用东八区
+        let now = OffsetDateTime::now_utc().to_offset(UtcOffset::from_hms(8, 0, 0).unwrap());
+        if let Some(coord) = location.coord {
+            return Ok(HandleResult::Finish(Message::Weather(
+                WeatherMessage::GetAstronomyResponse(astronomy::astronomy(&coord, now)),
+            )));
         }
-        .request_forecast_weather(
+        get_provider(ctx.clone())?.coord(
             ctx.clone(),
+            &location,
             Box::new(move |r| {
+                let r = r.and_then(|coord| {
+                    WeatherStorage(ipc::StorageClient(ctx.clone()))
+                        .set_coord(location.location_id, coord)?;
+                    Ok(astronomy::astronomy(&coord, now))
+                });
                 ctx.async_ready(
                     seq,
-                    Message::Weather(match (move || r?.try_into())() {
-                        Ok(x) => {
-                            if let Err(e) = ipc::StorageClient(ctx.clone()).set(
-                                "weather/cache/forecast_weather".into(),
-                                StorageValue::String(serde_json::to_string(&x).unwrap()),
-                            ) {
-                                WeatherMessage::Error(WeatherError::StorageError(e))
-                            } else {
-                                WeatherMessage::GetForecastWeatherResponse(x)
-                            }
-                        }
+                    Message::Weather(match r {
+                        Ok(x) => WeatherMessage::GetAstronomyResponse(x),
                         Err(e) => WeatherMessage::Error(e),
                     }),
-                )
+                );
             }),
         );
         Ok(HandleResult::Pending)
     }
 
-    fn city_lookup(seq: usize, ctx: Rc<dyn Context>, location: String) -> Result<HandleResult> {
-        geo::GeoCityLookupInput {
-            location,
-            key: Self::get_key(ctx.clone())?,
-            number: Some(5),
+    fn poll_warnings(ctx: Rc<dyn Context>) {
+        match WeatherStorage(ipc::StorageClient(ctx.clone())).get_locations() {
+            Ok(x) => Self::poll_location_warnings(ctx, x, Vec::new(), true),
+            Err(e) => error!("poll weather warnings err: {e:?}"),
         }
-        .request(
-            ctx.clone(),
+    }
+
+    /// 逐个位置查询，全部完成后统一通知，complete表示所有位置都查询成功
+    fn poll_location_warnings(
+        ctx: Rc<dyn Context>,
+        mut pending: Vec<Location>,
+        mut found: Vec<(String, WeatherWarning)>,
+        complete: bool,
+    ) {
+        let Some(location) = pending.pop() else {
+            Self::notify_warnings(ctx, found, complete);
+            return;
+        };
+        let location_id = Some(location.location_id);
+        ipc::WeatherClient(ctx.clone()).get_warnings(
+            location_id,
             Box::new(move |r| {
-                ctx.async_ready(
-                    seq,
-                    Message::Weather(match (move || r?.try_into())() {
-                        Ok(x) => WeatherMessage::CityLookUpResponse(x),
-                        Err(e) => WeatherMessage::Error(e),
-                    }),
-                );
+                let ok = match r {
+                    Ok(x) => {
+                        found.extend(
+                            x.warnings
+                                .into_iter()
+                                .map(|x| (location.location.clone(), x)),
+                        );
+                        true
+                    }
+                    // 数据源不支持预警时视为没有预警
+                    Err(WeatherError::Unsupported) => true,
+                    // 尚未配置时不提示
+                    Err(
+                        WeatherError::Offline
+                        | WeatherError::MissingKey
+                        | WeatherError::MissingLocation,
+                    ) => false,
+                    Err(e) => {
+                        error!("poll weather warnings err: {e:?}");
+                        false
+                    }
+                };
+                Self::poll_location_warnings(ctx, pending, found, complete && ok);
             }),
         );
-        Ok(HandleResult::Pending)
     }
 
-    fn get_now_air_quality(seq: usize, ctx: Rc<dyn Context>) -> Result<HandleResult> {
-        // 探测缓存
-        if let Some(x) = ipc::StorageClient(ctx.clone())
-            .get("weather/cache/now_air_quality".into())
-            .map_err(WeatherError::StorageError)?
-            .as_str()
-        {
-            match serde_json::from_str::<NowAirQuality>(&x) {
-                Ok(x) => {
-                    // https://dev.qweather.com/docs/best-practices/cache/ 缓存时间30min
-                    if OffsetDateTime::now_utc() - x.updated_time <= Duration::from_secs(60 * 30) {
-                        return Ok(HandleResult::Finish(Message::Weather(
-                            WeatherMessage::GetNowAirQualityResponse(x),
-                        )));
-                    }
-                }
-                Err(e) => {
-                    return Ok(HandleResult::Finish(Message::Weather(
-                        WeatherMessage::Error(WeatherError::SerdeError(format!("{e:?}"))),
-                    )));
-                }
-            };
+    /// 新的预警通过通知与提示音提醒，按id去重，已通知的id保存在存储中，重启后不会重复提醒
+    fn notify_warnings(
+        ctx: Rc<dyn Context>,
+        warnings: Vec<(String, WeatherWarning)>,
+        complete: bool,
+    ) {
+        let stg = WeatherStorage(ipc::StorageClient(ctx.clone()));
+        let notified = stg.get_notified_warnings().unwrap_or_default();
+        let new_warnings: Vec<_> = warnings
+            .iter()
+            .filter(|(_, x)| !notified.contains(&x.id))
+            .collect();
+        // 只保留生效中的预警，避免记录无限增长；部分位置查询失败时保留原有记录，避免重复提醒
+        let mut ids: Vec<_> = if complete {
+            Vec::new()
+        } else {
+            notified.clone()
+        };
+        for (_, x) in warnings.iter() {
+            if !ids.contains(&x.id) {
+                ids.push(x.id.clone());
+            }
         }
-        weather::WeatherQueryInput {
-            location: Self::get_location(ctx.clone())?.location_id.to_string(),
-            key: Self::get_key(ctx.clone())?,
+        if ids != notified {
+            if let Err(e) = stg.set_notified_warnings(&ids) {
+                error!("save notified warnings err: {e:?}");
+            }
         }
-        .request_now_air_quality(
+        let Some((location, first)) = new_warnings.first() else {
+            return;
+        };
+        let text = match new_warnings.len() {
+            1 => format!("{location}: {}", first.title),
+            n => format!("{location}: {}\n+{} more", first.title, n - 1),
+        };
+        ipc::NotifactionClient(ctx.clone()).show(
+            30_000,
+            NotifactionContent {
+                title: Some(first.type_name.clone()),
+                text: Some(text),
+                icon: None,
+            },
+            Box::new(|()| {}),
+        );
+        ipc::BuzzerClient(ctx).tone_series(
+            ToneSeries(
+                WARNING_TONE
+                    .iter()
+                    .map(|(f, d)| (*f, ToneDuration(*d)))
+                    .collect(),
+            ),
+            Box::new(|_| {}),
+        );
+    }
+
+    fn notify_failure(ctx: Rc<dyn Context>, e: &WeatherError) {
+        error!("weather update failed repeatedly: {e:?}");
+        ipc::NotifactionClient(ctx).show(
+            5000,
+            NotifactionContent {
+                title: Some("Weather".into()),
+                text: Some(format!("Update failed, showing cached data\n{e:?}")),
+                icon: None,
+            },
+            Box::new(|()| {}),
+        );
+    }
+
+    fn city_lookup(seq: usize, ctx: Rc<dyn Context>, location: String) -> Result<HandleResult> {
+        get_provider(ctx.clone())?.city_lookup(
             ctx.clone(),
+            location,
             Box::new(move |r| {
                 ctx.async_ready(
                     seq,
-                    Message::Weather(match (move || r?.try_into())() {
-                        Ok(x) => {
-                            if let Err(e) = ipc::StorageClient(ctx.clone()).set(
-                                "weather/cache/now_air_quality".into(),
-                                StorageValue::String(serde_json::to_string(&x).unwrap()),
-                            ) {
-                                WeatherMessage::Error(WeatherError::StorageError(e))
-                            } else {
-                                WeatherMessage::GetNowAirQualityResponse(x)
-                            }
-                        }
+                    Message::Weather(match r {
+                        Ok(x) => WeatherMessage::CityLookUpResponse(x),
                         Err(e) => WeatherMessage::Error(e),
                     }),
-                )
+                );
             }),
         );
         Ok(HandleResult::Pending)
@@ -233,26 +468,86 @@ impl Node for WeatherService {
     ) -> HandleResult {
         let seq = msg.seq;
         match msg.body {
+            Message::Lifecycle(LifecycleMessage::Init) => {
+                ctx.subscribe_topic(TopicName::Connectivity);
+                slint::Timer::single_shot(WARNING_FIRST_POLL_DELAY, {
+                    let ctx = ctx.clone();
+                    move || Self::poll_warnings(ctx)
+                });
+                self.warning_timer.start(
+                    slint::TimerMode::Repeated,
+                    WARNING_POLL_INTERVAL,
+                    move || Self::poll_warnings(ctx.clone()),
+                );
+                return HandleResult::Finish(Message::Empty);
+            }
+            // 恢复联网后立即检查，避免错过离线期间发布的预警
+            Message::Http(HttpMessage::Connectivity(ConnectivityState::Online)) => {
+                self.backoff.borrow_mut().retry_now();
+                Self::poll_warnings(ctx);
+                return HandleResult::Finish(Message::Empty);
+            }
             Message::Weather(msg) => match msg {
                 WeatherMessage::CityLookUpRequest(q) => {
                     return Self::handle_error(Self::city_lookup(seq, ctx, q));
                 }
-                WeatherMessage::GetForecastWeatherRequest => {
-                    return Self::handle_error(Self::get_forecast_weather(seq, ctx));
+                WeatherMessage::GetForecastWeatherRequest(id) => {
+                    return Self::handle_error(self.get_forecast_weather(seq, ctx, id));
+                }
+                WeatherMessage::GetNowWeatherRequest(id) => {
+                    return Self::handle_error(self.get_now_weather(seq, ctx, id));
+                }
+                WeatherMessage::GetNowAirQualityRequest(id) => {
+                    return Self::handle_error(self.get_now_air_quality(seq, ctx, id));
                 }
-                WeatherMessage::GetNowWeatherRequest => {
-                    return Self::handle_error(Self::get_now_weather(seq, ctx));
+                WeatherMessage::GetAirQualityForecastRequest(id) => {
+                    return Self::handle_error(self.get_air_quality_forecast(seq, ctx, id));
                 }
-                WeatherMessage::GetNowAirQualityRequest => {
-                    return Self::handle_error(Self::get_now_air_quality(seq, ctx));
+                WeatherMessage::GetHourlyForecastRequest(id) => {
+                    return Self::handle_error(self.get_hourly_forecast(seq, ctx, id));
+                }
+                WeatherMessage::GetWarningsRequest(id) => {
+                    return Self::handle_error(self.get_warnings(seq, ctx, id));
+                }
+                WeatherMessage::GetAstronomyRequest(id) => {
+                    return Self::handle_error(Self::get_astronomy(seq, ctx, id));
                 }
                 WeatherMessage::GetLocationRequest => {
-                    return Self::handle_error(Self::get_location(ctx).map(|x| {
+                    return Self::handle_error(Self::get_location(ctx, None).map(|x| {
                         HandleResult::Finish(Message::Weather(WeatherMessage::GetLocationResponse(
                             x,
                         )))
                     }));
                 }
+                WeatherMessage::SetLocationRequest(x) => {
+                    return Self::storage_result(ctx, |stg| {
+                        stg.set_location(x)?;
+                        Ok(WeatherMessage::SetLocationResponse)
+                    });
+                }
+                WeatherMessage::ListLocationsRequest => {
+                    return Self::storage_result(ctx, |stg| {
+                        Ok(WeatherMessage::ListLocationsResponse(stg.get_locations()?))
+                    });
+                }
+                WeatherMessage::AddLocationRequest(x) => {
+                    return Self::storage_result(ctx, |stg| {
+                        stg.add_location(x)?;
+                        Ok(WeatherMessage::AddLocationResponse)
+                    });
+                }
+                WeatherMessage::RemoveLocationRequest(id) => {
+                    return Self::storage_result(ctx, |stg| {
+                        stg.remove_location(id)?;
+                        Ok(WeatherMessage::RemoveLocationResponse)
+                    });
+                }
+                WeatherMessage::MoveLocationRequest { location_id, index } => {
+                    return Self::storage_result(ctx, |stg| {
+                        stg.move_location(location_id, index)?;
+                        Ok(WeatherMessage::MoveLocationResponse)
+                    });
+                }
                 _ => {}
             },
             _ => {}
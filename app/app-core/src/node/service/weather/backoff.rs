@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use time::OffsetDateTime;
+
+/// 首次失败后的重试间隔，之后每次失败翻倍
+const BASE_DELAY: Duration = Duration::from_secs(60);
+
+/// 最大重试间隔
+const MAX_DELAY: Duration = Duration::from_secs(60 * 30);
+
+/// 连续失败该轮数后通知一次
+const NOTIFY_AFTER: u32 = 3;
+
+/// 数据源请求失败后的退避，各类天气数据共用，成功一次即重置
+#[derive(Default)]
+pub struct Backoff {
+    /// 连续失败的轮数
+    failures: u32,
+    retry_at: Option<OffsetDateTime>,
+    notified: bool,
+}
+
+impl Backoff {
+    /// 是否可以请求数据源
+    pub fn ready(&self) -> bool {
+        !matches!(self.retry_at, Some(x) if OffsetDateTime::now_utc() < x)
+    }
+
+    /// 同时发出的多个请求失败只计为一轮
+    pub fn failed(&mut self) {
+        if !self.ready() {
+            return;
+        }
+        self.failures += 1;
+        let delay = BASE_DELAY
+            .saturating_mul(1 << (self.failures - 1).min(16))
+            .min(MAX_DELAY);
+        self.retry_at = Some(OffsetDateTime::now_utc() + delay);
+    }
+
+    pub fn succeeded(&mut self) {
+        *self = Self::default();
+    }
+
+    /// 恢复联网后立即重试，保留失败次数
+    pub fn retry_now(&mut self) {
+        self.retry_at = None;
+    }
+
+    /// 连续失败达到阈值时返回true，直到成功前只返回一次
+    pub fn should_notify(&mut self) -> bool {
+        if self.failures >= NOTIFY_AFTER && !self.notified {
+            self.notified = true;
+            return true;
+        }
+        false
+    }
+}
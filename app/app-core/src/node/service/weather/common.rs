@@ -1,7 +1,7 @@
-use std::{fmt::Display, str::FromStr};
+use std::{fmt::Display, rc::Rc, str::FromStr};
 
 use crate::proto::*;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use time::{format_description::well_known::Iso8601, OffsetDateTime};
 
 #[derive(Debug, Clone, Copy, Serialize)]
@@ -72,3 +72,25 @@ pub struct RgbColor {
     pub blue: u8,
     pub alpha: u8,
 }
+
+pub type Callback<T> = Box<dyn FnOnce(Result<T, WeatherError>)>;
+
+/// 请求并解析JSON响应
+pub fn request_json<T: DeserializeOwned + 'static>(
+    ctx: Rc<dyn Context>,
+    req: HttpRequest,
+    callback: Callback<T>,
+) {
+    ipc::HttpClient(ctx).request(
+        req,
+        Box::new(|r| {
+            callback(match r {
+                Ok(x) => x
+                    .body
+                    .deserialize_by_json()
+                    .map_err(|e| WeatherError::SerdeError(format!("{e}"))),
+                Err(e) => Err(e.into()),
+            });
+        }),
+    );
+}
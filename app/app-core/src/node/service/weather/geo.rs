@@ -11,12 +11,14 @@ pub struct GeoCityLookupInput {
     pub location: String,
     pub key: String,
     pub number: Option<u8>,
+    pub lang: String,
 }
 
 impl GeoCityLookupInput {
     fn to_url(&self) -> String {
         format!(
-            "https://geoapi.qweather.com/v2/city/lookup?gzip=n&lang=en&key={}&location={}&number={}",
+            "https://geoapi.qweather.com/v2/city/lookup?gzip=n&lang={}&key={}&location={}&number={}",
+            self.lang,
             self.key,
             self.location,
             self.number.unwrap_or(5),
@@ -29,17 +31,14 @@ impl GeoCityLookupInput {
         callback: Box<dyn FnOnce(Result<GeoCityLookupOutput, WeatherError>)>,
     ) {
         HttpClient(ctx).request(
-            HttpRequest {
-                method: HttpRequestMethod::Get,
-                url: self.to_url(),
-            },
+            HttpRequest::get(self.to_url()).cached(),
             Box::new(|r| {
                 callback(match r {
                     Ok(x) => x
                         .body
                         .deserialize_by_json()
                         .map_err(|e| WeatherError::SerdeError(format!("{e}"))),
-                    Err(e) => Err(WeatherError::HttpError(e)),
+                    Err(e) => Err(e.into()),
                 });
             }),
         );
@@ -51,6 +50,8 @@ pub struct GeoCityLookupItem {
     pub name: String,
     pub id: String,
     pub country: String,
+    pub lat: String,
+    pub lon: String,
 }
 
 impl From<GeoCityLookupItem> for proto::CityLookUpItem {
@@ -59,6 +60,10 @@ impl From<GeoCityLookupItem> for proto::CityLookUpItem {
             name: val.name,
             id: val.id,
             country: val.country,
+            coord: match (val.lat.parse(), val.lon.parse()) {
+                (Ok(lat), Ok(lon)) => Some(proto::Coord { lat, lon }),
+                _ => None,
+            },
         }
     }
 }
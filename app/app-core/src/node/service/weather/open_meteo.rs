@@ -0,0 +1,629 @@
+use std::rc::Rc;
+
+use crate::proto::*;
+use serde::Deserialize;
+use time::{OffsetDateTime, UtcOffset};
+
+use super::{
+    common::{request_json, Callback},
+    provider::WeatherProvider,
+};
+
+const GEOCODING_URL: &str = "https://geocoding-api.open-meteo.com/v1";
+const FORECAST_URL: &str = "https://api.open-meteo.com/v1/forecast";
+const AIR_QUALITY_URL: &str = "https://air-quality-api.open-meteo.com/v1/air-quality";
+
+/// WMO天气代码转换为和风天气的图标代码与文字描述
+fn wmo_weather(code: u8, is_day: bool, lang: Language) -> (u16, &'static str) {
+    let (icon, zh, en) = match (code, is_day) {
+        (0, true) => (100, "晴", "Sunny"),
+        (0, false) => (150, "晴", "Clear"),
+        (1, true) => (102, "少云", "Few Clouds"),
+        (1, false) => (152, "少云", "Few Clouds"),
+        (2, true) => (103, "多云", "Partly Cloudy"),
+        (2, false) => (153, "多云", "Partly Cloudy"),
+        (3, _) => (104, "阴", "Overcast"),
+        (45 | 48, _) => (501, "雾", "Foggy"),
+        (51 | 53 | 55, _) => (309, "毛毛雨", "Drizzle Rain"),
+        (56 | 57 | 66 | 67, _) => (313, "冻雨", "Freezing Rain"),
+        (61, _) => (305, "小雨", "Light Rain"),
+        (63, _) => (306, "中雨", "Moderate Rain"),
+        (65, _) => (307, "大雨", "Heavy Rain"),
+        (71 | 77, _) => (400, "小雪", "Light Snow"),
+        (73, _) => (401, "中雪", "Moderate Snow"),
+        (75, _) => (402, "大雪", "Heavy Snow"),
+        (80 | 81, true) => (300, "阵雨", "Shower Rain"),
+        (80 | 81, false) => (350, "阵雨", "Shower Rain"),
+        (82, _) => (301, "强阵雨", "Heavy Shower Rain"),
+        (85 | 86, true) => (407, "阵雪", "Snow Flurry"),
+        (85 | 86, false) => (457, "阵雪", "Snow Flurry"),
+        (95, _) => (302, "雷阵雨", "Thundershower"),
+        (96 | 99, _) => (304, "冰雹", "Hail"),
+        _ => (999, "未知", "Unknown"),
+    };
+    match lang {
+        Language::Zh => (icon, zh),
+        Language::En => (icon, en),
+    }
+}
+
+/// 美国AQI等级的名称与颜色
+fn us_aqi_category(value: u16, lang: Language) -> (&'static str, Rgb888Color) {
+    let (zh, en, color) = match value {
+        0..=50 => ("优", "Good", (0, 228, 0)),
+        51..=100 => ("良", "Moderate", (255, 255, 0)),
+        101..=150 => (
+            "对敏感人群不健康",
+            "Unhealthy for Sensitive Groups",
+            (255, 126, 0),
+        ),
+        151..=200 => ("不健康", "Unhealthy", (255, 0, 0)),
+        201..=300 => ("非常不健康", "Very Unhealthy", (143, 63, 151)),
+        _ => ("危险", "Hazardous", (126, 0, 35)),
+    };
+    match lang {
+        Language::Zh => (zh, color),
+        Language::En => (en, color),
+    }
+}
+
+/// 按美国AQI等级给出的健康影响与建议，来自AirNow
+fn us_aqi_health(value: u16, lang: Language) -> AirQualityHealth {
+    let (effect, general, sensitive) = match (value, lang) {
+        (0..=50, Language::Zh) => (
+            "空气质量令人满意，基本无空气污染",
+            "各类人群可正常活动",
+            "各类人群可正常活动",
+        ),
+        (0..=50, Language::En) => (
+            "Air quality is satisfactory and poses little or no risk",
+            "Enjoy outdoor activities",
+            "Enjoy outdoor activities",
+        ),
+        (51..=100, Language::Zh) => (
+            "空气质量可接受，极少数异常敏感人群可能受到影响",
+            "各类人群可正常活动",
+            "异常敏感人群应减少长时间或高强度的户外活动",
+        ),
+        (51..=100, Language::En) => (
+            "Air quality is acceptable, unusually sensitive people may be affected",
+            "Enjoy outdoor activities",
+            "Unusually sensitive people should reduce prolonged or heavy exertion outdoors",
+        ),
+        (101..=150, Language::Zh) => (
+            "敏感人群可能出现健康影响，一般人群影响不大",
+            "一般人群可正常活动",
+            "儿童、老人及心脏病、呼吸系统疾病患者应减少长时间或高强度的户外活动",
+        ),
+        (101..=150, Language::En) => (
+            "Members of sensitive groups may experience health effects",
+            "Outdoor activities are fine for most people",
+            "Reduce prolonged or heavy exertion outdoors",
+        ),
+        (151..=200, Language::Zh) => (
+            "一般人群可能出现健康影响，敏感人群影响更严重",
+            "减少长时间或高强度的户外活动",
+            "避免长时间或高强度的户外活动",
+        ),
+        (151..=200, Language::En) => (
+            "Some members of the general public may experience health effects",
+            "Reduce prolonged or heavy exertion outdoors",
+            "Avoid prolonged or heavy exertion outdoors",
+        ),
+        (201..=300, Language::Zh) => (
+            "所有人健康风险增加",
+            "避免长时间或高强度的户外活动",
+            "避免一切户外活动",
+        ),
+        (201..=300, Language::En) => (
+            "The risk of health effects is increased for everyone",
+            "Avoid prolonged or heavy exertion outdoors",
+            "Avoid all physical activity outdoors",
+        ),
+        (_, Language::Zh) => (
+            "紧急健康警报，所有人都可能受到影响",
+            "避免一切户外活动",
+            "留在室内并保持低活动量",
+        ),
+        (_, Language::En) => (
+            "Health warning of emergency conditions, everyone is likely to be affected",
+            "Avoid all physical activity outdoors",
+            "Remain indoors and keep activity levels low",
+        ),
+    };
+    AirQualityHealth {
+        effect: effect.into(),
+        general: general.into(),
+        sensitive: sensitive.into(),
+    }
+}
+
+/// 接口返回转换为proto中的类型，文字描述按语言在本地生成
+trait Convert<O> {
+    fn convert(self, lang: Language) -> Result<O, WeatherError>;
+}
+
+fn from_unix(t: i64) -> Result<OffsetDateTime, WeatherError> {
+    OffsetDateTime::from_unix_timestamp(t).map_err(|e| WeatherError::SerdeError(format!("{e}")))
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GeoItem {
+    id: u32,
+    name: String,
+    #[serde(default)]
+    country: String,
+    latitude: f32,
+    longitude: f32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct GeoSearchOutput {
+    /// 没有结果时不返回该字段
+    #[serde(default)]
+    results: Vec<GeoItem>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct CurrentWeather {
+    time: i64,
+    temperature_2m: f32,
+    relative_humidity_2m: f32,
+    weather_code: u8,
+    is_day: u8,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct CurrentWeatherOutput {
+    current: CurrentWeather,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct DailyWeather {
+    time: Vec<i64>,
+    weather_code: Vec<u8>,
+    temperature_2m_max: Vec<f32>,
+    temperature_2m_min: Vec<f32>,
+    relative_humidity_2m_mean: Vec<Option<f32>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct DailyWeatherOutput {
+    utc_offset_seconds: i32,
+    daily: DailyWeather,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct HourlyWeatherData {
+    time: Vec<i64>,
+    temperature_2m: Vec<f32>,
+    weather_code: Vec<u8>,
+    precipitation_probability: Vec<Option<u8>>,
+    is_day: Vec<u8>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct HourlyWeatherOutput {
+    utc_offset_seconds: i32,
+    hourly: HourlyWeatherData,
+}
+
+/// 浓度单位均为μg/m³，缺少数据时为null
+#[derive(Deserialize, Debug, Clone)]
+struct CurrentAirQuality {
+    time: i64,
+    us_aqi: Option<u16>,
+    pm2_5: Option<f32>,
+    pm10: Option<f32>,
+    ozone: Option<f32>,
+    nitrogen_dioxide: Option<f32>,
+    sulphur_dioxide: Option<f32>,
+    carbon_monoxide: Option<f32>,
+    us_aqi_pm2_5: Option<u16>,
+    us_aqi_pm10: Option<u16>,
+    us_aqi_ozone: Option<u16>,
+    us_aqi_nitrogen_dioxide: Option<u16>,
+    us_aqi_sulphur_dioxide: Option<u16>,
+    us_aqi_carbon_monoxide: Option<u16>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct AirQualityOutput {
+    current: CurrentAirQuality,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct HourlyAirQuality {
+    time: Vec<i64>,
+    us_aqi: Vec<Option<u16>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct AirQualityForecastOutput {
+    utc_offset_seconds: i32,
+    hourly: HourlyAirQuality,
+}
+
+impl Convert<NowWeather> for CurrentWeatherOutput {
+    fn convert(self, lang: Language) -> Result<NowWeather, WeatherError> {
+        let x = self.current;
+        let (icon, text) = wmo_weather(x.weather_code, x.is_day != 0, lang);
+        Ok(NowWeather {
+            updated_time: from_unix(x.time)?,
+            temp: x.temperature_2m.round() as _,
+            icon,
+            text: text.into(),
+            humidity: x.relative_humidity_2m.round() as _,
+            obs_time: None,
+        })
+    }
+}
+
+impl Convert<ForecastWeather> for DailyWeatherOutput {
+    fn convert(self, lang: Language) -> Result<ForecastWeather, WeatherError> {
+        let offset = UtcOffset::from_whole_seconds(self.utc_offset_seconds)
+            .map_err(|e| WeatherError::SerdeError(format!("{e}")))?;
+        let d = self.daily;
+        let mut daily = Vec::new();
+        for (i, t) in d.time.iter().enumerate() {
+            let field = |name: &str| WeatherError::MissingFieldError(format!("{name}[{i}]"));
+            let code = *d.weather_code.get(i).ok_or_else(|| field("weather_code"))?;
+            let (icon_day, text_day) = wmo_weather(code, true, lang);
+            let (icon_night, text_night) = wmo_weather(code, false, lang);
+            daily.push(ForecastOneDayWeather {
+                date: from_unix(*t)?.to_offset(offset).date(),
+                min_temp: d
+                    .temperature_2m_min
+                    .get(i)
+                    .ok_or_else(|| field("temperature_2m_min"))?
+                    .round() as _,
+                max_temp: d
+                    .temperature_2m_max
+                    .get(i)
+                    .ok_or_else(|| field("temperature_2m_max"))?
+                    .round() as _,
+                icon_day,
+                text_day: text_day.into(),
+                icon_night,
+                text_night: text_night.into(),
+                humidity: d
+                    .relative_humidity_2m_mean
+                    .get(i)
+                    .copied()
+                    .flatten()
+                    .unwrap_or_default()
+                    .round() as _,
+            });
+        }
+        Ok(ForecastWeather {
+            updated_time: OffsetDateTime::now_utc(),
+            daily,
+        })
+    }
+}
+
+impl Convert<HourlyForecast> for HourlyWeatherOutput {
+    fn convert(self, lang: Language) -> Result<HourlyForecast, WeatherError> {
+        let offset = UtcOffset::from_whole_seconds(self.utc_offset_seconds)
+            .map_err(|e| WeatherError::SerdeError(format!("{e}")))?;
+        let h = self.hourly;
+        let mut hourly = Vec::new();
+        for (i, t) in h.time.iter().enumerate() {
+            let field = |name: &str| WeatherError::MissingFieldError(format!("{name}[{i}]"));
+            let code = *h.weather_code.get(i).ok_or_else(|| field("weather_code"))?;
+            let is_day = *h.is_day.get(i).ok_or_else(|| field("is_day"))?;
+            let (icon, text) = wmo_weather(code, is_day != 0, lang);
+            hourly.push(HourlyWeather {
+                time: from_unix(*t)?.to_offset(offset),
+                temp: h
+                    .temperature_2m
+                    .get(i)
+                    .ok_or_else(|| field("temperature_2m"))?
+                    .round() as _,
+                icon,
+                text: text.into(),
+                pop: h.precipitation_probability.get(i).copied().flatten(),
+            });
+        }
+        Ok(HourlyForecast {
+            updated_time: OffsetDateTime::now_utc(),
+            hourly,
+        })
+    }
+}
+
+impl Convert<NowAirQuality> for AirQualityOutput {
+    fn convert(self, lang: Language) -> Result<NowAirQuality, WeatherError> {
+        let value = self.current.us_aqi.ok_or(WeatherError::MissingFieldError(
+            "missing field `us_aqi`".into(),
+        ))?;
+        let (category, color) = us_aqi_category(value, lang);
+        let x = self.current;
+        let pollutants: Vec<Pollutant> = [
+            ("pm2p5", "PM2.5", x.pm2_5, x.us_aqi_pm2_5),
+            ("pm10", "PM10", x.pm10, x.us_aqi_pm10),
+            ("o3", "O3", x.ozone, x.us_aqi_ozone),
+            ("no2", "NO2", x.nitrogen_dioxide, x.us_aqi_nitrogen_dioxide),
+            ("so2", "SO2", x.sulphur_dioxide, x.us_aqi_sulphur_dioxide),
+            ("co", "CO", x.carbon_monoxide, x.us_aqi_carbon_monoxide),
+        ]
+        .into_iter()
+        .filter_map(|(code, name, concentration, sub_index)| {
+            Some(Pollutant {
+                code: code.into(),
+                name: name.into(),
+                concentration: concentration?,
+                unit: "μg/m3".into(),
+                sub_index,
+            })
+        })
+        .collect();
+        // 美国标准中AQI超过50时分指数最高的为首要污染物
+        let primary_pollutant = if value > 50 {
+            pollutants
+                .iter()
+                .filter(|x| x.sub_index.is_some())
+                .max_by_key(|x| x.sub_index)
+                .map(|x| x.code.clone())
+        } else {
+            None
+        };
+        Ok(NowAirQuality {
+            updated_time: from_unix(x.time)?,
+            value,
+            category: category.into(),
+            color,
+            primary_pollutant,
+            pollutants,
+            health: Some(us_aqi_health(value, lang)),
+        })
+    }
+}
+
+impl Convert<AirQualityForecast> for AirQualityForecastOutput {
+    fn convert(self, lang: Language) -> Result<AirQualityForecast, WeatherError> {
+        let offset = UtcOffset::from_whole_seconds(self.utc_offset_seconds)
+            .map_err(|e| WeatherError::SerdeError(format!("{e}")))?;
+        let h = self.hourly;
+        // 按当地日期取每天的最大值
+        let mut daily: Vec<AirQualityOneDay> = Vec::new();
+        for (t, value) in h.time.iter().zip(h.us_aqi.iter()) {
+            let Some(value) = *value else {
+                continue;
+            };
+            let date = from_unix(*t)?.to_offset(offset).date();
+            match daily.last_mut() {
+                Some(x) if x.date == date => x.value = x.value.max(value),
+                _ => daily.push(AirQualityOneDay {
+                    date,
+                    value,
+                    category: String::new(),
+                    color: (0, 0, 0),
+                    primary_pollutant: None,
+                }),
+            }
+        }
+        for x in daily.iter_mut() {
+            let (category, color) = us_aqi_category(x.value, lang);
+            x.category = category.into();
+            x.color = color;
+        }
+        Ok(AirQualityForecast {
+            updated_time: OffsetDateTime::now_utc(),
+            daily,
+        })
+    }
+}
+
+/// Open-Meteo，不需要key。位置id为GeoNames id，请求前通过地理编码接口换取经纬度
+pub struct OpenMeteo {
+    pub display: DisplaySettings,
+}
+
+impl OpenMeteo {
+    /// 默认为摄氏度
+    fn temperature_unit(&self) -> &'static str {
+        match self.display.temp_unit {
+            TempUnit::Celsius => "",
+            TempUnit::Fahrenheit => "&temperature_unit=fahrenheit",
+        }
+    }
+
+    /// 位置已保存经纬度时不需要请求
+    fn coordinates(ctx: Rc<dyn Context>, location: &Location, callback: Callback<Coord>) {
+        if let Some(x) = location.coord {
+            callback(Ok(x));
+            return;
+        }
+        request_json(
+            ctx,
+            HttpRequest::get(format!("{GEOCODING_URL}/get?id={}", location.location_id))
+                .retry(2)
+                .cached(),
+            Box::new(|r: Result<GeoItem, WeatherError>| {
+                callback(r.map(|x| Coord {
+                    lat: x.latitude,
+                    lon: x.longitude,
+                }))
+            }),
+        );
+    }
+
+    /// 换取经纬度后请求url(latitude, longitude)
+    fn request<T, O>(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        url: impl FnOnce(f32, f32) -> String + 'static,
+        callback: Callback<O>,
+    ) where
+        T: Convert<O> + serde::de::DeserializeOwned + 'static,
+        O: 'static,
+    {
+        let lang = self.display.lang;
+        Self::coordinates(
+            ctx.clone(),
+            location,
+            Box::new(move |r| match r {
+                Ok(x) => request_json(
+                    ctx,
+                    HttpRequest::get(url(x.lat, x.lon)).retry(2).cached(),
+                    Box::new(move |r: Result<T, WeatherError>| {
+                        callback(r.and_then(|x| x.convert(lang)))
+                    }),
+                ),
+                Err(e) => callback(Err(e)),
+            }),
+        );
+    }
+}
+
+impl WeatherProvider for OpenMeteo {
+    fn now_weather(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<NowWeather>,
+    ) {
+        let unit = self.temperature_unit();
+        self.request::<CurrentWeatherOutput, _>(
+            ctx,
+            location,
+            move |lat, lon| {
+                format!(
+                    "{FORECAST_URL}?latitude={lat}&longitude={lon}&timeformat=unixtime\
+                    &current=temperature_2m,relative_humidity_2m,weather_code,is_day{unit}"
+                )
+            },
+            callback,
+        );
+    }
+
+    fn forecast_weather(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<ForecastWeather>,
+    ) {
+        let unit = self.temperature_unit();
+        self.request::<DailyWeatherOutput, _>(
+            ctx,
+            location,
+            move |lat, lon| {
+                format!(
+                    "{FORECAST_URL}?latitude={lat}&longitude={lon}&timeformat=unixtime\
+                    &timezone=auto&forecast_days=3&daily=weather_code,temperature_2m_max,\
+                    temperature_2m_min,relative_humidity_2m_mean{unit}"
+                )
+            },
+            callback,
+        );
+    }
+
+    fn now_air_quality(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<NowAirQuality>,
+    ) {
+        self.request::<AirQualityOutput, _>(
+            ctx,
+            location,
+            |lat, lon| {
+                format!(
+                    "{AIR_QUALITY_URL}?latitude={lat}&longitude={lon}&timeformat=unixtime\
+                    &current=us_aqi,pm2_5,pm10,ozone,nitrogen_dioxide,sulphur_dioxide,\
+                    carbon_monoxide,us_aqi_pm2_5,us_aqi_pm10,us_aqi_ozone,\
+                    us_aqi_nitrogen_dioxide,us_aqi_sulphur_dioxide,us_aqi_carbon_monoxide"
+                )
+            },
+            callback,
+        );
+    }
+
+    fn air_quality_forecast(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<AirQualityForecast>,
+    ) {
+        self.request::<AirQualityForecastOutput, _>(
+            ctx,
+            location,
+            |lat, lon| {
+                format!(
+                    "{AIR_QUALITY_URL}?latitude={lat}&longitude={lon}&timeformat=unixtime\
+                    &timezone=auto&forecast_days=5&hourly=us_aqi"
+                )
+            },
+            callback,
+        );
+    }
+
+    fn hourly_forecast(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<HourlyForecast>,
+    ) {
+        let unit = self.temperature_unit();
+        self.request::<HourlyWeatherOutput, _>(
+            ctx,
+            location,
+            move |lat, lon| {
+                format!(
+                    "{FORECAST_URL}?latitude={lat}&longitude={lon}&timeformat=unixtime\
+                    &timezone=auto&forecast_hours=24&hourly=temperature_2m,weather_code,\
+                    precipitation_probability,is_day{unit}"
+                )
+            },
+            callback,
+        );
+    }
+
+    fn warnings(
+        &self,
+        _ctx: Rc<dyn Context>,
+        _location: &Location,
+        callback: Callback<WeatherWarnings>,
+    ) {
+        callback(Err(WeatherError::Unsupported));
+    }
+
+    fn coord(&self, ctx: Rc<dyn Context>, location: &Location, callback: Callback<Coord>) {
+        Self::coordinates(ctx, location, callback);
+    }
+
+    fn city_lookup(
+        &self,
+        ctx: Rc<dyn Context>,
+        query: String,
+        callback: Callback<Vec<CityLookUpItem>>,
+    ) {
+        request_json(
+            ctx,
+            HttpRequest::get(format!("{GEOCODING_URL}/search"))
+                .query("name", &query)
+                .query("count", "5")
+                .query("language", self.display.lang.code())
+                .query("format", "json")
+                .cached(),
+            Box::new(|r: Result<GeoSearchOutput, WeatherError>| {
+                callback(r.map(|x| {
+                    x.results
+                        .into_iter()
+                        .map(|x| CityLookUpItem {
+                            name: x.name,
+                            id: x.id.to_string(),
+                            country: x.country,
+                            coord: Some(Coord {
+                                lat: x.latitude,
+                                lon: x.longitude,
+                            }),
+                        })
+                        .collect()
+                }))
+            }),
+        );
+    }
+}
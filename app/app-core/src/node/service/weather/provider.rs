@@ -0,0 +1,81 @@
+use std::rc::Rc;
+
+use crate::{
+    proto::*,
+    storage::{SystemStorage, WeatherStorage},
+};
+
+use super::{common::Callback, open_meteo::OpenMeteo, qweather::QWeather, server::ServerApi};
+
+/// 天气数据源，结果统一转换为proto中的类型，缓存由WeatherService负责
+pub trait WeatherProvider {
+    fn now_weather(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<NowWeather>,
+    );
+
+    fn forecast_weather(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<ForecastWeather>,
+    );
+
+    fn now_air_quality(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<NowAirQuality>,
+    );
+
+    /// 逐日AQI预报
+    fn air_quality_forecast(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<AirQualityForecast>,
+    );
+
+    /// 未来24小时的逐小时预报
+    fn hourly_forecast(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<HourlyForecast>,
+    );
+
+    /// 生效中的灾害预警
+    fn warnings(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<WeatherWarnings>,
+    );
+
+    /// 位置的经纬度，用于补充旧版保存的位置
+    fn coord(&self, ctx: Rc<dyn Context>, location: &Location, callback: Callback<Coord>);
+
+    /// 返回的id即该数据源的位置id
+    fn city_lookup(
+        &self,
+        ctx: Rc<dyn Context>,
+        query: String,
+        callback: Callback<Vec<CityLookUpItem>>,
+    );
+}
+
+/// 按存储中的配置创建数据源，温度单位与语言取自显示设置
+pub fn get_provider(ctx: Rc<dyn Context>) -> Result<Box<dyn WeatherProvider>, WeatherError> {
+    let display = SystemStorage(ipc::StorageClient(ctx.clone())).get_display_settings();
+    let stg = WeatherStorage(ipc::StorageClient(ctx));
+    Ok(match stg.get_provider()? {
+        WeatherProviderConfig::QWeather => Box::new(QWeather {
+            key: stg.get_key()?,
+            display,
+        }),
+        WeatherProviderConfig::OpenMeteo => Box::new(OpenMeteo { display }),
+        WeatherProviderConfig::Server(base_url) => Box::new(ServerApi { base_url, display }),
+    })
+}
@@ -0,0 +1,132 @@
+use std::rc::Rc;
+
+use crate::proto::*;
+
+use super::{
+    common::Callback, geo::GeoCityLookupInput, provider::WeatherProvider,
+    weather::WeatherQueryInput,
+};
+
+/// 和风天气，接口格式见geo与weather模块
+pub struct QWeather {
+    pub key: String,
+    pub display: DisplaySettings,
+}
+
+impl QWeather {
+    fn query(&self, location: &Location) -> WeatherQueryInput {
+        WeatherQueryInput {
+            location: location.location_id.to_string(),
+            key: self.key.clone(),
+            lang: self.display.lang.code().into(),
+            unit: match self.display.temp_unit {
+                TempUnit::Celsius => "m",
+                TempUnit::Fahrenheit => "i",
+            }
+            .into(),
+        }
+    }
+}
+
+impl WeatherProvider for QWeather {
+    fn now_weather(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<NowWeather>,
+    ) {
+        self.query(location)
+            .request_now_weather(ctx, Box::new(|r| callback(r.and_then(TryInto::try_into))));
+    }
+
+    fn forecast_weather(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<ForecastWeather>,
+    ) {
+        self.query(location)
+            .request_forecast_weather(ctx, Box::new(|r| callback(r.and_then(TryInto::try_into))));
+    }
+
+    fn now_air_quality(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<NowAirQuality>,
+    ) {
+        self.query(location)
+            .request_now_air_quality(ctx, Box::new(|r| callback(r.and_then(TryInto::try_into))));
+    }
+
+    fn air_quality_forecast(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<AirQualityForecast>,
+    ) {
+        self.query(location).request_air_quality_forecast(
+            ctx,
+            Box::new(|r| callback(r.and_then(TryInto::try_into))),
+        );
+    }
+
+    fn hourly_forecast(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<HourlyForecast>,
+    ) {
+        self.query(location)
+            .request_hourly_forecast(ctx, Box::new(|r| callback(r.and_then(TryInto::try_into))));
+    }
+
+    fn warnings(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<WeatherWarnings>,
+    ) {
+        self.query(location)
+            .request_warnings(ctx, Box::new(|r| callback(r.and_then(TryInto::try_into))));
+    }
+
+    fn coord(&self, ctx: Rc<dyn Context>, location: &Location, callback: Callback<Coord>) {
+        // 城市查询接口支持按位置id查询
+        GeoCityLookupInput {
+            location: location.location_id.to_string(),
+            key: self.key.clone(),
+            number: Some(1),
+            lang: self.display.lang.code().into(),
+        }
+        .request(
+            ctx,
+            Box::new(|r| {
+                callback(
+                    r.and_then(TryInto::<Vec<CityLookUpItem>>::try_into)
+                        .and_then(|x| {
+                            x.into_iter()
+                                .next()
+                                .and_then(|x| x.coord)
+                                .ok_or(WeatherError::MissingFieldError("coord".into()))
+                        }),
+                )
+            }),
+        );
+    }
+
+    fn city_lookup(
+        &self,
+        ctx: Rc<dyn Context>,
+        query: String,
+        callback: Callback<Vec<CityLookUpItem>>,
+    ) {
+        GeoCityLookupInput {
+            location: query,
+            key: self.key.clone(),
+            number: Some(5),
+            lang: self.display.lang.code().into(),
+        }
+        .request(ctx, Box::new(|r| callback(r.and_then(TryInto::try_into))));
+    }
+}
@@ -0,0 +1,159 @@
+use std::rc::Rc;
+
+use crate::proto::*;
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use super::{
+    common::{request_json, Callback, UtcDateTime},
+    provider::WeatherProvider,
+};
+
+#[derive(Deserialize, Debug, Clone)]
+struct CityLookupItem {
+    name: String,
+    id: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct CityLookupOutput {
+    items: Vec<CityLookupItem>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct NowOutput {
+    temp: i8,
+    humidity: u8,
+    icon: String,
+    text: String,
+    /// 数据观测时间
+    obs_time: UtcDateTime,
+}
+
+impl TryInto<NowWeather> for NowOutput {
+    type Error = WeatherError;
+
+    fn try_into(self) -> Result<NowWeather, Self::Error> {
+        Ok(NowWeather {
+            // 服务端会缓存1h，按接收时间判断本地缓存是否过期
+            updated_time: OffsetDateTime::now_utc(),
+            temp: self.temp,
+            icon: self
+                .icon
+                .parse()
+                .map_err(|e| WeatherError::SerdeError(format!("icon: {e}")))?,
+            text: self.text,
+            humidity: self.humidity,
+            obs_time: Some(self.obs_time.into()),
+        })
+    }
+}
+
+/// 自建的server服务，见server/src/service/weather.rs，位置id与和风天气相同
+///
+/// 服务端只返回摄氏度，按显示设置在本地转换
+pub struct ServerApi {
+    pub base_url: String,
+    pub display: DisplaySettings,
+}
+
+impl WeatherProvider for ServerApi {
+    fn now_weather(
+        &self,
+        ctx: Rc<dyn Context>,
+        location: &Location,
+        callback: Callback<NowWeather>,
+    ) {
+        let unit = self.display.temp_unit;
+        request_json(
+            ctx,
+            HttpRequest::get(format!(
+                "{}/weather/now?city_id={}",
+                self.base_url, location.location_id
+            ))
+            .retry(2)
+            .cached(),
+            Box::new(move |r: Result<NowOutput, WeatherError>| {
+                callback(r.and_then(TryInto::<NowWeather>::try_into).map(|mut x| {
+                    x.temp = unit.from_celsius(x.temp);
+                    x
+                }))
+            }),
+        );
+    }
+
+    fn forecast_weather(
+        &self,
+        _ctx: Rc<dyn Context>,
+        _location: &Location,
+        callback: Callback<ForecastWeather>,
+    ) {
+        callback(Err(WeatherError::Unsupported));
+    }
+
+    fn now_air_quality(
+        &self,
+        _ctx: Rc<dyn Context>,
+        _location: &Location,
+        callback: Callback<NowAirQuality>,
+    ) {
+        callback(Err(WeatherError::Unsupported));
+    }
+
+    fn air_quality_forecast(
+        &self,
+        _ctx: Rc<dyn Context>,
+        _location: &Location,
+        callback: Callback<AirQualityForecast>,
+    ) {
+        callback(Err(WeatherError::Unsupported));
+    }
+
+    fn hourly_forecast(
+        &self,
+        _ctx: Rc<dyn Context>,
+        _location: &Location,
+        callback: Callback<HourlyForecast>,
+    ) {
+        callback(Err(WeatherError::Unsupported));
+    }
+
+    fn warnings(
+        &self,
+        _ctx: Rc<dyn Context>,
+        _location: &Location,
+        callback: Callback<WeatherWarnings>,
+    ) {
+        callback(Err(WeatherError::Unsupported));
+    }
+
+    fn coord(&self, _ctx: Rc<dyn Context>, _location: &Location, callback: Callback<Coord>) {
+        callback(Err(WeatherError::Unsupported));
+    }
+
+    fn city_lookup(
+        &self,
+        ctx: Rc<dyn Context>,
+        query: String,
+        callback: Callback<Vec<CityLookUpItem>>,
+    ) {
+        request_json(
+            ctx,
+            HttpRequest::get(format!("{}/weather/city_lookup", self.base_url))
+                .query("query", &query),
+            Box::new(|r: Result<CityLookupOutput, WeatherError>| {
+                callback(r.map(|x| {
+                    x.items
+                        .into_iter()
+                        .map(|x| CityLookUpItem {
+                            name: x.name,
+                            id: x.id,
+                            country: String::new(),
+                            coord: None,
+                        })
+                        .collect()
+                }))
+            }),
+        );
+    }
+}
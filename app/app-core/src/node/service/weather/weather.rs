@@ -42,6 +42,7 @@ impl TryInto<proto::NowWeather> for WeatherNowOutput {
             icon: now.icon.take(),
             text: now.text,
             humidity: now.humidity.take(),
+            obs_time: None,
         })
     }
 }
@@ -125,13 +126,174 @@ impl TryInto<proto::ForecastWeather> for WeatherForecastOutput {
     }
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct WeatherHourlyData {
+    #[serde(rename = "fxTime")]
+    pub fx_time: UtcDateTime,
+    /// 温度，默认单位：摄氏度
+    pub temp: Number<i8>,
+    /// 天气图标代码
+    pub icon: Number<u16>,
+    /// 天气状况文字描述
+    pub text: String,
+    /// 降水概率，百分比数值，可能为空
+    pub pop: Option<String>,
+}
+
+impl From<WeatherHourlyData> for proto::HourlyWeather {
+    fn from(val: WeatherHourlyData) -> Self {
+        proto::HourlyWeather {
+            time: val.fx_time.into(),
+            temp: val.temp.take(),
+            icon: val.icon.take(),
+            text: val.text,
+            pop: val.pop.and_then(|x| x.parse().ok()),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WeatherHourlyOutput {
+    pub code: ErrorCode,
+    #[serde(rename = "updateTime")]
+    pub update_time: Option<UtcDateTime>,
+    pub hourly: Option<Vec<WeatherHourlyData>>,
+}
+
+impl TryInto<proto::HourlyForecast> for WeatherHourlyOutput {
+    type Error = WeatherError;
+
+    fn try_into(self) -> Result<proto::HourlyForecast, Self::Error> {
+        self.code.detect_error()?;
+        let updated_time = self.update_time.ok_or(WeatherError::MissingFieldError(
+            "missing field `updateTime`".into(),
+        ))?;
+        let hourly = self.hourly.ok_or(WeatherError::MissingFieldError(
+            "missing field `hourly`".into(),
+        ))?;
+        Ok(proto::HourlyForecast {
+            updated_time: updated_time.into(),
+            hourly: hourly.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WarningData {
+    pub id: String,
+    pub title: String,
+    /// active、update或cancel
+    pub status: String,
+    #[serde(rename = "severityColor")]
+    pub severity_color: String,
+    #[serde(rename = "typeName")]
+    pub type_name: String,
+    pub text: String,
+}
+
+impl From<WarningData> for proto::WeatherWarning {
+    fn from(val: WarningData) -> Self {
+        let color = match val.severity_color.as_str() {
+            "Blue" => (0x1e, 0x90, 0xff),
+            "Green" => (0x00, 0xa0, 0x00),
+            "Yellow" => (0xff, 0xd7, 0x00),
+            "Orange" => (0xff, 0x8c, 0x00),
+            "Red" => (0xe0, 0x00, 0x00),
+            "Black" => (0x40, 0x40, 0x40),
+            _ => (0xc0, 0xc0, 0xc0),
+        };
+        proto::WeatherWarning {
+            id: val.id,
+            title: val.title,
+            type_name: val.type_name,
+            text: val.text,
+            color,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WarningNowOutput {
+    pub code: ErrorCode,
+    #[serde(rename = "updateTime")]
+    pub update_time: Option<UtcDateTime>,
+    pub warning: Option<Vec<WarningData>>,
+}
+
+impl TryInto<proto::WeatherWarnings> for WarningNowOutput {
+    type Error = WeatherError;
+
+    fn try_into(self) -> Result<proto::WeatherWarnings, Self::Error> {
+        self.code.detect_error()?;
+        let updated_time = self.update_time.ok_or(WeatherError::MissingFieldError(
+            "missing field `updateTime`".into(),
+        ))?;
+        Ok(proto::WeatherWarnings {
+            updated_time: updated_time.into(),
+            warnings: self
+                .warning
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|x| x.status != "cancel")
+                .map(Into::into)
+                .collect(),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PrimaryPollutant {
+    pub code: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AirQualityAdvice {
+    #[serde(rename = "generalPopulation")]
+    pub general_population: String,
+    #[serde(rename = "sensitivePopulation")]
+    pub sensitive_population: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AirQualityHealth {
+    pub effect: String,
+    pub advice: AirQualityAdvice,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct AirQuality {
+    /// AQI标准，如cn-mee
+    pub code: String,
     #[serde(rename = "defaultLocalAqi")]
     pub default_local_aqi: bool,
     pub value: u16,
     pub category: String,
     pub color: RgbColor,
+    #[serde(rename = "primaryPollutant")]
+    pub primary_pollutant: Option<PrimaryPollutant>,
+    pub health: Option<AirQualityHealth>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Concentration {
+    pub value: f32,
+    pub unit: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PollutantSubIndex {
+    /// 对应的AQI标准
+    pub code: String,
+    pub value: u16,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PollutantData {
+    pub code: String,
+    pub name: String,
+    pub concentration: Concentration,
+    #[serde(rename = "subIndex", default)]
+    pub sub_index: Vec<PollutantSubIndex>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -140,6 +302,8 @@ pub struct AirQualityNowOutput {
     #[serde(rename = "updateTime")]
     pub update_time: Option<UtcDateTime>,
     pub aqi: Option<Vec<AirQuality>>,
+    #[serde(default)]
+    pub pollutant: Vec<PollutantData>,
 }
 
 impl TryInto<proto::NowAirQuality> for AirQualityNowOutput {
@@ -156,11 +320,96 @@ impl TryInto<proto::NowAirQuality> for AirQualityNowOutput {
         let aq = aqi.into_iter().find(|x| x.default_local_aqi).ok_or(
             WeatherError::MissingFieldError("missing defaultLocalAqi: true".into()),
         )?;
+        // 分指数按与AQI相同的标准取值
+        let pollutants = self
+            .pollutant
+            .into_iter()
+            .map(|x| proto::Pollutant {
+                sub_index: x
+                    .sub_index
+                    .iter()
+                    .find(|i| i.code == aq.code)
+                    .map(|i| i.value),
+                code: x.code,
+                name: x.name,
+                concentration: x.concentration.value,
+                unit: x.concentration.unit,
+            })
+            .collect();
         Ok(proto::NowAirQuality {
             updated_time: updated_time.into(),
             value: aq.value,
             category: aq.category,
             color: (aq.color.red, aq.color.green, aq.color.blue),
+            primary_pollutant: aq.primary_pollutant.map(|x| x.code),
+            pollutants,
+            health: aq.health.map(|x| proto::AirQualityHealth {
+                effect: x.effect,
+                general: x.advice.general_population,
+                sensitive: x.advice.sensitive_population,
+            }),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AirForecastData {
+    #[serde(rename = "fxDate", with = "date_serde")]
+    pub fx_date: time::Date,
+    pub aqi: Number<u16>,
+    /// 中国标准的1到6级
+    pub level: Number<u8>,
+    pub category: String,
+    /// 如PM2.5，没有首要污染物时为NA
+    pub primary: String,
+}
+
+impl From<AirForecastData> for proto::AirQualityOneDay {
+    fn from(val: AirForecastData) -> Self {
+        let color = match val.level.take() {
+            1 => (0, 228, 0),
+            2 => (255, 255, 0),
+            3 => (255, 126, 0),
+            4 => (255, 0, 0),
+            5 => (153, 0, 76),
+            _ => (126, 0, 35),
+        };
+        proto::AirQualityOneDay {
+            date: val.fx_date,
+            value: val.aqi.take(),
+            category: val.category,
+            color,
+            // 与实时空气质量的污染物code保持一致，如PM2.5为pm2p5
+            primary_pollutant: match val.primary.as_str() {
+                "NA" | "" => None,
+                x => Some(x.to_lowercase().replace('.', "p")),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AirForecastOutput {
+    pub code: ErrorCode,
+    #[serde(rename = "updateTime")]
+    pub update_time: Option<UtcDateTime>,
+    pub daily: Option<Vec<AirForecastData>>,
+}
+
+impl TryInto<proto::AirQualityForecast> for AirForecastOutput {
+    type Error = WeatherError;
+
+    fn try_into(self) -> Result<proto::AirQualityForecast, Self::Error> {
+        self.code.detect_error()?;
+        let updated_time = self.update_time.ok_or(WeatherError::MissingFieldError(
+            "missing field `updateTime`".into(),
+        ))?;
+        let daily = self.daily.ok_or(WeatherError::MissingFieldError(
+            "missing field `daily`".into(),
+        ))?;
+        Ok(proto::AirQualityForecast {
+            updated_time: updated_time.into(),
+            daily: daily.into_iter().map(Into::into).collect(),
         })
     }
 }
@@ -169,6 +418,10 @@ impl TryInto<proto::NowAirQuality> for AirQualityNowOutput {
 pub struct WeatherQueryInput {
     pub location: String,
     pub key: String,
+    /// zh或en
+    pub lang: String,
+    /// m为公制，i为英制
+    pub unit: String,
 }
 
 impl WeatherQueryInput {
@@ -178,20 +431,67 @@ impl WeatherQueryInput {
         callback: Box<dyn FnOnce(Result<WeatherForecastOutput, WeatherError>)>,
     ) {
         ipc::HttpClient(ctx).request(
-            HttpRequest {
-                method: HttpRequestMethod::Get,
-                url: format!(
-                    "https://devapi.qweather.com/v7/weather/3d?gzip=n&lang=en&key={}&location={}",
-                    self.key, self.location
-                ),
-            },
+            HttpRequest::get(format!(
+                "https://devapi.qweather.com/v7/weather/3d?gzip=n&lang={}&unit={}&key={}&location={}",
+                self.lang, self.unit, self.key, self.location
+            ))
+            .retry(2)
+            .cached(),
+            Box::new(|r| {
+                callback(match r {
+                    Ok(x) => x
+                        .body
+                        .deserialize_by_json()
+                        .map_err(|e| WeatherError::SerdeError(format!("{e}"))),
+                    Err(e) => Err(e.into()),
+                });
+            }),
+        );
+    }
+
+    pub fn request_hourly_forecast(
+        &self,
+        ctx: Rc<dyn Context>,
+        callback: Box<dyn FnOnce(Result<WeatherHourlyOutput, WeatherError>)>,
+    ) {
+        ipc::HttpClient(ctx).request(
+            HttpRequest::get(format!(
+                "https://devapi.qweather.com/v7/weather/24h?gzip=n&lang={}&unit={}&key={}&location={}",
+                self.lang, self.unit, self.key, self.location
+            ))
+            .retry(2)
+            .cached(),
             Box::new(|r| {
                 callback(match r {
                     Ok(x) => x
                         .body
                         .deserialize_by_json()
                         .map_err(|e| WeatherError::SerdeError(format!("{e}"))),
-                    Err(e) => Err(WeatherError::HttpError(e)),
+                    Err(e) => Err(e.into()),
+                });
+            }),
+        );
+    }
+
+    pub fn request_warnings(
+        &self,
+        ctx: Rc<dyn Context>,
+        callback: Box<dyn FnOnce(Result<WarningNowOutput, WeatherError>)>,
+    ) {
+        ipc::HttpClient(ctx).request(
+            HttpRequest::get(format!(
+                "https://devapi.qweather.com/v7/warning/now?gzip=n&lang={}&key={}&location={}",
+                self.lang, self.key, self.location
+            ))
+            .retry(2)
+            .cached(),
+            Box::new(|r| {
+                callback(match r {
+                    Ok(x) => x
+                        .body
+                        .deserialize_by_json()
+                        .map_err(|e| WeatherError::SerdeError(format!("{e}"))),
+                    Err(e) => Err(e.into()),
                 });
             }),
         );
@@ -203,20 +503,19 @@ impl WeatherQueryInput {
         callback: Box<dyn FnOnce(Result<WeatherNowOutput, WeatherError>)>,
     ) {
         ipc::HttpClient(ctx).request(
-            HttpRequest {
-                method: HttpRequestMethod::Get,
-                url: format!(
-                    "https://devapi.qweather.com/v7/weather/now?gzip=n&lang=en&key={}&location={}",
-                    self.key, self.location
-                ),
-            },
+            HttpRequest::get(format!(
+                "https://devapi.qweather.com/v7/weather/now?gzip=n&lang={}&unit={}&key={}&location={}",
+                self.lang, self.unit, self.key, self.location
+            ))
+            .retry(2)
+            .cached(),
             Box::new(|r| {
                 callback(match r {
                     Ok(x) => x
                         .body
                         .deserialize_by_json()
                         .map_err(|e| WeatherError::SerdeError(format!("{e}"))),
-                    Err(e) => Err(WeatherError::HttpError(e)),
+                    Err(e) => Err(e.into()),
                 });
             }),
         );
@@ -228,20 +527,43 @@ impl WeatherQueryInput {
         callback: Box<dyn FnOnce(Result<AirQualityNowOutput, WeatherError>)>,
     ) {
         ipc::HttpClient(ctx).request(
-            HttpRequest {
-                method: HttpRequestMethod::Get,
-                url: format!(
-                    "https://devapi.qweather.com/airquality/v1/now/{}?gzip=n&lang=en&key={}",
-                    self.location, self.key
-                ),
-            },
+            HttpRequest::get(format!(
+                "https://devapi.qweather.com/airquality/v1/now/{}?gzip=n&lang={}&key={}",
+                self.location, self.lang, self.key
+            ))
+            .retry(2)
+            .cached(),
+            Box::new(|r| {
+                callback(match r {
+                    Ok(x) => x
+                        .body
+                        .deserialize_by_json()
+                        .map_err(|e| WeatherError::SerdeError(format!("{e}"))),
+                    Err(e) => Err(e.into()),
+                });
+            }),
+        );
+    }
+
+    pub fn request_air_quality_forecast(
+        &self,
+        ctx: Rc<dyn Context>,
+        callback: Box<dyn FnOnce(Result<AirForecastOutput, WeatherError>)>,
+    ) {
+        ipc::HttpClient(ctx).request(
+            HttpRequest::get(format!(
+                "https://devapi.qweather.com/v7/air/5d?gzip=n&lang={}&key={}&location={}",
+                self.lang, self.key, self.location
+            ))
+            .retry(2)
+            .cached(),
             Box::new(|r| {
                 callback(match r {
                     Ok(x) => x
                         .body
                         .deserialize_by_json()
                         .map_err(|e| WeatherError::SerdeError(format!("{e}"))),
-                    Err(e) => Err(WeatherError::HttpError(e)),
+                    Err(e) => Err(e.into()),
                 });
             }),
         );
@@ -3,7 +3,8 @@ use std::collections::HashMap;
 use std::{rc::Rc, time::Duration};
 
 use ipc::StorageClient;
-use storage::UserAlarmStorage;
+use log::error;
+use storage::{SystemStorage, UserAlarmStorage};
 use time::UtcOffset;
 
 use crate::proto::*;
@@ -34,10 +35,16 @@ impl UserAlarmService {
         Self::default()
     }
 
-    fn on_alarm(ctx: Rc<dyn Context>, id: usize) {
-        // play
-        let stg = UserAlarmStorage(StorageClient(ctx.clone()));
-        let ret = stg.get(id).map_err(UserAlarmError::StorageError).unwrap();
+    fn on_alarm(ctx: Rc<dyn Context>, id: usize, e: &AlarmElement) {
+        // 读取备注失败时仍然响铃，只显示时间
+        let comment = match UserAlarmStorage(StorageClient(ctx.clone())).get(id) {
+            Ok(x) => x.comment,
+            Err(err) => {
+                error!("read alarm {id} error: {err:?}");
+                String::new()
+            }
+        };
+        let display = SystemStorage(StorageClient(ctx.clone())).get_display_settings();
 
         let play_tone = {
             let cli = ipc::BuzzerClient(ctx.clone());
@@ -81,8 +88,9 @@ impl UserAlarmService {
             2 * 60 * 1000,
             NotifactionContent {
                 text: Some(format!(
-                    "{:0>2}:{:0>2}\n{}",
-                    ret.time.0, ret.time.1, ret.comment
+                    "{}\n{}",
+                    display.format_time(e.hour, e.minute),
+                    comment
                 )),
                 title: None,
                 icon: None,
@@ -109,12 +117,14 @@ impl UserAlarmService {
                 };
                 if !today_actived {
                     // 闹铃应该被响起
-                    Self::on_alarm(ctx.clone(), *id);
+                    Self::on_alarm(ctx.clone(), *id, e);
                     // 标记一下闹铃今日已响过
                     *e.last_active_day.borrow_mut() = Some(now_day);
 
                     if let UserAlarmRepeatMode::Once = e.mode {
-                        stg.delete(*id).unwrap();
+                        if let Err(err) = stg.delete(*id) {
+                            error!("delete alarm {id} error: {err:?}");
+                        }
                         need_droped.push(*id);
                     }
                 }
@@ -133,7 +143,13 @@ impl UserAlarmService {
             .map_err(UserAlarmError::StorageError)
             .unwrap_or_default();
         for id in id_list {
-            let body = stg.get(id).unwrap();
+            let body = match stg.get(id) {
+                Ok(x) => x,
+                Err(e) => {
+                    error!("skip unreadable alarm {id}: {e:?}");
+                    continue;
+                }
+            };
             let ele = AlarmElement {
                 hour: body.time.0,
                 minute: body.time.1,
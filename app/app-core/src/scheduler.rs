@@ -60,7 +60,7 @@ struct ContextImpl {
     node_name: NodeName,
     mq_buffer: Rc<RefCell<Vec<MessageQueueItem>>>,
     nodes: Rc<RefCell<HashMap<NodeName, Box<dyn Node>>>>,
-    ready_result: Rc<RefCell<HashMap<usize, Message>>>,
+    ready_result: Rc<RefCell<HashMap<usize, HandleResult>>>,
     subscriber: Rc<RefCell<HashMap<TopicName, VecDeque<NodeName>>>>,
     wg_queue: Rc<RefCell<Vec<Rc<WaitGroupImpl>>>>,
 }
@@ -176,7 +176,15 @@ impl Context for ContextImpl {
 
     // 异步结果就绪
     fn async_ready(&self, seq: usize, result: Message) {
-        self.ready_result.borrow_mut().insert(seq, result);
+        self.ready_result
+            .borrow_mut()
+            .insert(seq, HandleResult::Finish(result));
+    }
+
+    fn async_discard(&self, seq: usize) {
+        self.ready_result
+            .borrow_mut()
+            .insert(seq, HandleResult::Discard);
     }
 
     fn create_wait_group(&self) -> Rc<dyn WaitGroup> {
@@ -184,6 +192,10 @@ impl Context for ContextImpl {
         self.wg_queue.borrow_mut().push(wg.clone());
         wg.clone()
     }
+
+    fn node_names(&self) -> Vec<NodeName> {
+        self.nodes.borrow().keys().cloned().collect()
+    }
 }
 
 #[derive(Default)]
@@ -192,7 +204,7 @@ pub struct Scheduler {
     nodes: Rc<RefCell<HashMap<NodeName, Box<dyn Node>>>>,
     mq_buffer1: RefCell<Vec<MessageQueueItem>>,
     mq_buffer2: Rc<RefCell<Vec<MessageQueueItem>>>,
-    ready_result: Rc<RefCell<HashMap<usize, Message>>>,
+    ready_result: Rc<RefCell<HashMap<usize, HandleResult>>>,
     subscriber: Rc<RefCell<HashMap<TopicName, VecDeque<NodeName>>>>,
     wg_queue1: RefCell<Vec<Rc<WaitGroupImpl>>>,
     wg_queue2: Rc<RefCell<Vec<Rc<WaitGroupImpl>>>>,
@@ -327,7 +339,7 @@ impl Scheduler {
                 info!("async message seq {} is ready: {:?}", message.seq, m);
                 // 如果消息已经就绪，则触发回调
                 if let Some(cb) = callback_once.take() {
-                    cb(HandleResult::Finish(m));
+                    cb(m);
                 }
             } else {
                 // 若仍无消息结果就绪，则继续将进入消息队列排队轮询
@@ -0,0 +1,417 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use app_core::{
+    proto::{
+        normalize_url, Bytes, ConnectivityState, Context, HandleResult, HttpBody, HttpError,
+        HttpMessage, HttpRequest, HttpRequestMethod, HttpResponse, Message, MessageWithHeader,
+        Node, NodeName, Secret, SecretMessage,
+    },
+    SecretService,
+};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+/// 回放时使用的和风天气key占位值，key不参与匹配，任意值都能命中录制文件
+const PLACEHOLDER_WEATHER_KEY: &str = "replay";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// 只从录制文件返回响应，不访问网络
+    Replay,
+    /// 正常请求网络，并把响应写入录制文件
+    Record,
+}
+
+impl CassetteMode {
+    /// 从环境变量HTTP_CASSETTE读取，格式为`replay:<dir>`或`record:<dir>`
+    pub fn from_env() -> Option<(Self, Cassette)> {
+        let s = std::env::var("HTTP_CASSETTE").ok()?;
+        let (mode, dir) = s.split_once(':')?;
+        let mode = match mode {
+            "replay" => Self::Replay,
+            "record" => Self::Record,
+            _ => {
+                warn!("unknown HTTP_CASSETTE mode {mode:?}");
+                return None;
+            }
+        };
+        Some((mode, Cassette::new(dir)))
+    }
+}
+
+/// 录制文件中的一条记录，文本响应体以明文保存，方便手工编辑
+#[derive(Debug, Serialize, Deserialize)]
+struct CassetteEntry {
+    method: HttpRequestMethod,
+    url: String,
+    status: u16,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    base64: Option<Bytes>,
+}
+
+/// 录制文件目录，每个请求(方法+URL)对应一个JSON文件
+pub struct Cassette {
+    dir: PathBuf,
+}
+
+impl Cassette {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    fn path(&self, method: &HttpRequestMethod, url: &str) -> PathBuf {
+        // FNV-1a，保证不同机器和编译器版本得到相同的文件名
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for b in url.bytes() {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        let method = format!("{method:?}").to_lowercase();
+        self.dir.join(format!("{method}-{hash:016x}.json"))
+    }
+
+    /// 读取请求对应的录制响应，非2xx的状态码同样以HttpError::Status返回
+    pub fn load(&self, req: &HttpRequest) -> Result<HttpResponse, HttpError> {
+        let url = normalize_url(&req.url);
+        let path = self.path(&req.method, &url);
+        let s = fs::read_to_string(&path).map_err(|e| {
+            HttpError::Connect(format!(
+                "no cassette for {:?} {url} ({}): {e}",
+                req.method,
+                path.display()
+            ))
+        })?;
+        let entry: CassetteEntry = serde_json::from_str(&s)
+            .map_err(|e| HttpError::Other(format!("broken cassette {}: {e}", path.display())))?;
+        let body = match (entry.text, entry.base64) {
+            (Some(x), _) => x.into_bytes(),
+            (None, Some(x)) => x.0,
+            (None, None) => Vec::new(),
+        };
+        HttpResponse {
+            status: entry.status,
+            headers: entry.headers,
+            body: HttpBody::Bytes(Bytes(body)),
+        }
+        // 录制的响应不会是流
+        .error_for_status(|_| {})
+    }
+
+    /// 记录一次请求结果，网络错误、304与流式响应不记录
+    pub fn save(&self, req: &HttpRequest, result: &Result<HttpResponse, HttpError>) {
+        let (status, headers, body) = match result {
+            Ok(HttpResponse {
+                status,
+                headers,
+                body: HttpBody::Bytes(body),
+            }) => (*status, headers.clone(), body.0.clone()),
+            // 304依赖本地缓存，回放时没有缓存，不能覆盖完整的响应
+            Err(HttpError::Status { status: 304, .. }) => {
+                warn!("not modified response of {} is not recorded", req.url);
+                return;
+            }
+            Err(HttpError::Status { status, body }) => (*status, Vec::new(), body.0.clone()),
+            Ok(_) => {
+                warn!("streaming response of {} is not recorded", req.url);
+                return;
+            }
+            Err(_) => return,
+        };
+        let url = normalize_url(&req.url);
+        let path = self.path(&req.method, &url);
+        let (text, base64) = match String::from_utf8(body) {
+            Ok(x) => (Some(x), None),
+            Err(e) => (None, Some(Bytes(e.into_bytes()))),
+        };
+        let entry = CassetteEntry {
+            method: req.method.clone(),
+            url,
+            status,
+            headers,
+            text,
+            base64,
+        };
+        let r = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&path, serde_json::to_string_pretty(&entry).unwrap()));
+        match r {
+            Ok(_) => info!(
+                "recorded {:?} {} to {}",
+                req.method,
+                req.url,
+                path.display()
+            ),
+            Err(e) => warn!("record cassette {} failed: {e}", path.display()),
+        }
+    }
+}
+
+/// 回放录制文件的HttpClient，完全不访问网络
+pub struct CassetteHttpClient {
+    cassette: Cassette,
+    // 流式请求的响应体与已读取的位置
+    streams: RefCell<HashMap<usize, (Vec<u8>, usize)>>,
+}
+
+impl CassetteHttpClient {
+    pub fn new(cassette: Cassette) -> Self {
+        Self {
+            cassette,
+            streams: Default::default(),
+        }
+    }
+
+    fn request(&self, seq: usize, req: HttpRequest) -> Result<HttpResponse, HttpError> {
+        let mut resp = self.cassette.load(&req)?;
+        if req.stream {
+            if let HttpBody::Bytes(body) = resp.body {
+                self.streams.borrow_mut().insert(seq, (body.0, 0));
+                resp.body = HttpBody::Stream(seq);
+            }
+        }
+        Ok(resp)
+    }
+
+    fn read_chunk(&self, handle: usize, len: usize) -> Result<Option<Bytes>, HttpError> {
+        let mut streams = self.streams.borrow_mut();
+        let (data, pos) = streams
+            .get_mut(&handle)
+            .ok_or_else(|| HttpError::Other(format!("stream {handle} not found")))?;
+        let end = data.len().min(*pos + len);
+        let chunk = data[*pos..end].to_vec();
+        *pos = end;
+        if chunk.is_empty() {
+            streams.remove(&handle);
+            return Ok(None);
+        }
+        Ok(Some(Bytes(chunk)))
+    }
+}
+
+impl Node for CassetteHttpClient {
+    fn node_name(&self) -> NodeName {
+        NodeName::HttpClient
+    }
+
+    fn handle_message(&self, _ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        let ret = match msg.body {
+            Message::Http(HttpMessage::Request(req)) => match self.request(msg.seq, req) {
+                Ok(x) => HttpMessage::Response(x),
+                Err(e) => HttpMessage::Error(e),
+            },
+            Message::Http(HttpMessage::ReadChunkRequest(handle, len)) => {
+                match self.read_chunk(handle, len) {
+                    Ok(x) => HttpMessage::ReadChunkResponse(x),
+                    Err(e) => HttpMessage::Error(e),
+                }
+            }
+            Message::Http(HttpMessage::CloseStreamRequest(handle)) => {
+                self.streams.borrow_mut().remove(&handle);
+                HttpMessage::CloseStreamResponse
+            }
+            // 回放模式下总是视为在线
+            Message::Http(HttpMessage::GetConnectivityRequest) => {
+                HttpMessage::Connectivity(ConnectivityState::Online)
+            }
+            _ => return HandleResult::Discard,
+        };
+        HandleResult::Finish(Message::Http(ret))
+    }
+}
+
+/// 回放模式下的secret服务，未配置和风天气key时返回占位值，
+/// 否则获取天气服务时就会因缺少key失败，无法回放和风天气的录制文件
+pub struct ReplaySecretService(pub SecretService);
+
+impl Node for ReplaySecretService {
+    fn node_name(&self) -> NodeName {
+        self.0.node_name()
+    }
+
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        let is_weather_key = matches!(
+            &msg.body,
+            Message::Secret(SecretMessage::GetRequest(name)) if name == "weather/key"
+        );
+        match self.0.handle_message(ctx, msg) {
+            HandleResult::Finish(Message::Secret(SecretMessage::GetResponse(None)))
+                if is_weather_key =>
+            {
+                HandleResult::Finish(Message::Secret(SecretMessage::GetResponse(Some(Secret(
+                    PLACEHOLDER_WEATHER_KEY.into(),
+                )))))
+            }
+            r => r,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use app_core::proto::{
+        MessageCallbackOnce, MessageTo, StorageMessage, StorageValue, TopicName, WaitGroup,
+    };
+
+    use super::*;
+
+    /// 每个测试使用独立的临时目录
+    fn temp_cassette(name: &str) -> Cassette {
+        let dir = std::env::temp_dir().join(format!("cassette-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Cassette::new(dir)
+    }
+
+    fn response(status: u16, body: &[u8]) -> HttpResponse {
+        HttpResponse {
+            status,
+            headers: vec![("content-type".into(), "application/json".into())],
+            body: HttpBody::Bytes(Bytes(body.to_vec())),
+        }
+    }
+
+    fn body_of(resp: HttpResponse) -> Vec<u8> {
+        match resp.body {
+            HttpBody::Bytes(x) => x.0,
+            HttpBody::Stream(_) => panic!("unexpected stream body"),
+        }
+    }
+
+    #[test]
+    fn normalize_url_redacts_key_and_sorts_params() {
+        assert_eq!(
+            normalize_url("https://api.example.com/v7/now?location=101010100&key=secret&lang=zh"),
+            "https://api.example.com/v7/now?lang=zh&location=101010100"
+        );
+        assert_eq!(
+            normalize_url("https://api.example.com/v7/now?key=secret"),
+            "https://api.example.com/v7/now"
+        );
+        let cassette = temp_cassette("path");
+        let a = normalize_url("https://api.example.com/now?b=2&key=x&a=1");
+        let b = normalize_url("https://api.example.com/now?a=1&b=2&key=y");
+        assert_eq!(
+            cassette.path(&HttpRequestMethod::Get, &a),
+            cassette.path(&HttpRequestMethod::Get, &b)
+        );
+    }
+
+    #[test]
+    fn record_then_replay() {
+        let cassette = temp_cassette("roundtrip");
+        let req = HttpRequest::get("https://api.example.com/now?location=1&key=real");
+        cassette.save(&req, &Ok(response(200, br#"{"temp":20}"#)));
+        let binary = HttpRequest::get("https://api.example.com/icon.png");
+        cassette.save(&binary, &Ok(response(200, &[0xff, 0xfe, 0x00])));
+        // 304不会覆盖已录制的响应
+        cassette.save(
+            &req,
+            &Err(HttpError::Status {
+                status: 304,
+                body: Bytes(Vec::new()),
+            }),
+        );
+        let missing = HttpRequest::get("https://api.example.com/missing");
+        cassette.save(
+            &missing,
+            &Err(HttpError::Status {
+                status: 404,
+                body: Bytes(b"not found".to_vec()),
+            }),
+        );
+
+        // 录制文件中不包含key
+        for entry in fs::read_dir(&cassette.dir).unwrap() {
+            let text = fs::read_to_string(entry.unwrap().path()).unwrap();
+            assert!(!text.contains("key="), "{text}");
+        }
+
+        // 回放时key与参数顺序不同也能命中
+        let replay = HttpRequest::get("https://api.example.com/now?key=replay&location=1");
+        let resp = cassette.load(&replay).unwrap();
+        assert_eq!(resp.status, 200);
+        assert_eq!(body_of(resp), br#"{"temp":20}"#);
+        assert_eq!(body_of(cassette.load(&binary).unwrap()), [0xff, 0xfe, 0x00]);
+        assert!(matches!(
+            cassette.load(&missing),
+            Err(HttpError::Status { status: 404, .. })
+        ));
+        assert!(matches!(
+            cassette.load(&HttpRequest::get("https://api.example.com/other")),
+            Err(HttpError::Connect(_))
+        ));
+        fs::remove_dir_all(&cassette.dir).unwrap();
+    }
+
+    /// 存储中没有任何secret的上下文
+    struct EmptyStorage;
+
+    impl Context for EmptyStorage {
+        fn broadcast_global(&self, _msg: Message) {}
+        fn broadcast_topic(&self, _topic: TopicName, _msg: Message) {}
+        fn subscribe_topic(&self, _topic: TopicName) {}
+        fn unsubscribe_topic(&self, _topic: TopicName) {}
+        fn async_call(&self, node: NodeName, msg: Message, callback: MessageCallbackOnce) {
+            callback(self.sync_call(node, msg))
+        }
+        fn sync_call(&self, node: NodeName, msg: Message) -> HandleResult {
+            match (node, msg) {
+                (NodeName::Storage, Message::Storage(StorageMessage::GetRequest(_))) => {
+                    HandleResult::Finish(Message::Storage(StorageMessage::GetResponse(
+                        StorageValue::None,
+                    )))
+                }
+                _ => HandleResult::Discard,
+            }
+        }
+        fn async_ready(&self, _seq: usize, _result: Message) {}
+        fn async_discard(&self, _seq: usize) {}
+        fn create_wait_group(&self) -> Rc<dyn WaitGroup> {
+            unimplemented!()
+        }
+        fn node_names(&self) -> Vec<NodeName> {
+            vec![NodeName::Storage]
+        }
+    }
+
+    fn get_secret(node: &ReplaySecretService, from: NodeName, name: &str) -> HandleResult {
+        node.handle_message(
+            Rc::new(EmptyStorage),
+            MessageWithHeader {
+                from,
+                to: MessageTo::Point(NodeName::Secret),
+                seq: 0,
+                body: Message::Secret(SecretMessage::GetRequest(name.into())),
+            },
+        )
+    }
+
+    #[test]
+    fn replay_uses_placeholder_weather_key() {
+        let node = ReplaySecretService(SecretService::new([7; 32]));
+        assert!(matches!(
+            get_secret(&node, NodeName::Weather, "weather/key"),
+            HandleResult::Finish(Message::Secret(SecretMessage::GetResponse(Some(Secret(x)))))
+                if x == PLACEHOLDER_WEATHER_KEY
+        ));
+        // 其他secret与权限检查不受影响
+        assert!(matches!(
+            get_secret(&node, NodeName::WiFi, "wifi/password"),
+            HandleResult::Finish(Message::Secret(SecretMessage::GetResponse(None)))
+        ));
+        assert!(matches!(
+            get_secret(&node, NodeName::HomePage, "weather/key"),
+            HandleResult::Finish(Message::Secret(SecretMessage::Error(_)))
+        ));
+    }
+}
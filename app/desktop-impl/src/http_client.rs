@@ -1,6 +1,7 @@
 use std::{
     cell::RefCell,
     collections::HashMap,
+    io::Read,
     rc::Rc,
     sync::{mpsc, Arc, Mutex},
     thread,
@@ -8,62 +9,175 @@ use std::{
 };
 
 use app_core::proto::{
-    Bytes, Context, HandleResult, HttpBody, HttpMessage, HttpRequest, HttpRequestMethod,
-    HttpResponse, Message, MessageWithHeader, Node, NodeName,
+    ipc::StorageClient,
+    storage::{HttpCacheAction, HttpCacheLayer},
+    Bytes, ConnectivityTracker, Context, HandleResult, HttpBody, HttpError, HttpMessage,
+    HttpRequest, HttpRequestMethod, HttpResponse, Message, MessageWithHeader, Node, NodeName,
+    TopicName,
 };
-use reqwest::blocking::ClientBuilder;
+use reqwest::blocking::{Client, ClientBuilder, Response};
+
+use crate::http_cassette::Cassette;
 
 fn convert(method: HttpRequestMethod) -> reqwest::Method {
     use reqwest::Method;
     match method {
         HttpRequestMethod::Get => Method::GET,
+        HttpRequestMethod::Post => Method::POST,
+        HttpRequestMethod::Put => Method::PUT,
+        HttpRequestMethod::Delete => Method::DELETE,
+    }
+}
+
+fn convert_error(e: reqwest::Error) -> HttpError {
+    if e.is_timeout() {
+        HttpError::Timeout
+    } else if e.is_builder() {
+        HttpError::InvalidRequest(e.to_string())
+    } else if e.is_connect() {
+        HttpError::Connect(e.to_string())
+    } else if e.is_body() || e.is_decode() {
+        HttpError::Body(e.to_string())
+    } else {
+        HttpError::Other(e.to_string())
     }
 }
 
+/// 未读完的流式响应，key为发起请求的消息seq
+type Streams = Arc<Mutex<HashMap<usize, Response>>>;
+
+enum Job {
+    Request(HttpRequest),
+    ReadChunk(usize, usize),
+}
+
+fn execute(
+    client: &Client,
+    streams: &Streams,
+    seq: usize,
+    req: HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    let stream = req.stream;
+    let timeout = req.get_timeout();
+    let mut builder = client
+        .request(convert(req.method), req.url)
+        .timeout(timeout);
+    for (k, v) in req.headers.into_iter() {
+        builder = builder.header(k, v);
+    }
+    if let Some(body) = req.body {
+        builder = builder.body(body.0);
+    }
+    let resp = client
+        .execute(builder.build().map_err(convert_error)?)
+        .map_err(convert_error)?;
+    let status = resp.status().as_u16();
+    let headers = resp
+        .headers()
+        .iter()
+        .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into()))
+        .collect();
+    if stream && resp.status().is_success() {
+        streams.lock().unwrap().insert(seq, resp);
+        return Ok(HttpResponse {
+            status,
+            headers,
+            body: HttpBody::Stream(seq),
+        });
+    }
+    let content = resp.bytes().map_err(convert_error)?.to_vec();
+    HttpResponse {
+        status,
+        headers,
+        body: HttpBody::Bytes(Bytes(content)),
+    }
+    .error_for_status(|h| {
+        streams.lock().unwrap().remove(&h);
+    })
+}
+
+fn read_chunk(streams: &Streams, handle: usize, len: usize) -> Result<Option<Bytes>, HttpError> {
+    // 读取期间不持有锁，避免阻塞其他流
+    let mut resp = streams
+        .lock()
+        .unwrap()
+        .remove(&handle)
+        .ok_or_else(|| HttpError::Other(format!("stream {handle} not found")))?;
+    let mut buf = vec![0; len];
+    let n = resp
+        .read(&mut buf)
+        .map_err(|e| HttpError::Body(e.to_string()))?;
+    if n == 0 {
+        return Ok(None);
+    }
+    streams.lock().unwrap().insert(handle, resp);
+    buf.truncate(n);
+    Ok(Some(Bytes(buf)))
+}
+
 struct State {
     // 已经就绪的响应
     ready_resp: HashMap<usize, Message>,
+    connectivity: ConnectivityTracker,
 }
 
 pub struct HttpClient {
     // 发送一个请求
-    req_tx: mpsc::Sender<(usize, HttpRequest)>,
+    req_tx: mpsc::Sender<(usize, Job)>,
     // 收到一个响应
     resp_rx: mpsc::Receiver<(usize, Message)>,
     state: RefCell<State>,
+    streams: Streams,
+    cache: HttpCacheLayer,
+    // 录制时不经过缓存，保证每个请求都访问网络并得到完整的响应
+    recording: bool,
 }
 
 impl HttpClient {
     pub fn new(threads: usize) -> Self {
-        let (req_tx, req_rx) = mpsc::channel::<(usize, HttpRequest)>();
+        Self::with_recorder(threads, None)
+    }
+
+    /// recorder不为空时，每个请求的响应都会写入录制文件
+    pub fn with_recorder(threads: usize, recorder: Option<Cassette>) -> Self {
+        let recording = recorder.is_some();
+        let recorder = recorder.map(Arc::new);
+        let (req_tx, req_rx) = mpsc::channel::<(usize, Job)>();
         let (resp_tx, resp_rx) = mpsc::channel();
         let client = ClientBuilder::new().gzip(true).build().unwrap();
 
+        let streams = Streams::default();
+
         let req_rx = Arc::new(Mutex::new(req_rx));
         for _ in 0..threads {
             let resp_tx = resp_tx.clone();
             let req_rx = req_rx.clone();
             let client = client.clone();
+            let streams = streams.clone();
+            let recorder = recorder.clone();
             thread::spawn(move || loop {
-                match req_rx.lock().unwrap().try_recv() {
-                    Ok((seq, req)) => {
-                        let resp = client
-                            .execute(
-                                client
-                                    .request(convert(req.method.clone()), req.url.clone())
-                                    .build()
-                                    .unwrap(),
-                            )
-                            .unwrap();
-                        let content = resp.bytes().unwrap().to_vec();
-                        resp_tx
-                            .send((
-                                seq,
-                                Message::Http(HttpMessage::Response(HttpResponse {
-                                    body: HttpBody::Bytes(Bytes(content)),
-                                })),
-                            ))
-                            .unwrap();
+                let job = req_rx.lock().unwrap().try_recv();
+                match job {
+                    Ok((seq, Job::Request(req))) => {
+                        let ret = req.retry.run(
+                            || execute(&client, &streams, seq, req.clone()),
+                            thread::sleep,
+                        );
+                        if let Some(x) = &recorder {
+                            x.save(&req, &ret);
+                        }
+                        let resp = match ret {
+                            Ok(x) => HttpMessage::Response(x),
+                            Err(e) => HttpMessage::Error(e),
+                        };
+                        resp_tx.send((seq, Message::Http(resp))).unwrap();
+                    }
+                    Ok((seq, Job::ReadChunk(handle, len))) => {
+                        let resp = match read_chunk(&streams, handle, len) {
+                            Ok(x) => HttpMessage::ReadChunkResponse(x),
+                            Err(e) => HttpMessage::Error(e),
+                        };
+                        resp_tx.send((seq, Message::Http(resp))).unwrap();
                     }
                     Err(e) => match e {
                         mpsc::TryRecvError::Empty => {
@@ -81,7 +195,11 @@ impl HttpClient {
             resp_rx,
             state: RefCell::new(State {
                 ready_resp: HashMap::new(),
+                connectivity: Default::default(),
             }),
+            streams,
+            cache: Default::default(),
+            recording,
         }
     }
 }
@@ -95,8 +213,17 @@ impl Node for HttpClient {
         let mut state = self.state.borrow_mut();
         match self.resp_rx.try_recv() {
             Ok((seq, resp)) => {
-                // 当消息执行完成后，消息转换为ready态
-                state.ready_resp.insert(seq, resp);
+                if let Some(x) = state.connectivity.record_message(&resp) {
+                    ctx.broadcast_topic(
+                        TopicName::Connectivity,
+                        Message::Http(HttpMessage::Connectivity(x)),
+                    );
+                }
+                // 当消息执行完成后，消息转换为ready态，合并的请求共享同一结果
+                let stg = StorageClient(ctx.clone());
+                for (seq, resp) in self.cache.on_response(&stg, seq, resp) {
+                    state.ready_resp.insert(seq, resp);
+                }
             }
             _ => {}
         }
@@ -107,13 +234,39 @@ impl Node for HttpClient {
         }
     }
 
-    fn handle_message(&self, _ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
         match msg.body {
+            Message::Http(HttpMessage::Request(req)) if self.recording => {
+                self.req_tx.send((msg.seq, Job::Request(req))).unwrap();
+                return HandleResult::Pending;
+            }
             Message::Http(HttpMessage::Request(req)) => {
-                // 传送消息
-                self.req_tx.send((msg.seq, req)).unwrap();
+                let stg = StorageClient(ctx.clone());
+                return match self.cache.on_request(&stg, msg.seq, req) {
+                    HttpCacheAction::Respond(x) => HandleResult::Finish(x),
+                    HttpCacheAction::Dispatch(req) => {
+                        // 传送消息
+                        self.req_tx.send((msg.seq, Job::Request(req))).unwrap();
+                        HandleResult::Pending
+                    }
+                    HttpCacheAction::Wait => HandleResult::Pending,
+                };
+            }
+            Message::Http(HttpMessage::ReadChunkRequest(handle, len)) => {
+                // 消费方拉取时才读取下一块，实现背压
+                self.req_tx
+                    .send((msg.seq, Job::ReadChunk(handle, len)))
+                    .unwrap();
                 return HandleResult::Pending;
             }
+            Message::Http(HttpMessage::GetConnectivityRequest) => {
+                let state = self.state.borrow().connectivity.state();
+                return HandleResult::Finish(Message::Http(HttpMessage::Connectivity(state)));
+            }
+            Message::Http(HttpMessage::CloseStreamRequest(handle)) => {
+                self.streams.lock().unwrap().remove(&handle);
+                return HandleResult::Finish(Message::Http(HttpMessage::CloseStreamResponse));
+            }
             _ => {}
         }
         HandleResult::Discard
@@ -1,59 +1,175 @@
-use std::rc::Rc;
+use std::{
+    cell::Cell,
+    io::{Read, Write},
+    rc::Rc,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
 
-use app_core::proto::*;
+use app_core::proto::{
+    gateway::{
+        handle_gateway_request, parse_bearer, serve_static, GatewayAuth, GatewayEvents,
+        GatewayRequest, GatewayResponse, DEFAULT_GATEWAY_ADDR,
+    },
+    *,
+};
+use app_core::CONSOLE_ASSETS;
 
-use log::error;
-use serde::{Deserialize, Serialize};
-use tiny_http::Response;
+use log::{error, info};
+use tiny_http::{Header, Request, Response};
+use tungstenite::{handshake::derive_accept_key, protocol::Role, WebSocket};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Req {
-    to: MessageTo,
-    body: Message,
+/// WebSocket连接线程发给节点的事件，None表示连接已断开
+type WsEvent = (usize, Option<String>);
+
+/// 没有待推送的帧时，两次ping之间的最长间隔
+const WS_PING_INTERVAL: Duration = Duration::from_millis(100);
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+/// 在独立线程中维护一个WebSocket连接。
+/// tiny_http升级后的连接无法拆分读写也无法设置超时，因此每次推送后都发送一个ping，
+/// 客户端自动回复pong，保证阻塞的read能及时返回
+fn run_ws<S: Read + Write>(
+    stream: S,
+    id: usize,
+    out_rx: Receiver<String>,
+    event_tx: Sender<WsEvent>,
+) {
+    use tungstenite::Message as WsMessage;
+
+    let mut ws = WebSocket::from_raw_socket(stream, Role::Server, None);
+    let mut pending = Vec::new();
+    'conn: loop {
+        pending.extend(out_rx.try_iter());
+        for x in pending.drain(..) {
+            if ws.write(WsMessage::Text(x)).is_err() {
+                break 'conn;
+            }
+        }
+        if ws.send(WsMessage::Ping(Vec::new())).is_err() {
+            break;
+        }
+        match ws.read() {
+            Ok(WsMessage::Text(x)) => {
+                if event_tx.send((id, Some(x))).is_err() {
+                    break;
+                }
+                continue;
+            }
+            Ok(WsMessage::Close(_)) | Err(_) => break,
+            Ok(_) => {}
+        }
+        match out_rx.recv_timeout(WS_PING_INTERVAL) {
+            Ok(x) => pending.push(x),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    let _ = event_tx.send((id, None));
+}
+
+fn random(bs: &mut [u8]) {
+    getrandom::getrandom(bs).unwrap();
 }
 
 pub struct HttpServer {
     h: tiny_http::Server,
+    auth: GatewayAuth,
+    events: GatewayEvents,
+    event_tx: Sender<WsEvent>,
+    event_rx: Receiver<WsEvent>,
+    next_ws_id: Cell<usize>,
 }
 
 impl HttpServer {
+    /// 监听地址可通过环境变量GATEWAY_ADDR修改
     pub fn new() -> Self {
-        let h = tiny_http::Server::http("127.0.0.1:38080").unwrap();
-        Self { h }
+        let addr = std::env::var("GATEWAY_ADDR").unwrap_or(DEFAULT_GATEWAY_ADDR.into());
+        info!("gateway listen on {addr}");
+        let h = tiny_http::Server::http(&addr).unwrap();
+        let (event_tx, event_rx) = mpsc::channel();
+        Self {
+            h,
+            auth: GatewayAuth::new(random),
+            events: Default::default(),
+            event_tx,
+            event_rx,
+            next_ws_id: Cell::new(0),
+        }
+    }
+
+    /// 完成WebSocket握手并交给连接线程，不是WebSocket请求时原样返回
+    fn upgrade_ws(&self, raw_req: Request) -> Option<Request> {
+        let key = raw_req
+            .headers()
+            .iter()
+            .find(|x| x.field.equiv("Sec-WebSocket-Key"))
+            .map(|x| derive_accept_key(x.value.as_bytes()));
+        let (true, Some(accept)) = (raw_req.url() == "/ws", key) else {
+            return Some(raw_req);
+        };
+        let resp = Response::empty(101)
+            .with_header(header("Upgrade", "websocket"))
+            .with_header(header("Connection", "Upgrade"))
+            .with_header(header("Sec-WebSocket-Accept", &accept));
+        let stream = raw_req.upgrade("websocket", resp);
+
+        let id = self.next_ws_id.get();
+        self.next_ws_id.set(id + 1);
+        let (out_tx, out_rx) = mpsc::channel();
+        self.events
+            .open(id, Box::new(move |x| out_tx.send(x).is_ok()));
+        let event_tx = self.event_tx.clone();
+        thread::spawn(move || run_ws(stream, id, out_rx, event_tx));
+        None
     }
 
     fn handle(&self, ctx: Rc<dyn Context>) {
-        match self.h.try_recv() {
-            Ok(Some(mut raw_req)) => match serde_json::from_reader::<_, Req>(raw_req.as_reader()) {
-                Ok(req_msg) => match req_msg.to {
-                    MessageTo::Broadcast => {
-                        ctx.broadcast_global(req_msg.body);
-                    }
-                    MessageTo::Topic(topic) => {
-                        ctx.broadcast_topic(topic, req_msg.body);
-                    }
-                    MessageTo::Point(p) => {
-                        ctx.async_call(
-                            p,
-                            req_msg.body,
-                            Box::new(|r| {
-                                let bs = serde_json::to_vec(&r).unwrap();
-                                if let Err(e) = raw_req.respond(Response::from_data(bs)) {
-                                    error!("http server write err: {e:?}");
-                                }
-                            }),
-                        );
-                    }
-                },
-                Err(e) => {
-                    if let Err(e) =
-                        raw_req.respond(Response::from_string(e.to_string()).with_status_code(400))
-                    {
-                        error!("http server write err: {e:?}");
-                    }
-                }
-            },
-            _ => {}
+        for (id, x) in self.event_rx.try_iter() {
+            match x {
+                Some(x) => self.events.on_frame(ctx.clone(), &self.auth, id, &x),
+                None => self.events.close(id),
+            }
+        }
+
+        let Ok(Some(raw_req)) = self.h.try_recv() else {
+            return;
+        };
+        let Some(mut raw_req) = self.upgrade_ws(raw_req) else {
+            return;
+        };
+        let mut body = Vec::new();
+        if let Err(e) = raw_req.as_reader().read_to_end(&mut body) {
+            error!("http server read err: {e:?}");
+            return;
+        }
+        let token = raw_req
+            .headers()
+            .iter()
+            .find(|x| x.field.equiv("Authorization"))
+            .and_then(|x| parse_bearer(x.value.as_str()));
+        let req = GatewayRequest {
+            method: raw_req.method().as_str().into(),
+            path: raw_req.url().into(),
+            token,
+            body,
+        };
+        let respond = move |resp: GatewayResponse| {
+            let mut x = Response::from_data(resp.body).with_status_code(resp.status);
+            for (k, v) in resp.headers.iter() {
+                x.add_header(header(k, v));
+            }
+            if let Err(e) = raw_req.respond(x) {
+                error!("http server write err: {e:?}");
+            }
+        };
+        match serve_static(CONSOLE_ASSETS, &req) {
+            Some(resp) => respond(resp),
+            None => handle_gateway_request(ctx, &self.auth, req, Box::new(respond)),
         }
     }
 }
@@ -64,6 +180,7 @@ impl Node for HttpServer {
     }
 
     fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        self.events.on_message(&msg);
         match msg.body {
             Message::Lifecycle(LifecycleMessage::Init) => ctx.subscribe_topic(TopicName::Scheduler),
             Message::Empty => self.handle(ctx.clone()),
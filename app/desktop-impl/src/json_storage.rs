@@ -1,26 +1,411 @@
-use std::{cell::RefCell, collections::HashMap, io::Seek};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    io::{ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use app_core::proto::*;
+use serde::{Deserialize, Serialize};
+
+/// 超过该大小的Bytes值会存为data目录下的独立文件
+const INLINE_BYTES_LIMIT: usize = 1024;
+
+/// 最后一次修改之后，延迟多久写回配置文件
+const FLUSH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// 持续有修改时，第一次未落盘的修改最多延迟多久写回
+const FLUSH_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// 落盘的值，前三项与StorageValue一致，兼容旧版的配置文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum DiskValue {
+    None,
+    Bytes(Bytes),
+    String(String),
+    /// 存放在data目录下的blob文件
+    File {
+        name: String,
+        size: usize,
+    },
+}
+
+impl DiskValue {
+    fn size(&self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Bytes(bs) => bs.0.len(),
+            Self::String(x) => x.len(),
+            Self::File { size, .. } => *size,
+        }
+    }
+}
+
+fn io_err(e: std::io::Error) -> StorageError {
+    StorageError::IOError(e.to_string())
+}
+
+/// rename之后fsync所在目录，保证rename本身已落盘。Windows无法打开目录，不需要
+fn sync_parent(path: &Path) -> Result<(), StorageError> {
+    if cfg!(unix) {
+        let dir = match path.parent() {
+            Some(x) if !x.as_os_str().is_empty() => x,
+            _ => Path::new("."),
+        };
+        fs::File::open(dir)
+            .and_then(|x| x.sync_all())
+            .map_err(io_err)?;
+    }
+    Ok(())
+}
+
+/// 先写临时文件并fsync，再rename覆盖目标文件，保证目标文件要么是旧内容要么是新内容
+fn atomic_write(
+    path: &Path,
+    f: impl FnOnce(&mut fs::File) -> Result<(), StorageError>,
+) -> Result<(), StorageError> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    let mut file = fs::File::create(&tmp_path).map_err(io_err)?;
+    f(&mut file)?;
+    file.sync_all().map_err(io_err)?;
+    fs::rename(&tmp_path, path).map_err(io_err)?;
+    sync_parent(path)
+}
+
+/// 未提交的blob写入会话，数据直接写入临时文件
+struct BlobSession {
+    key: String,
+    name: String,
+    file: fs::File,
+    size: usize,
+}
 
 pub struct JsonStorageService {
-    json_file: RefCell<std::fs::File>,
-    data: RefCell<HashMap<String, StorageValue>>,
+    json_path: PathBuf,
+    data_dir: PathBuf,
+    data: RefCell<HashMap<String, DiskValue>>,
+    // 第一次未落盘修改的时间
+    dirty_since: RefCell<Option<Instant>>,
+    // 最后一次修改的时间
+    last_write: RefCell<Instant>,
+    blob_sessions: RefCell<HashMap<usize, BlobSession>>,
+    blob_next_handle: RefCell<usize>,
 }
 
 impl JsonStorageService {
-    pub fn new(json_file_path: &str) -> Self {
-        let json_file = std::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(json_file_path)
-            .unwrap();
-        let data: HashMap<String, StorageValue> =
-            serde_json::from_reader(&json_file).expect("文件读取失败");
+    pub fn new(json_file_path: &str) -> Result<Self, StorageError> {
+        let json_path = PathBuf::from(json_file_path);
+        let data_dir = json_path.with_extension("data");
+        fs::create_dir_all(&data_dir).map_err(io_err)?;
+        let data: HashMap<String, DiskValue> = match fs::read(&json_path) {
+            Ok(bs) if bs.is_empty() => HashMap::new(),
+            Ok(bs) => serde_json::from_slice(&bs)
+                .map_err(|e| StorageError::TypeError(format!("{json_file_path}: {e}")))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(io_err(e)),
+        };
         log::debug!("配置加载成功：{:?}", data);
-        Self {
-            json_file: RefCell::new(json_file),
+        Ok(Self {
+            json_path,
+            data_dir,
             data: RefCell::new(data),
+            dirty_since: RefCell::new(None),
+            last_write: RefCell::new(Instant::now()),
+            blob_sessions: RefCell::new(HashMap::new()),
+            blob_next_handle: RefCell::new(0),
+        })
+    }
+
+    /// 配置文件无法读取时，将其重命名为`.broken`保留现场，并以空配置启动
+    pub fn new_or_reset(json_file_path: &str) -> Self {
+        Self::new(json_file_path).unwrap_or_else(|e| {
+            log::error!("配置加载失败：{e:?}，使用空配置启动");
+            let broken_path = format!("{json_file_path}.broken");
+            if let Err(e) = fs::rename(json_file_path, &broken_path) {
+                log::error!("备份损坏的配置文件失败：{e:?}");
+            }
+            Self::new(json_file_path).expect("初始化空配置失败")
+        })
+    }
+
+    /// 读取data目录下的设备密钥，不存在时随机生成一个
+    pub fn device_key(&self) -> Result<[u8; 32], StorageError> {
+        let path = self.data_dir.join("device.key");
+        match fs::read(&path) {
+            Ok(bs) => bs
+                .try_into()
+                .map_err(|_| StorageError::TypeError("invalid device key length".into())),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let mut key = [0u8; 32];
+                getrandom::getrandom(&mut key).map_err(|e| StorageError::Other(e.to_string()))?;
+                atomic_write(&path, |f| {
+                    #[cfg(unix)]
+                    {
+                        use std::os::unix::fs::PermissionsExt;
+                        f.set_permissions(fs::Permissions::from_mode(0o600))
+                            .map_err(io_err)?;
+                    }
+                    f.write_all(&key).map_err(io_err)
+                })?;
+                Ok(key)
+            }
+            Err(e) => Err(io_err(e)),
+        }
+    }
+
+    fn gen_blob_name(&self) -> String {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        format!("{nanos:x}-{}.bin", self.blob_next_handle.borrow())
+    }
+
+    fn mark_dirty(&self) {
+        let now = Instant::now();
+        self.dirty_since.borrow_mut().get_or_insert(now);
+        *self.last_write.borrow_mut() = now;
+    }
+
+    fn flush(&self) -> Result<(), StorageError> {
+        atomic_write(&self.json_path, |f| {
+            serde_json::to_writer_pretty(f, &*self.data.borrow())
+                .map_err(|e| StorageError::TypeError(e.to_string()))
+        })?;
+        *self.dirty_since.borrow_mut() = None;
+        Ok(())
+    }
+
+    fn flush_if_idle(&self) {
+        let dirty_since = *self.dirty_since.borrow();
+        if let Some(t) = dirty_since {
+            if self.last_write.borrow().elapsed() >= FLUSH_DEBOUNCE
+                || t.elapsed() >= FLUSH_MAX_DELAY
+            {
+                if let Err(e) = self.flush() {
+                    log::error!("配置写入失败：{e:?}");
+                }
+            }
+        }
+    }
+
+    /// 替换一个key的值，涉及blob文件时立即落盘，之后才删除旧文件
+    fn replace(&self, k: String, v: Option<DiskValue>) -> Result<(), StorageError> {
+        let is_file = matches!(v, Some(DiskValue::File { .. }));
+        let old = match v {
+            Some(v) => self.data.borrow_mut().insert(k, v),
+            None => self.data.borrow_mut().remove(&k),
+        };
+        match old {
+            Some(DiskValue::File { name, .. }) => {
+                self.flush()?;
+                if let Err(e) = fs::remove_file(self.data_dir.join(&name)) {
+                    log::warn!("删除blob文件{name}失败：{e:?}");
+                }
+            }
+            _ if is_file => self.flush()?,
+            _ => self.mark_dirty(),
+        }
+        Ok(())
+    }
+
+    fn usage(&self, prefix: &str) -> StorageUsage {
+        self.data
+            .borrow()
+            .iter()
+            .filter(|(k, _)| k.starts_with(prefix))
+            .fold(StorageUsage::default(), |acc, (_, v)| StorageUsage {
+                keys: acc.keys + 1,
+                bytes: acc.bytes + v.size(),
+            })
+    }
+
+    fn check_quota(&self, k: &str, new_size: usize) -> Result<(), StorageError> {
+        let old_size = self.data.borrow().get(k).map(DiskValue::size).unwrap_or(0);
+        check_storage_quota(k, old_size, new_size, |prefix| self.usage(prefix).bytes)
+    }
+
+    fn delete_prefix(&self, prefix: &str) -> Result<usize, StorageError> {
+        let mut removed_files = vec![];
+        let mut n = 0;
+        self.data.borrow_mut().retain(|k, v| {
+            if !k.starts_with(prefix) {
+                return true;
+            }
+            if let DiskValue::File { name, .. } = v {
+                removed_files.push(name.clone());
+            }
+            n += 1;
+            false
+        });
+        self.flush()?;
+        for name in removed_files.into_iter() {
+            if let Err(e) = fs::remove_file(self.data_dir.join(&name)) {
+                log::warn!("删除blob文件{name}失败：{e:?}");
+            }
+        }
+        Ok(n)
+    }
+
+    fn get(&self, k: String) -> Result<StorageValue, StorageError> {
+        Ok(match self.data.borrow().get(&k) {
+            None | Some(DiskValue::None) => StorageValue::None,
+            Some(DiskValue::String(x)) => StorageValue::String(x.clone()),
+            Some(DiskValue::Bytes(bs)) => StorageValue::Bytes(bs.clone()),
+            Some(DiskValue::File { name, .. }) => {
+                StorageValue::Bytes(Bytes(fs::read(self.data_dir.join(name)).map_err(io_err)?))
+            }
+        })
+    }
+
+    fn set(&self, k: String, v: StorageValue) -> Result<(), StorageError> {
+        self.check_quota(&k, v.size())?;
+        let v = match v {
+            StorageValue::None => None,
+            StorageValue::String(x) => Some(DiskValue::String(x)),
+            StorageValue::Bytes(bs) if bs.0.len() > INLINE_BYTES_LIMIT => {
+                let name = self.gen_blob_name();
+                atomic_write(&self.data_dir.join(&name), |f| {
+                    f.write_all(&bs.0).map_err(io_err)
+                })?;
+                Some(DiskValue::File {
+                    name,
+                    size: bs.0.len(),
+                })
+            }
+            StorageValue::Bytes(bs) => Some(DiskValue::Bytes(bs)),
+        };
+        self.replace(k, v)
+    }
+
+    fn blob_open(&self, k: String) -> Result<usize, StorageError> {
+        *self.blob_next_handle.borrow_mut() += 1;
+        let handle = *self.blob_next_handle.borrow();
+        let name = self.gen_blob_name();
+        let mut tmp_name = name.clone();
+        tmp_name.push_str(".tmp");
+        let file = fs::File::create(self.data_dir.join(tmp_name)).map_err(io_err)?;
+        self.blob_sessions.borrow_mut().insert(
+            handle,
+            BlobSession {
+                key: k,
+                name,
+                file,
+                size: 0,
+            },
+        );
+        Ok(handle)
+    }
+
+    fn blob_write(&self, handle: usize, chunk: Bytes) -> Result<(), StorageError> {
+        let mut sessions = self.blob_sessions.borrow_mut();
+        let s = sessions
+            .get_mut(&handle)
+            .ok_or_else(|| StorageError::Other(format!("blob session {handle} not found")))?;
+        s.file.write_all(&chunk.0).map_err(io_err)?;
+        s.size += chunk.0.len();
+        Ok(())
+    }
+
+    fn blob_commit(&self, handle: usize) -> Result<(), StorageError> {
+        let s = self
+            .blob_sessions
+            .borrow_mut()
+            .remove(&handle)
+            .ok_or_else(|| StorageError::Other(format!("blob session {handle} not found")))?;
+        let path = self.data_dir.join(&s.name);
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        if let Err(e) = self.check_quota(&s.key, s.size) {
+            drop(s.file);
+            let _ = fs::remove_file(tmp_path);
+            return Err(e);
+        }
+        s.file.sync_all().map_err(io_err)?;
+        fs::rename(tmp_path, &path).map_err(io_err)?;
+        sync_parent(&path)?;
+        self.replace(
+            s.key,
+            Some(DiskValue::File {
+                name: s.name,
+                size: s.size,
+            }),
+        )
+    }
+
+    /// 放弃写入会话并删除临时文件，会话不存在时什么也不做
+    fn blob_abort(&self, handle: usize) -> Result<(), StorageError> {
+        let Some(s) = self.blob_sessions.borrow_mut().remove(&handle) else {
+            return Ok(());
+        };
+        drop(s.file);
+        let mut tmp_name = s.name;
+        tmp_name.push_str(".tmp");
+        fs::remove_file(self.data_dir.join(tmp_name)).map_err(io_err)
+    }
+
+    /// 删除上次运行时未提交或未完成原子写入的临时文件
+    fn remove_stale_tmp_files(&self) {
+        let entries = match fs::read_dir(&self.data_dir) {
+            Ok(x) => x,
+            Err(e) => {
+                log::warn!("读取blob目录失败：{e:?}");
+                return;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|x| x == "tmp") {
+                log::info!("删除未提交的临时文件{path:?}");
+                if let Err(e) = fs::remove_file(&path) {
+                    log::warn!("删除临时文件{path:?}失败：{e:?}");
+                }
+            }
+        }
+    }
+
+    fn blob_read(&self, k: String, offset: usize, len: usize) -> Result<Bytes, StorageError> {
+        match self.data.borrow().get(&k) {
+            Some(DiskValue::Bytes(bs)) => {
+                let start = offset.min(bs.0.len());
+                let end = offset.saturating_add(len).min(bs.0.len());
+                Ok(Bytes(bs.0[start..end].to_vec()))
+            }
+            Some(DiskValue::File { name, size }) => {
+                let mut f = fs::File::open(self.data_dir.join(name)).map_err(io_err)?;
+                f.seek(SeekFrom::Start(offset.min(*size) as u64))
+                    .map_err(io_err)?;
+                let mut buf = Vec::with_capacity(len.min(*size));
+                f.take(len as u64).read_to_end(&mut buf).map_err(io_err)?;
+                Ok(Bytes(buf))
+            }
+            Some(DiskValue::String(_)) => {
+                Err(StorageError::TypeError(format!("{k} is not a blob")))
+            }
+            None | Some(DiskValue::None) => Err(StorageError::Other(format!("{k} not found"))),
+        }
+    }
+
+    fn blob_size(&self, k: String) -> Option<usize> {
+        match self.data.borrow().get(&k) {
+            Some(DiskValue::Bytes(bs)) => Some(bs.0.len()),
+            Some(DiskValue::File { size, .. }) => Some(*size),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for JsonStorageService {
+    fn drop(&mut self) {
+        if self.dirty_since.borrow().is_some() {
+            if let Err(e) = self.flush() {
+                log::error!("配置写入失败：{e:?}");
+            }
         }
     }
 }
@@ -33,39 +418,70 @@ impl Node for JsonStorageService {
         NodeName::Storage
     }
 
-    fn handle_message(
-        &self,
-        _ctx: std::rc::Rc<dyn Context>,
-        msg: MessageWithHeader,
-    ) -> HandleResult {
-        if let Message::Storage(sm) = msg.body {
-            let mut data = self.data.borrow_mut();
-            return HandleResult::Finish(Message::Storage(match sm {
-                StorageMessage::GetRequest(k) => {
-                    StorageMessage::GetResponse(data.get(&k).cloned().unwrap_or(StorageValue::None))
-                }
-                StorageMessage::SetRequest(k, v) => {
-                    match v {
-                        StorageValue::None => {
-                            data.remove(&k);
-                        }
-                        v => {
-                            data.insert(k, v);
-                        }
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        match msg.body {
+            Message::Lifecycle(LifecycleMessage::Init) => {
+                ctx.subscribe_topic(TopicName::Scheduler);
+                self.remove_stale_tmp_files();
+            }
+            Message::Empty => {
+                // 调度器心跳，检查是否需要写回配置
+                self.flush_if_idle();
+            }
+            Message::Storage(sm) => {
+                let resp = match sm {
+                    StorageMessage::GetRequest(k) => self.get(k).map(StorageMessage::GetResponse),
+                    StorageMessage::SetRequest(k, v) => {
+                        self.set(k, v).map(|_| StorageMessage::SetResponse)
                     }
-                    self.json_file.borrow_mut().set_len(0).unwrap();
-                    self.json_file.borrow_mut().rewind().unwrap();
-                    serde_json::to_writer_pretty(&*self.json_file.borrow(), &*data).unwrap();
-                    StorageMessage::SetResponse
-                }
-                StorageMessage::ListKeysRequest(prefix) => StorageMessage::ListKeysResponse(
-                    data.keys()
-                        .filter(|x| x.starts_with(&prefix))
-                        .map(|x| x.into())
-                        .collect(),
-                ),
-                m => panic!("unexcepted message {m:?}"),
-            }));
+                    StorageMessage::ListKeysRequest(prefix) => {
+                        Ok(StorageMessage::ListKeysResponse(
+                            self.data
+                                .borrow()
+                                .keys()
+                                .filter(|x| x.starts_with(&prefix))
+                                .map(|x| x.into())
+                                .collect(),
+                        ))
+                    }
+                    StorageMessage::BlobOpenRequest(k) => {
+                        self.blob_open(k).map(StorageMessage::BlobOpenResponse)
+                    }
+                    StorageMessage::BlobWriteRequest(handle, chunk) => self
+                        .blob_write(handle, chunk)
+                        .map(|_| StorageMessage::BlobWriteResponse),
+                    StorageMessage::BlobCommitRequest(handle) => self
+                        .blob_commit(handle)
+                        .map(|_| StorageMessage::BlobCommitResponse),
+                    StorageMessage::BlobAbortRequest(handle) => self
+                        .blob_abort(handle)
+                        .map(|_| StorageMessage::BlobAbortResponse),
+                    StorageMessage::BlobReadRequest(k, offset, len) => self
+                        .blob_read(k, offset, len)
+                        .map(StorageMessage::BlobReadResponse),
+                    StorageMessage::BlobSizeRequest(k) => {
+                        Ok(StorageMessage::BlobSizeResponse(self.blob_size(k)))
+                    }
+                    StorageMessage::UsageRequest(prefix) => {
+                        Ok(StorageMessage::UsageResponse(self.usage(&prefix)))
+                    }
+                    StorageMessage::CapacityRequest => {
+                        Ok(StorageMessage::CapacityResponse(StorageCapacity {
+                            total: None,
+                            used: self.usage("").bytes,
+                            free: None,
+                        }))
+                    }
+                    StorageMessage::DeletePrefixRequest(prefix) => self
+                        .delete_prefix(&prefix)
+                        .map(StorageMessage::DeletePrefixResponse),
+                    m => panic!("unexcepted message {m:?}"),
+                };
+                return HandleResult::Finish(Message::Storage(
+                    resp.unwrap_or_else(StorageMessage::Error),
+                ));
+            }
+            _ => {}
         }
         HandleResult::Discard
     }
@@ -1,4 +1,4 @@
-use app_core::{get_app_window, get_scheduler, Scheduler};
+use app_core::{get_app_window, get_scheduler, MockSensorService, Scheduler, SecretService};
 use log::info;
 use std::rc::Rc;
 use std::time::Duration;
@@ -6,6 +6,9 @@ use std::time::Duration;
 mod http_client;
 use http_client::HttpClient;
 
+mod http_cassette;
+use http_cassette::{CassetteHttpClient, CassetteMode, ReplaySecretService};
+
 mod http_server;
 use http_server::HttpServer;
 
@@ -15,6 +18,30 @@ use midi_player::MidiPlayer;
 mod json_storage;
 use json_storage::JsonStorageService;
 
+mod remote_node;
+use remote_node::load_remote_nodes;
+
+/// 从`SENSOR_FILE`指定的文件读取模拟读数，每行为`温度,湿度`
+fn load_sensor_file() -> Option<Vec<(f32, f32)>> {
+    let path = std::env::var("SENSOR_FILE").ok()?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(x) => x,
+        Err(e) => {
+            log::warn!("read sensor file {path:?} error: {e}");
+            return None;
+        }
+    };
+    let readings = text
+        .lines()
+        .filter_map(|line| {
+            let (temp, humidity) = line.trim().split_once(',')?;
+            Some((temp.trim().parse().ok()?, humidity.trim().parse().ok()?))
+        })
+        .collect::<Vec<_>>();
+    log::info!("Load {} sensor readings from {}", readings.len(), path);
+    Some(readings)
+}
+
 fn start_scheduler() -> Rc<Scheduler> {
     let config_path = std::env::args()
         .skip(1)
@@ -22,10 +49,34 @@ fn start_scheduler() -> Rc<Scheduler> {
         .unwrap_or("config.json".into());
     log::info!("Load config: {}", config_path);
     let sche = get_scheduler();
-    sche.register_node(JsonStorageService::new(&config_path));
-    sche.register_node(HttpClient::new(4));
+    let storage = JsonStorageService::new_or_reset(&config_path);
+    let secret = SecretService::new(storage.device_key().expect("加载设备密钥失败"));
+    sche.register_node(storage);
+    match CassetteMode::from_env() {
+        Some((CassetteMode::Replay, cassette)) => {
+            log::info!("HTTP replay mode");
+            sche.register_node(ReplaySecretService(secret));
+            sche.register_node(CassetteHttpClient::new(cassette));
+        }
+        Some((CassetteMode::Record, cassette)) => {
+            log::info!("HTTP record mode");
+            sche.register_node(secret);
+            sche.register_node(HttpClient::with_recorder(4, Some(cassette)));
+        }
+        None => {
+            sche.register_node(secret);
+            sche.register_node(HttpClient::new(4));
+        }
+    }
     sche.register_node(HttpServer::new());
     sche.register_node(MidiPlayer::new());
+    sche.register_node(match load_sensor_file() {
+        Some(x) => MockSensorService::replay(x),
+        None => MockSensorService::new(),
+    });
+    for x in load_remote_nodes() {
+        sche.register_node(x);
+    }
     sche
 }
 
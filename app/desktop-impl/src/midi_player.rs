@@ -1,4 +1,7 @@
-use app_core::proto::*;
+use app_core::{
+    proto::{ipc::StorageClient, *},
+    storage::MusicStorage,
+};
 use std::rc::Rc;
 pub struct MidiPlayer {}
 
@@ -13,12 +16,19 @@ impl Node for MidiPlayer {
         NodeName::MidiPlayer
     }
 
-    fn handle_message(&self, _ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
         match msg.body {
             Message::Midi(MidiMessage::PlayRequest(r)) => {
                 println!("{r:?}");
                 return HandleResult::Finish(Message::Midi(MidiMessage::PlayResponse(false)));
             }
+            Message::Midi(MidiMessage::PlayMusicRequest(filename)) => {
+                println!("play music {filename}");
+                return HandleResult::Finish(Message::Midi(MidiMessage::PlayResponse(false)));
+            }
+            Message::Midi(MidiMessage::DownloadRequest { filename, url }) => {
+                return MusicStorage(StorageClient(ctx)).handle_download(msg.seq, filename, url);
+            }
             _ => {}
         }
         HandleResult::Discard
@@ -0,0 +1,117 @@
+use std::{
+    io::ErrorKind,
+    net::TcpStream,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+use app_core::proto::gateway::{ProxyEvent, ProxyTransport, RemoteNodeConfig, RemoteNodeProxy};
+use log::{error, info, warn};
+use tungstenite::{stream::MaybeTlsStream, Message as WsMessage, WebSocket};
+
+/// 读超时，决定发送帧的最大延迟
+const WS_READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// 连接断开后重连的间隔
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 连接断开时返回，之后的帧需要等待重连
+fn run_conn(
+    ws: &mut WebSocket<MaybeTlsStream<TcpStream>>,
+    out_rx: &Receiver<String>,
+    event_tx: &Sender<ProxyEvent>,
+) {
+    loop {
+        for x in out_rx.try_iter() {
+            if ws.write(WsMessage::Text(x)).is_err() {
+                return;
+            }
+        }
+        if ws.flush().is_err() {
+            return;
+        }
+        match ws.read() {
+            Ok(WsMessage::Text(x)) => {
+                if event_tx.send(ProxyEvent::Frame(x)).is_err() {
+                    return;
+                }
+            }
+            Ok(WsMessage::Close(_)) => return,
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(_) => return,
+        }
+    }
+}
+
+/// 在独立线程中维护到远程网关的WebSocket连接，断开后自动重连
+pub struct WsTransport {
+    out_tx: Sender<String>,
+    event_rx: Receiver<ProxyEvent>,
+}
+
+impl WsTransport {
+    pub fn connect(url: String) -> Self {
+        let (out_tx, out_rx) = mpsc::channel::<String>();
+        let (event_tx, event_rx) = mpsc::channel();
+        thread::spawn(move || loop {
+            match tungstenite::connect(url.as_str()) {
+                Ok((mut ws, _)) => {
+                    if let MaybeTlsStream::Plain(x) = ws.get_ref() {
+                        let _ = x.set_read_timeout(Some(WS_READ_TIMEOUT));
+                    }
+                    info!("remote gateway {url} connected");
+                    // 断开期间的帧已无意义，重连后代理会重新认证与订阅
+                    out_rx.try_iter().for_each(drop);
+                    if event_tx.send(ProxyEvent::Connected).is_err() {
+                        return;
+                    }
+                    run_conn(&mut ws, &out_rx, &event_tx);
+                    warn!("remote gateway {url} disconnected");
+                    if event_tx.send(ProxyEvent::Disconnected).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => error!("connect remote gateway {url} err: {e:?}"),
+            }
+            thread::sleep(RECONNECT_INTERVAL);
+        });
+        Self { out_tx, event_rx }
+    }
+}
+
+impl ProxyTransport for WsTransport {
+    fn send(&self, text: String) {
+        let _ = self.out_tx.send(text);
+    }
+
+    fn poll(&self) -> Option<ProxyEvent> {
+        self.event_rx.try_recv().ok()
+    }
+}
+
+/// 从环境变量REMOTE_NODES指定的JSON文件加载远程节点，文件内容为RemoteNodeConfig的数组
+pub fn load_remote_nodes() -> Vec<RemoteNodeProxy> {
+    let Ok(path) = std::env::var("REMOTE_NODES") else {
+        return Vec::new();
+    };
+    let configs = std::fs::read(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|x| Ok(serde_json::from_slice::<Vec<RemoteNodeConfig>>(&x)?));
+    match configs {
+        Ok(x) => x
+            .into_iter()
+            .map(|x| {
+                info!("remote node {} -> {:?}@{}", x.name, x.remote, x.url);
+                let transport = WsTransport::connect(x.url.clone());
+                RemoteNodeProxy::new(x, Box::new(transport))
+            })
+            .collect(),
+        Err(e) => {
+            error!("load remote nodes from {path} err: {e:?}");
+            Vec::new()
+        }
+    }
+}
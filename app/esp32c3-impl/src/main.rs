@@ -11,12 +11,13 @@ use std::{
 };
 
 use crate::node::*;
-use app_core::get_scheduler;
+use app_core::{get_scheduler, SecretService};
 use display_interface_spi::SPIInterface;
 use embedded_graphics_mux::{DisplayMux, LogicalDisplay};
 use esp_idf_hal::{
     delay::FreeRtos,
     gpio::{AnyIOPin, PinDriver},
+    i2c::{I2cConfig, I2cDriver},
     ledc::{config::TimerConfig, LedcDriver, LedcTimerDriver},
     prelude::*,
     rmt::{
@@ -131,6 +132,18 @@ fn main() -> anyhow::Result<()> {
         .unwrap();
         sche.register_node(BuzzerService::new(beep_tx));
     }
+    if let Some(sensor) = peripherals.sensor {
+        // 传感器是可选的外设，初始化失败时不影响其他功能
+        match I2cDriver::new(
+            sensor.i2c,
+            sensor.sda,
+            sensor.scl,
+            &I2cConfig::new().baudrate(100.kHz().into()),
+        ) {
+            Ok(i2c) => sche.register_node(Sht3xSensorService::new(i2c)),
+            Err(e) => log::warn!("init i2c for sensor error, run without sensor: {e:?}"),
+        }
+    }
 
     sche.register_node(SystemService::new(frame_counter));
     sche.register_node(WiFiService::new(
@@ -141,6 +154,7 @@ fn main() -> anyhow::Result<()> {
     sche.register_node(SntpService::new());
     sche.register_node(HttpClientService::new());
     sche.register_node(NvsStorageService::new(nvs.clone()));
+    sche.register_node(SecretService::new(load_device_key(nvs.clone())?));
     sche.register_node(HttpServerService::new());
     sche.register_node(CanvasView::new(display_mux.clone()));
     let sche_timer = slint::Timer::default();
@@ -3,6 +3,7 @@ mod canvas;
 mod httpclient;
 mod httpserver;
 mod onebutton;
+mod sht3x;
 mod sntp;
 mod storage;
 mod system;
@@ -13,7 +14,8 @@ pub use canvas::CanvasView;
 pub use httpclient::HttpClientService;
 pub use httpserver::HttpServerService;
 pub use onebutton::OneButtonService;
+pub use sht3x::Sht3xSensorService;
 pub use sntp::SntpService;
-pub use storage::NvsStorageService;
+pub use storage::{load_device_key, NvsStorageService};
 pub use system::SystemService;
 pub use wifi::WiFiService;
@@ -1,4 +1,5 @@
 use std::{
+    cell::RefCell,
     collections::HashMap,
     io::Read,
     rc::Rc,
@@ -7,58 +8,210 @@ use std::{
     time::Duration,
 };
 
-use app_core::proto::*;
+use app_core::proto::{
+    storage::{HttpCacheAction, HttpCacheLayer},
+    *,
+};
 use embedded_io_adapters::std::ToStd;
-use embedded_svc::http::client::Client;
+use embedded_svc::{http::Method, io::Write};
 use esp_idf_svc::http::client::{Configuration, EspHttpConnection};
 use esp_idf_sys as _;
 use libflate::gzip::{self};
 
+/// esp-idf的客户端无法遍历响应头，只收集业务上需要的响应头
+const RESPONSE_HEADERS: &[&str] = &[
+    "content-type",
+    "content-length",
+    "content-encoding",
+    "cache-control",
+    "etag",
+    "last-modified",
+    "location",
+    "retry-after",
+];
+
+fn convert(method: &HttpRequestMethod) -> Method {
+    match method {
+        HttpRequestMethod::Get => Method::Get,
+        HttpRequestMethod::Post => Method::Post,
+        HttpRequestMethod::Put => Method::Put,
+        HttpRequestMethod::Delete => Method::Delete,
+    }
+}
+
+fn convert_error(e: esp_idf_sys::EspError) -> HttpError {
+    if [
+        esp_idf_sys::ESP_ERR_TIMEOUT,
+        esp_idf_sys::ESP_ERR_HTTP_EAGAIN,
+    ]
+    .contains(&(e.code() as u32))
+    {
+        HttpError::Timeout
+    } else {
+        HttpError::Connect(format!("{e}"))
+    }
+}
+
+/// 发起请求并读取响应头，返回的reader用于读取(解压后的)响应体
+fn open(req: &HttpRequest) -> Result<(u16, Vec<(String, String)>, Box<dyn Read>), HttpError> {
+    let mut conn = EspHttpConnection::new(&Configuration {
+        crt_bundle_attach: Some(esp_idf_sys::esp_crt_bundle_attach), // https支持
+        timeout: Some(req.get_timeout()),
+        ..Default::default()
+    })
+    .map_err(|e| HttpError::Other(format!("{e}")))?;
+
+    let content_len = req.body.as_ref().map(|x| x.0.len().to_string());
+    let mut headers = req
+        .headers
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect::<Vec<_>>();
+    if let Some(x) = &content_len {
+        if !headers
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        {
+            headers.push(("content-length", x));
+        }
+    }
+    // DNS解析与建立连接都在这里完成，失败时可以重试
+    conn.initiate_request(convert(&req.method), &req.url, &headers)
+        .map_err(convert_error)?;
+    if let Some(body) = &req.body {
+        conn.write_all(&body.0)
+            .map_err(|e| HttpError::Connect(format!("{e}")))?;
+    }
+    conn.initiate_response().map_err(convert_error)?;
+
+    let status = conn.status();
+    let headers = RESPONSE_HEADERS
+        .iter()
+        .filter_map(|k| conn.header(k).map(|v| (k.to_string(), v.to_string())))
+        .collect();
+    let reader: Box<dyn Read> = if let Some("gzip") = conn.header("content-encoding") {
+        Box::new(gzip::Decoder::new(ToStd::new(conn)).map_err(|e| HttpError::Body(format!("{e}")))?)
+    } else {
+        Box::new(ToStd::new(conn))
+    };
+    Ok((status, headers, reader))
+}
+
+fn execute(
+    streams: &mut HashMap<usize, Box<dyn Read>>,
+    seq: usize,
+    req: &HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    let (status, headers, mut reader) = open(req)?;
+    if req.stream && (200..300).contains(&status) {
+        // 连接保留在工作线程中，由消费方逐块拉取
+        streams.insert(seq, reader);
+        return Ok(HttpResponse {
+            status,
+            headers,
+            body: HttpBody::Stream(seq),
+        });
+    }
+    let mut resp_body = Vec::new();
+    reader
+        .read_to_end(&mut resp_body)
+        .map_err(|e| HttpError::Body(format!("{e}")))?;
+    HttpResponse {
+        status,
+        headers,
+        body: HttpBody::Bytes(Bytes(resp_body)),
+    }
+    .error_for_status(|h| {
+        streams.remove(&h);
+    })
+}
+
+fn read_chunk(
+    streams: &mut HashMap<usize, Box<dyn Read>>,
+    handle: usize,
+    len: usize,
+) -> Result<Option<Bytes>, HttpError> {
+    let reader = streams
+        .get_mut(&handle)
+        .ok_or_else(|| HttpError::Other(format!("stream {handle} not found")))?;
+    let mut buf = vec![0; len];
+    let n = reader
+        .read(&mut buf)
+        .map_err(|e| HttpError::Body(format!("{e}")))?;
+    if n == 0 {
+        streams.remove(&handle);
+        return Ok(None);
+    }
+    buf.truncate(n);
+    Ok(Some(Bytes(buf)))
+}
+
+enum Job {
+    Request(HttpRequest),
+    ReadChunk(usize, usize),
+}
+
+/// 待执行的任务与执行结果，工作线程取走任务后释放锁再执行，
+/// 避免请求与重试等待期间阻塞poll
+type State = Arc<Mutex<HashMap<usize, (Option<Job>, Option<Message>)>>>;
+
 pub struct HttpClientService {
-    state: Arc<Mutex<HashMap<usize, (HttpRequest, Option<Message>)>>>,
+    state: State,
+    // 待关闭的流，由工作线程释放连接
+    closing: Arc<Mutex<Vec<usize>>>,
+    connectivity: RefCell<ConnectivityTracker>,
+    cache: HttpCacheLayer,
 }
 
 impl HttpClientService {
     pub fn new() -> Self {
-        let state: Arc<Mutex<HashMap<usize, (HttpRequest, Option<Message>)>>> =
-            Arc::new(Mutex::new(HashMap::new()));
+        let state: State = Default::default();
+        let closing: Arc<Mutex<Vec<usize>>> = Default::default();
 
         let state_ref = state.clone();
+        let closing_ref = closing.clone();
         thread::Builder::new()
             .stack_size(4 * 1024)
             .spawn(move || {
+                // 未读完的流式响应体，key为发起请求的消息seq
+                let mut streams: HashMap<usize, Box<dyn Read>> = HashMap::new();
                 loop {
-                    for (_, (req, result)) in state_ref.lock().unwrap().iter_mut() {
-                        let ret = (|| -> anyhow::Result<_> {
-                            let conn = EspHttpConnection::new(&Configuration {
-                                crt_bundle_attach: Some(esp_idf_sys::esp_crt_bundle_attach), // https支持
-                                ..Default::default()
-                            })?;
-                            let mut client = Client::wrap(conn);
-
-                            let resp = client.get(&req.url)?.submit()?;
-                            let resp_body = if let Some("gzip") = resp.header("content-encoding") {
-                                gzip::Decoder::new(ToStd::new(resp))?
-                                    .bytes()
-                                    .try_collect::<Vec<_>>()?
-                            } else {
-                                ToStd::new(resp).bytes().try_collect::<Vec<_>>()?
-                            };
-                            Ok(HttpMessage::Response(HttpResponse {
-                                body: HttpBody::Bytes(Bytes(resp_body)),
-                            }))
-                        })();
-                        *result = Some(Message::Http(match ret {
-                            Ok(x) => x,
-                            Err(e) => HttpMessage::Error(HttpError::Other(format!("{e}"))),
-                        }));
+                    for handle in closing_ref.lock().unwrap().drain(..) {
+                        streams.remove(&handle);
+                    }
+                    let jobs = state_ref
+                        .lock()
+                        .unwrap()
+                        .iter_mut()
+                        .filter_map(|(seq, (job, _))| job.take().map(|x| (*seq, x)))
+                        .collect::<Vec<_>>();
+                    for (seq, job) in jobs.into_iter() {
+                        let ret = match job {
+                            Job::Request(req) => req
+                                .retry
+                                .run(|| execute(&mut streams, seq, &req), thread::sleep)
+                                .map(HttpMessage::Response),
+                            Job::ReadChunk(handle, len) => read_chunk(&mut streams, handle, len)
+                                .map(HttpMessage::ReadChunkResponse),
+                        };
+                        if let Some((_, result)) = state_ref.lock().unwrap().get_mut(&seq) {
+                            *result = Some(Message::Http(match ret {
+                                Ok(x) => x,
+                                Err(e) => HttpMessage::Error(e),
+                            }));
+                        }
                     }
 
                     thread::sleep(Duration::from_millis(16));
                 }
             })
             .unwrap();
-        Self { state }
+        Self {
+            state,
+            closing,
+            connectivity: Default::default(),
+            cache: Default::default(),
+        }
     }
 }
 
@@ -68,20 +221,64 @@ impl Node for HttpClientService {
     }
 
     fn poll(&self, ctx: Rc<dyn Context>, seq: usize) {
-        let mut l = self.state.lock().unwrap();
-        if let (_, Some(result)) = &l[&seq] {
+        let result = {
+            let mut l = self.state.lock().unwrap();
+            match l.get(&seq) {
+                Some((_, Some(_))) => l.remove(&seq).and_then(|(_, x)| x),
+                _ => None,
+            }
+        };
+        if let Some(result) = result {
             // 消息有结果了
-            ctx.async_ready(seq, result.clone());
-            l.remove(&seq);
+            if let Some(x) = self.connectivity.borrow_mut().record_message(&result) {
+                ctx.broadcast_topic(
+                    TopicName::Connectivity,
+                    Message::Http(HttpMessage::Connectivity(x)),
+                );
+            }
+            // 合并的请求共享同一结果
+            let stg = ipc::StorageClient(ctx.clone());
+            for (seq, result) in self.cache.on_response(&stg, seq, result) {
+                ctx.async_ready(seq, result);
+            }
         }
     }
 
-    fn handle_message(&self, _ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
-        if let Message::Http(HttpMessage::Request(req)) = msg.body {
-            // 传送消息
-            self.state.lock().unwrap().insert(msg.seq, (req, None));
-            return HandleResult::Pending;
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        match msg.body {
+            Message::Http(HttpMessage::Request(req)) => {
+                let stg = ipc::StorageClient(ctx.clone());
+                match self.cache.on_request(&stg, msg.seq, req) {
+                    HttpCacheAction::Respond(x) => HandleResult::Finish(x),
+                    HttpCacheAction::Dispatch(req) => {
+                        // 传送消息
+                        self.state
+                            .lock()
+                            .unwrap()
+                            .insert(msg.seq, (Some(Job::Request(req)), None));
+                        HandleResult::Pending
+                    }
+                    HttpCacheAction::Wait => HandleResult::Pending,
+                }
+            }
+            Message::Http(HttpMessage::ReadChunkRequest(handle, len)) => {
+                // 消费方拉取时才从连接读取下一块，实现背压
+                self.state
+                    .lock()
+                    .unwrap()
+                    .insert(msg.seq, (Some(Job::ReadChunk(handle, len)), None));
+                HandleResult::Pending
+            }
+            Message::Http(HttpMessage::GetConnectivityRequest) => {
+                HandleResult::Finish(Message::Http(HttpMessage::Connectivity(
+                    self.connectivity.borrow().state(),
+                )))
+            }
+            Message::Http(HttpMessage::CloseStreamRequest(handle)) => {
+                self.closing.lock().unwrap().push(handle);
+                HandleResult::Finish(Message::Http(HttpMessage::CloseStreamResponse))
+            }
+            _ => HandleResult::Discard,
         }
-        HandleResult::Discard
     }
 }
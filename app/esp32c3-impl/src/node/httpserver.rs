@@ -1,131 +1,180 @@
 use std::{
     cell::RefCell,
+    io::Read,
     rc::Rc,
-    sync::mpsc::{self, Receiver, SyncSender},
+    sync::{
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Mutex,
+    },
 };
 
-use app_core::proto::*;
+use app_core::proto::{
+    gateway::{
+        handle_gateway_request, parse_bearer, serve_static, GatewayAuth, GatewayEvents,
+        GatewayRequest, GatewayResponse,
+    },
+    *,
+};
+use app_core::CONSOLE_ASSETS;
 use embedded_io_adapters::std::ToStd;
+use embedded_svc::ws::FrameType;
 use esp_idf_hal::io::Write as _;
 use esp_idf_svc::http::{
-    server::{Configuration, EspHttpServer},
+    server::{ws::EspHttpWsDetachedSender, Configuration, EspHttpServer},
     Method,
 };
-use serde::{Deserialize, Serialize};
-use serde_json::json;
 
-// static INDEX_HTML: &[u8] = include_bytes!("../../../../vue-console/dist/index.html");
+/// 网关端口保存在存储中的key，未设置时使用80端口
+const GATEWAY_PORT_KEY: &str = "gateway/port";
+
+/// 请求体的最大长度，超出时返回413
+const MAX_BODY_SIZE: usize = 16 * 1024;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct HttpMessage {
-    to: MessageTo,
-    body: Message,
-    // 是否为同步消息？
-    #[serde(default)]
-    is_sync: bool,
+/// WebSocket单帧的最大长度
+const MAX_WS_FRAME_SIZE: usize = 4 * 1024;
+
+/// WebSocket连接事件，以会话的socket描述符区分连接
+enum WsEvent {
+    Open(usize, EspHttpWsDetachedSender),
+    Frame(usize, String),
+    Close(usize),
 }
 
 struct State {
     _server: EspHttpServer<'static>,
-    req_rx: Receiver<HttpMessage>,
-    resp_tx: SyncSender<HandleResult>,
+    req_rx: Receiver<GatewayRequest>,
+    resp_tx: SyncSender<GatewayResponse>,
+    ws_rx: Receiver<WsEvent>,
 }
 
 impl State {
-    fn new() -> Self {
+    fn new(port: u16) -> Self {
         let (req_tx, req_rx) = mpsc::sync_channel(1);
         let (resp_tx, resp_rx) = mpsc::sync_channel(1);
+        // 请求与响应一一对应，同一时间只处理一个请求
+        let channel = Arc::new(Mutex::new((req_tx, resp_rx)));
         let mut server = EspHttpServer::new(&Configuration {
+            http_port: port,
+            uri_match_wildcard: true,
             ..Default::default()
         })
         .unwrap();
+        for method in [Method::Get, Method::Post, Method::Put, Method::Delete] {
+            let channel = channel.clone();
+            server
+                .fn_handler("/*", method, move |mut req| {
+                    let mut body = Vec::new();
+                    ToStd::new(&mut req)
+                        .take(MAX_BODY_SIZE as u64 + 1)
+                        .read_to_end(&mut body)?;
+                    let gateway_req = GatewayRequest {
+                        method: format!("{:?}", req.method()).to_uppercase(),
+                        path: req.uri().into(),
+                        token: req.header("Authorization").and_then(parse_bearer),
+                        body,
+                    };
+                    let resp = if gateway_req.body.len() > MAX_BODY_SIZE {
+                        GatewayResponse::error(413, "request body too large")
+                    } else if let Some(x) = serve_static(CONSOLE_ASSETS, &gateway_req) {
+                        // 静态文件不需要经过调度器，直接在服务线程中响应
+                        x
+                    } else {
+                        let (tx, rx) = &*channel.lock().unwrap();
+                        tx.send(gateway_req)?;
+                        rx.recv()?
+                    };
+                    let headers = resp
+                        .headers
+                        .iter()
+                        .map(|(k, v)| (k.as_str(), v.as_str()))
+                        .collect::<Vec<_>>();
+                    req.into_response(resp.status, None, &headers)?
+                        .write_all(&resp.body)?;
+                    anyhow::Ok(())
+                })
+                .unwrap();
+        }
+        let (ws_tx, ws_rx) = mpsc::channel::<WsEvent>();
+        let ws_tx = Mutex::new(ws_tx);
         server
-            .fn_handler("/", Method::Get, |req| {
-                req.into_ok_response()?.write_all(b"not found index.html")?;
-                anyhow::Ok(())
-            })
-            .unwrap()
-            .fn_handler("/", Method::Post, move |mut req| {
-                let resp_body: anyhow::Result<HandleResult> = (|| {
-                    let req_body = serde_json::from_reader::<_, HttpMessage>(ToStd::new(&mut req))?;
-                    req_tx.send(req_body)?;
-                    Ok(resp_rx.recv()?)
-                })();
-                match resp_body {
-                    Ok(x) => {
-                        let resp = req.into_ok_response()?;
-                        serde_json::to_writer(ToStd::new(resp), &x)?;
-                    }
-                    Err(e) => {
-                        let resp = req.into_status_response(500)?;
-                        serde_json::to_writer(
-                            ToStd::new(resp),
-                            &json!({
-                                "error": format!("{e:?}"),
-                            }),
-                        )?;
-                    }
+            .ws_handler("/ws", move |ws| {
+                let id = ws.session() as usize;
+                let send = |e| ws_tx.lock().unwrap().send(e);
+                if ws.is_new() {
+                    send(WsEvent::Open(id, ws.create_detached_sender()?)).ok();
+                    return Ok(());
+                }
+                if ws.is_closed() {
+                    send(WsEvent::Close(id)).ok();
+                    return Ok(());
+                }
+                let (_, len) = ws.recv(&mut [])?;
+                if len > MAX_WS_FRAME_SIZE {
+                    ws.send(FrameType::Close, &[])?;
+                    return Ok(());
                 }
-                anyhow::Ok(())
+                let mut buf = vec![0; len];
+                if let (FrameType::Text(_), _) = ws.recv(&mut buf)? {
+                    // 文本帧末尾带有\0
+                    let text = String::from_utf8_lossy(&buf);
+                    send(WsEvent::Frame(id, text.trim_end_matches('\0').into())).ok();
+                }
+                Ok::<(), esp_idf_sys::EspError>(())
             })
             .unwrap();
-        // .ws_handler("/ws", move |mut conn| -> anyhow::Result<()> {
-        //     let mut buf = [0; 4096];
-        //     let (ft, us) = conn.recv(&mut buf)?;
-        //     let s = serde_json::from_slice::<HttpMessage>(&buf[..us])?;
-        //     req_tx.send(s)?;
-        //     req_rx.recv()
-        //     Ok(())
-        // })
-        // .unwrap();
         Self {
             req_rx,
             resp_tx,
+            ws_rx,
             _server: server,
         }
     }
 }
 
+fn random(bs: &mut [u8]) {
+    unsafe { esp_idf_sys::esp_fill_random(bs.as_mut_ptr() as *mut _, bs.len()) };
+}
+
 pub struct HttpServerService {
     state: RefCell<Option<State>>,
+    auth: GatewayAuth,
+    events: GatewayEvents,
 }
 
 impl HttpServerService {
     pub fn new() -> Self {
         Self {
             state: RefCell::new(None),
+            auth: GatewayAuth::new(random),
+            events: Default::default(),
         }
     }
 
     fn handle_request(&self, ctx: Rc<dyn Context>) {
         if let Some(s) = &*self.state.borrow() {
-            if let Ok(x) = s.req_rx.try_recv() {
-                let tx = s.resp_tx.clone();
-                match x.to {
-                    MessageTo::Broadcast => {
-                        ctx.broadcast_global(x.body);
-                        tx.send(HandleResult::Finish(Message::Empty)).unwrap();
-                    }
-                    MessageTo::Topic(topic) => {
-                        ctx.broadcast_topic(topic, x.body);
-                        tx.send(HandleResult::Finish(Message::Empty)).unwrap();
-                    }
-                    MessageTo::Point(node) => {
-                        if x.is_sync {
-                            let m = ctx.sync_call(node, x.body);
-                            tx.send(m).unwrap();
-                        } else {
-                            ctx.async_call(
-                                node,
-                                x.body,
-                                Box::new(move |m| {
-                                    tx.send(m).unwrap();
-                                }),
-                            );
-                        }
-                    }
+            for e in s.ws_rx.try_iter() {
+                match e {
+                    WsEvent::Open(id, mut sender) => self.events.open(
+                        id,
+                        Box::new(move |x| {
+                            sender.send(FrameType::Text(false), x.as_bytes()).is_ok()
+                        }),
+                    ),
+                    WsEvent::Frame(id, x) => self.events.on_frame(ctx.clone(), &self.auth, id, &x),
+                    WsEvent::Close(id) => self.events.close(id),
                 }
             }
+            if let Ok(req) = s.req_rx.try_recv() {
+                let tx = s.resp_tx.clone();
+                handle_gateway_request(
+                    ctx,
+                    &self.auth,
+                    req,
+                    Box::new(move |resp| {
+                        tx.send(resp).unwrap();
+                    }),
+                );
+            }
         }
     }
 }
@@ -136,6 +185,7 @@ impl Node for HttpServerService {
     }
 
     fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        self.events.on_message(&msg);
         match msg.body {
             Message::Lifecycle(LifecycleMessage::Init) => {
                 ctx.subscribe_topic(TopicName::WiFi);
@@ -143,7 +193,13 @@ impl Node for HttpServerService {
             Message::WiFi(WiFiMessage::ConnectedBroadcast | WiFiMessage::APStartedBroadcast) => {
                 // STA模式已连接或AP模式已启动
                 ctx.subscribe_topic(TopicName::Scheduler);
-                self.state.borrow_mut().replace(State::new());
+                let port = ipc::StorageClient(ctx.clone())
+                    .get(GATEWAY_PORT_KEY.into())
+                    .ok()
+                    .and_then(|x| x.as_str())
+                    .and_then(|x| x.parse().ok())
+                    .unwrap_or(80);
+                self.state.borrow_mut().replace(State::new(port));
                 return HandleResult::Finish(Message::Empty);
             }
             Message::Empty => {
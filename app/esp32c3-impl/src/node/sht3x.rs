@@ -0,0 +1,105 @@
+use std::{
+    rc::Rc,
+    sync::mpsc::{sync_channel, Receiver, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use app_core::proto::*;
+use esp_idf_hal::{delay::BLOCK, i2c::I2cDriver};
+use esp_idf_sys as _;
+use log::warn;
+use time::OffsetDateTime;
+
+/// SHT3x的默认I2C地址（ADDR引脚接地）
+const SHT3X_ADDR: u8 = 0x44;
+
+/// 单次测量、高重复性、不使用时钟延展
+const MEASURE_CMD: [u8; 2] = [0x24, 0x00];
+
+const READ_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 数据手册中的CRC-8，多项式0x31，初始值0xFF
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0xffu8;
+    for x in data {
+        crc ^= x;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x31
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn measure(i2c: &mut I2cDriver<'static>) -> anyhow::Result<(f32, f32)> {
+    i2c.write(SHT3X_ADDR, &MEASURE_CMD, BLOCK)?;
+    // 高重复性测量最长需要15ms
+    thread::sleep(Duration::from_millis(20));
+    let mut buf = [0u8; 6];
+    i2c.read(SHT3X_ADDR, &mut buf, BLOCK)?;
+    if crc8(&buf[0..2]) != buf[2] || crc8(&buf[3..5]) != buf[5] {
+        anyhow::bail!("crc mismatch");
+    }
+    let raw_temp = u16::from_be_bytes([buf[0], buf[1]]) as f32;
+    let raw_humi = u16::from_be_bytes([buf[3], buf[4]]) as f32;
+    Ok((
+        -45.0 + 175.0 * raw_temp / 65535.0,
+        100.0 * raw_humi / 65535.0,
+    ))
+}
+
+/// 外接的SHT3x温湿度传感器，未连接时不广播读数
+pub struct Sht3xSensorService {
+    rx: Receiver<SensorReading>,
+}
+
+impl Sht3xSensorService {
+    pub fn new(mut i2c: I2cDriver<'static>) -> Self {
+        let (tx, rx) = sync_channel(1);
+
+        thread::spawn(move || loop {
+            match measure(&mut i2c) {
+                Ok((temp, humidity)) => {
+                    let reading = SensorReading {
+                        time: OffsetDateTime::now_utc(),
+                        temp,
+                        humidity,
+                    };
+                    // 调度器尚未取走上一次读数时丢弃本次
+                    let _ = tx.try_send(reading);
+                }
+                Err(e) => warn!("read sht3x error: {e:?}"),
+            }
+            thread::sleep(READ_INTERVAL);
+        });
+        Self { rx }
+    }
+}
+
+impl Node for Sht3xSensorService {
+    fn node_name(&self) -> NodeName {
+        NodeName::Other("EspSht3x".into())
+    }
+
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        match msg.body {
+            Message::Lifecycle(LifecycleMessage::Init) => {
+                ctx.subscribe_topic(TopicName::Scheduler);
+            }
+            Message::Empty => match self.rx.try_recv() {
+                Ok(x) => ctx.broadcast_topic(
+                    TopicName::Sensor,
+                    Message::Sensor(SensorMessage::ReadingBroadcast(x)),
+                ),
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => unreachable!(),
+            },
+            _ => {}
+        }
+        HandleResult::Discard
+    }
+}
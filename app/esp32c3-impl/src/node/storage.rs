@@ -8,11 +8,19 @@ use std::{
 use app_core::proto::*;
 use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
 
-#[derive(Debug, Deserialize, Serialize)]
+/// 分块blob中单个分块的大小，NVS单个blob不宜过大
+const CHUNK_SIZE: usize = 4000;
+
+/// 记录未提交写入会话使用的idx，重启后据此清理残留的分块
+const PENDING_KEY: &str = "pending";
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
 enum ItemType {
     None,
     String,
     Blob,
+    /// 分块存储的blob，记录总大小，分块的key为`{idx}.{n}`
+    Chunks(usize),
 }
 
 fn m(v: &StorageValue) -> ItemType {
@@ -23,9 +31,40 @@ fn m(v: &StorageValue) -> ItemType {
     }
 }
 
+/// 读取独立NVS命名空间中的设备密钥，不存在时由硬件随机数生成
+pub fn load_device_key(nvs: EspDefaultNvsPartition) -> Result<[u8; 32]> {
+    let mut nvs = EspNvs::new(nvs, "devicekey", true)?;
+    let mut key = [0u8; 32];
+    if let Some(x) = nvs.get_blob("key", &mut key)? {
+        if x.len() == 32 {
+            return Ok(key);
+        }
+    }
+    unsafe { esp_idf_sys::esp_fill_random(key.as_mut_ptr() as *mut _, key.len()) };
+    nvs.set_blob("key", &key)?;
+    Ok(key)
+}
+
+fn chunk_key(idx: u16, n: usize) -> String {
+    format!("{idx}.{n}")
+}
+
+/// 未提交的分块写入会话
+struct BlobSession {
+    key: String,
+    // 分块写入到的新idx，提交前不会出现在index中
+    idx: u16,
+    // 已经写入NVS的字节数
+    size: usize,
+    // 未满一个分块的剩余数据
+    buf: Vec<u8>,
+}
+
 pub struct NvsStorageService {
     nvs: RefCell<EspNvs<NvsDefault>>,
     index: RefCell<HashMap<String, (u16, ItemType)>>,
+    blob_sessions: RefCell<HashMap<usize, BlobSession>>,
+    blob_next_handle: RefCell<usize>,
 }
 
 impl NvsStorageService {
@@ -34,6 +73,8 @@ impl NvsStorageService {
         let ret = Self {
             nvs: RefCell::new(nvs),
             index: RefCell::new(HashMap::new()),
+            blob_sessions: RefCell::new(HashMap::new()),
+            blob_next_handle: RefCell::new(0),
         };
         ret.load_meta();
         ret
@@ -102,28 +143,290 @@ impl NvsStorageService {
                     StorageValue::None
                 }
             }
+            ItemType::Chunks(size) => StorageValue::Bytes(Bytes(self.read_chunks(*idx, 0, *size)?)),
         })
     }
 
     fn gen_next_idx(&self) -> u16 {
-        self.index.borrow().values().map(|x| x.0).max().unwrap_or(0) + 1
+        let index_max = self.index.borrow().values().map(|x| x.0).max().unwrap_or(0);
+        let session_max = self
+            .blob_sessions
+            .borrow()
+            .values()
+            .map(|x| x.idx)
+            .max()
+            .unwrap_or(0);
+        index_max.max(session_max) + 1
     }
 
     fn set(&self, k: String, value: StorageValue) -> Result<()> {
-        if !self.index.borrow().contains_key(&k) {
-            let idx = self.gen_next_idx();
-            self.index.borrow_mut().insert(k.clone(), (idx, m(&value)));
-        }
-        let (idx, _) = &self.index.borrow()[&k];
-        match value {
-            StorageValue::None => self.remove_raw(idx.to_string()),
-            StorageValue::Bytes(Bytes(x)) => self.set_raw_blob(idx.to_string(), &x),
-            StorageValue::String(x) => self.set_raw_str(idx.to_string(), x),
-        }?;
+        if let StorageValue::None = value {
+            return self.remove(&k);
+        }
+        let typ = m(&value);
+        if let Some((idx, ItemType::Chunks(size))) = self.index.borrow().get(&k) {
+            // 旧值为分块blob时，先清理所有分块
+            self.remove_chunks(*idx, *size)?;
+        }
+        let existing = self.index.borrow().get(&k).map(|x| (x.0, x.1 == typ));
+        // 索引不变时不重写，HTTP缓存等频繁更新的数据每次只写一次NVS
+        let (idx, meta_changed) = match existing {
+            Some((idx, true)) => (idx, false),
+            Some((idx, false)) => {
+                self.index.borrow_mut().get_mut(&k).unwrap().1 = typ;
+                (idx, true)
+            }
+            None => {
+                let idx = self.gen_next_idx();
+                self.index.borrow_mut().insert(k.clone(), (idx, typ));
+                (idx, true)
+            }
+        };
+        // 删除已在开头处理
+        if let StorageValue::Bytes(Bytes(x)) = value {
+            self.set_raw_blob(idx.to_string(), &x)?;
+        } else if let StorageValue::String(x) = value {
+            self.set_raw_str(idx.to_string(), x)?;
+        }
+        if meta_changed {
+            self.store_meta()?;
+        }
+        Ok(())
+    }
+
+    /// 删除key及其在NVS中的数据，不存在时什么也不做
+    fn remove(&self, k: &str) -> Result<()> {
+        let Some((idx, typ)) = self.index.borrow_mut().remove(k) else {
+            return Ok(());
+        };
+        match typ {
+            ItemType::Chunks(size) => self.remove_chunks(idx, size)?,
+            ItemType::String | ItemType::Blob => self.remove_raw(idx.to_string())?,
+            ItemType::None => {}
+        }
+        self.store_meta()
+    }
+
+    fn delete_prefix(&self, prefix: String) -> Result<usize> {
+        let keys = self.list(prefix)?;
+        for k in keys.iter() {
+            self.remove(k)?;
+        }
+        Ok(keys.len())
+    }
+
+    /// key的值占用的字节数
+    fn item_size(&self, k: &str) -> usize {
+        let nvs = self.nvs.borrow();
+        match self.index.borrow().get(k) {
+            Some((idx, ItemType::String)) => nvs
+                .str_len(&idx.to_string())
+                .ok()
+                .flatten()
+                // str_len包含结尾的\0
+                .map(|x| x.saturating_sub(1))
+                .unwrap_or(0),
+            Some((idx, ItemType::Blob)) => {
+                nvs.blob_len(&idx.to_string()).ok().flatten().unwrap_or(0)
+            }
+            Some((_, ItemType::Chunks(size))) => *size,
+            _ => 0,
+        }
+    }
+
+    fn usage(&self, prefix: &str) -> StorageUsage {
+        let keys = self.list(prefix.to_string()).unwrap_or_default();
+        StorageUsage {
+            keys: keys.len(),
+            bytes: keys.iter().map(|k| self.item_size(k)).sum(),
+        }
+    }
+
+    fn check_quota(&self, k: &str, new_size: usize) -> Result<(), StorageError> {
+        check_storage_quota(k, self.item_size(k), new_size, |prefix| {
+            self.usage(prefix).bytes
+        })
+    }
+
+    /// NVS分区的条目统计，每个条目32字节
+    fn capacity(&self) -> Result<StorageCapacity> {
+        const ENTRY_SIZE: usize = 32;
+        let mut stats = esp_idf_sys::nvs_stats_t::default();
+        esp_idf_sys::esp!(unsafe { esp_idf_sys::nvs_get_stats(std::ptr::null(), &mut stats) })?;
+        Ok(StorageCapacity {
+            total: Some(stats.total_entries * ENTRY_SIZE),
+            used: stats.used_entries * ENTRY_SIZE,
+            free: Some(stats.free_entries * ENTRY_SIZE),
+        })
+    }
+
+    fn remove_chunks(&self, idx: u16, size: usize) -> Result<()> {
+        for n in 0..size.div_ceil(CHUNK_SIZE) {
+            self.remove_raw(chunk_key(idx, n))?;
+        }
+        Ok(())
+    }
+
+    fn read_chunks(&self, idx: u16, offset: usize, len: usize) -> Result<Vec<u8>> {
+        let mut ret = Vec::with_capacity(len);
+        let end = offset + len;
+        for n in offset / CHUNK_SIZE..end.div_ceil(CHUNK_SIZE) {
+            let chunk = self
+                .get_raw_blob(chunk_key(idx, n))?
+                .ok_or_else(|| anyhow::anyhow!("missing chunk {n} of {idx}"))?;
+            let chunk_start = n * CHUNK_SIZE;
+            let start = offset.saturating_sub(chunk_start).min(chunk.len());
+            let stop = (end - chunk_start).min(chunk.len());
+            ret.extend_from_slice(&chunk[start..stop]);
+        }
+        Ok(ret)
+    }
+
+    fn store_pending(&self) -> Result<()> {
+        let pending = self
+            .blob_sessions
+            .borrow()
+            .values()
+            .map(|x| x.idx)
+            .collect::<Vec<_>>();
+        if pending.is_empty() {
+            return self.remove_raw(PENDING_KEY.to_string());
+        }
+        self.set_raw_str(PENDING_KEY.to_string(), serde_json::to_string(&pending)?)
+    }
+
+    /// 删除上次运行时未提交的写入会话留下的分块
+    fn remove_stale_chunks(&self) -> Result<()> {
+        let Some(x) = self.get_raw_str(PENDING_KEY.to_string())? else {
+            return Ok(());
+        };
+        let pending: Vec<u16> = serde_json::from_str(&x).unwrap_or_default();
+        let committed = self
+            .index
+            .borrow()
+            .values()
+            .map(|x| x.0)
+            .collect::<HashSet<_>>();
+        // 提交后未能更新记录的会话已在index中，不能删除
+        for idx in pending.into_iter().filter(|x| !committed.contains(x)) {
+            let mut n = 0;
+            while self.nvs.borrow().blob_len(&chunk_key(idx, n))?.is_some() {
+                self.remove_raw(chunk_key(idx, n))?;
+                n += 1;
+            }
+            log::info!("removed {n} stale chunks of {idx}");
+        }
+        self.store_pending()
+    }
+
+    fn blob_open(&self, k: String) -> Result<usize> {
+        let idx = self.gen_next_idx();
+        let handle = {
+            let mut handle = self.blob_next_handle.borrow_mut();
+            *handle += 1;
+            *handle
+        };
+        self.blob_sessions.borrow_mut().insert(
+            handle,
+            BlobSession {
+                key: k,
+                idx,
+                size: 0,
+                buf: Vec::new(),
+            },
+        );
+        self.store_pending()?;
+        Ok(handle)
+    }
+
+    /// 放弃写入会话并删除已落盘的分块，会话不存在时什么也不做
+    fn blob_abort(&self, handle: usize) -> Result<()> {
+        let Some(s) = self.blob_sessions.borrow_mut().remove(&handle) else {
+            return Ok(());
+        };
+        self.remove_chunks(s.idx, s.size)?;
+        self.store_pending()
+    }
+
+    fn blob_write(&self, handle: usize, chunk: Vec<u8>) -> Result<(), StorageError> {
+        let mut sessions = self.blob_sessions.borrow_mut();
+        let s = sessions
+            .get_mut(&handle)
+            .ok_or_else(|| StorageError::Other(format!("blob session {handle} not found")))?;
+        // 旧值在提交前仍然占用空间，按替换后的大小检查配额
+        self.check_quota(&s.key, s.size + s.buf.len() + chunk.len())?;
+        s.buf.extend(chunk);
+        // 凑满一个分块就落盘，避免在内存中缓存整个blob
+        while s.buf.len() >= CHUNK_SIZE {
+            let rest = s.buf.split_off(CHUNK_SIZE);
+            self.set_raw_blob(chunk_key(s.idx, s.size / CHUNK_SIZE), &s.buf)
+                .map_err(|e| StorageError::IOError(e.to_string()))?;
+            s.size += CHUNK_SIZE;
+            s.buf = rest;
+        }
+        Ok(())
+    }
+
+    fn blob_commit(&self, handle: usize) -> Result<()> {
+        let s = self
+            .blob_sessions
+            .borrow_mut()
+            .remove(&handle)
+            .ok_or_else(|| anyhow::anyhow!("blob session {handle} not found"))?;
+        let mut size = s.size;
+        if !s.buf.is_empty() {
+            self.set_raw_blob(chunk_key(s.idx, size / CHUNK_SIZE), &s.buf)?;
+            size += s.buf.len();
+        }
+        // 新分块已全部写入，替换index并清理旧值
+        let old = self
+            .index
+            .borrow_mut()
+            .insert(s.key, (s.idx, ItemType::Chunks(size)));
         self.store_meta()?;
+        self.store_pending()?;
+        match old {
+            Some((idx, ItemType::Chunks(size))) => self.remove_chunks(idx, size)?,
+            Some((idx, ItemType::String | ItemType::Blob)) => self.remove_raw(idx.to_string())?,
+            _ => {}
+        }
         Ok(())
     }
 
+    fn blob_read(&self, k: String, offset: usize, len: usize) -> Result<Vec<u8>> {
+        let chunks = match self.index.borrow().get(&k) {
+            Some((idx, ItemType::Chunks(size))) => Some((*idx, *size)),
+            Some((_, ItemType::Blob)) => None,
+            _ => anyhow::bail!("blob {k} not found"),
+        };
+        match chunks {
+            Some((idx, size)) => {
+                let start = offset.min(size);
+                let end = offset.saturating_add(len).min(size);
+                self.read_chunks(idx, start, end - start)
+            }
+            None => {
+                // 兼容未分块存储的旧blob
+                let data = match self.get(k)? {
+                    StorageValue::Bytes(bs) => bs.0,
+                    _ => Vec::new(),
+                };
+                let start = offset.min(data.len());
+                let end = offset.saturating_add(len).min(data.len());
+                Ok(data[start..end].to_vec())
+            }
+        }
+    }
+
+    fn blob_size(&self, k: String) -> Result<Option<usize>> {
+        let size = match self.index.borrow().get(&k) {
+            Some((_, ItemType::Chunks(size))) => return Ok(Some(*size)),
+            Some((idx, ItemType::Blob)) => self.nvs.borrow().blob_len(&idx.to_string())?,
+            _ => None,
+        };
+        Ok(size)
+    }
+
     fn list(&self, prefix: String) -> Result<HashSet<String>> {
         Ok(self
             .index
@@ -159,6 +462,12 @@ impl Node for NvsStorageService {
         _ctx: std::rc::Rc<dyn Context>,
         msg: MessageWithHeader,
     ) -> HandleResult {
+        if let Message::Lifecycle(LifecycleMessage::Init) = msg.body {
+            if let Err(e) = self.remove_stale_chunks() {
+                log::warn!("remove stale chunks error: {e:?}");
+            }
+            return HandleResult::Discard;
+        }
         if let Message::Storage(sm) = msg.body {
             return HandleResult::Finish(Message::Storage(match sm {
                 StorageMessage::GetRequest(k) => {
@@ -172,7 +481,9 @@ impl Node for NvsStorageService {
                     }
                 }
                 StorageMessage::SetRequest(k, v) => {
-                    if let Err(e) = self.set(k, v) {
+                    if let Err(e) = self.check_quota(&k, v.size()) {
+                        StorageMessage::Error(e)
+                    } else if let Err(e) = self.set(k, v) {
                         StorageMessage::Error(StorageError::Other(e.to_string()))
                     } else {
                         StorageMessage::SetResponse
@@ -182,6 +493,45 @@ impl Node for NvsStorageService {
                     Ok(x) => StorageMessage::ListKeysResponse(x),
                     Err(e) => StorageMessage::Error(StorageError::Other(e.to_string())),
                 },
+                StorageMessage::BlobOpenRequest(k) => match self.blob_open(k) {
+                    Ok(x) => StorageMessage::BlobOpenResponse(x),
+                    Err(e) => StorageMessage::Error(StorageError::Other(e.to_string())),
+                },
+                StorageMessage::BlobWriteRequest(handle, chunk) => {
+                    match self.blob_write(handle, chunk.0) {
+                        Ok(_) => StorageMessage::BlobWriteResponse,
+                        Err(e) => StorageMessage::Error(e),
+                    }
+                }
+                StorageMessage::BlobCommitRequest(handle) => match self.blob_commit(handle) {
+                    Ok(_) => StorageMessage::BlobCommitResponse,
+                    Err(e) => StorageMessage::Error(StorageError::Other(e.to_string())),
+                },
+                StorageMessage::BlobAbortRequest(handle) => match self.blob_abort(handle) {
+                    Ok(_) => StorageMessage::BlobAbortResponse,
+                    Err(e) => StorageMessage::Error(StorageError::Other(e.to_string())),
+                },
+                StorageMessage::BlobReadRequest(k, offset, len) => {
+                    match self.blob_read(k, offset, len) {
+                        Ok(x) => StorageMessage::BlobReadResponse(Bytes(x)),
+                        Err(e) => StorageMessage::Error(StorageError::Other(e.to_string())),
+                    }
+                }
+                StorageMessage::BlobSizeRequest(k) => match self.blob_size(k) {
+                    Ok(x) => StorageMessage::BlobSizeResponse(x),
+                    Err(e) => StorageMessage::Error(StorageError::Other(e.to_string())),
+                },
+                StorageMessage::UsageRequest(prefix) => {
+                    StorageMessage::UsageResponse(self.usage(&prefix))
+                }
+                StorageMessage::CapacityRequest => match self.capacity() {
+                    Ok(x) => StorageMessage::CapacityResponse(x),
+                    Err(e) => StorageMessage::Error(StorageError::Other(e.to_string())),
+                },
+                StorageMessage::DeletePrefixRequest(prefix) => match self.delete_prefix(prefix) {
+                    Ok(n) => StorageMessage::DeletePrefixResponse(n),
+                    Err(e) => StorageMessage::Error(StorageError::Other(e.to_string())),
+                },
                 m => panic!("unexcepted message {m:?}"),
             }));
         }
@@ -1,6 +1,6 @@
 use esp_idf_hal::ledc::{LedcChannel, LedcTimer};
 use esp_idf_hal::rmt::RmtChannel;
-use esp_idf_svc::hal::gpio::{AnyInputPin, AnyOutputPin, PinDriver};
+use esp_idf_svc::hal::gpio::{AnyIOPin, AnyInputPin, AnyOutputPin, PinDriver};
 use esp_idf_svc::hal::modem::Modem;
 
 #[cfg(feature = "pyclock")]
@@ -15,14 +15,22 @@ pub struct SystemPeripherals<
     BacklightLedcTimer,
     BoardLedLedcChannel,
     BoardLedLedcTimer,
+    I2C,
 > {
     pub buzzer: Option<BuzzerPeripherals<BuzzerRmtChannel>>,
     pub board_led: Option<LedcPeripherals<BoardLedLedcChannel, BoardLedLedcTimer>>,
     pub button: Option<AnyInputPin>,
     pub display: DisplaySpiPeripherals<SPI, BacklightLedcChannel, BacklightLedcTimer>,
+    pub sensor: Option<I2cPeripherals<I2C>>,
     pub modem: Modem,
 }
 
+pub struct I2cPeripherals<I2C> {
+    pub i2c: I2C,
+    pub sda: AnyIOPin,
+    pub scl: AnyIOPin,
+}
+
 pub struct BuzzerPeripherals<C> {
     pub pin: AnyOutputPin,
     pub rmt_channel: C,
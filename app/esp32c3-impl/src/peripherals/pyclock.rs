@@ -1,9 +1,9 @@
-use esp_idf_hal::{gpio, ledc, rmt, spi};
+use esp_idf_hal::{gpio, i2c, ledc, rmt, spi};
 use esp_idf_svc::hal::peripherals::Peripherals;
 
 use super::{
-    BuzzerPeripherals, DisplayControlPeripherals, DisplaySpiPeripherals, LedcPeripherals,
-    SystemPeripherals,
+    BuzzerPeripherals, DisplayControlPeripherals, DisplaySpiPeripherals, I2cPeripherals,
+    LedcPeripherals, SystemPeripherals,
 };
 
 impl
@@ -14,6 +14,7 @@ impl
         ledc::TIMER0,
         ledc::CHANNEL1,
         ledc::TIMER1,
+        i2c::I2C0,
     >
 {
     pub fn take() -> Self {
@@ -41,6 +42,16 @@ impl
                 sdo: peripherals.pins.gpio7.into(),
                 cs: peripherals.pins.gpio5.into(),
             },
+            // 外接温湿度传感器，未接传感器时可关闭sht3x feature
+            sensor: if cfg!(feature = "sht3x") {
+                Some(I2cPeripherals {
+                    i2c: peripherals.i2c0,
+                    sda: peripherals.pins.gpio1.into(),
+                    scl: peripherals.pins.gpio3.into(),
+                })
+            } else {
+                None
+            },
             modem: peripherals.modem,
             buzzer: Some(BuzzerPeripherals {
                 pin: peripherals.pins.gpio0.into(),
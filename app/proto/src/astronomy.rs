@@ -0,0 +1,139 @@
+//! 日出日落、月相与节气的本地计算，使用低精度公式，误差在数分钟以内
+//!
+//! 日出日落见 https://en.wikipedia.org/wiki/Sunrise_equation ，
+//! 太阳黄经见 Jean Meeus, Astronomical Algorithms 第25章
+
+use std::f64::consts::PI;
+
+use time::{macros::time, Date, OffsetDateTime};
+
+use crate::{Astronomy, Coord, MoonPhase, SolarTerm};
+
+/// J2000.0的儒略日
+const J2000: f64 = 2451545.0;
+
+/// 平均朔望月长度，天
+pub const SYNODIC_MONTH: f64 = 29.530588853;
+
+/// 2000-01-06 18:14 UTC的新月
+const NEW_MOON_EPOCH: f64 = 2451550.26;
+
+fn sin_deg(x: f64) -> f64 {
+    x.to_radians().sin()
+}
+
+fn cos_deg(x: f64) -> f64 {
+    x.to_radians().cos()
+}
+
+fn julian_day(t: OffsetDateTime) -> f64 {
+    t.unix_timestamp() as f64 / 86400.0 + 2440587.5
+}
+
+fn from_julian_day(jd: f64) -> OffsetDateTime {
+    let secs = ((jd - 2440587.5) * 86400.0).round() as i64;
+    OffsetDateTime::from_unix_timestamp(secs).unwrap_or(OffsetDateTime::UNIX_EPOCH)
+}
+
+/// 返回(日出, 日落, 白昼分钟数)，极昼与极夜时没有日出日落
+pub fn sun_times(
+    coord: &Coord,
+    date: Date,
+) -> (Option<OffsetDateTime>, Option<OffsetDateTime>, u16) {
+    let (lat, lon) = (coord.lat as f64, coord.lon as f64);
+    let noon = julian_day(date.with_time(time!(12:00)).assume_utc());
+    let n = (noon - J2000 + 0.0008).round();
+    // 平太阳时
+    let j = n - lon / 360.0;
+    let m = (357.5291 + 0.98560028 * j).rem_euclid(360.0);
+    let c = 1.9148 * sin_deg(m) + 0.02 * sin_deg(2.0 * m) + 0.0003 * sin_deg(3.0 * m);
+    let lambda = (m + c + 180.0 + 102.9372).rem_euclid(360.0);
+    let transit = J2000 + j + 0.0053 * sin_deg(m) - 0.0069 * sin_deg(2.0 * lambda);
+    let sin_d = sin_deg(lambda) * sin_deg(23.4397);
+    let cos_d = (1.0 - sin_d * sin_d).sqrt();
+    // 计入大气折射与太阳视半径
+    let cos_w = (sin_deg(-0.833) - sin_deg(lat) * sin_d) / (cos_deg(lat) * cos_d);
+    if cos_w <= -1.0 {
+        return (None, None, 24 * 60);
+    }
+    if cos_w >= 1.0 {
+        return (None, None, 0);
+    }
+    let w = cos_w.acos().to_degrees() / 360.0;
+    let day_length = (w * 2.0 * 24.0 * 60.0).round() as u16;
+    (
+        Some(from_julian_day(transit - w)),
+        Some(from_julian_day(transit + w)),
+        day_length,
+    )
+}
+
+/// 返回(月相, 月龄, 被照亮的百分比)，使用平均朔望月，误差在半天以内
+pub fn moon_phase(t: OffsetDateTime) -> (MoonPhase, f32, u8) {
+    let age = (julian_day(t) - NEW_MOON_EPOCH).rem_euclid(SYNODIC_MONTH);
+    let f = age / SYNODIC_MONTH;
+    let illumination = (1.0 - (2.0 * PI * f).cos()) / 2.0;
+    let phase = match ((f * 8.0).round() as u8) % 8 {
+        0 => MoonPhase::New,
+        1 => MoonPhase::WaxingCrescent,
+        2 => MoonPhase::FirstQuarter,
+        3 => MoonPhase::WaxingGibbous,
+        4 => MoonPhase::Full,
+        5 => MoonPhase::WaningGibbous,
+        6 => MoonPhase::LastQuarter,
+        _ => MoonPhase::WaningCrescent,
+    };
+    (phase, age as f32, (illumination * 100.0).round() as u8)
+}
+
+/// 太阳视黄经，度
+pub fn sun_longitude(t: OffsetDateTime) -> f64 {
+    let t = (julian_day(t) - J2000) / 36525.0;
+    let l0 = 280.46646 + 36000.76983 * t + 0.0003032 * t * t;
+    let m = 357.52911 + 35999.05029 * t - 0.0001537 * t * t;
+    let c = (1.914602 - 0.004817 * t - 0.000014 * t * t) * sin_deg(m)
+        + (0.019993 - 0.000101 * t) * sin_deg(2.0 * m)
+        + 0.000289 * sin_deg(3.0 * m);
+    let omega = 125.04 - 1934.136 * t;
+    (l0 + c - 0.00569 - 0.00478 * sin_deg(omega)).rem_euclid(360.0)
+}
+
+/// t所处的节气
+pub fn solar_term(t: OffsetDateTime) -> SolarTerm {
+    SolarTerm((sun_longitude(t) / 15.0) as u8 % 24)
+}
+
+/// 距t最近的一次交节时间
+pub fn solar_term_time(term: SolarTerm, t: OffsetDateTime) -> OffsetDateTime {
+    let target = term.0 as f64 * 15.0;
+    let mut jd = julian_day(t);
+    // 太阳每天约移动0.9856度，牛顿迭代数次即可收敛到秒级
+    for _ in 0..5 {
+        let diff = target - sun_longitude(from_julian_day(jd));
+        jd += ((diff + 180.0).rem_euclid(360.0) - 180.0) / 0.98564736;
+    }
+    from_julian_day(jd)
+}
+
+/// now的偏移决定日出日落按哪一天计算
+pub fn astronomy(coord: &Coord, now: OffsetDateTime) -> Astronomy {
+    let (sunrise, sunset, day_length) = sun_times(coord, now.date());
+    let (moon_phase, moon_age, moon_illumination) = moon_phase(now);
+    let term = solar_term(now);
+    let next = SolarTerm((term.0 + 1) % 24);
+    let term_time = solar_term_time(term, now);
+    let next_time = solar_term_time(next, now);
+    let offset = now.offset();
+    Astronomy {
+        sunrise: sunrise.map(|x| x.to_offset(offset)),
+        sunset: sunset.map(|x| x.to_offset(offset)),
+        day_length,
+        moon_phase,
+        moon_age,
+        moon_illumination,
+        solar_term: term,
+        solar_term_time: term_time.to_offset(offset),
+        next_solar_term: next,
+        next_solar_term_time: next_time.to_offset(offset),
+    }
+}
@@ -0,0 +1,263 @@
+//! 设备网关的REST路由，桌面端与ESP32的HTTP服务共用，只负责把HTTP请求转换为节点消息
+//!
+//! 除配对与静态文件外都需要携带`Authorization: Bearer <token>`，并受check_remote_access限制
+//!
+//! - `POST /pair` 没有code时在屏幕上显示配对码，为`{code, name}`时返回`{token}`
+//! - `GET /nodes` 列出已注册的节点
+//! - `POST /nodes/{name}` 向节点发送消息，返回节点的响应消息，`?sync=true`时使用同步调用
+//! - `POST /topics/{topic}` 向话题广播消息
+//! - `POST /broadcast` 全局广播消息
+//! - `POST /` 旧版`{to, body}`信封格式，仅为兼容保留
+//! - `GET /ws` WebSocket事件流，由各平台处理握手，协议见GatewayEvents
+//!
+//! RemoteNodeProxy通过另一台设备的`/ws`把远程节点代理为本地节点
+//! - 其余`GET`请求为管理后台的静态文件，见serve_static
+
+mod assets;
+mod auth;
+mod events;
+mod proxy;
+
+use std::rc::Rc;
+
+pub use assets::*;
+pub use auth::*;
+pub use events::*;
+pub use proxy::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{Context, HandleResult, Message, MessageTo, NodeName, TopicName};
+
+/// 桌面端默认的网关监听地址
+pub const DEFAULT_GATEWAY_ADDR: &str = "127.0.0.1:38080";
+
+pub struct GatewayRequest {
+    pub method: String,
+    /// 请求路径，可以带查询参数
+    pub path: String,
+    /// 请求携带的令牌，见parse_bearer
+    pub token: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// 网关响应，除静态文件外响应体都是JSON
+#[derive(Debug)]
+pub struct GatewayResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl GatewayResponse {
+    pub fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".into(), "application/json".into())],
+            body: serde_json::to_vec(value).unwrap(),
+        }
+    }
+
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Self::json(status, &json!({ "error": message.into() }))
+    }
+}
+
+pub type GatewayResponder = Box<dyn FnOnce(GatewayResponse)>;
+
+/// 配对请求体，没有code时开始配对
+#[derive(Debug, Default, Deserialize)]
+struct PairRequest {
+    code: Option<String>,
+    #[serde(default)]
+    name: String,
+}
+
+/// 旧版接口的请求体
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    to: MessageTo,
+    body: Message,
+    #[serde(default)]
+    is_sync: bool,
+}
+
+/// 节点在路径中的名称，扩展节点Other(x)直接使用x
+pub fn node_path_name(node: &NodeName) -> String {
+    match node {
+        NodeName::Other(x) => x.clone(),
+        x => serde_json::to_value(x)
+            .ok()
+            .and_then(|x| x.as_str().map(Into::into))
+            .unwrap_or_else(|| format!("{x:?}")),
+    }
+}
+
+fn parse_node_name(name: &str) -> NodeName {
+    serde_json::from_value(Value::String(name.into()))
+        .unwrap_or_else(|_| NodeName::Other(name.into()))
+}
+
+fn parse_topic_name(name: &str) -> Option<TopicName> {
+    serde_json::from_value(Value::String(name.into())).ok()
+}
+
+fn parse_message(body: &[u8]) -> Result<Message, GatewayResponse> {
+    serde_json::from_slice(body)
+        .map_err(|e| GatewayResponse::error(400, format!("invalid message: {e}")))
+}
+
+fn call_node(ctx: Rc<dyn Context>, name: &str, sync: bool, body: &[u8], respond: GatewayResponder) {
+    let node = parse_node_name(name);
+    if !ctx.node_names().contains(&node) {
+        return respond(GatewayResponse::error(
+            404,
+            format!("node {name} not found"),
+        ));
+    }
+    let msg = match parse_message(body) {
+        Ok(x) => x,
+        Err(e) => return respond(e),
+    };
+    if let Err(e) = check_remote_access(&MessageTo::Point(node.clone()), &msg) {
+        return respond(e.into());
+    }
+    let on_result = move |r: HandleResult| {
+        respond(match r {
+            HandleResult::Finish(x) => GatewayResponse::json(200, &x),
+            HandleResult::Discard => GatewayResponse::error(422, "message not handled by node"),
+            r => GatewayResponse::error(500, format!("unexpected result {r:?}")),
+        })
+    };
+    if sync {
+        on_result(ctx.sync_call(node, msg));
+    } else {
+        ctx.async_call(node, msg, Box::new(on_result));
+    }
+}
+
+fn legacy(ctx: Rc<dyn Context>, body: &[u8], respond: GatewayResponder) {
+    let x = match serde_json::from_slice::<Envelope>(body) {
+        Ok(x) => x,
+        Err(e) => return respond(GatewayResponse::error(400, e.to_string())),
+    };
+    if let Err(e) = check_remote_access(&x.to, &x.body) {
+        return respond(e.into());
+    }
+    let empty = HandleResult::Finish(Message::Empty);
+    match x.to {
+        MessageTo::Broadcast => {
+            ctx.broadcast_global(x.body);
+            respond(GatewayResponse::json(200, &empty));
+        }
+        MessageTo::Topic(topic) => {
+            ctx.broadcast_topic(topic, x.body);
+            respond(GatewayResponse::json(200, &empty));
+        }
+        MessageTo::Point(node) if x.is_sync => {
+            respond(GatewayResponse::json(200, &ctx.sync_call(node, x.body)));
+        }
+        MessageTo::Point(node) => ctx.async_call(
+            node,
+            x.body,
+            Box::new(move |r| respond(GatewayResponse::json(200, &r))),
+        ),
+    }
+}
+
+impl From<AuthError> for GatewayResponse {
+    fn from(e: AuthError) -> Self {
+        GatewayResponse::error(e.status(), e.to_string())
+    }
+}
+
+fn pair(ctx: Rc<dyn Context>, auth: &GatewayAuth, body: &[u8], respond: GatewayResponder) {
+    let x = if body.iter().all(|x| x.is_ascii_whitespace()) {
+        PairRequest::default()
+    } else {
+        match serde_json::from_slice::<Option<PairRequest>>(body) {
+            Ok(x) => x.unwrap_or_default(),
+            Err(e) => return respond(GatewayResponse::error(400, e.to_string())),
+        }
+    };
+    let Some(code) = x.code else {
+        return respond(match auth.start_pairing(ctx) {
+            Ok(_) => GatewayResponse::json(202, &json!({})),
+            Err(e) => e.into(),
+        });
+    };
+    respond(match auth.pair(ctx, &code, &x.name) {
+        Ok(token) => GatewayResponse::json(200, &json!({ "token": token })),
+        Err(e) => e.into(),
+    })
+}
+
+/// 处理一个网关请求，需在调度器线程中调用，节点响应后才会调用respond
+pub fn handle_gateway_request(
+    ctx: Rc<dyn Context>,
+    auth: &GatewayAuth,
+    req: GatewayRequest,
+    respond: GatewayResponder,
+) {
+    let (path, query) = req.path.split_once('?').unwrap_or((&req.path, ""));
+    let segments = path
+        .split('/')
+        .filter(|x| !x.is_empty())
+        .collect::<Vec<_>>();
+    let sync = query.split('&').any(|x| x == "sync=true" || x == "sync=1");
+    let method = req.method.to_ascii_uppercase();
+    match (method.as_str(), segments.as_slice()) {
+        ("POST", ["pair"]) => return pair(ctx, auth, &req.body, respond),
+        (_, ["pair"]) => return respond(GatewayResponse::error(405, "method not allowed")),
+        _ => {}
+    }
+    if let Err(e) = auth.verify(ctx.clone(), req.token.as_deref()) {
+        return respond(e.into());
+    }
+    match (method.as_str(), segments.as_slice()) {
+        ("GET", ["nodes"]) => {
+            let mut nodes = ctx
+                .node_names()
+                .iter()
+                .map(node_path_name)
+                .collect::<Vec<_>>();
+            nodes.sort();
+            respond(GatewayResponse::json(200, &nodes));
+        }
+        ("POST", ["nodes", name]) => call_node(ctx, name, sync, &req.body, respond),
+        ("POST", ["topics", name]) => {
+            let Some(topic) = parse_topic_name(name) else {
+                return respond(GatewayResponse::error(
+                    404,
+                    format!("topic {name} not found"),
+                ));
+            };
+            let to = MessageTo::Topic(topic.clone());
+            match parse_message(&req.body) {
+                Ok(msg) => match check_remote_access(&to, &msg) {
+                    Ok(_) => {
+                        ctx.broadcast_topic(topic, msg);
+                        respond(GatewayResponse::json(202, &json!({})));
+                    }
+                    Err(e) => respond(e.into()),
+                },
+                Err(e) => respond(e),
+            }
+        }
+        ("POST", ["broadcast"]) => match parse_message(&req.body) {
+            Ok(msg) => match check_remote_access(&MessageTo::Broadcast, &msg) {
+                Ok(_) => {
+                    ctx.broadcast_global(msg);
+                    respond(GatewayResponse::json(202, &json!({})));
+                }
+                Err(e) => respond(e.into()),
+            },
+            Err(e) => respond(e),
+        },
+        ("POST", []) => legacy(ctx, &req.body, respond),
+        (_, ["nodes"] | ["nodes", _] | ["topics", _] | ["broadcast"] | []) => {
+            respond(GatewayResponse::error(405, "method not allowed"))
+        }
+        _ => respond(GatewayResponse::error(404, format!("{path} not found"))),
+    }
+}
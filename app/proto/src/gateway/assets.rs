@@ -0,0 +1,58 @@
+use super::{GatewayRequest, GatewayResponse};
+
+/// 编译时嵌入的静态文件，内容为gzip压缩后的数据
+#[derive(Debug)]
+pub struct StaticAsset {
+    /// 以/开头的请求路径
+    pub path: &'static str,
+    pub content_type: &'static str,
+    pub gzip: &'static [u8],
+}
+
+/// 以这些路径开头的请求属于网关接口，不作为静态文件处理
+const API_PREFIXES: [&str; 4] = ["/nodes", "/topics", "/broadcast", "/ws"];
+
+/// 前端使用history路由，没有扩展名的路径都返回index.html
+const INDEX_PATH: &str = "/index.html";
+
+fn find<'a>(assets: &'a [StaticAsset], path: &str) -> Option<&'a StaticAsset> {
+    assets.iter().find(|x| x.path == path)
+}
+
+/// 处理静态文件请求，只响应GET且不属于网关接口的路径，其余请求返回None交给网关路由。
+/// 可在任意线程调用，浏览器都支持gzip，因此总是返回压缩后的内容
+pub fn serve_static(assets: &[StaticAsset], req: &GatewayRequest) -> Option<GatewayResponse> {
+    if !req.method.eq_ignore_ascii_case("GET") {
+        return None;
+    }
+    let path = req.path.split(['?', '#']).next().unwrap_or_default();
+    if API_PREFIXES
+        .iter()
+        .any(|x| path == *x || path.starts_with(&format!("{x}/")))
+    {
+        return None;
+    }
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let asset = match path {
+        "" | "/" => find(assets, INDEX_PATH),
+        _ if !name.contains('.') => find(assets, INDEX_PATH),
+        _ => find(assets, path),
+    };
+    let Some(asset) = asset else {
+        let message = match find(assets, INDEX_PATH) {
+            Some(_) => format!("{path} not found"),
+            None => "web console is not included in this build".into(),
+        };
+        return Some(GatewayResponse::error(404, message));
+    };
+    Some(GatewayResponse {
+        status: 200,
+        headers: vec![
+            ("Content-Type".into(), asset.content_type.into()),
+            ("Content-Encoding".into(), "gzip".into()),
+            // 固件更新后内容会变化，需要浏览器每次验证
+            ("Cache-Control".into(), "no-cache".into()),
+        ],
+        body: asset.gzip.to_vec(),
+    })
+}
@@ -0,0 +1,398 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashSet,
+    rc::Rc,
+};
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{
+    ipc::StorageClient, Context, Message, MessageTo, NodeName, NotifactionContent,
+    NotifactionMessage, SecretMessage, SensorMessage, StorageError, StorageMessage, StorageValue,
+    TopicName, WiFiMessage,
+};
+
+/// 已配对客户端的令牌保存在此命名空间下，远程调用者不能通过Storage访问
+pub const GATEWAY_AUTH_PREFIX: &str = "gateway/auth/";
+
+/// 配对码显示的时长
+const PAIRING_CODE_DURATION: usize = 60_000;
+
+/// 配对码输错的次数上限，超过后需要重新请求配对码
+const MAX_PAIRING_ATTEMPTS: usize = 3;
+
+/// 一个配对码输错次数用尽后的冷却时间，之后每用尽一个配对码加倍
+const PAIRING_COOLDOWN_SECS: i64 = 60;
+
+/// 冷却时间的上限
+const MAX_PAIRING_COOLDOWN_SECS: i64 = 3600;
+
+/// 每个时间窗口内最多请求配对码的次数，避免反复刷新屏幕
+const MAX_PAIRING_REQUESTS: usize = 3;
+const PAIRING_REQUEST_WINDOW_SECS: i64 = 60;
+
+#[derive(Debug, Clone)]
+pub enum AuthError {
+    /// 没有令牌或令牌无效
+    Unauthorized,
+    /// 策略不允许远程访问
+    Forbidden(String),
+    /// 没有进行中的配对或配对码错误
+    PairingFailed,
+    /// 配对码输错过多或请求过于频繁，需等待冷却
+    TooManyAttempts,
+    StorageError(StorageError),
+}
+
+impl AuthError {
+    pub fn status(&self) -> u16 {
+        match self {
+            AuthError::Unauthorized => 401,
+            AuthError::Forbidden(_) | AuthError::PairingFailed => 403,
+            AuthError::TooManyAttempts => 429,
+            AuthError::StorageError(_) => 500,
+        }
+    }
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::Unauthorized => f.write_str("unauthorized, pair with the device first"),
+            AuthError::Forbidden(x) => write!(f, "forbidden: {x}"),
+            AuthError::PairingFailed => f.write_str("pairing failed"),
+            AuthError::TooManyAttempts => f.write_str("too many pairing attempts, retry later"),
+            AuthError::StorageError(e) => write!(f, "storage error: {e:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PairedClient {
+    name: String,
+    token: String,
+}
+
+struct Pairing {
+    code: String,
+    attempts: usize,
+}
+
+/// 网关的配对与令牌校验，随机数由各平台提供
+pub struct GatewayAuth {
+    random: fn(&mut [u8]),
+    // 首次校验时从存储加载
+    tokens: RefCell<Option<HashSet<String>>>,
+    pairing: RefCell<Option<Pairing>>,
+    // 以下限制不随新的配对码重置，配对成功后清零
+    failures: Cell<usize>,
+    locked_until: Cell<i64>,
+    // 当前时间窗口的开始时间与窗口内请求配对码的次数
+    request_window: Cell<(i64, usize)>,
+}
+
+fn now() -> i64 {
+    OffsetDateTime::now_utc().unix_timestamp()
+}
+
+/// 比较耗时与内容无关，避免通过响应时间逐位猜测令牌或配对码
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn to_hex(bs: &[u8]) -> String {
+    bs.iter().map(|x| format!("{x:02x}")).collect()
+}
+
+/// 解析`Authorization: Bearer <token>`请求头
+pub fn parse_bearer(value: &str) -> Option<String> {
+    let (scheme, token) = value.trim().split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim().to_string())
+}
+
+impl GatewayAuth {
+    pub fn new(random: fn(&mut [u8])) -> Self {
+        Self {
+            random,
+            tokens: Default::default(),
+            pairing: Default::default(),
+            failures: Default::default(),
+            locked_until: Default::default(),
+            request_window: Default::default(),
+        }
+    }
+
+    fn check_locked(&self) -> Result<(), AuthError> {
+        if now() < self.locked_until.get() {
+            return Err(AuthError::TooManyAttempts);
+        }
+        Ok(())
+    }
+
+    fn load_tokens(&self, ctx: Rc<dyn Context>) -> Result<(), AuthError> {
+        if self.tokens.borrow().is_some() {
+            return Ok(());
+        }
+        let stg = StorageClient(ctx);
+        let mut tokens = HashSet::new();
+        for key in stg
+            .list(GATEWAY_AUTH_PREFIX.into())
+            .map_err(AuthError::StorageError)?
+        {
+            let value = stg.get(key).map_err(AuthError::StorageError)?;
+            if let Some(x) = value
+                .as_str()
+                .and_then(|x| serde_json::from_str::<PairedClient>(&x).ok())
+            {
+                tokens.insert(x.token);
+            }
+        }
+        self.tokens.replace(Some(tokens));
+        Ok(())
+    }
+
+    /// 校验请求携带的令牌
+    pub fn verify(&self, ctx: Rc<dyn Context>, token: Option<&str>) -> Result<(), AuthError> {
+        let Some(token) = token else {
+            return Err(AuthError::Unauthorized);
+        };
+        self.load_tokens(ctx)?;
+        match self.tokens.borrow().as_ref() {
+            // 逐个比较全部令牌，不提前返回
+            Some(x)
+                if x.iter()
+                    .fold(false, |acc, t| acc | constant_time_eq(t, token)) =>
+            {
+                Ok(())
+            }
+            _ => Err(AuthError::Unauthorized),
+        }
+    }
+
+    /// 生成新的配对码并显示在屏幕上，之前的配对码失效
+    pub fn start_pairing(&self, ctx: Rc<dyn Context>) -> Result<(), AuthError> {
+        self.check_locked()?;
+        let now = now();
+        let (start, count) = match self.request_window.get() {
+            (start, count) if now - start < PAIRING_REQUEST_WINDOW_SECS => (start, count),
+            _ => (now, 0),
+        };
+        if count >= MAX_PAIRING_REQUESTS {
+            return Err(AuthError::TooManyAttempts);
+        }
+        self.request_window.set((start, count + 1));
+        let mut bs = [0u8; 4];
+        (self.random)(&mut bs);
+        let code = format!("{:06}", u32::from_le_bytes(bs) % 1_000_000);
+        ctx.async_call(
+            NodeName::Notifaction,
+            Message::Notifaction(NotifactionMessage::ShowRequest {
+                duration: PAIRING_CODE_DURATION,
+                content: NotifactionContent {
+                    title: Some("配对码".into()),
+                    text: Some(code.clone()),
+                    icon: None,
+                },
+            }),
+            Box::new(|_| {}),
+        );
+        self.pairing.replace(Some(Pairing { code, attempts: 0 }));
+        Ok(())
+    }
+
+    /// 使用屏幕上的配对码换取令牌，name用于标识客户端
+    pub fn pair(&self, ctx: Rc<dyn Context>, code: &str, name: &str) -> Result<String, AuthError> {
+        self.check_locked()?;
+        {
+            let mut pairing = self.pairing.borrow_mut();
+            let Some(x) = pairing.as_mut() else {
+                return Err(AuthError::PairingFailed);
+            };
+            if !constant_time_eq(&x.code, code) {
+                x.attempts += 1;
+                self.failures.set(self.failures.get() + 1);
+                if x.attempts >= MAX_PAIRING_ATTEMPTS {
+                    pairing.take();
+                    let rounds = (self.failures.get() / MAX_PAIRING_ATTEMPTS).max(1);
+                    let cooldown = (PAIRING_COOLDOWN_SECS << (rounds - 1).min(6))
+                        .min(MAX_PAIRING_COOLDOWN_SECS);
+                    self.locked_until.set(now() + cooldown);
+                }
+                return Err(AuthError::PairingFailed);
+            }
+            pairing.take();
+            self.failures.set(0);
+        }
+        self.load_tokens(ctx.clone())?;
+
+        let mut bs = [0u8; 20];
+        (self.random)(&mut bs);
+        // key与令牌使用不同的随机数，列举key不会泄露令牌
+        let key = format!("{GATEWAY_AUTH_PREFIX}{}", to_hex(&bs[..4]));
+        let token = to_hex(&bs[4..]);
+        let client = PairedClient {
+            name: name.into(),
+            token: token.clone(),
+        };
+        StorageClient(ctx.clone())
+            .set(
+                key,
+                StorageValue::String(serde_json::to_string(&client).unwrap()),
+            )
+            .map_err(AuthError::StorageError)?;
+        if let Some(x) = self.tokens.borrow_mut().as_mut() {
+            x.insert(token.clone());
+        }
+        ctx.sync_call(
+            NodeName::Notifaction,
+            Message::Notifaction(NotifactionMessage::Close),
+        );
+        Ok(token)
+    }
+}
+
+/// 存储消息是否会读写令牌
+fn touches_auth(msg: &StorageMessage) -> bool {
+    match msg {
+        StorageMessage::GetRequest(k)
+        | StorageMessage::SetRequest(k, _)
+        | StorageMessage::BlobOpenRequest(k)
+        | StorageMessage::BlobReadRequest(k, _, _)
+        | StorageMessage::BlobSizeRequest(k) => k.starts_with(GATEWAY_AUTH_PREFIX),
+        // 前缀可能包含整个命名空间
+        StorageMessage::DeletePrefixRequest(k) => {
+            k.starts_with(GATEWAY_AUTH_PREFIX) || GATEWAY_AUTH_PREFIX.starts_with(k.as_str())
+        }
+        _ => false,
+    }
+}
+
+/// 消息族内的变体名，如`GetRequest`
+fn variant_name(body: &Message) -> Option<String> {
+    match serde_json::to_value(body).ok()? {
+        serde_json::Value::Object(x) => match x.into_iter().next()?.1 {
+            serde_json::Value::String(x) => Some(x),
+            serde_json::Value::Object(x) => x.into_iter().next().map(|x| x.0),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// 应答与事件只能由设备自身产生，节点收到未预期的变体会panic
+fn is_reply_or_event(body: &Message) -> bool {
+    variant_name(body)
+        .is_some_and(|x| x == "Error" || x.ends_with("Response") || x.ends_with("Broadcast"))
+}
+
+/// 处理该消息族请求的节点
+fn owner_of(body: &Message) -> Option<NodeName> {
+    Some(match body {
+        Message::Weather(_) => NodeName::Weather,
+        Message::Http(_) => NodeName::HttpClient,
+        Message::Storage(_) => NodeName::Storage,
+        Message::System(_) => NodeName::System,
+        Message::Timer(_) => NodeName::Timer,
+        Message::WiFi(_) => NodeName::WiFi,
+        Message::Buzzer(_) => NodeName::Buzzer,
+        Message::Midi(_) => NodeName::MidiPlayer,
+        Message::Notifaction(_) => NodeName::Notifaction,
+        Message::BootPage(_) => NodeName::BootPage,
+        Message::Router(_) => NodeName::Router,
+        Message::Canvas(_) => NodeName::Canvas,
+        Message::UserAlarm(_) => NodeName::Alarm,
+        Message::Secret(_) => NodeName::Secret,
+        Message::Sensor(_) => NodeName::Sensor,
+        Message::Empty | Message::Lifecycle(_) | Message::OneButton(_) | Message::Sntp(_) => {
+            return None
+        }
+    })
+}
+
+/// 节点是否接受该消息，页面只处理按键事件，扩展节点自行校验
+fn accepts(node: &NodeName, body: &Message) -> bool {
+    match (node, body) {
+        (_, Message::Empty) | (NodeName::Other(_), _) => true,
+        (
+            NodeName::HomePage
+            | NodeName::MenuPage
+            | NodeName::WeatherPage
+            | NodeName::MusicPage
+            | NodeName::AirQualityPage
+            | NodeName::IndoorPage,
+            Message::OneButton(_),
+        ) => true,
+        _ => owner_of(body).as_ref() == Some(node),
+    }
+}
+
+/// 话题只接受对应的事件
+fn topic_accepts(topic: &TopicName, body: &Message) -> bool {
+    match (topic, body) {
+        (TopicName::Scheduler | TopicName::Connectivity, _) => false,
+        (_, Message::Empty)
+        | (TopicName::OneButton, Message::OneButton(_))
+        | (TopicName::Sntp, Message::Sntp(_))
+        | (
+            TopicName::WiFi,
+            Message::WiFi(WiFiMessage::ConnectedBroadcast | WiFiMessage::APStartedBroadcast),
+        )
+        | (TopicName::Sensor, Message::Sensor(SensorMessage::ReadingBroadcast(_))) => true,
+        _ => false,
+    }
+}
+
+fn check_point(node: &NodeName, body: &Message) -> bool {
+    if !accepts(node, body) || is_reply_or_event(body) {
+        return false;
+    }
+    match (node, body) {
+        (NodeName::Scheduler | NodeName::StorageMigration | NodeName::HttpClient, _) => false,
+        (NodeName::Secret, m) => matches!(
+            m,
+            Message::Secret(SecretMessage::SetRequest(..) | SecretMessage::ExportRequest)
+        ),
+        (NodeName::Storage, Message::Storage(m)) => !touches_auth(m),
+        _ => true,
+    }
+}
+
+/// 远程调用者的访问策略，已配对的调用者也需遵守
+///
+/// | 目标 | 规则 |
+/// | --- | --- |
+/// | 所有目标 | 禁止应答、错误与`*Broadcast`事件，只能由设备自身产生 |
+/// | 节点 | 消息族须与节点匹配，页面只接受按键事件 |
+/// | Scheduler、StorageMigration | 禁止 |
+/// | HttpClient | 禁止，避免设备被用作任意HTTP代理 |
+/// | Secret | 只允许写入与备份时导出全部，不能读取单个secret |
+/// | Storage | 禁止读写或删除`gateway/auth/`下的key |
+/// | 全局广播 | 禁止Lifecycle，其他消息按处理该消息族的节点检查 |
+/// | 话题Scheduler、Connectivity | 禁止 |
+/// | 其他话题 | 只允许该话题的事件 |
+pub fn check_remote_access(to: &MessageTo, body: &Message) -> Result<(), AuthError> {
+    let allowed = match to {
+        MessageTo::Point(node) => check_point(node, body),
+        // 全局广播会交给所有节点，存储等节点不区分消息的目标
+        MessageTo::Broadcast => match body {
+            Message::Lifecycle(_) => false,
+            m => owner_of(m).is_none_or(|node| check_point(&node, m)),
+        },
+        MessageTo::Topic(topic) => topic_accepts(topic, body),
+    };
+    if allowed {
+        Ok(())
+    } else {
+        Err(AuthError::Forbidden(format!(
+            "{body} to {to:?} is not accessible remotely",
+            body = variant_name(body).unwrap_or_else(|| body.debug_msg().into())
+        )))
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{check_remote_access, GatewayAuth};
+use crate::{Context, HandleResult, Message, MessageTo, MessageWithHeader, NodeName, TopicName};
+
+/// 客户端通过WebSocket发送的帧
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum GatewayClientFrame {
+    /// 连接后需首先发送配对得到的令牌，浏览器无法为WebSocket设置请求头
+    Auth(String),
+    /// 订阅话题，之后这些话题上的消息都会推送给客户端
+    Subscribe(Vec<TopicName>),
+    Unsubscribe(Vec<TopicName>),
+    /// 是否接收全局广播
+    WatchBroadcast(bool),
+    /// 向节点发送请求，结果以相同id的Response返回
+    Request {
+        id: u64,
+        to: NodeName,
+        body: Message,
+        #[serde(default)]
+        sync: bool,
+    },
+    /// 发送广播或话题消息
+    Publish {
+        to: MessageTo,
+        body: Message,
+    },
+}
+
+/// 网关通过WebSocket推送的帧
+#[derive(Debug, Serialize, Deserialize)]
+pub enum GatewayServerFrame {
+    Topic {
+        topic: TopicName,
+        from: NodeName,
+        body: Message,
+    },
+    Broadcast {
+        from: NodeName,
+        body: Message,
+    },
+    Response {
+        id: u64,
+        result: HandleResult,
+    },
+    Error {
+        id: Option<u64>,
+        error: String,
+    },
+    /// 令牌无效或尚未认证，重新发送Auth之前的帧都会被拒绝
+    Unauthorized(String),
+}
+
+/// 把一帧文本发送给客户端，连接已断开时返回false
+pub type GatewaySink = Box<dyn FnMut(String) -> bool>;
+
+struct Session {
+    authorized: bool,
+    topics: HashSet<TopicName>,
+    broadcast: bool,
+    sink: Rc<RefCell<GatewaySink>>,
+}
+
+/// WebSocket会话管理，桌面端与ESP32共用，只负责协议，连接由各平台维护。
+/// 所有方法都需要在网关节点的handle_message中调用，ctx为网关节点的上下文
+#[derive(Default)]
+pub struct GatewayEvents {
+    sessions: RefCell<HashMap<usize, Session>>,
+    // 网关节点已经订阅过的话题
+    subscribed: RefCell<HashSet<TopicName>>,
+}
+
+fn send(sink: &RefCell<GatewaySink>, frame: &GatewayServerFrame) -> bool {
+    (sink.borrow_mut())(serde_json::to_string(frame).unwrap())
+}
+
+impl GatewayEvents {
+    pub fn open(&self, id: usize, sink: GatewaySink) {
+        self.sessions.borrow_mut().insert(
+            id,
+            Session {
+                authorized: false,
+                topics: HashSet::new(),
+                broadcast: false,
+                sink: Rc::new(RefCell::new(sink)),
+            },
+        );
+    }
+
+    pub fn close(&self, id: usize) {
+        self.sessions.borrow_mut().remove(&id);
+    }
+
+    /// 处理客户端发来的一帧
+    pub fn on_frame(&self, ctx: Rc<dyn Context>, auth: &GatewayAuth, id: usize, text: &str) {
+        let Some((authorized, sink)) = self
+            .sessions
+            .borrow()
+            .get(&id)
+            .map(|x| (x.authorized, x.sink.clone()))
+        else {
+            return;
+        };
+        let frame = match serde_json::from_str::<GatewayClientFrame>(text) {
+            Ok(x) => x,
+            Err(e) => {
+                let error = format!("invalid frame: {e}");
+                send(&sink, &GatewayServerFrame::Error { id: None, error });
+                return;
+            }
+        };
+        if let GatewayClientFrame::Auth(token) = frame {
+            match auth.verify(ctx, Some(&token)) {
+                Ok(_) => {
+                    if let Some(x) = self.sessions.borrow_mut().get_mut(&id) {
+                        x.authorized = true;
+                    }
+                }
+                Err(e) => {
+                    send(&sink, &GatewayServerFrame::Unauthorized(e.to_string()));
+                }
+            }
+            return;
+        }
+        if !authorized {
+            let error = "unauthorized, send Auth first".to_string();
+            // 请求需要带上id，否则调用者会一直等待
+            let frame = match frame {
+                GatewayClientFrame::Request { id, .. } => GatewayServerFrame::Error {
+                    id: Some(id),
+                    error,
+                },
+                _ => GatewayServerFrame::Unauthorized(error),
+            };
+            send(&sink, &frame);
+            return;
+        }
+        let access = match &frame {
+            GatewayClientFrame::Request { id, to, body, .. } => {
+                check_remote_access(&MessageTo::Point(to.clone()), body).map_err(|e| (Some(*id), e))
+            }
+            GatewayClientFrame::Publish { to, body } => {
+                check_remote_access(to, body).map_err(|e| (None, e))
+            }
+            _ => Ok(()),
+        };
+        if let Err((id, e)) = access {
+            let error = e.to_string();
+            send(&sink, &GatewayServerFrame::Error { id, error });
+            return;
+        }
+        match frame {
+            GatewayClientFrame::Auth(_) => {}
+            GatewayClientFrame::Subscribe(topics) => {
+                // 调度器心跳每帧都会发送，不允许订阅
+                if topics.contains(&TopicName::Scheduler) {
+                    let error = "topic Scheduler can not be subscribed".into();
+                    send(&sink, &GatewayServerFrame::Error { id: None, error });
+                    return;
+                }
+                for topic in topics.iter() {
+                    // 网关节点只订阅不退订，没有会话关注的话题消息会在on_message中被忽略
+                    if self.subscribed.borrow_mut().insert(topic.clone()) {
+                        ctx.subscribe_topic(topic.clone());
+                    }
+                }
+                if let Some(x) = self.sessions.borrow_mut().get_mut(&id) {
+                    x.topics.extend(topics);
+                }
+            }
+            GatewayClientFrame::Unsubscribe(topics) => {
+                if let Some(x) = self.sessions.borrow_mut().get_mut(&id) {
+                    x.topics.retain(|t| !topics.contains(t));
+                }
+            }
+            GatewayClientFrame::WatchBroadcast(enable) => {
+                if let Some(x) = self.sessions.borrow_mut().get_mut(&id) {
+                    x.broadcast = enable;
+                }
+            }
+            GatewayClientFrame::Request {
+                id: req_id,
+                to,
+                body,
+                sync,
+            } => {
+                let respond = move |result| {
+                    send(&sink, &GatewayServerFrame::Response { id: req_id, result });
+                };
+                if sync {
+                    respond(ctx.sync_call(to, body));
+                } else {
+                    ctx.async_call(to, body, Box::new(respond));
+                }
+            }
+            GatewayClientFrame::Publish { to, body } => match to {
+                MessageTo::Broadcast => ctx.broadcast_global(body),
+                MessageTo::Topic(topic) => ctx.broadcast_topic(topic, body),
+                MessageTo::Point(_) => {
+                    let error = "use Request to send a message to a node".into();
+                    send(&sink, &GatewayServerFrame::Error { id: None, error });
+                }
+            },
+        }
+    }
+
+    /// 网关节点收到的广播与话题消息，推送给关注的会话
+    pub fn on_message(&self, msg: &MessageWithHeader) {
+        let frame = match &msg.to {
+            MessageTo::Topic(TopicName::Scheduler) | MessageTo::Point(_) => return,
+            MessageTo::Topic(topic) => GatewayServerFrame::Topic {
+                topic: topic.clone(),
+                from: msg.from.clone(),
+                body: msg.body.clone(),
+            },
+            MessageTo::Broadcast => GatewayServerFrame::Broadcast {
+                from: msg.from.clone(),
+                body: msg.body.clone(),
+            },
+        };
+        let mut text = None;
+        self.sessions.borrow_mut().retain(|_, x| {
+            let watched = match &msg.to {
+                MessageTo::Topic(topic) => x.topics.contains(topic),
+                _ => x.broadcast,
+            };
+            if !watched {
+                return true;
+            }
+            let text = text.get_or_insert_with(|| serde_json::to_string(&frame).unwrap());
+            // 发送失败说明连接已断开
+            (x.sink.borrow_mut())(text.clone())
+        });
+    }
+}
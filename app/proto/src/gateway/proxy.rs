@@ -0,0 +1,185 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use super::{GatewayClientFrame, GatewayServerFrame};
+use crate::{
+    Context, HandleResult, LifecycleMessage, Message, MessageTo, MessageWithHeader, Node, NodeName,
+    TopicName,
+};
+
+/// 远程节点代理的配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteNodeConfig {
+    /// 在本地注册的节点名，即NodeName::Other(name)
+    pub name: String,
+    /// 远程网关的WebSocket地址，如ws://192.168.1.10/ws
+    pub url: String,
+    /// 与远程设备配对得到的令牌
+    pub token: String,
+    /// 远程调度器上的节点
+    pub remote: NodeName,
+    /// 本地话题上的消息会转发给远程节点
+    #[serde(default)]
+    pub subscribe: Vec<TopicName>,
+    /// 远程节点在这些话题上发布的消息会转发到本地
+    #[serde(default)]
+    pub publish: Vec<TopicName>,
+}
+
+pub enum ProxyEvent {
+    /// 连接建立，需要重新认证与订阅
+    Connected,
+    Frame(String),
+    Disconnected,
+}
+
+/// 代理与远程网关之间的连接，由各平台实现，需自行处理重连
+pub trait ProxyTransport {
+    fn send(&self, text: String);
+    /// 取出一个连接事件，没有时返回None
+    fn poll(&self) -> Option<ProxyEvent>;
+}
+
+/// 把另一个调度器上的节点代理为本地节点。
+/// 本地发给代理的点对点消息通过网关转发给远程节点，异步返回远程节点的结果，
+/// 远程节点未处理时同样返回Discard；远程网关的错误、认证失败或连接断开时返回Message::Empty，
+/// 因此只能使用异步调用
+pub struct RemoteNodeProxy {
+    config: RemoteNodeConfig,
+    transport: Box<dyn ProxyTransport>,
+    connected: Cell<bool>,
+    next_id: Cell<u64>,
+    // 请求id到本地消息seq
+    pending: RefCell<HashMap<u64, usize>>,
+}
+
+impl RemoteNodeProxy {
+    pub fn new(config: RemoteNodeConfig, transport: Box<dyn ProxyTransport>) -> Self {
+        Self {
+            config,
+            transport,
+            connected: Cell::new(false),
+            next_id: Cell::new(0),
+            pending: Default::default(),
+        }
+    }
+
+    fn send(&self, frame: GatewayClientFrame) {
+        self.transport.send(serde_json::to_string(&frame).unwrap());
+    }
+
+    fn request(&self, body: Message) -> u64 {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        self.send(GatewayClientFrame::Request {
+            id,
+            to: self.config.remote.clone(),
+            body,
+            sync: false,
+        });
+        id
+    }
+
+    fn resolve(&self, ctx: &Rc<dyn Context>, id: u64, result: HandleResult) {
+        // 转发的话题消息没有等待的调用者
+        if let Some(seq) = self.pending.borrow_mut().remove(&id) {
+            match result {
+                HandleResult::Finish(m) => ctx.async_ready(seq, m),
+                HandleResult::Discard => ctx.async_discard(seq),
+                _ => ctx.async_ready(seq, Message::Empty),
+            }
+        }
+    }
+
+    /// 连接不可用，所有等待中的调用都返回Message::Empty
+    fn disconnect(&self, ctx: &Rc<dyn Context>) {
+        self.connected.set(false);
+        for (_, seq) in self.pending.borrow_mut().drain() {
+            ctx.async_ready(seq, Message::Empty);
+        }
+    }
+
+    fn on_frame(&self, ctx: &Rc<dyn Context>, text: &str) {
+        let Ok(frame) = serde_json::from_str::<GatewayServerFrame>(text) else {
+            return;
+        };
+        match frame {
+            GatewayServerFrame::Response { id, result } => self.resolve(ctx, id, result),
+            GatewayServerFrame::Topic { topic, from, body }
+                if from == self.config.remote && self.config.publish.contains(&topic) =>
+            {
+                ctx.broadcast_topic(topic, body);
+            }
+            GatewayServerFrame::Broadcast { from, body } if from == self.config.remote => {
+                ctx.broadcast_global(body);
+            }
+            GatewayServerFrame::Error { id: Some(id), .. } => {
+                self.resolve(ctx, id, HandleResult::Finish(Message::Empty));
+            }
+            // 令牌无效时不会再有响应，与连接断开相同
+            GatewayServerFrame::Unauthorized(error) => {
+                if self.connected.get() {
+                    warn!("remote node {} unauthorized: {error}", self.config.name);
+                }
+                self.disconnect(ctx);
+            }
+            _ => {}
+        }
+    }
+
+    fn poll_transport(&self, ctx: &Rc<dyn Context>) {
+        while let Some(e) = self.transport.poll() {
+            match e {
+                ProxyEvent::Connected => {
+                    self.connected.set(true);
+                    self.send(GatewayClientFrame::Auth(self.config.token.clone()));
+                    self.send(GatewayClientFrame::Subscribe(self.config.publish.clone()));
+                    self.send(GatewayClientFrame::WatchBroadcast(true));
+                }
+                ProxyEvent::Disconnected => self.disconnect(ctx),
+                ProxyEvent::Frame(text) => self.on_frame(ctx, &text),
+            }
+        }
+    }
+}
+
+impl Node for RemoteNodeProxy {
+    fn node_name(&self) -> NodeName {
+        NodeName::Other(self.config.name.clone())
+    }
+
+    fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
+        match msg.to {
+            MessageTo::Topic(TopicName::Scheduler) => self.poll_transport(&ctx),
+            MessageTo::Broadcast => {
+                if let Message::Lifecycle(LifecycleMessage::Init) = msg.body {
+                    ctx.subscribe_topic(TopicName::Scheduler);
+                    for topic in self.config.subscribe.iter() {
+                        ctx.subscribe_topic(topic.clone());
+                    }
+                }
+            }
+            MessageTo::Topic(_) => {
+                // 从远程转发到本地的消息不再转发回去
+                if self.connected.get() && msg.from != self.node_name() {
+                    self.request(msg.body);
+                }
+            }
+            MessageTo::Point(_) => {
+                if !self.connected.get() {
+                    return HandleResult::Discard;
+                }
+                let id = self.request(msg.body);
+                self.pending.borrow_mut().insert(id, msg.seq);
+                return HandleResult::Pending;
+            }
+        }
+        HandleResult::Discard
+    }
+}
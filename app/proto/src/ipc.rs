@@ -5,6 +5,8 @@ mod buzzer;
 mod httpclient;
 mod midi;
 mod notifaction;
+mod secret;
+mod sensor;
 mod storage;
 mod system;
 mod useralarm;
@@ -12,6 +14,7 @@ mod weather;
 
 pub use {
     buzzer::BuzzerClient, httpclient::HttpClient, midi::MidiPlayerClient,
-    notifaction::NotifactionClient, storage::StorageClient, system::SystemClient,
-    useralarm::UserAlarmClient, weather::WeatherClient,
+    notifaction::NotifactionClient, secret::SecretClient, sensor::SensorClient,
+    storage::StorageClient, system::SystemClient, useralarm::UserAlarmClient,
+    weather::WeatherClient,
 };
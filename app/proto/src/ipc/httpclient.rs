@@ -3,7 +3,7 @@ use std::rc::Rc;
 use crate::{Context, Message, NodeName};
 
 use super::AsyncResultCallback;
-use crate::message::{HttpError, HttpMessage, HttpRequest, HttpResponse};
+use crate::message::{ConnectivityState, HttpError, HttpMessage, HttpRequest, HttpResponse};
 
 #[derive(Clone)]
 pub struct HttpClient(pub Rc<dyn Context>);
@@ -26,4 +26,81 @@ impl HttpClient {
             }),
         )
     }
+
+    /// 从流式响应体中读取至多len字节，读完时返回None
+    pub fn read_chunk(
+        &self,
+        handle: usize,
+        len: usize,
+        callback: AsyncResultCallback<Option<Vec<u8>>, HttpError>,
+    ) {
+        self.0.async_call(
+            NodeName::HttpClient,
+            Message::Http(HttpMessage::ReadChunkRequest(handle, len)),
+            Box::new(|r| {
+                callback(match r.unwrap() {
+                    Message::Http(HttpMessage::ReadChunkResponse(chunk)) => Ok(chunk.map(|x| x.0)),
+                    Message::Http(HttpMessage::Error(e)) => Err(e),
+                    m => panic!("unexpected HandleResult {:?}", m),
+                });
+            }),
+        )
+    }
+
+    pub fn close_stream(&self, handle: usize) {
+        let r = self.0.sync_call(
+            NodeName::HttpClient,
+            Message::Http(HttpMessage::CloseStreamRequest(handle)),
+        );
+        match r.unwrap() {
+            Message::Http(HttpMessage::CloseStreamResponse) => {}
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    /// 当前的网络连通状态
+    pub fn connectivity(&self) -> ConnectivityState {
+        let r = self.0.sync_call(
+            NodeName::HttpClient,
+            Message::Http(HttpMessage::GetConnectivityRequest),
+        );
+        match r.unwrap() {
+            Message::Http(HttpMessage::Connectivity(x)) => x,
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    /// 逐块拉取流式响应体，上一块处理完才会请求下一块，内存中最多只有一个分块。
+    /// on_chunk返回false时提前结束，结束后流会被关闭
+    pub fn for_each_chunk(
+        &self,
+        handle: usize,
+        len: usize,
+        mut on_chunk: Box<dyn FnMut(Vec<u8>) -> bool>,
+        callback: AsyncResultCallback<(), HttpError>,
+    ) {
+        let this = self.clone();
+        self.read_chunk(
+            handle,
+            len,
+            Box::new(move |r| match r {
+                Ok(Some(chunk)) => {
+                    if on_chunk(chunk) {
+                        this.for_each_chunk(handle, len, on_chunk, callback);
+                    } else {
+                        this.close_stream(handle);
+                        callback(Ok(()));
+                    }
+                }
+                Ok(None) => {
+                    this.close_stream(handle);
+                    callback(Ok(()));
+                }
+                Err(e) => {
+                    this.close_stream(handle);
+                    callback(Err(e));
+                }
+            }),
+        )
+    }
 }
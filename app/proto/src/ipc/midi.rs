@@ -27,6 +27,46 @@ impl MidiPlayerClient {
         );
     }
 
+    pub fn play_music(&self, filename: String, callback: AsyncResultCallback<bool, MidiError>) {
+        self.0.async_call(
+            NodeName::MidiPlayer,
+            Message::Midi(MidiMessage::PlayMusicRequest(filename)),
+            Box::new(|r| {
+                callback(match r.unwrap() {
+                    Message::Midi(msg) => match msg {
+                        MidiMessage::PlayResponse(is_finished) => Ok(is_finished),
+                        MidiMessage::Error(e) => Err(e),
+                        m => panic!("unexpected response, {:?}", m),
+                    },
+                    m => panic!("unexpected response, {:?}", m),
+                });
+            }),
+        );
+    }
+
+    /// 由设备下载音乐到音乐存储
+    pub fn download(
+        &self,
+        filename: String,
+        url: String,
+        callback: AsyncResultCallback<(), MidiError>,
+    ) {
+        self.0.async_call(
+            NodeName::MidiPlayer,
+            Message::Midi(MidiMessage::DownloadRequest { filename, url }),
+            Box::new(|r| {
+                callback(match r.unwrap() {
+                    Message::Midi(msg) => match msg {
+                        MidiMessage::DownloadResponse => Ok(()),
+                        MidiMessage::Error(e) => Err(e),
+                        m => panic!("unexpected response, {:?}", m),
+                    },
+                    m => panic!("unexpected response, {:?}", m),
+                });
+            }),
+        );
+    }
+
     pub fn off(&self) {
         self.0
             .sync_call(NodeName::MidiPlayer, Message::Midi(MidiMessage::Off));
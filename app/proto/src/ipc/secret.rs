@@ -0,0 +1,46 @@
+use std::rc::Rc;
+
+use crate::{Context, Message, NodeName};
+
+use crate::message::{Secret, SecretError, SecretMessage};
+
+#[derive(Clone)]
+pub struct SecretClient(pub Rc<dyn Context>);
+
+impl SecretClient {
+    pub fn get(&self, name: String) -> Result<Option<Secret>, SecretError> {
+        let r = self.0.sync_call(
+            NodeName::Secret,
+            Message::Secret(SecretMessage::GetRequest(name)),
+        );
+        match r.unwrap() {
+            Message::Secret(SecretMessage::GetResponse(r)) => Ok(r),
+            Message::Secret(SecretMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn set(&self, name: String, value: Option<Secret>) -> Result<(), SecretError> {
+        let r = self.0.sync_call(
+            NodeName::Secret,
+            Message::Secret(SecretMessage::SetRequest(name, value)),
+        );
+        match r.unwrap() {
+            Message::Secret(SecretMessage::SetResponse) => Ok(()),
+            Message::Secret(SecretMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn export(&self) -> Result<Vec<(String, Secret)>, SecretError> {
+        let r = self.0.sync_call(
+            NodeName::Secret,
+            Message::Secret(SecretMessage::ExportRequest),
+        );
+        match r.unwrap() {
+            Message::Secret(SecretMessage::ExportResponse(r)) => Ok(r),
+            Message::Secret(SecretMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use std::rc::Rc;
+
+use crate::{Context, Message, NodeName};
+
+use crate::message::{SensorMessage, SensorReading};
+
+#[derive(Clone)]
+pub struct SensorClient(pub Rc<dyn Context>);
+
+impl SensorClient {
+    pub fn get_latest(&self) -> Option<SensorReading> {
+        let r = self.0.sync_call(
+            NodeName::Sensor,
+            Message::Sensor(SensorMessage::GetLatestRequest),
+        );
+        match r.unwrap() {
+            Message::Sensor(SensorMessage::GetLatestResponse(x)) => x,
+            m => panic!("unexpected response, {:?}", m),
+        }
+    }
+
+    pub fn get_history(&self) -> Vec<SensorReading> {
+        let r = self.0.sync_call(
+            NodeName::Sensor,
+            Message::Sensor(SensorMessage::GetHistoryRequest),
+        );
+        match r.unwrap() {
+            Message::Sensor(SensorMessage::GetHistoryResponse(x)) => x,
+            m => panic!("unexpected response, {:?}", m),
+        }
+    }
+}
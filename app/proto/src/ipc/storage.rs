@@ -2,7 +2,9 @@ use std::{collections::HashSet, rc::Rc};
 
 use crate::{Context, Message, NodeName};
 
-use crate::message::{StorageError, StorageMessage, StorageValue};
+use crate::message::{
+    Bytes, StorageCapacity, StorageError, StorageMessage, StorageUsage, StorageValue,
+};
 #[derive(Clone)]
 pub struct StorageClient(pub Rc<dyn Context>);
 
@@ -41,4 +43,117 @@ impl StorageClient {
             m => panic!("unexpected message {:?}", m),
         }
     }
+
+    pub fn blob_open(&self, key: String) -> Result<usize, StorageError> {
+        let r = self.0.sync_call(
+            NodeName::Storage,
+            Message::Storage(StorageMessage::BlobOpenRequest(key)),
+        );
+        match r.unwrap() {
+            Message::Storage(StorageMessage::BlobOpenResponse(handle)) => Ok(handle),
+            Message::Storage(StorageMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn blob_write(&self, handle: usize, chunk: Vec<u8>) -> Result<(), StorageError> {
+        let r = self.0.sync_call(
+            NodeName::Storage,
+            Message::Storage(StorageMessage::BlobWriteRequest(handle, Bytes(chunk))),
+        );
+        match r.unwrap() {
+            Message::Storage(StorageMessage::BlobWriteResponse) => Ok(()),
+            Message::Storage(StorageMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn blob_commit(&self, handle: usize) -> Result<(), StorageError> {
+        let r = self.0.sync_call(
+            NodeName::Storage,
+            Message::Storage(StorageMessage::BlobCommitRequest(handle)),
+        );
+        match r.unwrap() {
+            Message::Storage(StorageMessage::BlobCommitResponse) => Ok(()),
+            Message::Storage(StorageMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn blob_abort(&self, handle: usize) -> Result<(), StorageError> {
+        let r = self.0.sync_call(
+            NodeName::Storage,
+            Message::Storage(StorageMessage::BlobAbortRequest(handle)),
+        );
+        match r.unwrap() {
+            Message::Storage(StorageMessage::BlobAbortResponse) => Ok(()),
+            Message::Storage(StorageMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn blob_read(
+        &self,
+        key: String,
+        offset: usize,
+        len: usize,
+    ) -> Result<Vec<u8>, StorageError> {
+        let r = self.0.sync_call(
+            NodeName::Storage,
+            Message::Storage(StorageMessage::BlobReadRequest(key, offset, len)),
+        );
+        match r.unwrap() {
+            Message::Storage(StorageMessage::BlobReadResponse(bs)) => Ok(bs.0),
+            Message::Storage(StorageMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn blob_size(&self, key: String) -> Result<Option<usize>, StorageError> {
+        let r = self.0.sync_call(
+            NodeName::Storage,
+            Message::Storage(StorageMessage::BlobSizeRequest(key)),
+        );
+        match r.unwrap() {
+            Message::Storage(StorageMessage::BlobSizeResponse(size)) => Ok(size),
+            Message::Storage(StorageMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn usage(&self, prefix: String) -> Result<StorageUsage, StorageError> {
+        let r = self.0.sync_call(
+            NodeName::Storage,
+            Message::Storage(StorageMessage::UsageRequest(prefix)),
+        );
+        match r.unwrap() {
+            Message::Storage(StorageMessage::UsageResponse(r)) => Ok(r),
+            Message::Storage(StorageMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn capacity(&self) -> Result<StorageCapacity, StorageError> {
+        let r = self.0.sync_call(
+            NodeName::Storage,
+            Message::Storage(StorageMessage::CapacityRequest),
+        );
+        match r.unwrap() {
+            Message::Storage(StorageMessage::CapacityResponse(r)) => Ok(r),
+            Message::Storage(StorageMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn delete_prefix(&self, prefix: String) -> Result<usize, StorageError> {
+        let r = self.0.sync_call(
+            NodeName::Storage,
+            Message::Storage(StorageMessage::DeletePrefixRequest(prefix)),
+        );
+        match r.unwrap() {
+            Message::Storage(StorageMessage::DeletePrefixResponse(n)) => Ok(n),
+            Message::Storage(StorageMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
 }
@@ -1,8 +1,8 @@
 use std::rc::Rc;
 
 use crate::{
-    CityLookUpItem, Context, ForecastWeather, Location, Message, NodeName, NowAirQuality,
-    NowWeather,
+    AirQualityForecast, Astronomy, CityLookUpItem, Context, ForecastWeather, HourlyForecast,
+    Location, Message, NodeName, NowAirQuality, NowWeather, WeatherWarnings,
 };
 
 use crate::message::{WeatherError, WeatherMessage};
@@ -33,11 +33,12 @@ impl WeatherClient {
 
     pub fn get_forecast_weather(
         &self,
+        location_id: Option<u32>,
         callback: AsyncResultCallback<ForecastWeather, WeatherError>,
     ) {
         self.0.async_call(
             NodeName::Weather,
-            Message::Weather(WeatherMessage::GetForecastWeatherRequest),
+            Message::Weather(WeatherMessage::GetForecastWeatherRequest(location_id)),
             Box::new(|r| {
                 callback(match r.unwrap() {
                     Message::Weather(WeatherMessage::GetForecastWeatherResponse(r)) => Ok(r),
@@ -48,10 +49,14 @@ impl WeatherClient {
         );
     }
 
-    pub fn get_now_weather(&self, callback: AsyncResultCallback<NowWeather, WeatherError>) {
+    pub fn get_now_weather(
+        &self,
+        location_id: Option<u32>,
+        callback: AsyncResultCallback<NowWeather, WeatherError>,
+    ) {
         self.0.async_call(
             NodeName::Weather,
-            Message::Weather(WeatherMessage::GetNowWeatherRequest),
+            Message::Weather(WeatherMessage::GetNowWeatherRequest(location_id)),
             Box::new(|r| {
                 callback(match r.unwrap() {
                     Message::Weather(WeatherMessage::GetNowWeatherResponse(r)) => Ok(r),
@@ -62,10 +67,14 @@ impl WeatherClient {
         );
     }
 
-    pub fn get_now_air_quality(&self, callback: AsyncResultCallback<NowAirQuality, WeatherError>) {
+    pub fn get_now_air_quality(
+        &self,
+        location_id: Option<u32>,
+        callback: AsyncResultCallback<NowAirQuality, WeatherError>,
+    ) {
         self.0.async_call(
             NodeName::Weather,
-            Message::Weather(WeatherMessage::GetNowAirQualityRequest),
+            Message::Weather(WeatherMessage::GetNowAirQualityRequest(location_id)),
             Box::new(|r| {
                 callback(match r.unwrap() {
                     Message::Weather(WeatherMessage::GetNowAirQualityResponse(r)) => Ok(r),
@@ -76,6 +85,78 @@ impl WeatherClient {
         );
     }
 
+    pub fn get_air_quality_forecast(
+        &self,
+        location_id: Option<u32>,
+        callback: AsyncResultCallback<AirQualityForecast, WeatherError>,
+    ) {
+        self.0.async_call(
+            NodeName::Weather,
+            Message::Weather(WeatherMessage::GetAirQualityForecastRequest(location_id)),
+            Box::new(|r| {
+                callback(match r.unwrap() {
+                    Message::Weather(WeatherMessage::GetAirQualityForecastResponse(r)) => Ok(r),
+                    Message::Weather(WeatherMessage::Error(e)) => Err(e),
+                    m => panic!("unexpected message {:?}", m),
+                });
+            }),
+        );
+    }
+
+    pub fn get_hourly_forecast(
+        &self,
+        location_id: Option<u32>,
+        callback: AsyncResultCallback<HourlyForecast, WeatherError>,
+    ) {
+        self.0.async_call(
+            NodeName::Weather,
+            Message::Weather(WeatherMessage::GetHourlyForecastRequest(location_id)),
+            Box::new(|r| {
+                callback(match r.unwrap() {
+                    Message::Weather(WeatherMessage::GetHourlyForecastResponse(r)) => Ok(r),
+                    Message::Weather(WeatherMessage::Error(e)) => Err(e),
+                    m => panic!("unexpected message {:?}", m),
+                });
+            }),
+        );
+    }
+
+    pub fn get_warnings(
+        &self,
+        location_id: Option<u32>,
+        callback: AsyncResultCallback<WeatherWarnings, WeatherError>,
+    ) {
+        self.0.async_call(
+            NodeName::Weather,
+            Message::Weather(WeatherMessage::GetWarningsRequest(location_id)),
+            Box::new(|r| {
+                callback(match r.unwrap() {
+                    Message::Weather(WeatherMessage::GetWarningsResponse(r)) => Ok(r),
+                    Message::Weather(WeatherMessage::Error(e)) => Err(e),
+                    m => panic!("unexpected message {:?}", m),
+                });
+            }),
+        );
+    }
+
+    pub fn get_astronomy(
+        &self,
+        location_id: Option<u32>,
+        callback: AsyncResultCallback<Astronomy, WeatherError>,
+    ) {
+        self.0.async_call(
+            NodeName::Weather,
+            Message::Weather(WeatherMessage::GetAstronomyRequest(location_id)),
+            Box::new(|r| {
+                callback(match r.unwrap() {
+                    Message::Weather(WeatherMessage::GetAstronomyResponse(r)) => Ok(r),
+                    Message::Weather(WeatherMessage::Error(e)) => Err(e),
+                    m => panic!("unexpected message {:?}", m),
+                });
+            }),
+        );
+    }
+
     pub fn set_location(&self, loc: Location) -> Result<(), WeatherError> {
         match self
             .0
@@ -105,4 +186,64 @@ impl WeatherClient {
             m => panic!("unexpected message {:?}", m),
         }
     }
+
+    pub fn list_locations(&self) -> Result<Vec<Location>, WeatherError> {
+        match self
+            .0
+            .sync_call(
+                NodeName::Weather,
+                Message::Weather(WeatherMessage::ListLocationsRequest),
+            )
+            .unwrap()
+        {
+            Message::Weather(WeatherMessage::ListLocationsResponse(r)) => Ok(r),
+            Message::Weather(WeatherMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn add_location(&self, loc: Location) -> Result<(), WeatherError> {
+        match self
+            .0
+            .sync_call(
+                NodeName::Weather,
+                Message::Weather(WeatherMessage::AddLocationRequest(loc)),
+            )
+            .unwrap()
+        {
+            Message::Weather(WeatherMessage::AddLocationResponse) => Ok(()),
+            Message::Weather(WeatherMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn remove_location(&self, location_id: u32) -> Result<(), WeatherError> {
+        match self
+            .0
+            .sync_call(
+                NodeName::Weather,
+                Message::Weather(WeatherMessage::RemoveLocationRequest(location_id)),
+            )
+            .unwrap()
+        {
+            Message::Weather(WeatherMessage::RemoveLocationResponse) => Ok(()),
+            Message::Weather(WeatherMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
+
+    pub fn move_location(&self, location_id: u32, index: usize) -> Result<(), WeatherError> {
+        match self
+            .0
+            .sync_call(
+                NodeName::Weather,
+                Message::Weather(WeatherMessage::MoveLocationRequest { location_id, index }),
+            )
+            .unwrap()
+        {
+            Message::Weather(WeatherMessage::MoveLocationResponse) => Ok(()),
+            Message::Weather(WeatherMessage::Error(e)) => Err(e),
+            m => panic!("unexpected message {:?}", m),
+        }
+    }
 }
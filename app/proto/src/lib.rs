@@ -1,4 +1,7 @@
+pub mod astronomy;
+pub mod gateway;
 pub mod ipc;
+pub mod sensor;
 pub mod storage;
 
 pub mod message;
@@ -60,8 +63,14 @@ pub trait Context {
     // 消息就绪，并传递值
     fn async_ready(&self, seq: usize, result: Message);
 
+    // 异步消息最终未被处理，调用者收到HandleResult::Discard
+    fn async_discard(&self, seq: usize);
+
     // 创建等待器
     fn create_wait_group(&self) -> Rc<dyn WaitGroup>;
+
+    // 已注册的节点
+    fn node_names(&self) -> Vec<NodeName>;
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -15,6 +15,9 @@ pub use lifecycle::LifecycleMessage;
 mod weather;
 pub use weather::*;
 
+mod display;
+pub use display::*;
+
 mod storage;
 pub use storage::*;
 
@@ -51,6 +54,12 @@ pub use timer::*;
 mod canvas;
 pub use canvas::*;
 
+mod secret;
+pub use secret::*;
+
+mod sensor;
+pub use sensor::*;
+
 #[derive(Debug, Serialize, Clone, Deserialize, Default)]
 pub enum Message {
     /// 空消息
@@ -79,6 +88,10 @@ pub enum Message {
     Sntp(SntpMessage),
     Canvas(CanvasMessage),
     UserAlarm(UserAlarmMessage),
+    /// 加密存储的敏感信息
+    Secret(SecretMessage),
+    /// 室内温湿度传感器
+    Sensor(SensorMessage),
 }
 
 impl Message {
@@ -103,6 +116,8 @@ impl Message {
                     RoutePage::Menu => "router/gotopage/menu",
                     RoutePage::Weather => "router/gotopage/weather",
                     RoutePage::Music => "router/gotopage/music",
+                    RoutePage::AirQuality => "router/gotopage/air_quality",
+                    RoutePage::Indoor => "router/gotopage/indoor",
                 },
             },
             Message::Weather(_) => "weather",
@@ -110,8 +125,21 @@ impl Message {
                 HttpMessage::Error(_) => "http/error",
                 HttpMessage::Request(_) => "http/request",
                 HttpMessage::Response(_) => "http/response",
+                HttpMessage::ReadChunkRequest(..) => "http/readchunk/request",
+                HttpMessage::ReadChunkResponse(_) => "http/readchunk/response",
+                HttpMessage::CloseStreamRequest(_) => "http/closestream/request",
+                HttpMessage::CloseStreamResponse => "http/closestream/response",
+                HttpMessage::Connectivity(_) => "http/connectivity",
+                HttpMessage::GetConnectivityRequest => "http/connectivity/request",
             },
             Message::Storage(_) => "storage",
+            Message::Sensor(msg) => match msg {
+                SensorMessage::ReadingBroadcast(_) => "sensor/reading",
+                SensorMessage::GetLatestRequest => "sensor/latest/request",
+                SensorMessage::GetLatestResponse(_) => "sensor/latest/response",
+                SensorMessage::GetHistoryRequest => "sensor/history/request",
+                SensorMessage::GetHistoryResponse(_) => "sensor/history/response",
+            },
             Message::System(_) => "system",
             _ => "unknown",
         }
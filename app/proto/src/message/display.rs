@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use time::Weekday;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TempUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+impl TempUnit {
+    /// 将摄氏度转换为该单位
+    pub fn from_celsius(&self, temp: i8) -> i8 {
+        match self {
+            TempUnit::Celsius => temp,
+            TempUnit::Fahrenheit => (temp as f32 * 1.8 + 32.0).round() as i8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClockFormat {
+    #[default]
+    H24,
+    H12,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Language {
+    Zh,
+    #[default]
+    En,
+}
+
+impl Language {
+    /// 请求天气数据源时使用的语言代码
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::Zh => "zh",
+            Language::En => "en",
+        }
+    }
+}
+
+/// 显示设置，保存在`system/display`，天气数据源按其中的单位与语言请求
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplaySettings {
+    #[serde(default)]
+    pub temp_unit: TempUnit,
+    #[serde(default)]
+    pub clock: ClockFormat,
+    #[serde(default)]
+    pub lang: Language,
+}
+
+impl DisplaySettings {
+    pub fn temp_symbol(&self) -> &'static str {
+        match self.temp_unit {
+            TempUnit::Celsius => "℃",
+            TempUnit::Fahrenheit => "℉",
+        }
+    }
+
+    /// 带一位小数与单位的温度，用于室内读数，如`24.5℃`
+    pub fn format_temp(&self, celsius: f32) -> String {
+        let temp = match self.temp_unit {
+            TempUnit::Celsius => celsius,
+            TempUnit::Fahrenheit => celsius * 1.8 + 32.0,
+        };
+        format!("{temp:.1}{}", self.temp_symbol())
+    }
+
+    /// 12小时制的小时数与上下午标记
+    fn hour12(&self, hour: u8) -> (u8, &'static str) {
+        let h = (hour + 11) % 12 + 1;
+        match (self.lang, hour < 12) {
+            (Language::Zh, true) => (h, "上午"),
+            (Language::Zh, false) => (h, "下午"),
+            (Language::En, true) => (h, "AM"),
+            (Language::En, false) => (h, "PM"),
+        }
+    }
+
+    /// 在分钟部分前加上小时，12小时制时加上上下午标记
+    fn with_hour(&self, hour: u8, rest: &str) -> String {
+        match self.clock {
+            ClockFormat::H24 => format!("{hour:0>2}:{rest}"),
+            ClockFormat::H12 => match self.hour12(hour) {
+                (h, x) if self.lang == Language::Zh => format!("{x}{h}:{rest}"),
+                (h, x) => format!("{h}:{rest} {x}"),
+            },
+        }
+    }
+
+    /// 如`07:05`、`7:05 AM`、`上午7:05`
+    pub fn format_time(&self, hour: u8, minute: u8) -> String {
+        self.with_hour(hour, &format!("{minute:0>2}"))
+    }
+
+    /// 带秒，用于首页时钟
+    pub fn format_time_seconds(&self, hour: u8, minute: u8, second: u8) -> String {
+        self.with_hour(hour, &format!("{minute:0>2}:{second:0>2}"))
+    }
+
+    /// 不带上下午标记，用于空间有限的位置
+    pub fn format_time_compact(&self, hour: u8, minute: u8) -> String {
+        match self.clock {
+            ClockFormat::H24 => format!("{hour:0>2}:{minute:0>2}"),
+            ClockFormat::H12 => format!("{}:{minute:0>2}", self.hour12(hour).0),
+        }
+    }
+
+    /// 逐小时预报的小时标签，如`08`、`8a`
+    pub fn format_hour(&self, hour: u8) -> String {
+        match self.clock {
+            ClockFormat::H24 => format!("{hour:0>2}"),
+            ClockFormat::H12 => {
+                let h = self.hour12(hour).0;
+                if hour < 12 {
+                    format!("{h}a")
+                } else {
+                    format!("{h}p")
+                }
+            }
+        }
+    }
+
+    pub fn weekday(&self, weekday: Weekday) -> &'static str {
+        let i = weekday.number_days_from_sunday() as usize;
+        match self.lang {
+            Language::Zh => ["周日", "周一", "周二", "周三", "周四", "周五", "周六"][i],
+            Language::En => ["Sun.", "Mon.", "Tue.", "Wed.", "Thu.", "Fri.", "Sat."][i],
+        }
+    }
+}
@@ -1,11 +1,14 @@
+use std::time::Duration;
+
 use serde::{de, Deserialize, Serialize};
 
-use super::Bytes;
+use super::{Bytes, Message};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HttpBody {
     Bytes(Bytes),
-    Stream,
+    /// 流式响应体的句柄，通过ReadChunkRequest按需拉取，读完或放弃时需CloseStreamRequest
+    Stream(usize),
 }
 
 impl HttpBody {
@@ -15,9 +18,9 @@ impl HttpBody {
     {
         match self {
             HttpBody::Bytes(bs) => serde_json::from_slice::<T>(&bs.0),
-            HttpBody::Stream => {
-                unimplemented!("not implement");
-            }
+            HttpBody::Stream(_) => Err(de::Error::custom(
+                "streaming body must be read by chunks before deserializing",
+            )),
         }
     }
 }
@@ -25,28 +28,353 @@ impl HttpBody {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HttpRequestMethod {
     Get,
+    Post,
+    Put,
+    Delete,
+}
+
+fn encode_query_component(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                ret.push(b as char)
+            }
+            _ => ret.push_str(&format!("%{b:02X}")),
+        }
+    }
+    ret
+}
+
+/// 不应随URL保存的查询参数，如和风天气的API key
+pub const REDACTED_QUERY_PARAMS: &[&str] = &["key"];
+
+/// 去掉敏感参数并按参数排序后的URL，用于缓存与录制文件的匹配
+pub fn normalize_url(url: &str) -> String {
+    let Some((base, query)) = url.split_once('?') else {
+        return url.into();
+    };
+    let mut params = query
+        .split('&')
+        .filter(|x| {
+            let name = x.split('=').next().unwrap_or_default();
+            !x.is_empty() && !REDACTED_QUERY_PARAMS.contains(&name)
+        })
+        .collect::<Vec<_>>();
+    params.sort_unstable();
+    if params.is_empty() {
+        base.into()
+    } else {
+        format!("{base}?{}", params.join("&"))
+    }
+}
+
+/// 请求未指定超时时间时使用的默认超时
+pub const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 请求失败后的重试策略，只有可重试的错误才会重试，两次重试之间指数退避
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpRetryPolicy {
+    /// 最大重试次数，0表示不重试
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for HttpRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+        }
+    }
+}
+
+impl HttpRetryPolicy {
+    /// 第attempt次重试(从0开始)前需要等待的时间
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let ms = self
+            .initial_backoff_ms
+            .saturating_mul(1u64 << attempt.min(16))
+            .min(self.max_backoff_ms);
+        Duration::from_millis(ms)
+    }
+
+    /// 按策略执行一个阻塞的请求，sleep用于退避等待，返回最后一次尝试的结果
+    pub fn run<T>(
+        &self,
+        mut f: impl FnMut() -> Result<T, HttpError>,
+        mut sleep: impl FnMut(Duration),
+    ) -> Result<T, HttpError> {
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Err(e) if e.is_retryable() && attempt < self.max_retries => {
+                    sleep(self.backoff(attempt));
+                    attempt += 1;
+                }
+                r => return r,
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HttpRequest {
     pub method: HttpRequestMethod,
     pub url: String,
+    /// 请求头，同名头可以出现多次
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub body: Option<Bytes>,
+    /// 为true时响应体以HttpBody::Stream返回，不在客户端缓存完整内容
+    #[serde(default)]
+    pub stream: bool,
+    /// 单次尝试的超时时间，None时使用DEFAULT_HTTP_TIMEOUT
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub retry: HttpRetryPolicy,
+    /// 为true时GET请求经过HttpClient节点内的缓存，见storage::HttpCacheLayer
+    #[serde(default)]
+    pub cache: bool,
+}
+
+impl HttpRequest {
+    pub fn new(method: HttpRequestMethod, url: impl Into<String>) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            headers: Vec::new(),
+            body: None,
+            stream: false,
+            timeout_ms: None,
+            retry: Default::default(),
+            cache: false,
+        }
+    }
+
+    pub fn get(url: impl Into<String>) -> Self {
+        Self::new(HttpRequestMethod::Get, url)
+    }
+
+    pub fn post(url: impl Into<String>) -> Self {
+        Self::new(HttpRequestMethod::Post, url)
+    }
+
+    pub fn put(url: impl Into<String>) -> Self {
+        Self::new(HttpRequestMethod::Put, url)
+    }
+
+    pub fn delete(url: impl Into<String>) -> Self {
+        Self::new(HttpRequestMethod::Delete, url)
+    }
+
+    /// 追加一个URL查询参数，名称与值会被百分号编码
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        let sep = if self.url.contains('?') { '&' } else { '?' };
+        self.url.push(sep);
+        self.url.push_str(&encode_query_component(name));
+        self.url.push('=');
+        self.url.push_str(&encode_query_component(value));
+        self
+    }
+
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = Some(Bytes(body));
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_ms = Some(timeout.as_millis() as u64);
+        self
+    }
+
+    /// 可重试的错误最多重试max_retries次
+    pub fn retry(mut self, max_retries: u32) -> Self {
+        self.retry.max_retries = max_retries;
+        self
+    }
+
+    pub fn get_timeout(&self) -> Duration {
+        self.timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_HTTP_TIMEOUT)
+    }
+
+    /// 启用HTTP缓存
+    pub fn cached(mut self) -> Self {
+        self.cache = true;
+        self
+    }
+
+    /// 以流的方式接收响应体
+    pub fn streaming(mut self) -> Self {
+        self.stream = true;
+        self
+    }
+
+    /// 以JSON作为请求体，并设置content-type
+    pub fn json<T: Serialize>(self, value: &T) -> serde_json::Result<Self> {
+        Ok(self
+            .header("content-type", "application/json")
+            .body(serde_json::to_vec(value)?))
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
     pub body: HttpBody,
 }
 
+impl HttpResponse {
+    /// 按名称查找响应头，忽略大小写
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// 非2xx状态码转换为HttpError::Status，各HttpClient节点返回响应前调用，
+    /// 错误响应体通常很小，流式请求也应先完整读取错误响应体。
+    /// 响应体为流时通过close_stream关闭，避免句柄泄漏
+    pub fn error_for_status(self, close_stream: impl FnOnce(usize)) -> Result<Self, HttpError> {
+        if (200..300).contains(&self.status) {
+            return Ok(self);
+        }
+        Err(HttpError::Status {
+            status: self.status,
+            body: match self.body {
+                HttpBody::Bytes(bs) => bs,
+                HttpBody::Stream(handle) => {
+                    close_stream(handle);
+                    Bytes(Vec::new())
+                }
+            },
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HttpError {
     Timeout,
+    /// 请求无法构造，例如URL或请求头非法
+    InvalidRequest(String),
+    /// 无法建立连接，例如DNS解析失败、连接被拒绝、TLS握手失败
+    Connect(String),
+    /// 服务端返回了非2xx状态码
+    Status {
+        status: u16,
+        body: Bytes,
+    },
+    /// 读取或解码响应体失败
+    Body(String),
     Other(String),
 }
 
+impl HttpError {
+    /// 网络层面的失败，连续出现时视为离线
+    pub fn is_network(&self) -> bool {
+        matches!(self, Self::Timeout | Self::Connect(_))
+    }
+
+    /// 重试可能成功的错误
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Status { status, .. } => *status == 429 || *status >= 500,
+            e => e.is_network(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConnectivityState {
+    Online,
+    Offline,
+}
+
+/// 连续网络失败达到该次数后视为离线
+const OFFLINE_THRESHOLD: u32 = 3;
+
+/// 根据请求结果推断网络连通状态，各HttpClient节点在请求完成时记录
+#[derive(Debug)]
+pub struct ConnectivityTracker {
+    failures: u32,
+    state: ConnectivityState,
+}
+
+impl Default for ConnectivityTracker {
+    fn default() -> Self {
+        Self {
+            failures: 0,
+            state: ConnectivityState::Online,
+        }
+    }
+}
+
+impl ConnectivityTracker {
+    pub fn state(&self) -> ConnectivityState {
+        self.state
+    }
+
+    /// 记录一次请求结果，状态发生变化时返回新状态
+    pub fn record(&mut self, error: Option<&HttpError>) -> Option<ConnectivityState> {
+        let state = match error {
+            Some(e) if e.is_network() => {
+                self.failures += 1;
+                if self.failures < OFFLINE_THRESHOLD {
+                    return None;
+                }
+                ConnectivityState::Offline
+            }
+            // 收到了服务端的响应(包括错误状态码)说明网络是通的
+            _ => {
+                self.failures = 0;
+                ConnectivityState::Online
+            }
+        };
+        if state == self.state {
+            return None;
+        }
+        self.state = state;
+        Some(state)
+    }
+
+    /// 记录HttpClient节点返回的消息
+    pub fn record_message(&mut self, msg: &Message) -> Option<ConnectivityState> {
+        match msg {
+            Message::Http(HttpMessage::Error(e)) => self.record(Some(e)),
+            Message::Http(_) => self.record(None),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum HttpMessage {
     Error(HttpError),
     Request(HttpRequest),
     Response(HttpResponse),
+
+    /// 从流式响应体中读取至多len字节，返回None表示已经读完
+    ReadChunkRequest(usize, usize),
+    ReadChunkResponse(Option<Bytes>),
+
+    /// 关闭流式响应体，释放连接
+    CloseStreamRequest(usize),
+    CloseStreamResponse,
+
+    /// 网络连通状态，状态变化时广播到TopicName::Connectivity
+    Connectivity(ConnectivityState),
+    GetConnectivityRequest,
 }
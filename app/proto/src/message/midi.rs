@@ -11,6 +11,14 @@ pub enum MidiError {
 pub enum MidiMessage {
     Error(MidiError),
     PlayRequest(Bytes),
+    /// 播放音乐存储中的文件，由播放器按需读取，避免整个文件经过消息传递
+    PlayMusicRequest(String),
     PlayResponse(bool),
     Off,
+    /// 由设备下载音乐并保存到音乐存储，文件名相同时替换
+    DownloadRequest {
+        filename: String,
+        url: String,
+    },
+    DownloadResponse,
 }
@@ -8,6 +8,8 @@ pub enum RoutePage {
     Menu,
     Weather,
     Music,
+    AirQuality,
+    Indoor,
 }
 
 impl RoutePage {
@@ -18,6 +20,8 @@ impl RoutePage {
             RoutePage::Menu => NodeName::MenuPage,
             RoutePage::Weather => NodeName::WeatherPage,
             RoutePage::Music => NodeName::MusicPage,
+            RoutePage::AirQuality => NodeName::AirQualityPage,
+            RoutePage::Indoor => NodeName::IndoorPage,
         }
     }
 }
@@ -0,0 +1,44 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{NodeName, StorageError};
+
+/// secret以设备密钥加密后保存在此命名空间下，只能在本设备上解密
+pub const SECRET_PREFIX: &str = "secret/";
+
+/// 敏感字符串，Debug输出时隐藏内容，避免出现在消息日志中
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(pub String);
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SecretError {
+    /// 请求的节点不是该secret的所属节点
+    PermissionDenied(NodeName),
+    StorageError(StorageError),
+    CryptoError(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SecretMessage {
+    Error(SecretError),
+
+    /// 读取secret，只有所属节点可以读取
+    GetRequest(String),
+    GetResponse(Option<Secret>),
+
+    /// 设置secret，设置为None表示删除
+    SetRequest(String, Option<Secret>),
+    SetResponse,
+
+    /// 解密导出所有secret，用于备份，无法解密的secret会被跳过
+    ExportRequest,
+    ExportResponse(Vec<(String, Secret)>),
+}
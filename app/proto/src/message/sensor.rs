@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+use time::serde::rfc3339;
+use time::OffsetDateTime;
+
+/// 室内温湿度传感器的一次读数
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SensorReading {
+    #[serde(with = "rfc3339")]
+    pub time: OffsetDateTime,
+    /// 摄氏度
+    pub temp: f32,
+    /// 相对湿度，百分比数值
+    pub humidity: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SensorMessage {
+    /// 传感器驱动的新读数，广播到TopicName::Sensor
+    ReadingBroadcast(SensorReading),
+
+    // 最近一次读数，尚无读数时为None
+    GetLatestRequest,
+    GetLatestResponse(Option<SensorReading>),
+
+    // 按采样间隔保存的历史读数，按时间升序
+    GetHistoryRequest,
+    GetHistoryResponse(Vec<SensorReading>),
+}
@@ -8,9 +8,51 @@ use super::Bytes;
 pub enum StorageError {
     IOError(String),
     TypeError(String),
+    /// 写入后会超出命名空间的配额
+    QuotaExceeded {
+        namespace: String,
+        quota: usize,
+    },
     Other(String),
 }
 
+/// 命名空间配额，限制该前缀下所有值的总字节数，避免某类数据挤占其他数据的空间
+pub const STORAGE_QUOTAS: &[(&str, usize)] = &[("music/", 256 * 1024), ("http_cache/", 64 * 1024)];
+
+/// 检查将key的值从old_size字节改写为new_size字节后，是否会超出所在命名空间的配额，
+/// usage用于统计给定前缀下现有的总字节数
+pub fn check_storage_quota(
+    key: &str,
+    old_size: usize,
+    new_size: usize,
+    usage: impl FnOnce(&str) -> usize,
+) -> Result<(), StorageError> {
+    if let Some((namespace, quota)) = STORAGE_QUOTAS.iter().find(|(x, _)| key.starts_with(x)) {
+        if usage(namespace).saturating_sub(old_size) + new_size > *quota {
+            return Err(StorageError::QuotaExceeded {
+                namespace: namespace.to_string(),
+                quota: *quota,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// 某个前缀下的存储用量
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StorageUsage {
+    pub keys: usize,
+    pub bytes: usize,
+}
+
+/// 存储后端的容量，后端无法获知时total与free为None
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StorageCapacity {
+    pub total: Option<usize>,
+    pub used: usize,
+    pub free: Option<usize>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub enum StorageValue {
     None,
@@ -19,6 +61,15 @@ pub enum StorageValue {
 }
 
 impl StorageValue {
+    /// 值占用的字节数
+    pub fn size(&self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Bytes(bs) => bs.0.len(),
+            Self::String(x) => x.len(),
+        }
+    }
+
     pub fn as_str(self) -> Option<String> {
         match self {
             Self::String(x) => {
@@ -81,4 +132,40 @@ pub enum StorageMessage {
     /// 根据给定一个前缀，列举出所有的keys
     ListKeysRequest(String),
     ListKeysResponse(HashSet<String>),
+
+    /// 打开一个blob分块写入会话，返回会话句柄
+    BlobOpenRequest(String),
+    BlobOpenResponse(usize),
+
+    /// 向写入会话追加一个分块
+    BlobWriteRequest(usize, Bytes),
+    BlobWriteResponse,
+
+    /// 提交写入会话，提交后才会覆盖原有的值
+    BlobCommitRequest(usize),
+    BlobCommitResponse,
+
+    /// 放弃写入会话并删除已写入的分块，原有的值保持不变
+    BlobAbortRequest(usize),
+    BlobAbortResponse,
+
+    /// 读取blob的一个区间(key, offset, len)，越界部分会被截断
+    BlobReadRequest(String, usize, usize),
+    BlobReadResponse(Bytes),
+
+    /// 获取blob的总大小，不存在时返回None
+    BlobSizeRequest(String),
+    BlobSizeResponse(Option<usize>),
+
+    /// 统计给定前缀下的key数量与字节数
+    UsageRequest(String),
+    UsageResponse(StorageUsage),
+
+    /// 获取存储后端的容量与剩余空间
+    CapacityRequest,
+    CapacityResponse(StorageCapacity),
+
+    /// 删除给定前缀下的所有key，返回删除的数量
+    DeletePrefixRequest(String),
+    DeletePrefixResponse(usize),
 }
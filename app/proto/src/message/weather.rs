@@ -1,6 +1,6 @@
-use crate::StorageError;
+use crate::{SecretError, StorageError};
 
-use super::{HttpError, Rgb888Color};
+use super::{HttpError, Language, Rgb888Color};
 use serde::{Deserialize, Serialize};
 use time::serde::rfc3339;
 use time::OffsetDateTime;
@@ -10,25 +10,71 @@ pub struct NowWeather {
     /// 更新时间
     #[serde(with = "rfc3339")]
     pub updated_time: OffsetDateTime,
-    /// 当前温度，摄氏度
+    /// 当前温度，单位见显示设置
     pub temp: i8,
-    /// 天气图标
+    /// 天气图标，各数据源统一使用和风天气的图标代码
     pub icon: u16,
     /// 天气状况文字描述
     pub text: String,
     /// 相对湿度，百分比数值
     pub humidity: u8,
+    /// 观测时间，数据源会缓存数据时与更新时间不同，界面上优先显示
+    #[serde(default, with = "rfc3339::option")]
+    pub obs_time: Option<OffsetDateTime>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WeatherError {
     StorageError(StorageError),
+    SecretError(SecretError),
     SerdeError(String),
     HttpError(HttpError),
     ApiError(u16),
     MissingFieldError(String),
     MissingKey,
     MissingLocation,
+    /// 网络不可用且没有可用的缓存
+    Offline,
+    /// 当前天气源不支持该查询
+    Unsupported,
+}
+
+impl From<HttpError> for WeatherError {
+    fn from(e: HttpError) -> Self {
+        if e.is_network() {
+            Self::Offline
+        } else {
+            Self::HttpError(e)
+        }
+    }
+}
+
+/// 天气数据源，保存在`weather/provider`，未设置时使用和风天气。
+/// 各数据源的位置id互不通用，切换后需重新设置位置
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum WeatherProviderConfig {
+    /// 和风天气，需要设置key
+    #[default]
+    QWeather,
+    /// Open-Meteo，不需要key，位置id为GeoNames id
+    OpenMeteo,
+    /// 自建的server服务地址，如`http://192.168.1.2:3000/api`，位置id与和风天气相同，
+    /// 目前只提供实时天气与城市查询
+    Server(String),
+}
+
+impl WeatherProviderConfig {
+    /// 位置id是否通用，和风天气与自建server都使用和风天气的位置id
+    pub fn shares_location_ids(&self, other: &Self) -> bool {
+        matches!(self, Self::OpenMeteo) == matches!(other, Self::OpenMeteo)
+    }
+}
+
+/// 经纬度，东经与北纬为正
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Coord {
+    pub lat: f32,
+    pub lon: f32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,6 +82,9 @@ pub struct CityLookUpItem {
     pub name: String,
     pub id: String,
     pub country: String,
+    /// 数据源不返回经纬度时为None
+    #[serde(default)]
+    pub coord: Option<Coord>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,6 +132,72 @@ mod date_serde {
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HourlyWeather {
+    /// 预报时间，时区为所在位置的时区
+    #[serde(with = "rfc3339")]
+    pub time: OffsetDateTime,
+    /// 温度，单位见显示设置
+    pub temp: i8,
+    pub icon: u16,
+    pub text: String,
+    /// 降水概率，百分比数值，数据源不提供时为None
+    pub pop: Option<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HourlyForecast {
+    /// 更新时间
+    #[serde(with = "rfc3339")]
+    pub updated_time: OffsetDateTime,
+    /// 未来24小时
+    pub hourly: Vec<HourlyWeather>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeatherWarning {
+    /// 预警的唯一标识，预警更新时不变
+    pub id: String,
+    pub title: String,
+    /// 预警类型，如Rainstorm、Typhoon
+    pub type_name: String,
+    pub text: String,
+    /// 预警等级的颜色
+    pub color: Rgb888Color,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeatherWarnings {
+    #[serde(with = "rfc3339")]
+    pub updated_time: OffsetDateTime,
+    /// 生效中的预警，已解除的不包含在内
+    pub warnings: Vec<WeatherWarning>,
+}
+
+/// 单项污染物的浓度与分指数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pollutant {
+    /// pm2p5、pm10、o3、no2、so2或co
+    pub code: String,
+    /// 显示名称，如PM2.5
+    pub name: String,
+    pub concentration: f32,
+    /// 浓度单位，如μg/m3
+    pub unit: String,
+    /// 按数据源的默认AQI标准计算，部分数据源不提供
+    pub sub_index: Option<u16>,
+}
+
+/// 空气质量对健康的影响与建议
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AirQualityHealth {
+    pub effect: String,
+    /// 对一般人群的建议
+    pub general: String,
+    /// 对敏感人群的建议
+    pub sensitive: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NowAirQuality {
     #[serde(with = "rfc3339")]
@@ -90,39 +205,200 @@ pub struct NowAirQuality {
     pub value: u16,
     pub category: String,
     pub color: Rgb888Color,
+    /// 首要污染物的code，空气质量为优时没有
+    #[serde(default)]
+    pub primary_pollutant: Option<String>,
+    #[serde(default)]
+    pub pollutants: Vec<Pollutant>,
+    #[serde(default)]
+    pub health: Option<AirQualityHealth>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AirQualityOneDay {
+    #[serde(with = "date_serde")]
+    pub date: time::Date,
+    pub value: u16,
+    pub category: String,
+    pub color: Rgb888Color,
+    pub primary_pollutant: Option<String>,
+}
+
+/// 逐日AQI预报，第一项为今天
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AirQualityForecast {
+    #[serde(with = "rfc3339")]
+    pub updated_time: OffsetDateTime,
+    pub daily: Vec<AirQualityOneDay>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Location {
     pub location_id: u32,
     pub location: String,
+    /// 用于本地计算日出日落等，旧版保存的位置没有经纬度，首次使用时通过数据源查询
+    #[serde(default)]
+    pub coord: Option<Coord>,
+}
+
+/// 月相，按月龄划分为8个阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MoonPhase {
+    New,
+    WaxingCrescent,
+    FirstQuarter,
+    WaxingGibbous,
+    Full,
+    WaningGibbous,
+    LastQuarter,
+    WaningCrescent,
+}
+
+impl MoonPhase {
+    pub fn name(&self, lang: Language) -> &'static str {
+        let (zh, en) = match self {
+            MoonPhase::New => ("新月", "New Moon"),
+            MoonPhase::WaxingCrescent => ("蛾眉月", "Waxing Crescent"),
+            MoonPhase::FirstQuarter => ("上弦月", "First Quarter"),
+            MoonPhase::WaxingGibbous => ("盈凸月", "Waxing Gibbous"),
+            MoonPhase::Full => ("满月", "Full Moon"),
+            MoonPhase::WaningGibbous => ("亏凸月", "Waning Gibbous"),
+            MoonPhase::LastQuarter => ("下弦月", "Last Quarter"),
+            MoonPhase::WaningCrescent => ("残月", "Waning Crescent"),
+        };
+        match lang {
+            Language::Zh => zh,
+            Language::En => en,
+        }
+    }
+}
+
+/// 二十四节气，太阳黄经为`self.0 * 15`度，0为春分
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SolarTerm(pub u8);
+
+impl SolarTerm {
+    const NAMES: [(&'static str, &'static str); 24] = [
+        ("春分", "Spring Equinox"),
+        ("清明", "Clear and Bright"),
+        ("谷雨", "Grain Rain"),
+        ("立夏", "Start of Summer"),
+        ("小满", "Grain Buds"),
+        ("芒种", "Grain in Ear"),
+        ("夏至", "Summer Solstice"),
+        ("小暑", "Minor Heat"),
+        ("大暑", "Major Heat"),
+        ("立秋", "Start of Autumn"),
+        ("处暑", "End of Heat"),
+        ("白露", "White Dew"),
+        ("秋分", "Autumn Equinox"),
+        ("寒露", "Cold Dew"),
+        ("霜降", "Frost's Descent"),
+        ("立冬", "Start of Winter"),
+        ("小雪", "Minor Snow"),
+        ("大雪", "Major Snow"),
+        ("冬至", "Winter Solstice"),
+        ("小寒", "Minor Cold"),
+        ("大寒", "Major Cold"),
+        ("立春", "Start of Spring"),
+        ("雨水", "Rain Water"),
+        ("惊蛰", "Awakening of Insects"),
+    ];
+
+    pub fn name(&self, lang: Language) -> &'static str {
+        let (zh, en) = Self::NAMES[self.0 as usize % 24];
+        match lang {
+            Language::Zh => zh,
+            Language::En => en,
+        }
+    }
+}
+
+/// 本地计算的天文数据，不需要联网
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Astronomy {
+    /// 日出时间，极昼或极夜时为None
+    #[serde(with = "rfc3339::option")]
+    pub sunrise: Option<OffsetDateTime>,
+    /// 日落时间，极昼或极夜时为None
+    #[serde(with = "rfc3339::option")]
+    pub sunset: Option<OffsetDateTime>,
+    /// 白昼时长，分钟
+    pub day_length: u16,
+    pub moon_phase: MoonPhase,
+    /// 月龄，天
+    pub moon_age: f32,
+    /// 月面被照亮的比例，百分比数值
+    pub moon_illumination: u8,
+    /// 当前所处的节气
+    pub solar_term: SolarTerm,
+    /// 当前节气的交节时间
+    #[serde(with = "rfc3339")]
+    pub solar_term_time: OffsetDateTime,
+    pub next_solar_term: SolarTerm,
+    #[serde(with = "rfc3339")]
+    pub next_solar_term_time: OffsetDateTime,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WeatherMessage {
     Error(WeatherError),
 
-    // 设置位置
+    // 设置首选位置
     SetLocationRequest(Location),
     SetLocationResponse,
 
-    // 获取位置
+    // 获取首选位置
     GetLocationRequest,
     GetLocationResponse(Location),
 
+    // 已保存的位置列表，第一项为首选位置
+    ListLocationsRequest,
+    ListLocationsResponse(Vec<Location>),
+
+    // 添加位置，已存在时更新名称
+    AddLocationRequest(Location),
+    AddLocationResponse,
+
+    // 删除位置及其缓存(location_id)
+    RemoveLocationRequest(u32),
+    RemoveLocationResponse,
+
+    // 调整位置顺序
+    MoveLocationRequest { location_id: u32, index: usize },
+    MoveLocationResponse,
+
+    // 以下查询的参数为location_id，None表示首选位置
+
     // 实时天气
-    GetNowWeatherRequest,
+    GetNowWeatherRequest(Option<u32>),
     GetNowWeatherResponse(NowWeather),
 
     // 天气预报
-    GetForecastWeatherRequest,
+    GetForecastWeatherRequest(Option<u32>),
     GetForecastWeatherResponse(ForecastWeather),
 
     // 城市查询
     CityLookUpRequest(String),
     CityLookUpResponse(Vec<CityLookUpItem>),
 
-    // 空气质量查询(location_id)
-    GetNowAirQualityRequest,
+    // 空气质量
+    GetNowAirQualityRequest(Option<u32>),
     GetNowAirQualityResponse(NowAirQuality),
+
+    // 逐日空气质量预报
+    GetAirQualityForecastRequest(Option<u32>),
+    GetAirQualityForecastResponse(AirQualityForecast),
+
+    // 逐小时预报
+    GetHourlyForecastRequest(Option<u32>),
+    GetHourlyForecastResponse(HourlyForecast),
+
+    // 灾害预警
+    GetWarningsRequest(Option<u32>),
+    GetWarningsResponse(WeatherWarnings),
+
+    // 日出日落、月相与节气，本地计算
+    GetAstronomyRequest(Option<u32>),
+    GetAstronomyResponse(Astronomy),
 }
@@ -1,12 +1,23 @@
 use std::net::Ipv4Addr;
 
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct WiFiStorageConfiguration {
     pub ssid: String,
     pub password: Option<String>,
 }
 
+impl fmt::Debug for WiFiStorageConfiguration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WiFiStorageConfiguration")
+            .field("ssid", &self.ssid)
+            .field("password", &self.password.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetIpInfo {
     pub ip: Ipv4Addr,
@@ -16,6 +16,10 @@ pub enum NodeName {
     Router,
     // 本地存储
     Storage,
+    // 存储布局迁移
+    StorageMigration,
+    // 加密存储的敏感信息
+    Secret,
     // WiFi
     WiFi,
     // 蜂鸣器
@@ -40,6 +44,12 @@ pub enum NodeName {
     WeatherPage,
     // 音乐播放器
     MusicPage,
+    // 空气质量详情页
+    AirQualityPage,
+    // 室内温湿度，保存最近读数与历史
+    Sensor,
+    // 室内温湿度历史页
+    IndoorPage,
     // 其他扩展节点
     Other(String),
 }
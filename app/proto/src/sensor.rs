@@ -0,0 +1,58 @@
+//! 室内温湿度的历史记录，容量固定，写满后覆盖最旧的读数
+
+use std::{collections::VecDeque, time::Duration};
+
+use crate::SensorReading;
+
+/// 默认每10分钟采样一次，保存24小时
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(60 * 10);
+pub const HISTORY_CAPACITY: usize = 24 * 6;
+
+pub struct SensorHistory {
+    buf: VecDeque<SensorReading>,
+    capacity: usize,
+    interval: Duration,
+}
+
+impl Default for SensorHistory {
+    fn default() -> Self {
+        Self::new(HISTORY_CAPACITY, SAMPLE_INTERVAL)
+    }
+}
+
+impl SensorHistory {
+    pub fn new(capacity: usize, interval: Duration) -> Self {
+        Self {
+            buf: VecDeque::with_capacity(capacity),
+            capacity,
+            interval,
+        }
+    }
+
+    /// 距上次采样不足采样间隔时丢弃，返回是否已保存
+    pub fn sample(&mut self, reading: SensorReading) -> bool {
+        if let Some(last) = self.buf.back() {
+            if reading.time - last.time < self.interval {
+                return false;
+            }
+        }
+        if self.buf.len() == self.capacity {
+            self.buf.pop_front();
+        }
+        self.buf.push_back(reading);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// 按时间升序
+    pub fn to_vec(&self) -> Vec<SensorReading> {
+        self.buf.iter().copied().collect()
+    }
+}
@@ -1,3 +1,6 @@
+mod backup;
+mod http_cache;
+mod migration;
 mod music;
 mod system;
 mod useralarm;
@@ -5,9 +8,52 @@ mod weather;
 mod wifi;
 
 pub use {
-    music::MusicStorage, system::SystemStorage, useralarm::UserAlarmStorage,
-    weather::WeatherStorage, wifi::WiFiStorage,
+    backup::{BackupArchive, BackupEntry, StorageBackup},
+    http_cache::{
+        HttpCache, HttpCacheAction, HttpCacheEntry, HttpCacheLayer, HTTP_CACHE_HEADER,
+        HTTP_CACHE_PREFIX,
+    },
+    migration::{
+        latest_schema_version, Migration, StorageMigrator, BROKEN_SUFFIX, MIGRATIONS,
+        SCHEMA_VERSION_KEY,
+    },
+    music::MusicStorage,
+    system::SystemStorage,
+    useralarm::UserAlarmStorage,
+    weather::WeatherStorage,
+    wifi::WiFiStorage,
 };
 
-use crate::StorageError;
+use crate::{ipc::StorageClient, StorageError};
 type Result<T> = std::result::Result<T, StorageError>;
+
+/// 分块读写blob时单个分块的大小，需小于NVS单个blob的限制
+const BLOB_CHUNK_SIZE: usize = 4096;
+
+/// 分块写入一个完整的blob
+fn write_blob(stg: &StorageClient, key: String, data: &[u8]) -> Result<()> {
+    let handle = stg.blob_open(key)?;
+    for chunk in data.chunks(BLOB_CHUNK_SIZE) {
+        if let Err(e) = stg.blob_write(handle, chunk.to_vec()) {
+            let _ = stg.blob_abort(handle);
+            return Err(e);
+        }
+    }
+    stg.blob_commit(handle)
+}
+
+/// 分块读取一个完整的blob，不存在时返回None
+fn read_blob(stg: &StorageClient, key: String) -> Result<Option<Vec<u8>>> {
+    let Some(size) = stg.blob_size(key.clone())? else {
+        return Ok(None);
+    };
+    let mut data = Vec::with_capacity(size);
+    while data.len() < size {
+        let chunk = stg.blob_read(key.clone(), data.len(), BLOB_CHUNK_SIZE)?;
+        if chunk.is_empty() {
+            break;
+        }
+        data.extend(chunk);
+    }
+    Ok(Some(data))
+}
@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::{http_cache::HTTP_CACHE_PREFIX, read_blob, write_blob, Result};
+use crate::{
+    gateway::GATEWAY_AUTH_PREFIX,
+    ipc::{SecretClient, StorageClient},
+    Bytes, Secret, StorageError, StorageValue, SECRET_PREFIX,
+};
+
+/// 备份文件格式版本
+const ARCHIVE_VERSION: u32 = 1;
+
+/// 与设备绑定的数据，不按原值导出也不恢复：网关令牌只对本设备有效且不允许远程读取，
+/// secret以设备密钥加密，在其他设备上无法解密，需解密后单独导出，HTTP缓存可以随时丢弃
+const DEVICE_BOUND_PREFIXES: [&str; 3] = [GATEWAY_AUTH_PREFIX, SECRET_PREFIX, HTTP_CACHE_PREFIX];
+
+fn is_device_bound(key: &str) -> bool {
+    DEVICE_BOUND_PREFIXES.iter().any(|x| key.starts_with(x))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BackupEntry {
+    String(String),
+    Bytes(Bytes),
+}
+
+/// 存储的完整快照，只记录逻辑key与值，与具体存储后端无关
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupArchive {
+    pub version: u32,
+    pub entries: Vec<(String, BackupEntry)>,
+    /// 解密后的secret(WiFi密码、天气API key等)，明文保存，只在明确要求时导出
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<(String, Secret)>,
+}
+
+pub struct StorageBackup(pub StorageClient);
+
+impl StorageBackup {
+    /// 导出除网关令牌、secret与HTTP缓存以外的所有key，blob按分块逐页读取，
+    /// 避免单条消息过大。WiFi密码与天气API key保存在secret中，见dump_with_secrets
+    pub fn dump(&self) -> Result<BackupArchive> {
+        let mut keys = self
+            .0
+            .list("".into())?
+            .into_iter()
+            .filter(|x| !is_device_bound(x))
+            .collect::<Vec<_>>();
+        keys.sort();
+        let mut entries = Vec::with_capacity(keys.len());
+        for key in keys.into_iter() {
+            if let Some(data) = read_blob(&self.0, key.clone())? {
+                entries.push((key, BackupEntry::Bytes(Bytes(data))));
+                continue;
+            }
+            match self.0.get(key.clone())? {
+                StorageValue::String(x) => entries.push((key, BackupEntry::String(x))),
+                StorageValue::Bytes(bs) => entries.push((key, BackupEntry::Bytes(bs))),
+                StorageValue::None => {}
+            }
+        }
+        Ok(BackupArchive {
+            version: ARCHIVE_VERSION,
+            entries,
+            secrets: Vec::new(),
+        })
+    }
+
+    /// 同dump，并附带解密后的secret，恢复时以目标设备的密钥重新加密
+    pub fn dump_with_secrets(&self) -> Result<BackupArchive> {
+        let mut archive = self.dump()?;
+        archive.secrets = SecretClient(self.0 .0.clone())
+            .export()
+            .map_err(|e| StorageError::Other(format!("export secrets error: {e:?}")))?;
+        Ok(archive)
+    }
+
+    /// 写回快照中的所有key，快照中不存在的key保持不变，见restore_clean
+    pub fn restore(&self, archive: BackupArchive) -> Result<()> {
+        if archive.version != ARCHIVE_VERSION {
+            return Err(StorageError::Other(format!(
+                "unsupported backup version {}",
+                archive.version
+            )));
+        }
+        for (key, entry) in archive.entries.into_iter() {
+            // 旧版备份可能包含与设备绑定的数据
+            if is_device_bound(&key) {
+                continue;
+            }
+            match entry {
+                BackupEntry::String(x) => self.0.set(key, StorageValue::String(x))?,
+                BackupEntry::Bytes(bs) => write_blob(&self.0, key, &bs.0)?,
+            }
+        }
+        let secrets = SecretClient(self.0 .0.clone());
+        for (name, value) in archive.secrets.into_iter() {
+            secrets
+                .set(name.clone(), Some(value))
+                .map_err(|e| StorageError::Other(format!("restore secret {name} error: {e:?}")))?;
+        }
+        Ok(())
+    }
+
+    /// 写回快照后删除快照中不存在的key，使设备与快照一致，与设备绑定的数据除外
+    pub fn restore_clean(&self, archive: BackupArchive) -> Result<()> {
+        let keep = archive
+            .entries
+            .iter()
+            .map(|(k, _)| k.clone())
+            .collect::<HashSet<_>>();
+        self.restore(archive)?;
+        for key in self.0.list("".into())? {
+            if !keep.contains(&key) && !is_device_bound(&key) {
+                self.0.set(key, StorageValue::None)?;
+            }
+        }
+        Ok(())
+    }
+}
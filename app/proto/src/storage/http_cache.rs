@@ -0,0 +1,268 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{
+    ipc::StorageClient, normalize_url, Bytes, HttpBody, HttpError, HttpMessage, HttpRequest,
+    HttpRequestMethod, HttpResponse, Message, StorageError, StorageValue,
+};
+
+/// 缓存可以随时丢弃，不会导出到备份
+pub const HTTP_CACHE_PREFIX: &str = "http_cache/";
+
+/// 缓存命中方式，通过响应头x-cache告知调用方
+pub const HTTP_CACHE_HEADER: &str = "x-cache";
+
+/// 一条缓存的响应，url已去掉API key等敏感参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpCacheEntry {
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Bytes,
+    /// 写入或最近一次重新验证的时间，unix时间戳秒
+    pub stored_at: i64,
+    /// Cache-Control: max-age，没有时为0，即每次使用前都需要重新验证
+    pub max_age: u64,
+    /// Cache-Control: no-cache
+    pub no_cache: bool,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl HttpCacheEntry {
+    /// 由一个成功的响应生成缓存条目，不可缓存时返回None
+    pub fn from_response(url: &str, resp: &HttpResponse, now: i64) -> Option<Self> {
+        let HttpBody::Bytes(body) = &resp.body else {
+            return None;
+        };
+        if resp.status != 200 {
+            return None;
+        }
+        let mut max_age = 0;
+        let mut no_cache = false;
+        for directive in resp.header("cache-control").unwrap_or_default().split(',') {
+            let directive = directive.trim().to_ascii_lowercase();
+            match directive.split_once('=') {
+                Some(("max-age", x)) => max_age = x.trim_matches('"').parse().unwrap_or(0),
+                _ if directive == "no-store" => return None,
+                _ if directive == "no-cache" => no_cache = true,
+                _ => {}
+            }
+        }
+        Some(Self {
+            url: normalize_url(url),
+            status: resp.status,
+            headers: resp.headers.clone(),
+            body: body.clone(),
+            stored_at: now,
+            max_age,
+            no_cache,
+            etag: resp.header("etag").map(Into::into),
+            last_modified: resp.header("last-modified").map(Into::into),
+        })
+    }
+
+    pub fn is_fresh(&self, now: i64) -> bool {
+        !self.no_cache && now.saturating_sub(self.stored_at) < self.max_age as i64
+    }
+
+    /// 重新验证请求需要附带的条件请求头
+    pub fn conditional_headers(&self) -> Vec<(String, String)> {
+        let mut ret = Vec::new();
+        if let Some(x) = &self.etag {
+            ret.push(("If-None-Match".into(), x.clone()));
+        }
+        if let Some(x) = &self.last_modified {
+            ret.push(("If-Modified-Since".into(), x.clone()));
+        }
+        ret
+    }
+
+    /// 转换为响应，kind为hit、revalidated或stale
+    pub fn to_response(&self, kind: &str) -> HttpResponse {
+        let mut headers = self.headers.clone();
+        headers.push((HTTP_CACHE_HEADER.into(), kind.into()));
+        HttpResponse {
+            status: self.status,
+            headers,
+            body: HttpBody::Bytes(self.body.clone()),
+        }
+    }
+}
+
+/// 通过存储服务持久化的HTTP缓存
+pub struct HttpCache(pub StorageClient);
+
+impl HttpCache {
+    fn key(url: &str) -> String {
+        // FNV-1a，URL可能超过存储后端对key长度的限制
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for b in url.bytes() {
+            hash ^= b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        format!("{HTTP_CACHE_PREFIX}{hash:016x}")
+    }
+
+    pub fn get(&self, url: &str) -> Result<Option<HttpCacheEntry>, StorageError> {
+        let url = normalize_url(url);
+        Ok(self
+            .0
+            .get(Self::key(&url))?
+            .as_str()
+            .and_then(|x| serde_json::from_str::<HttpCacheEntry>(&x).ok())
+            // 哈希冲突时视为未命中
+            .filter(|x| x.url == url))
+    }
+
+    pub fn put(&self, entry: &HttpCacheEntry) -> Result<(), StorageError> {
+        let key = Self::key(&entry.url);
+        let value = StorageValue::String(serde_json::to_string(entry).unwrap());
+        match self.0.set(key.clone(), value.clone()) {
+            // 缓存数据可以随时丢弃，超出配额时清空后重试一次
+            Err(StorageError::QuotaExceeded { .. }) => {
+                self.clear()?;
+                self.0.set(key, value)
+            }
+            r => r,
+        }
+    }
+
+    pub fn clear(&self) -> Result<usize, StorageError> {
+        self.0.delete_prefix(HTTP_CACHE_PREFIX.into())
+    }
+}
+
+/// HttpClient节点收到请求后的处理方式
+pub enum HttpCacheAction {
+    /// 直接以该消息作为结果
+    Respond(Message),
+    /// 发起网络请求，请求可能附带了条件请求头
+    Dispatch(HttpRequest),
+    /// 相同的请求正在进行中，等待其结果
+    Wait,
+}
+
+struct InFlight {
+    key: String,
+    url: String,
+    cache: bool,
+    stale: Option<HttpCacheEntry>,
+}
+
+/// HttpClient节点内的缓存与请求合并，与具体平台的网络实现无关。
+/// 节点收到请求时调用on_request，网络请求完成后调用on_response，
+/// 由on_response返回的每个(seq, 结果)都需要async_ready
+#[derive(Default)]
+pub struct HttpCacheLayer {
+    // 进行中的请求标识 -> 等待同一结果的其他seq
+    waiters: RefCell<HashMap<String, Vec<usize>>>,
+    // 实际发起网络请求的seq -> 请求信息
+    inflight: RefCell<HashMap<usize, InFlight>>,
+}
+
+impl HttpCacheLayer {
+    fn now() -> i64 {
+        OffsetDateTime::now_utc().unix_timestamp()
+    }
+
+    /// 只有不带请求体的GET请求可以合并，请求头不同视为不同的请求
+    fn coalesce_key(req: &HttpRequest) -> Option<String> {
+        if !matches!(req.method, HttpRequestMethod::Get) || req.stream || req.body.is_some() {
+            return None;
+        }
+        let mut key = req.url.clone();
+        for (k, v) in req.headers.iter() {
+            key.push_str(&format!("\n{}:{v}", k.to_ascii_lowercase()));
+        }
+        Some(key)
+    }
+
+    pub fn on_request(
+        &self,
+        stg: &StorageClient,
+        seq: usize,
+        mut req: HttpRequest,
+    ) -> HttpCacheAction {
+        let Some(key) = Self::coalesce_key(&req) else {
+            return HttpCacheAction::Dispatch(req);
+        };
+        if let Some(x) = self.waiters.borrow_mut().get_mut(&key) {
+            x.push(seq);
+            return HttpCacheAction::Wait;
+        }
+        let mut stale = None;
+        if req.cache {
+            // 存储不可用时当作未命中，不影响请求本身
+            if let Ok(Some(entry)) = HttpCache(stg.clone()).get(&req.url) {
+                if entry.is_fresh(Self::now()) {
+                    return HttpCacheAction::Respond(Message::Http(HttpMessage::Response(
+                        entry.to_response("hit"),
+                    )));
+                }
+                req.headers.extend(entry.conditional_headers());
+                stale = Some(entry);
+            }
+        }
+        self.waiters.borrow_mut().insert(key.clone(), Vec::new());
+        self.inflight.borrow_mut().insert(
+            seq,
+            InFlight {
+                key,
+                url: req.url.clone(),
+                cache: req.cache,
+                stale,
+            },
+        );
+        HttpCacheAction::Dispatch(req)
+    }
+
+    pub fn on_response(
+        &self,
+        stg: &StorageClient,
+        seq: usize,
+        msg: Message,
+    ) -> Vec<(usize, Message)> {
+        let Some(x) = self.inflight.borrow_mut().remove(&seq) else {
+            return vec![(seq, msg)];
+        };
+        let waiters = self.waiters.borrow_mut().remove(&x.key).unwrap_or_default();
+        let msg = if x.cache {
+            Self::update(stg, x, msg)
+        } else {
+            msg
+        };
+        std::iter::once(seq)
+            .chain(waiters)
+            .map(|seq| (seq, msg.clone()))
+            .collect()
+    }
+
+    fn update(stg: &StorageClient, x: InFlight, msg: Message) -> Message {
+        let cache = HttpCache(stg.clone());
+        let now = Self::now();
+        match (msg, x.stale) {
+            (Message::Http(HttpMessage::Response(resp)), _) => {
+                if let Some(entry) = HttpCacheEntry::from_response(&x.url, &resp, now) {
+                    let _ = cache.put(&entry);
+                }
+                Message::Http(HttpMessage::Response(resp))
+            }
+            // 304响应体为空，使用缓存的内容并刷新有效期
+            (
+                Message::Http(HttpMessage::Error(HttpError::Status { status: 304, .. })),
+                Some(mut entry),
+            ) => {
+                entry.stored_at = now;
+                let _ = cache.put(&entry);
+                Message::Http(HttpMessage::Response(entry.to_response("revalidated")))
+            }
+            (Message::Http(HttpMessage::Error(e)), Some(entry)) if e.is_retryable() => {
+                Message::Http(HttpMessage::Response(entry.to_response("stale")))
+            }
+            (msg, _) => msg,
+        }
+    }
+}
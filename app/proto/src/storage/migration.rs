@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+
+use log::warn;
+use serde::de::DeserializeOwned;
+
+use super::{read_blob, write_blob, Result};
+use crate::{ipc::StorageClient, Location, StorageError, StorageValue, UserAlarmBody};
+
+/// 记录当前存储布局版本的key，不存在时视为版本0(未引入版本号之前的布局)
+pub const SCHEMA_VERSION_KEY: &str = "schema/version";
+
+/// 一次存储布局迁移，将数据从version - 1升级到version
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub run: fn(&StorageClient) -> Result<()>,
+}
+
+/// 迁移注册表，按版本号递增排列，新增迁移只能追加在末尾
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "useralarm: drop dangling ids, move unreadable and orphan alarms to .broken",
+        run: migrate_useralarm_v1,
+    },
+    Migration {
+        version: 2,
+        description: "music: rewrite legacy blobs as chunked blobs",
+        run: migrate_music_v2,
+    },
+    Migration {
+        version: 3,
+        description: "weather: move unreadable location to .broken",
+        run: migrate_weather_location_v3,
+    },
+    Migration {
+        version: 4,
+        description: "weather: move location into location list, delete global cache",
+        run: migrate_weather_locations_v4,
+    },
+];
+
+/// 迁移时无法使用的数据改存到`<key>.broken`，保留现场便于排查
+pub const BROKEN_SUFFIX: &str = ".broken";
+
+/// 最新的存储布局版本
+pub fn latest_schema_version() -> u32 {
+    MIGRATIONS.last().map(|x| x.version).unwrap_or(0)
+}
+
+pub struct StorageMigrator(pub StorageClient);
+
+impl StorageMigrator {
+    pub fn get_version(&self) -> Result<u32> {
+        match self.0.get(SCHEMA_VERSION_KEY.into())? {
+            StorageValue::None => Ok(0),
+            StorageValue::String(x) => x
+                .parse()
+                .map_err(|_| StorageError::TypeError(format!("invalid schema version {x:?}"))),
+            m => Err(StorageError::TypeError(format!(
+                "unexpected schema version {m:?}"
+            ))),
+        }
+    }
+
+    fn set_version(&self, version: u32) -> Result<()> {
+        self.0.set(
+            SCHEMA_VERSION_KEY.into(),
+            StorageValue::String(version.to_string()),
+        )
+    }
+
+    /// 依次执行所有高于当前版本的迁移，每完成一个迁移就写入版本号，
+    /// 中途失败时停留在最后一个成功的版本，下次启动会从失败处重试。
+    /// 返回迁移前后的版本号
+    pub fn migrate(&self) -> Result<(u32, u32)> {
+        let from = self.get_version()?;
+        let mut current = from;
+        for m in MIGRATIONS.iter().filter(|m| m.version > from) {
+            (m.run)(&self.0).map_err(|e| {
+                StorageError::Other(format!(
+                    "migration {} ({}) failed: {e:?}",
+                    m.version, m.description
+                ))
+            })?;
+            self.set_version(m.version)?;
+            current = m.version;
+        }
+        Ok((from, current))
+    }
+}
+
+/// 读取JSON字符串并反序列化，不存在或无法解析时返回None
+fn get_json<T: DeserializeOwned>(stg: &StorageClient, key: &str) -> Result<Option<T>> {
+    Ok(stg
+        .get(key.into())?
+        .as_str()
+        .and_then(|x| serde_json::from_str(&x).ok()))
+}
+
+fn set_json<T: serde::Serialize>(stg: &StorageClient, key: &str, value: &T) -> Result<()> {
+    stg.set(
+        key.into(),
+        StorageValue::String(serde_json::to_string(value).unwrap()),
+    )
+}
+
+/// 把key的值移动到`<key>.broken`，重复执行时不会覆盖已保存的值
+fn move_to_broken(stg: &StorageClient, key: String) -> Result<()> {
+    let value = stg.get(key.clone())?;
+    if !matches!(value, StorageValue::None) {
+        warn!("storage migration: move unusable {key} to {key}{BROKEN_SUFFIX}");
+        stg.set(format!("{key}{BROKEN_SUFFIX}"), value)?;
+    }
+    stg.set(key, StorageValue::None)
+}
+
+fn migrate_useralarm_v1(stg: &StorageClient) -> Result<()> {
+    let ids: Vec<usize> = get_json(stg, "useralarm/list")?.unwrap_or_default();
+    let mut kept = Vec::new();
+    for id in ids.into_iter() {
+        if kept.contains(&id) {
+            continue;
+        }
+        let key = format!("useralarm/data/{id}");
+        if get_json::<UserAlarmBody>(stg, &key)?.is_some() {
+            kept.push(id);
+        } else {
+            move_to_broken(stg, key)?;
+        }
+    }
+    // 不在列表中的数据永远不会被读取
+    for key in stg.list("useralarm/data/".into())?.into_iter() {
+        if key.ends_with(BROKEN_SUFFIX) {
+            continue;
+        }
+        let id = key["useralarm/data/".len()..].parse::<usize>().ok();
+        if !id.is_some_and(|x| kept.contains(&x)) {
+            move_to_broken(stg, key)?;
+        }
+    }
+    set_json(stg, "useralarm/list", &kept)
+}
+
+fn migrate_music_v2(stg: &StorageClient) -> Result<()> {
+    let list: Vec<String> = get_json(stg, "music/list")?.unwrap_or_default();
+    let mut kept = Vec::new();
+    let mut seen = HashSet::new();
+    for name in list.into_iter() {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        let key = format!("music/data/{name}");
+        // 旧版通过SetRequest整体写入，重新分块写入后才能按范围读取
+        if let Some(data) = read_blob(stg, key.clone())? {
+            write_blob(stg, key, &data)?;
+            kept.push(name);
+        }
+    }
+    set_json(stg, "music/list", &kept)
+}
+
+fn migrate_weather_location_v3(stg: &StorageClient) -> Result<()> {
+    match stg.get("weather/location".into())? {
+        StorageValue::None => Ok(()),
+        StorageValue::String(x) if serde_json::from_str::<Location>(&x).is_ok() => Ok(()),
+        // 无法解析的位置会导致天气查询失败，移走后回到未设置位置的状态
+        _ => move_to_broken(stg, "weather/location".into()),
+    }
+}
+
+fn migrate_weather_locations_v4(stg: &StorageClient) -> Result<()> {
+    // v3已移走无法解析的位置
+    if let Some(location) = get_json::<Location>(stg, "weather/location")? {
+        let mut locations: Vec<Location> = get_json(stg, "weather/locations")?.unwrap_or_default();
+        if !locations
+            .iter()
+            .any(|x| x.location_id == location.location_id)
+        {
+            locations.insert(0, location);
+        }
+        set_json(stg, "weather/locations", &locations)?;
+        stg.set("weather/location".into(), StorageValue::None)?;
+    }
+    // 旧版缓存不区分位置，缓存按位置存放在weather/cache/{location_id}/下。
+    // 缓存可以重新获取，直接删除
+    for key in stg.list("weather/cache/".into())?.into_iter() {
+        if !key["weather/cache/".len()..].contains('/') {
+            stg.set(key, StorageValue::None)?;
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,11 @@
-use crate::{ipc::StorageClient, Bytes, StorageValue};
+use std::{cell::RefCell, rc::Rc};
+
+use super::{read_blob, write_blob, BLOB_CHUNK_SIZE};
+use crate::{
+    ipc::{HttpClient, StorageClient},
+    HandleResult, HttpBody, HttpError, HttpRequest, Message, MidiError, MidiMessage, StorageError,
+    StorageValue,
+};
 
 pub struct MusicStorage(pub StorageClient);
 impl MusicStorage {
@@ -20,25 +27,167 @@ impl MusicStorage {
             .unwrap_or_default()
     }
 
+    pub fn get_size(&self, filename: String) -> Option<usize> {
+        self.0
+            .blob_size(format!("music/data/{filename}"))
+            .expect("get music size error")
+    }
+
+    pub fn read_range(&self, filename: String, offset: usize, len: usize) -> Vec<u8> {
+        self.0
+            .blob_read(format!("music/data/{filename}"), offset, len)
+            .expect("read music data error")
+    }
+
     pub fn get_data(&self, filename: String) -> Vec<u8> {
-        match self
-            .0
-            .get(format!("music/data/{filename}"))
+        read_blob(&self.0, format!("music/data/{filename}"))
+            .expect("read music data error")
             .expect("not found music data")
-        {
-            StorageValue::Bytes(bs) => bs.0,
-            m => panic!("unexpected storage value {m:?}"),
+    }
+
+    /// 只读取MIDI文件头与第一个音轨，组成单音轨的MIDI文件。
+    /// 蜂鸣器只播放第一个音轨，按区间读取，不需要把整个文件读入内存
+    pub fn read_first_track(&self, filename: String) -> Result<Vec<u8>, StorageError> {
+        let key = format!("music/data/{filename}");
+        let not_midi = || StorageError::TypeError(format!("{filename} is not a midi file"));
+        // 每个块为4字节类型、4字节大端长度与数据
+        let read_chunk_header = |offset: usize| -> Result<([u8; 4], usize), StorageError> {
+            let bs = self.0.blob_read(key.clone(), offset, 8)?;
+            if bs.len() < 8 {
+                return Err(not_midi());
+            }
+            let len = u32::from_be_bytes([bs[4], bs[5], bs[6], bs[7]]) as usize;
+            Ok(([bs[0], bs[1], bs[2], bs[3]], len))
+        };
+        let (typ, len) = read_chunk_header(0)?;
+        let mut header = self.0.blob_read(key.clone(), 8, len)?;
+        if &typ != b"MThd" || len < 6 || header.len() < len {
+            return Err(not_midi());
+        }
+        // 音轨数改为1
+        header[2..4].copy_from_slice(&1u16.to_be_bytes());
+        let mut ret = Vec::with_capacity(8 + len);
+        ret.extend_from_slice(b"MThd");
+        ret.extend_from_slice(&(len as u32).to_be_bytes());
+        ret.extend(header);
+
+        let mut offset = 8 + len;
+        loop {
+            let (typ, len) = read_chunk_header(offset)?;
+            offset += 8;
+            // 跳过未知类型的块
+            if &typ != b"MTrk" {
+                offset += len;
+                continue;
+            }
+            ret.reserve(8 + len);
+            ret.extend_from_slice(b"MTrk");
+            ret.extend_from_slice(&(len as u32).to_be_bytes());
+            let end = offset + len;
+            while offset < end {
+                let chunk =
+                    self.0
+                        .blob_read(key.clone(), offset, (end - offset).min(BLOB_CHUNK_SIZE))?;
+                if chunk.is_empty() {
+                    return Err(not_midi());
+                }
+                offset += chunk.len();
+                ret.extend(chunk);
+            }
+            return Ok(ret);
         }
     }
 
     pub fn upload(&self, filename: String, data: Vec<u8>) {
-        // 上传文件内容
-        self.0
-            .set(
-                format!("music/data/{filename}"),
-                StorageValue::Bytes(Bytes(data)),
-            )
-            .unwrap();
+        // 分块上传文件内容
+        write_blob(&self.0, format!("music/data/{filename}"), &data).unwrap();
+        self.add_to_list(filename);
+    }
+
+    /// 流式下载音乐并逐块写入存储，内存中最多只有一个分块
+    pub fn download(
+        &self,
+        filename: String,
+        url: String,
+        callback: Box<dyn FnOnce(Result<(), HttpError>)>,
+    ) {
+        let stg = self.0.clone();
+        let http = HttpClient(stg.0.clone());
+        http.clone().request(
+            HttpRequest::get(url).streaming(),
+            Box::new(move |r| {
+                let handle = match r.map(|x| x.body) {
+                    Ok(HttpBody::Stream(handle)) => handle,
+                    Ok(HttpBody::Bytes(bs)) => {
+                        MusicStorage(stg).upload(filename, bs.0);
+                        return callback(Ok(()));
+                    }
+                    Err(e) => return callback(Err(e)),
+                };
+                let key = format!("music/data/{filename}");
+                let blob = match stg.blob_open(key) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        http.close_stream(handle);
+                        return callback(Err(HttpError::Other(format!("storage: {e:?}"))));
+                    }
+                };
+                let write_error = Rc::new(RefCell::new(None));
+                http.for_each_chunk(
+                    handle,
+                    BLOB_CHUNK_SIZE,
+                    Box::new({
+                        let stg = stg.clone();
+                        let write_error = write_error.clone();
+                        move |chunk| match stg.blob_write(blob, chunk) {
+                            Ok(()) => true,
+                            Err(e) => {
+                                *write_error.borrow_mut() = Some(e);
+                                false
+                            }
+                        }
+                    }),
+                    Box::new(move |r| {
+                        // 放弃未提交的blob，旧数据不会被替换
+                        if let Some(e) = write_error.take() {
+                            let _ = stg.blob_abort(blob);
+                            return callback(Err(HttpError::Other(format!("storage: {e:?}"))));
+                        }
+                        if let Err(e) = r {
+                            let _ = stg.blob_abort(blob);
+                            return callback(Err(e));
+                        }
+                        if let Err(e) = stg.blob_commit(blob) {
+                            return callback(Err(HttpError::Other(format!("storage: {e:?}"))));
+                        }
+                        MusicStorage(stg).add_to_list(filename);
+                        callback(Ok(()))
+                    }),
+                );
+            }),
+        );
+    }
+
+    /// 处理MidiMessage::DownloadRequest，各平台的播放器共用
+    pub fn handle_download(&self, seq: usize, filename: String, url: String) -> HandleResult {
+        let ctx = self.0 .0.clone();
+        self.download(
+            filename,
+            url,
+            Box::new(move |r| {
+                ctx.async_ready(
+                    seq,
+                    Message::Midi(match r {
+                        Ok(()) => MidiMessage::DownloadResponse,
+                        Err(e) => MidiMessage::Error(MidiError::Other(format!("{e:?}"))),
+                    }),
+                )
+            }),
+        );
+        HandleResult::Pending
+    }
+
+    fn add_to_list(&self, filename: String) {
         // 更新元数据
         let mut list = self
             .get_list()
@@ -1,4 +1,4 @@
-use crate::{ipc::StorageClient, StorageValue};
+use crate::{ipc::StorageClient, DisplaySettings, StorageValue};
 
 pub struct SystemStorage(pub StorageClient);
 
@@ -21,4 +21,24 @@ impl SystemStorage {
             )
             .unwrap();
     }
+
+    pub fn get_display_settings(&self) -> DisplaySettings {
+        self.0
+            .get("system/display".into())
+            .ok()
+            .and_then(|x| x.as_str())
+            .and_then(|x| serde_json::from_str(&x).ok())
+            .unwrap_or_default()
+    }
+
+    /// 缓存的天气数据与单位和语言有关，修改后一并清除
+    pub fn set_display_settings(&self, settings: &DisplaySettings) {
+        self.0
+            .set(
+                "system/display".into(),
+                StorageValue::String(serde_json::to_string(settings).unwrap()),
+            )
+            .unwrap();
+        self.0.delete_prefix("weather/cache/".into()).unwrap();
+    }
 }
@@ -1,5 +1,5 @@
 use super::Result;
-use crate::{ipc::StorageClient, StorageValue, UserAlarmBody};
+use crate::{ipc::StorageClient, StorageError, StorageValue, UserAlarmBody};
 pub struct UserAlarmStorage(pub StorageClient);
 
 impl UserAlarmStorage {
@@ -21,8 +21,11 @@ impl UserAlarmStorage {
 
     pub fn get(&self, id: usize) -> Result<UserAlarmBody> {
         match self.0.get(format!("useralarm/data/{id}"))? {
-            StorageValue::String(x) => Ok(serde_json::from_str(&x).unwrap()),
-            m => panic!("unexpected storage value {m:?}"),
+            StorageValue::String(x) => serde_json::from_str(&x)
+                .map_err(|e| StorageError::TypeError(format!("useralarm {id}: {e}"))),
+            m => Err(StorageError::TypeError(format!(
+                "useralarm {id}: unexpected storage value {m:?}"
+            ))),
         }
     }
 
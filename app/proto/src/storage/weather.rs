@@ -1,28 +1,218 @@
-use crate::{ipc::StorageClient, Location, StorageValue, WeatherError};
+use log::warn;
+
+use crate::{
+    ipc::{SecretClient, StorageClient},
+    Coord, Location, Secret, SecretError, StorageValue, WeatherError, WeatherProviderConfig,
+};
 
 type Result<T> = std::result::Result<T, WeatherError>;
 
+/// 默认的首页位置轮播间隔
+const DEFAULT_ROTATION_INTERVAL: u32 = 30;
+
 pub struct WeatherStorage(pub StorageClient);
 
 impl WeatherStorage {
+    pub fn get_key(&self) -> Result<String> {
+        let secrets = SecretClient(self.0 .0.clone());
+        match secrets.get("weather/key".into()) {
+            Ok(Some(Secret(x))) => return Ok(x),
+            Ok(None) => {}
+            // 无法解密(例如从其他设备恢复的备份)时视为未设置
+            Err(SecretError::CryptoError(e)) => {
+                warn!("decrypt weather key error: {e}");
+                return Err(WeatherError::MissingKey);
+            }
+            Err(e) => return Err(WeatherError::SecretError(e)),
+        }
+        // 迁移旧版明文存储的key
+        let x = self
+            .0
+            .get("weather/key".into())
+            .map_err(WeatherError::StorageError)?
+            .as_str()
+            .ok_or(WeatherError::MissingKey)?;
+        self.set_key(x.clone())?;
+        self.0
+            .set("weather/key".into(), StorageValue::None)
+            .map_err(WeatherError::StorageError)?;
+        Ok(x)
+    }
+
     pub fn set_key(&self, key: String) -> Result<()> {
+        SecretClient(self.0 .0.clone())
+            .set("weather/key".into(), Some(Secret(key)))
+            .map_err(WeatherError::SecretError)?;
+        Ok(())
+    }
+
+    /// 已保存的位置，第一项为首选位置
+    pub fn get_locations(&self) -> Result<Vec<Location>> {
+        match self
+            .0
+            .get("weather/locations".into())
+            .map_err(WeatherError::StorageError)?
+            .as_str()
+        {
+            Some(x) => {
+                serde_json::from_str(&x).map_err(|e| WeatherError::SerdeError(format!("{e:?}")))
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn set_locations(&self, locations: &[Location]) -> Result<()> {
+        self.0
+            .set(
+                "weather/locations".into(),
+                StorageValue::String(serde_json::to_string(locations).unwrap()),
+            )
+            .map_err(WeatherError::StorageError)?;
+        Ok(())
+    }
+
+    /// 添加到列表末尾，已存在时更新名称，未提供经纬度时保留原有的
+    pub fn add_location(&self, location: Location) -> Result<()> {
+        let mut locations = self.get_locations()?;
+        match locations
+            .iter_mut()
+            .find(|x| x.location_id == location.location_id)
+        {
+            Some(x) => {
+                x.location = location.location;
+                x.coord = location.coord.or(x.coord);
+            }
+            None => locations.push(location),
+        }
+        self.set_locations(&locations)
+    }
+
+    /// 补充位置的经纬度，位置已被删除时忽略
+    pub fn set_coord(&self, location_id: u32, coord: Coord) -> Result<()> {
+        let mut locations = self.get_locations()?;
+        if let Some(x) = locations.iter_mut().find(|x| x.location_id == location_id) {
+            x.coord = Some(coord);
+            self.set_locations(&locations)?;
+        }
+        Ok(())
+    }
+
+    /// 删除位置及其缓存
+    pub fn remove_location(&self, location_id: u32) -> Result<()> {
+        let mut locations = self.get_locations()?;
+        let len = locations.len();
+        locations.retain(|x| x.location_id != location_id);
+        if locations.len() == len {
+            return Err(WeatherError::MissingLocation);
+        }
+        self.set_locations(&locations)?;
         self.0
-            .set("weather/key".into(), StorageValue::String(key))
+            .delete_prefix(format!("weather/cache/{location_id}/"))
             .map_err(WeatherError::StorageError)?;
         Ok(())
     }
 
-    pub fn set_location(&self, location_id: u32, location: String) -> Result<()> {
+    /// 移动到指定位置，超出范围时移动到末尾
+    pub fn move_location(&self, location_id: u32, index: usize) -> Result<()> {
+        let mut locations = self.get_locations()?;
+        let i = locations
+            .iter()
+            .position(|x| x.location_id == location_id)
+            .ok_or(WeatherError::MissingLocation)?;
+        let location = locations.remove(i);
+        locations.insert(index.min(locations.len()), location);
+        self.set_locations(&locations)
+    }
+
+    /// 设置首选位置
+    pub fn set_location(&self, location: Location) -> Result<()> {
+        let location_id = location.location_id;
+        self.add_location(location)?;
+        self.move_location(location_id, 0)
+    }
+
+    /// 缓存按位置区分，删除位置时一并清除
+    pub fn cache_key(location_id: u32, name: &str) -> String {
+        format!("weather/cache/{location_id}/{name}")
+    }
+
+    /// 首页轮播位置的间隔秒数，0表示只通过按键切换
+    pub fn get_rotation_interval(&self) -> Result<u32> {
+        match self
+            .0
+            .get("weather/rotation_interval".into())
+            .map_err(WeatherError::StorageError)?
+            .as_str()
+        {
+            Some(x) => x
+                .parse()
+                .map_err(|e| WeatherError::SerdeError(format!("{e:?}"))),
+            None => Ok(DEFAULT_ROTATION_INTERVAL),
+        }
+    }
+
+    pub fn set_rotation_interval(&self, secs: u32) -> Result<()> {
+        self.0
+            .set(
+                "weather/rotation_interval".into(),
+                StorageValue::String(secs.to_string()),
+            )
+            .map_err(WeatherError::StorageError)?;
+        Ok(())
+    }
+
+    pub fn get_provider(&self) -> Result<WeatherProviderConfig> {
+        match self
+            .0
+            .get("weather/provider".into())
+            .map_err(WeatherError::StorageError)?
+            .as_str()
+        {
+            Some(x) => {
+                serde_json::from_str(&x).map_err(|e| WeatherError::SerdeError(format!("{e:?}")))
+            }
+            None => Ok(WeatherProviderConfig::default()),
+        }
+    }
+
+    /// 切换数据源，同时清除其他数据源的缓存。
+    /// 位置id不通用时清空已保存的位置，需要重新选择
+    pub fn set_provider(&self, provider: WeatherProviderConfig) -> Result<()> {
+        if !self.get_provider()?.shares_location_ids(&provider) {
+            self.set_locations(&[])?;
+        }
+        self.0
+            .set(
+                "weather/provider".into(),
+                StorageValue::String(serde_json::to_string(&provider).unwrap()),
+            )
+            .map_err(WeatherError::StorageError)?;
+        self.0
+            .delete_prefix("weather/cache/".into())
+            .map_err(WeatherError::StorageError)?;
+        Ok(())
+    }
+
+    /// 已通知过的预警id
+    pub fn get_notified_warnings(&self) -> Result<Vec<String>> {
+        match self
+            .0
+            .get("weather/warnings_notified".into())
+            .map_err(WeatherError::StorageError)?
+            .as_str()
+        {
+            Some(x) => {
+                serde_json::from_str(&x).map_err(|e| WeatherError::SerdeError(format!("{e:?}")))
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn set_notified_warnings(&self, ids: &[String]) -> Result<()> {
         self.0
             .set(
-                "weather/location".into(),
-                StorageValue::String(
-                    serde_json::to_string(&Location {
-                        location_id,
-                        location,
-                    })
-                    .unwrap(),
-                ),
+                "weather/warnings_notified".into(),
+                StorageValue::String(serde_json::to_string(ids).unwrap()),
             )
             .map_err(WeatherError::StorageError)?;
         Ok(())
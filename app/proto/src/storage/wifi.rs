@@ -1,4 +1,7 @@
-use crate::{ipc::StorageClient, StorageValue};
+use crate::{
+    ipc::{SecretClient, StorageClient},
+    Secret, StorageValue,
+};
 
 pub struct WiFiStorage(pub StorageClient);
 
@@ -8,7 +11,20 @@ impl WiFiStorage {
     }
 
     pub fn get_password(&self) -> Option<String> {
-        self.0.get("wifi/password".into()).unwrap().as_str()
+        let secrets = SecretClient(self.0 .0.clone());
+        // 无法解密(例如从其他设备恢复的备份)时视为未设置
+        if let Ok(Some(Secret(x))) = secrets.get("wifi/password".into()) {
+            return Some(x);
+        }
+        // 迁移旧版明文存储的密码
+        let x = self.0.get("wifi/password".into()).unwrap().as_str()?;
+        secrets
+            .set("wifi/password".into(), Some(Secret(x.clone())))
+            .unwrap();
+        self.0
+            .set("wifi/password".into(), StorageValue::None)
+            .unwrap();
+        Some(x)
     }
 
     pub fn set_ssid(&self, val: Option<String>) {
@@ -24,14 +40,8 @@ impl WiFiStorage {
     }
 
     pub fn set_password(&self, val: Option<String>) {
-        self.0
-            .set(
-                "wifi/password".into(),
-                match val {
-                    Some(x) => StorageValue::String(x),
-                    None => StorageValue::None,
-                },
-            )
+        SecretClient(self.0 .0.clone())
+            .set("wifi/password".into(), val.map(Secret))
             .unwrap();
     }
 }
@@ -6,4 +6,8 @@ pub enum TopicName {
     Scheduler,
     Sntp,
     WiFi,
+    /// 网络连通状态变化，消息为HttpMessage::Connectivity
+    Connectivity,
+    /// 室内温湿度读数，消息为SensorMessage::ReadingBroadcast
+    Sensor,
 }
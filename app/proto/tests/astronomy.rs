@@ -0,0 +1,63 @@
+use proto::{astronomy, Coord, Language, MoonPhase, SolarTerm};
+use time::{macros::datetime, OffsetDateTime};
+
+const BEIJING: Coord = Coord {
+    lat: 39.9042,
+    lon: 116.4074,
+};
+
+fn assert_near(t: OffsetDateTime, expected: OffsetDateTime, minutes: i64) {
+    assert!(
+        (t - expected).whole_minutes().abs() <= minutes,
+        "{t} != {expected}"
+    );
+}
+
+#[test]
+fn sunrise_sunset() {
+    let now = datetime!(2024-06-21 12:00 +8);
+    let x = astronomy::astronomy(&BEIJING, now);
+    assert_near(x.sunrise.unwrap(), datetime!(2024-06-21 04:46 +8), 2);
+    assert_near(x.sunset.unwrap(), datetime!(2024-06-21 19:46 +8), 2);
+    assert!((898..=902).contains(&x.day_length));
+
+    // 特罗姆瑟的极昼与极夜
+    let tromso = Coord {
+        lat: 69.65,
+        lon: 18.96,
+    };
+    let x = astronomy::sun_times(&tromso, datetime!(2024-06-21 0:00 UTC).date());
+    assert_eq!(x, (None, None, 24 * 60));
+    let x = astronomy::sun_times(&tromso, datetime!(2024-12-21 0:00 UTC).date());
+    assert_eq!(x, (None, None, 0));
+}
+
+#[test]
+fn moon_phase() {
+    let (phase, _, illumination) = astronomy::moon_phase(datetime!(2024-01-25 17:54 UTC));
+    assert_eq!(phase, MoonPhase::Full);
+    assert!(illumination >= 98);
+    let (phase, age, illumination) = astronomy::moon_phase(datetime!(2024-02-09 22:59 UTC));
+    assert_eq!(phase, MoonPhase::New);
+    assert!(!(1.0..=28.5).contains(&age));
+    assert!(illumination <= 2);
+}
+
+#[test]
+fn solar_terms() {
+    let x = astronomy::astronomy(&BEIJING, datetime!(2024-06-25 08:00 +8));
+    assert_eq!(x.solar_term.name(Language::Zh), "夏至");
+    assert_near(x.solar_term_time, datetime!(2024-06-21 04:51 +8), 15);
+    assert_eq!(x.next_solar_term.name(Language::Zh), "小暑");
+    assert_near(x.next_solar_term_time, datetime!(2024-07-06 22:20 +8), 15);
+
+    // 跨越黄经0度
+    let x = astronomy::astronomy(&BEIJING, datetime!(2024-03-10 08:00 +8));
+    assert_eq!(x.solar_term.name(Language::Zh), "惊蛰");
+    assert_eq!(x.next_solar_term, SolarTerm(0));
+    assert_near(x.next_solar_term_time, datetime!(2024-03-20 11:06 +8), 15);
+
+    let x = astronomy::astronomy(&BEIJING, datetime!(2024-12-31 08:00 +8));
+    assert_eq!(x.solar_term.name(Language::Zh), "冬至");
+    assert_near(x.solar_term_time, datetime!(2024-12-21 17:21 +8), 15);
+}
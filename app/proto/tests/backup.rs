@@ -0,0 +1,94 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::MemoryStorage;
+use proto::{
+    ipc::{SecretClient, StorageClient},
+    storage::{BackupEntry, StorageBackup},
+    Bytes, Secret, StorageValue,
+};
+
+fn set(stg: &StorageClient, key: &str, value: &str) {
+    stg.set(key.into(), StorageValue::String(value.into()))
+        .unwrap();
+}
+
+#[test]
+fn device_bound_keys_are_skipped_and_clean_restore_removes_extras() {
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    set(&stg, "system/display", "{}");
+    set(&stg, "gateway/auth/0001", "token");
+    set(&stg, "http_cache/0001", "cached");
+    stg.set(
+        "secret/wifi/password".into(),
+        StorageValue::Bytes(Bytes(vec![1, 2, 3])),
+    )
+    .unwrap();
+
+    let mut archive = StorageBackup(stg.clone()).dump().unwrap();
+    let keys = archive
+        .entries
+        .iter()
+        .map(|(k, _)| k.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec!["system/display"]);
+
+    // 恢复时同样忽略旧版备份中的secret
+    archive.entries.push((
+        "secret/weather/key".into(),
+        BackupEntry::Bytes(Bytes(vec![4, 5, 6])),
+    ));
+    let other = StorageClient(Rc::new(MemoryStorage::default()));
+    set(&other, "wifi/ssid", "home");
+    set(&other, "secret/wifi/password", "device bound");
+    StorageBackup(other.clone())
+        .restore(archive.clone())
+        .unwrap();
+    assert_eq!(other.list("".into()).unwrap().len(), 3);
+    assert!(other
+        .get("secret/weather/key".into())
+        .unwrap()
+        .as_str()
+        .is_none());
+
+    StorageBackup(other.clone()).restore_clean(archive).unwrap();
+    let mut keys = other
+        .list("".into())
+        .unwrap()
+        .into_iter()
+        .collect::<Vec<_>>();
+    keys.sort();
+    assert_eq!(keys, vec!["secret/wifi/password", "system/display"]);
+}
+
+#[test]
+fn secrets_are_exported_only_on_request() {
+    let device = Rc::new(MemoryStorage::default());
+    let stg = StorageClient(device.clone());
+    set(&stg, "wifi/ssid", "home");
+    SecretClient(device.clone())
+        .set("wifi/password".into(), Some(Secret("12345678".into())))
+        .unwrap();
+
+    let backup = StorageBackup(stg.clone());
+    let plain = serde_json::to_string(&backup.dump().unwrap()).unwrap();
+    assert!(!plain.contains("12345678"), "{plain}");
+    assert!(!plain.contains("secrets"), "{plain}");
+
+    let archive = backup.dump_with_secrets().unwrap();
+    assert_eq!(
+        archive.secrets,
+        vec![("wifi/password".to_string(), Secret("12345678".into()))]
+    );
+
+    // 新设备恢复后secret由其Secret节点重新保存
+    let other = Rc::new(MemoryStorage::default());
+    StorageBackup(StorageClient(other.clone()))
+        .restore_clean(archive)
+        .unwrap();
+    assert_eq!(
+        SecretClient(other.clone()).export().unwrap(),
+        vec![("wifi/password".to_string(), Secret("12345678".into()))]
+    );
+}
@@ -0,0 +1,157 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
+
+use proto::{
+    Bytes, Context, HandleResult, HttpMessage, Message, MessageCallbackOnce, NodeName, Secret,
+    SecretError, SecretMessage, StorageMessage, StorageValue, TopicName, WaitGroup,
+};
+
+pub type HttpHandler = Box<dyn FnMut(HttpMessage) -> HttpMessage>;
+
+/// 只实现存储与HttpClient节点的内存上下文
+#[derive(Default)]
+pub struct MemoryStorage {
+    data: RefCell<BTreeMap<String, StorageValue>>,
+    blob_sessions: RefCell<HashMap<usize, (String, Vec<u8>)>>,
+    /// 模拟Secret节点，不加密
+    pub secrets: RefCell<BTreeMap<String, Secret>>,
+    /// 设置后读取secret返回该错误
+    pub secret_error: RefCell<Option<SecretError>>,
+    /// 模拟HttpClient节点，异步调用会立即回调
+    pub http: RefCell<Option<HttpHandler>>,
+    /// 记录广播的消息，全局广播的话题为None
+    pub published: RefCell<Vec<(Option<TopicName>, Message)>>,
+    /// 记录异步完成的结果
+    pub ready: RefCell<Vec<(usize, Message)>>,
+    /// 记录异步未被处理的seq
+    pub discarded: RefCell<Vec<usize>>,
+}
+
+impl MemoryStorage {
+    fn handle(&self, sm: StorageMessage) -> StorageMessage {
+        let mut data = self.data.borrow_mut();
+        match sm {
+            StorageMessage::GetRequest(k) => {
+                StorageMessage::GetResponse(data.get(&k).cloned().unwrap_or(StorageValue::None))
+            }
+            StorageMessage::SetRequest(k, v) => {
+                match v {
+                    StorageValue::None => data.remove(&k),
+                    v => data.insert(k, v),
+                };
+                StorageMessage::SetResponse
+            }
+            StorageMessage::ListKeysRequest(prefix) => StorageMessage::ListKeysResponse(
+                data.keys()
+                    .filter(|x| x.starts_with(&prefix))
+                    .cloned()
+                    .collect(),
+            ),
+            StorageMessage::DeletePrefixRequest(prefix) => {
+                let before = data.len();
+                data.retain(|k, _| !k.starts_with(&prefix));
+                StorageMessage::DeletePrefixResponse(before - data.len())
+            }
+            StorageMessage::BlobOpenRequest(k) => {
+                let mut sessions = self.blob_sessions.borrow_mut();
+                let handle = sessions.len() + 1;
+                sessions.insert(handle, (k, Vec::new()));
+                StorageMessage::BlobOpenResponse(handle)
+            }
+            StorageMessage::BlobWriteRequest(handle, chunk) => {
+                let mut sessions = self.blob_sessions.borrow_mut();
+                sessions.get_mut(&handle).unwrap().1.extend(chunk.0);
+                StorageMessage::BlobWriteResponse
+            }
+            StorageMessage::BlobCommitRequest(handle) => {
+                let (k, buf) = self.blob_sessions.borrow_mut().remove(&handle).unwrap();
+                data.insert(k, StorageValue::Bytes(Bytes(buf)));
+                StorageMessage::BlobCommitResponse
+            }
+            StorageMessage::BlobAbortRequest(handle) => {
+                self.blob_sessions.borrow_mut().remove(&handle);
+                StorageMessage::BlobAbortResponse
+            }
+            StorageMessage::BlobReadRequest(k, offset, len) => match data.get(&k) {
+                Some(StorageValue::Bytes(bs)) => {
+                    let start = offset.min(bs.0.len());
+                    let end = offset.saturating_add(len).min(bs.0.len());
+                    StorageMessage::BlobReadResponse(Bytes(bs.0[start..end].to_vec()))
+                }
+                _ => StorageMessage::BlobReadResponse(Bytes(Vec::new())),
+            },
+            StorageMessage::BlobSizeRequest(k) => {
+                StorageMessage::BlobSizeResponse(match data.get(&k) {
+                    Some(StorageValue::Bytes(bs)) => Some(bs.0.len()),
+                    _ => None,
+                })
+            }
+            m => panic!("unexpected message {m:?}"),
+        }
+    }
+}
+
+impl Context for MemoryStorage {
+    fn broadcast_global(&self, msg: Message) {
+        self.published.borrow_mut().push((None, msg));
+    }
+    fn broadcast_topic(&self, topic: TopicName, msg: Message) {
+        self.published.borrow_mut().push((Some(topic), msg));
+    }
+    fn subscribe_topic(&self, _topic: TopicName) {}
+    fn unsubscribe_topic(&self, _topic: TopicName) {}
+    fn async_call(&self, node: NodeName, msg: Message, callback: MessageCallbackOnce) {
+        callback(self.sync_call(node, msg))
+    }
+    fn sync_call(&self, node: NodeName, msg: Message) -> HandleResult {
+        match (node, msg) {
+            (NodeName::Storage, Message::Storage(sm)) => {
+                HandleResult::Finish(Message::Storage(self.handle(sm)))
+            }
+            (NodeName::Secret, Message::Secret(sm)) => {
+                let mut secrets = self.secrets.borrow_mut();
+                HandleResult::Finish(Message::Secret(match sm {
+                    SecretMessage::GetRequest(k) => match self.secret_error.borrow().clone() {
+                        Some(e) => SecretMessage::Error(e),
+                        None => SecretMessage::GetResponse(secrets.get(&k).cloned()),
+                    },
+                    SecretMessage::SetRequest(k, Some(v)) => {
+                        secrets.insert(k, v);
+                        SecretMessage::SetResponse
+                    }
+                    SecretMessage::SetRequest(k, None) => {
+                        secrets.remove(&k);
+                        SecretMessage::SetResponse
+                    }
+                    SecretMessage::ExportRequest => SecretMessage::ExportResponse(
+                        secrets
+                            .iter()
+                            .map(|(k, v)| (k.clone(), v.clone()))
+                            .collect(),
+                    ),
+                    m => panic!("unexpected message {m:?}"),
+                }))
+            }
+            (NodeName::HttpClient, Message::Http(hm)) => {
+                let mut http = self.http.borrow_mut();
+                HandleResult::Finish(Message::Http(http.as_mut().unwrap()(hm)))
+            }
+            _ => HandleResult::Discard,
+        }
+    }
+    fn async_ready(&self, seq: usize, result: Message) {
+        self.ready.borrow_mut().push((seq, result));
+    }
+    fn async_discard(&self, seq: usize) {
+        self.discarded.borrow_mut().push(seq);
+    }
+    fn create_wait_group(&self) -> Rc<dyn WaitGroup> {
+        unimplemented!()
+    }
+    fn node_names(&self) -> Vec<NodeName> {
+        vec![NodeName::Storage, NodeName::HttpClient]
+    }
+}
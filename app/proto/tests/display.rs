@@ -0,0 +1,58 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::MemoryStorage;
+use proto::{
+    ipc::StorageClient, storage::SystemStorage, storage::WeatherStorage, ClockFormat,
+    DisplaySettings, Language, StorageValue, TempUnit,
+};
+use time::Weekday;
+
+#[test]
+fn format_time() {
+    let mut x = DisplaySettings::default();
+    assert_eq!(x.format_time(7, 5), "07:05");
+    assert_eq!(x.format_hour(0), "00");
+    assert_eq!(x.weekday(Weekday::Sunday), "Sun.");
+
+    x.clock = ClockFormat::H12;
+    assert_eq!(x.format_time(0, 30), "12:30 AM");
+    assert_eq!(x.format_time(12, 0), "12:00 PM");
+    assert_eq!(x.format_time_seconds(19, 5, 9), "7:05:09 PM");
+    assert_eq!(x.format_time_compact(19, 5), "7:05");
+    assert_eq!(x.format_hour(0), "12a");
+    assert_eq!(x.format_hour(13), "1p");
+
+    x.lang = Language::Zh;
+    assert_eq!(x.format_time(7, 5), "上午7:05");
+    assert_eq!(x.format_time(23, 59), "下午11:59");
+    assert_eq!(x.weekday(Weekday::Saturday), "周六");
+
+    assert_eq!(TempUnit::Fahrenheit.from_celsius(-40), -40);
+    assert_eq!(TempUnit::Fahrenheit.from_celsius(20), 68);
+    assert_eq!(x.format_temp(24.46), "24.5℃");
+    x.temp_unit = TempUnit::Fahrenheit;
+    assert_eq!(x.format_temp(25.0), "77.0℉");
+}
+
+#[test]
+fn display_settings_clear_weather_cache() {
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    let system = SystemStorage(stg.clone());
+    assert_eq!(system.get_display_settings(), DisplaySettings::default());
+
+    stg.set(
+        WeatherStorage::cache_key(2950159, "now_weather"),
+        StorageValue::String("{}".into()),
+    )
+    .unwrap();
+    let settings = DisplaySettings {
+        temp_unit: TempUnit::Fahrenheit,
+        clock: ClockFormat::H12,
+        lang: Language::Zh,
+    };
+    system.set_display_settings(&settings);
+    assert_eq!(system.get_display_settings(), settings);
+    assert!(stg.list("weather/cache/".into()).unwrap().is_empty());
+}
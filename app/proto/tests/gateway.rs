@@ -0,0 +1,591 @@
+mod common;
+
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use common::MemoryStorage;
+use proto::{
+    gateway::{
+        handle_gateway_request, serve_static, GatewayAuth, GatewayEvents, GatewayRequest,
+        GatewayResponse, ProxyEvent, ProxyTransport, RemoteNodeConfig, RemoteNodeProxy,
+        StaticAsset,
+    },
+    HandleResult, HttpMessage, HttpRequest, Message, MessageTo, MessageWithHeader, Node, NodeName,
+    StorageMessage, StorageValue, TopicName, WiFiMessage,
+};
+use serde_json::{json, Value};
+
+/// 固定的随机数，配对码为PAIRING_CODE
+fn random(bs: &mut [u8]) {
+    bs.fill(7);
+}
+
+const PAIRING_CODE: &str = "901063";
+
+struct Gateway {
+    ctx: Rc<MemoryStorage>,
+    auth: GatewayAuth,
+    token: Option<String>,
+}
+
+impl Gateway {
+    /// 已完成配对的网关
+    fn new() -> Self {
+        let mut gw = Self {
+            ctx: Rc::new(MemoryStorage::default()),
+            auth: GatewayAuth::new(random),
+            token: None,
+        };
+        assert_eq!(gw.request("POST", "/pair", Value::Null).0, 202);
+        let (status, body) = gw.request("POST", "/pair", json!({"code": PAIRING_CODE}));
+        assert_eq!(status, 200);
+        gw.token = body["token"].as_str().map(Into::into);
+        gw
+    }
+
+    fn request(&self, method: &str, path: &str, body: Value) -> (u16, Value) {
+        let ret: Rc<RefCell<Option<GatewayResponse>>> = Default::default();
+        handle_gateway_request(
+            self.ctx.clone(),
+            &self.auth,
+            GatewayRequest {
+                method: method.into(),
+                path: path.into(),
+                token: self.token.clone(),
+                body: serde_json::to_vec(&body).unwrap(),
+            },
+            Box::new({
+                let ret = ret.clone();
+                move |x| *ret.borrow_mut() = Some(x)
+            }),
+        );
+        let resp = ret.take().expect("gateway should respond");
+        (resp.status, serde_json::from_slice(&resp.body).unwrap())
+    }
+
+    /// 打开一个已认证的会话
+    fn open(&self, events: &GatewayEvents, id: usize, closed: bool) -> Frames {
+        let frames = open(events, id, closed);
+        let auth = json!({"Auth": self.token});
+        events.on_frame(self.ctx.clone(), &self.auth, id, &auth.to_string());
+        frames
+    }
+
+    fn frame(&self, events: &GatewayEvents, id: usize, frame: Value) {
+        events.on_frame(self.ctx.clone(), &self.auth, id, &frame.to_string());
+    }
+}
+
+#[test]
+fn list_nodes() {
+    let gw = Gateway::new();
+    let (status, body) = gw.request("GET", "/nodes", Value::Null);
+    assert_eq!(status, 200);
+    assert_eq!(body, json!(["HttpClient", "Storage"]));
+}
+
+#[test]
+fn call_node() {
+    let gw = Gateway::new();
+    let set = Message::Storage(StorageMessage::SetRequest(
+        "wifi/ssid".into(),
+        StorageValue::String("home".into()),
+    ));
+    let (status, _) = gw.request("POST", "/nodes/Storage", json!(set));
+    assert_eq!(status, 200);
+
+    let get = Message::Storage(StorageMessage::GetRequest("wifi/ssid".into()));
+    let (status, body) = gw.request("POST", "/nodes/Storage?sync=true", json!(get));
+    assert_eq!(status, 200);
+    assert_eq!(
+        body,
+        json!({"Storage": {"GetResponse": {"String": "home"}}})
+    );
+}
+
+#[test]
+fn errors_are_json() {
+    let gw = Gateway::new();
+    let cases = [
+        ("POST", "/nodes/Weather", json!("Empty"), 404),
+        ("POST", "/nodes/Storage", json!({"Unknown": 1}), 400),
+        ("POST", "/nodes/Storage", json!("Empty"), 422),
+        ("POST", "/topics/NoSuchTopic", json!("Empty"), 404),
+        ("DELETE", "/nodes", Value::Null, 405),
+        ("GET", "/no/such/path", Value::Null, 404),
+    ];
+    for (method, path, body, expected) in cases {
+        let (status, body) = gw.request(method, path, body);
+        assert_eq!(status, expected, "{method} {path}");
+        assert!(body["error"].is_string(), "{method} {path}: {body}");
+    }
+}
+
+#[test]
+fn publish_topic_and_legacy_envelope() {
+    let gw = Gateway::new();
+    let (status, _) = gw.request("POST", "/topics/WiFi", json!("Empty"));
+    assert_eq!(status, 202);
+
+    let (status, body) = gw.request("POST", "/", json!({"to": "Broadcast", "body": "Empty"}));
+    assert_eq!(status, 200);
+    assert_eq!(body, json!({"Finish": "Empty"}));
+}
+
+type Frames = Rc<RefCell<Vec<Value>>>;
+
+/// 打开一个会话，返回收到的帧，closed为true时模拟连接已断开
+fn open(events: &GatewayEvents, id: usize, closed: bool) -> Frames {
+    let frames: Frames = Default::default();
+    events.open(
+        id,
+        Box::new({
+            let frames = frames.clone();
+            move |x| {
+                frames.borrow_mut().push(serde_json::from_str(&x).unwrap());
+                !closed
+            }
+        }),
+    );
+    frames
+}
+
+fn message(to: MessageTo, body: Message) -> MessageWithHeader {
+    MessageWithHeader {
+        from: NodeName::WiFi,
+        to,
+        seq: 0,
+        body,
+    }
+}
+
+#[test]
+fn events_fan_out_to_watching_sessions() {
+    let gw = Gateway::new();
+    let events = GatewayEvents::default();
+    let wifi = gw.open(&events, 1, false);
+    let all = gw.open(&events, 2, false);
+    gw.frame(&events, 1, json!({"Subscribe": ["WiFi"]}));
+    gw.frame(&events, 2, json!({"WatchBroadcast": true}));
+
+    let connected = Message::WiFi(WiFiMessage::ConnectedBroadcast);
+    events.on_message(&message(
+        MessageTo::Topic(TopicName::WiFi),
+        connected.clone(),
+    ));
+    events.on_message(&message(MessageTo::Broadcast, Message::Empty));
+    events.on_message(&message(MessageTo::Point(NodeName::WiFi), Message::Empty));
+
+    assert_eq!(
+        *wifi.borrow(),
+        vec![json!({"Topic": {"topic": "WiFi", "from": "WiFi", "body": connected}})]
+    );
+    assert_eq!(
+        *all.borrow(),
+        vec![json!({"Broadcast": {"from": "WiFi", "body": "Empty"}})]
+    );
+
+    gw.frame(&events, 1, json!({"Unsubscribe": ["WiFi"]}));
+    events.on_message(&message(MessageTo::Topic(TopicName::WiFi), connected));
+    assert_eq!(wifi.borrow().len(), 1);
+}
+
+#[test]
+fn events_request_and_errors() {
+    let gw = Gateway::new();
+    let events = GatewayEvents::default();
+    let frames = gw.open(&events, 1, false);
+    let get = Message::Storage(StorageMessage::GetRequest("wifi/ssid".into()));
+    gw.frame(
+        &events,
+        1,
+        json!({"Request": {"id": 7, "to": "Storage", "body": get}}),
+    );
+    events.on_frame(gw.ctx.clone(), &gw.auth, 1, "not json");
+    gw.frame(&events, 1, json!({"Subscribe": ["Scheduler"]}));
+
+    let frames = frames.borrow();
+    assert_eq!(frames[0]["Response"]["id"], json!(7));
+    assert!(frames[0]["Response"]["result"]["Finish"]["Storage"].is_object());
+    assert!(frames[1]["Error"]["error"].is_string());
+    assert!(frames[2]["Error"]["error"].is_string());
+}
+
+#[test]
+fn events_drop_closed_sessions() {
+    let gw = Gateway::new();
+    let events = GatewayEvents::default();
+    let frames = gw.open(&events, 1, true);
+    gw.frame(&events, 1, json!({"WatchBroadcast": true}));
+    events.on_message(&message(MessageTo::Broadcast, Message::Empty));
+    events.on_message(&message(MessageTo::Broadcast, Message::Empty));
+    assert_eq!(frames.borrow().len(), 1);
+}
+
+#[test]
+fn static_assets() {
+    const ASSETS: &[StaticAsset] = &[
+        StaticAsset {
+            path: "/index.html",
+            content_type: "text/html",
+            gzip: b"index",
+        },
+        StaticAsset {
+            path: "/assets/app.js",
+            content_type: "text/javascript",
+            gzip: b"app",
+        },
+    ];
+    let get = |method: &str, path: &str| {
+        serve_static(
+            ASSETS,
+            &GatewayRequest {
+                method: method.into(),
+                path: path.into(),
+                token: None,
+                body: vec![],
+            },
+        )
+    };
+    for (path, body) in [
+        ("/", "index"),
+        ("/events?x=1", "index"),
+        ("/assets/app.js", "app"),
+    ] {
+        let resp = get("GET", path).unwrap();
+        assert_eq!((resp.status, resp.body.as_slice()), (200, body.as_bytes()));
+        assert!(resp
+            .headers
+            .contains(&("Content-Encoding".into(), "gzip".into())));
+    }
+    assert_eq!(get("GET", "/assets/none.js").unwrap().status, 404);
+    // 网关接口与非GET请求交给路由处理
+    assert!(get("GET", "/nodes").is_none());
+    assert!(get("GET", "/ws").is_none());
+    assert!(get("POST", "/").is_none());
+}
+
+#[test]
+fn requests_require_paired_token() {
+    let mut gw = Gateway::new();
+    let token = gw.token.take();
+    assert_eq!(gw.request("GET", "/nodes", Value::Null).0, 401);
+    gw.token = Some("wrong".into());
+    assert_eq!(gw.request("GET", "/nodes", Value::Null).0, 401);
+
+    // 配对码错误次数过多后进入冷却，重新请求配对码也不能解除
+    assert_eq!(gw.request("POST", "/pair", Value::Null).0, 202);
+    for _ in 0..3 {
+        let (status, _) = gw.request("POST", "/pair", json!({"code": "000000"}));
+        assert_eq!(status, 403);
+    }
+    let (status, _) = gw.request("POST", "/pair", json!({"code": PAIRING_CODE}));
+    assert_eq!(status, 429);
+    assert_eq!(gw.request("POST", "/pair", Value::Null).0, 429);
+
+    // 令牌保存在存储中，重启后仍然有效
+    gw.auth = GatewayAuth::new(random);
+    gw.token = token;
+    assert_eq!(gw.request("GET", "/nodes", Value::Null).0, 200);
+}
+
+#[test]
+fn pairing_requests_are_rate_limited() {
+    let gw = Gateway {
+        ctx: Rc::new(MemoryStorage::default()),
+        auth: GatewayAuth::new(random),
+        token: None,
+    };
+    for _ in 0..3 {
+        assert_eq!(gw.request("POST", "/pair", Value::Null).0, 202);
+    }
+    assert_eq!(gw.request("POST", "/pair", Value::Null).0, 429);
+    // 已显示的配对码仍然有效
+    let (status, _) = gw.request("POST", "/pair", json!({"code": PAIRING_CODE}));
+    assert_eq!(status, 200);
+}
+
+#[test]
+fn remote_access_policy() {
+    let gw = Gateway::new();
+    let read_token = Message::Storage(StorageMessage::ListKeysRequest("gateway/auth/".into()));
+    let (_, keys) = gw.request("POST", "/nodes/Storage?sync=true", json!(read_token));
+    let key = keys["Storage"]["ListKeysResponse"][0].as_str().unwrap();
+    let fetch = Message::Http(HttpMessage::Request(HttpRequest::get("http://example.com")));
+    let cases = [
+        ("/nodes/Storage", json!({"Storage": {"GetRequest": key}})),
+        (
+            "/nodes/Storage",
+            json!({"Storage": {"DeletePrefixRequest": ""}}),
+        ),
+        ("/nodes/HttpClient", json!(fetch)),
+        // 应答与不属于目标节点的消息族
+        ("/nodes/Storage", json!({"Storage": "SetResponse"})),
+        ("/nodes/Storage", json!(fetch)),
+        ("/topics/Scheduler", json!("Empty")),
+        (
+            "/topics/WiFi",
+            json!({"Storage": {"DeletePrefixRequest": ""}}),
+        ),
+        ("/broadcast", json!({"Lifecycle": "Init"})),
+        // 全局广播同样会交给Storage与HttpClient
+        (
+            "/broadcast",
+            json!({"Storage": {"DeletePrefixRequest": ""}}),
+        ),
+        ("/broadcast", json!(fetch)),
+    ];
+    for (path, body) in cases {
+        let (status, body) = gw.request("POST", path, body);
+        assert_eq!(status, 403, "{path}: {body}");
+    }
+
+    let events = GatewayEvents::default();
+    let frames = open(&events, 1, false);
+    gw.frame(&events, 1, json!({"WatchBroadcast": true}));
+    gw.frame(&events, 1, json!({"Auth": gw.token}));
+    let publish = json!({"Publish": {"to": "Broadcast", "body": {"Lifecycle": "Init"}}});
+    gw.frame(&events, 1, publish);
+    let wipe = json!({"Storage": {"DeletePrefixRequest": ""}});
+    gw.frame(
+        &events,
+        1,
+        json!({"Publish": {"to": "Broadcast", "body": wipe}}),
+    );
+    let frames = frames.borrow();
+    assert_eq!(frames.len(), 3);
+    assert!(frames[0]["Unauthorized"].is_string());
+    assert!(frames[1..].iter().all(|x| x["Error"]["error"].is_string()));
+}
+
+/// 内存中的代理连接，帧直接交给远程网关的GatewayEvents
+#[derive(Default, Clone)]
+struct Loopback {
+    sent: Rc<RefCell<Vec<String>>>,
+    events: Rc<RefCell<VecDeque<ProxyEvent>>>,
+}
+
+impl ProxyTransport for Loopback {
+    fn send(&self, text: String) {
+        self.sent.borrow_mut().push(text);
+    }
+
+    fn poll(&self) -> Option<ProxyEvent> {
+        self.events.borrow_mut().pop_front()
+    }
+}
+
+struct Remote {
+    gw: Gateway,
+    events: GatewayEvents,
+    link: Loopback,
+    local: Rc<MemoryStorage>,
+    proxy: RemoteNodeProxy,
+}
+
+impl Remote {
+    /// 把远程网关的Storage代理为本地的Kitchen节点
+    fn new() -> Self {
+        Self::with_token(None)
+    }
+
+    /// token不为空时代理使用该令牌代替配对得到的令牌
+    fn with_token(token: Option<&str>) -> Self {
+        let gw = Gateway::new();
+        let set = Message::Storage(StorageMessage::SetRequest(
+            "wifi/ssid".into(),
+            StorageValue::String("home".into()),
+        ));
+        gw.request("POST", "/nodes/Storage", json!(set));
+        let link = Loopback::default();
+        let events = GatewayEvents::default();
+        events.open(
+            1,
+            Box::new({
+                let link = link.clone();
+                move |x| {
+                    link.events.borrow_mut().push_back(ProxyEvent::Frame(x));
+                    true
+                }
+            }),
+        );
+        let proxy = RemoteNodeProxy::new(
+            RemoteNodeConfig {
+                name: "Kitchen".into(),
+                url: "ws://kitchen/ws".into(),
+                token: token.map(Into::into).or(gw.token.clone()).unwrap(),
+                remote: NodeName::Storage,
+                subscribe: vec![TopicName::OneButton],
+                publish: vec![TopicName::WiFi],
+            },
+            Box::new(link.clone()),
+        );
+        link.events.borrow_mut().push_back(ProxyEvent::Connected);
+        let remote = Self {
+            gw,
+            events,
+            link,
+            local: Default::default(),
+            proxy,
+        };
+        remote.pump();
+        remote
+    }
+
+    fn send(&self, to: MessageTo, seq: usize, body: Message) -> HandleResult {
+        self.proxy.handle_message(
+            self.local.clone(),
+            MessageWithHeader {
+                from: NodeName::WiFi,
+                to,
+                seq,
+                body,
+            },
+        )
+    }
+
+    /// 交换双方的帧直到没有新的帧
+    fn pump(&self) {
+        loop {
+            self.send(MessageTo::Topic(TopicName::Scheduler), 0, Message::Empty);
+            let sent: Vec<_> = self.link.sent.borrow_mut().drain(..).collect();
+            if sent.is_empty() && self.link.events.borrow().is_empty() {
+                break;
+            }
+            for x in sent {
+                self.events
+                    .on_frame(self.gw.ctx.clone(), &self.gw.auth, 1, &x);
+            }
+        }
+    }
+}
+
+#[test]
+fn proxy_forwards_point_messages() {
+    let remote = Remote::new();
+    let get = Message::Storage(StorageMessage::GetRequest("wifi/ssid".into()));
+    let to = MessageTo::Point(NodeName::Other("Kitchen".into()));
+    assert!(matches!(remote.send(to, 5, get), HandleResult::Pending));
+    remote.pump();
+    let ready = remote.local.ready.borrow();
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].0, 5);
+    assert_eq!(
+        json!(ready[0].1),
+        json!({"Storage": {"GetResponse": {"String": "home"}}})
+    );
+}
+
+#[test]
+fn proxy_mirrors_topics() {
+    let remote = Remote::new();
+    let connected = Message::WiFi(WiFiMessage::ConnectedBroadcast);
+    // 只转发远程节点本身发布的消息
+    for from in [NodeName::Storage, NodeName::WiFi] {
+        remote.events.on_message(&MessageWithHeader {
+            from,
+            to: MessageTo::Topic(TopicName::WiFi),
+            seq: 0,
+            body: connected.clone(),
+        });
+    }
+    remote.pump();
+    let published = remote.local.published.borrow();
+    assert_eq!(published.len(), 1);
+    assert_eq!(published[0].0, Some(TopicName::WiFi));
+
+    // 本地话题转发给远程节点，代理自身广播的消息不回传
+    let topic = MessageTo::Topic(TopicName::OneButton);
+    remote.send(topic.clone(), 0, Message::Empty);
+    remote.proxy.handle_message(
+        remote.local.clone(),
+        MessageWithHeader {
+            from: NodeName::Other("Kitchen".into()),
+            to: topic,
+            seq: 0,
+            body: Message::Empty,
+        },
+    );
+    let sent = remote.link.sent.borrow();
+    assert_eq!(sent.len(), 1);
+    let frame: Value = serde_json::from_str(&sent[0]).unwrap();
+    assert_eq!(frame["Request"]["to"], json!("Storage"));
+}
+
+#[test]
+fn proxy_disconnect_resolves_pending() {
+    let remote = Remote::new();
+    let to = MessageTo::Point(NodeName::Other("Kitchen".into()));
+    remote.send(to.clone(), 3, Message::Empty);
+    remote
+        .link
+        .events
+        .borrow_mut()
+        .push_back(ProxyEvent::Disconnected);
+    remote.send(MessageTo::Topic(TopicName::Scheduler), 0, Message::Empty);
+    assert_eq!(
+        json!(remote.local.ready.borrow().as_slice()),
+        json!([[3, "Empty"]])
+    );
+    assert!(matches!(
+        remote.send(to, 4, Message::Empty),
+        HandleResult::Discard
+    ));
+}
+
+#[test]
+fn unauthorized_request_is_answered() {
+    let gw = Gateway::new();
+    let events = GatewayEvents::default();
+    let frames = open(&events, 1, false);
+    gw.frame(&events, 1, json!({"Auth": "revoked"}));
+    let get = json!({"Storage": {"GetRequest": "wifi/ssid"}});
+    gw.frame(
+        &events,
+        1,
+        json!({"Request": {"id": 7, "to": "Storage", "body": get}}),
+    );
+    let frames = frames.borrow();
+    assert_eq!(frames.len(), 2);
+    assert!(frames[0]["Unauthorized"].is_string());
+    assert_eq!(frames[1]["Error"]["id"], json!(7));
+}
+
+#[test]
+fn proxy_relays_discard() {
+    let remote = Remote::new();
+    let to = MessageTo::Point(NodeName::Other("Kitchen".into()));
+    // 远程的Storage不处理Empty
+    assert!(matches!(
+        remote.send(to, 6, Message::Empty),
+        HandleResult::Pending
+    ));
+    remote.pump();
+    assert!(remote.local.ready.borrow().is_empty());
+    assert_eq!(remote.local.discarded.borrow().as_slice(), [6]);
+}
+
+#[test]
+fn proxy_auth_failure_resolves_pending() {
+    let remote = Remote::with_token(Some("revoked"));
+    let to = MessageTo::Point(NodeName::Other("Kitchen".into()));
+    // 认证失败后视为断开
+    assert!(matches!(
+        remote.send(to.clone(), 1, Message::Empty),
+        HandleResult::Discard
+    ));
+
+    // 重连后认证结果返回前发出的请求
+    remote
+        .link
+        .events
+        .borrow_mut()
+        .push_back(ProxyEvent::Connected);
+    remote.send(MessageTo::Topic(TopicName::Scheduler), 0, Message::Empty);
+    let get = Message::Storage(StorageMessage::GetRequest("wifi/ssid".into()));
+    assert!(matches!(remote.send(to, 8, get), HandleResult::Pending));
+    remote.pump();
+    assert_eq!(
+        json!(remote.local.ready.borrow().as_slice()),
+        json!([[8, "Empty"]])
+    );
+}
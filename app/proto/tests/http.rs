@@ -0,0 +1,218 @@
+mod common;
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use common::MemoryStorage;
+use proto::{
+    ipc::StorageClient, storage::MusicStorage, Bytes, ConnectivityState, ConnectivityTracker,
+    HandleResult, HttpBody, HttpError, HttpMessage, HttpRequest, HttpResponse, HttpRetryPolicy,
+    Message, MidiMessage,
+};
+
+#[test]
+fn query_is_percent_encoded() {
+    let req = HttpRequest::get("https://example.com/geo")
+        .query("location", "北京 朝阳")
+        .query("number", "5");
+    assert_eq!(
+        req.url,
+        "https://example.com/geo?location=%E5%8C%97%E4%BA%AC%20%E6%9C%9D%E9%98%B3&number=5"
+    );
+}
+
+#[test]
+fn non_2xx_is_status_error() {
+    let resp = |status| HttpResponse {
+        status,
+        headers: vec![("Content-Type".into(), "application/json".into())],
+        body: HttpBody::Bytes(Bytes(b"{}".to_vec())),
+    };
+    assert_eq!(resp(204).header("content-type"), Some("application/json"));
+    assert!(resp(204).error_for_status(|_| unreachable!()).is_ok());
+    match resp(401).error_for_status(|_| unreachable!()) {
+        Err(HttpError::Status { status, body }) => {
+            assert_eq!(status, 401);
+            assert_eq!(body.0, b"{}");
+        }
+        r => panic!("unexpected {r:?}"),
+    }
+
+    // 流式响应出错时关闭句柄
+    let mut closed = None;
+    let r = HttpResponse {
+        status: 500,
+        headers: Vec::new(),
+        body: HttpBody::Stream(3),
+    }
+    .error_for_status(|h| closed = Some(h));
+    assert!(matches!(r, Err(HttpError::Status { status: 500, .. })));
+    assert_eq!(closed, Some(3));
+}
+
+#[test]
+fn music_download_pulls_stream_by_chunks() {
+    let data = (0..10000).map(|x| x as u8).collect::<Vec<_>>();
+    let ctx = Rc::new(MemoryStorage::default());
+    let reads = Rc::new(RefCell::new(Vec::new()));
+    let closed = Rc::new(RefCell::new(false));
+    *ctx.http.borrow_mut() = Some(Box::new({
+        let data = data.clone();
+        let reads = reads.clone();
+        let closed = closed.clone();
+        let mut offset = 0;
+        move |m| match m {
+            HttpMessage::Request(req) => {
+                assert!(req.stream);
+                HttpMessage::Response(HttpResponse {
+                    status: 200,
+                    headers: Vec::new(),
+                    body: HttpBody::Stream(7),
+                })
+            }
+            HttpMessage::ReadChunkRequest(7, len) => {
+                let end = (offset + len).min(data.len());
+                let chunk = data[offset..end].to_vec();
+                offset = end;
+                reads.borrow_mut().push(chunk.len());
+                HttpMessage::ReadChunkResponse((!chunk.is_empty()).then_some(Bytes(chunk)))
+            }
+            HttpMessage::CloseStreamRequest(7) => {
+                *closed.borrow_mut() = true;
+                HttpMessage::CloseStreamResponse
+            }
+            m => panic!("unexpected message {m:?}"),
+        }
+    }));
+
+    let music = MusicStorage(StorageClient(ctx.clone()));
+    let result = Rc::new(RefCell::new(None));
+    music.download(
+        "song.mid".into(),
+        "https://example.com/song.mid".into(),
+        Box::new({
+            let result = result.clone();
+            move |r| *result.borrow_mut() = Some(r)
+        }),
+    );
+
+    assert!(matches!(*result.borrow(), Some(Ok(()))));
+    assert!(*closed.borrow());
+    // 每次只拉取一个分块
+    assert!(reads.borrow().len() > 1);
+    assert!(reads.borrow().iter().all(|x| *x <= 4096));
+    assert_eq!(music.get_list(), vec!["song.mid".to_string()]);
+    assert_eq!(music.get_data("song.mid".into()), data);
+}
+
+#[test]
+fn music_download_keeps_list_on_http_error() {
+    let ctx = Rc::new(MemoryStorage::default());
+    *ctx.http.borrow_mut() = Some(Box::new(|m| match m {
+        HttpMessage::Request(_) => HttpMessage::Error(HttpError::Status {
+            status: 404,
+            body: Bytes(Vec::new()),
+        }),
+        m => panic!("unexpected message {m:?}"),
+    }));
+    let music = MusicStorage(StorageClient(ctx.clone()));
+    let result = Rc::new(RefCell::new(None));
+    music.download(
+        "song.mid".into(),
+        "https://example.com/song.mid".into(),
+        Box::new({
+            let result = result.clone();
+            move |r| *result.borrow_mut() = Some(r)
+        }),
+    );
+    assert!(matches!(
+        *result.borrow(),
+        Some(Err(HttpError::Status { status: 404, .. }))
+    ));
+    assert!(music.get_list().is_empty());
+}
+
+#[test]
+fn music_download_request_replies_async() {
+    let ctx = Rc::new(MemoryStorage::default());
+    *ctx.http.borrow_mut() = Some(Box::new(|m| match m {
+        HttpMessage::Request(_) => HttpMessage::Response(HttpResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: HttpBody::Bytes(Bytes(b"MThd".to_vec())),
+        }),
+        m => panic!("unexpected message {m:?}"),
+    }));
+    let music = MusicStorage(StorageClient(ctx.clone()));
+    let r = music.handle_download(5, "song.mid".into(), "https://example.com/song.mid".into());
+    assert!(matches!(r, HandleResult::Pending));
+    assert!(matches!(
+        ctx.ready.borrow().as_slice(),
+        [(5, Message::Midi(MidiMessage::DownloadResponse))]
+    ));
+    assert_eq!(music.get_data("song.mid".into()), b"MThd");
+}
+
+#[test]
+fn retry_backs_off_exponentially() {
+    let policy = HttpRetryPolicy {
+        max_retries: 3,
+        ..Default::default()
+    };
+    assert_eq!(policy.backoff(0), Duration::from_millis(500));
+    assert_eq!(policy.backoff(2), Duration::from_millis(2000));
+    assert_eq!(policy.backoff(10), Duration::from_millis(30_000));
+
+    let attempts = RefCell::new(0);
+    let mut slept = Vec::new();
+    let r = policy.run(
+        || {
+            *attempts.borrow_mut() += 1;
+            Err::<(), _>(HttpError::Timeout)
+        },
+        |d| slept.push(d.as_millis()),
+    );
+    assert!(matches!(r, Err(HttpError::Timeout)));
+    assert_eq!(*attempts.borrow(), 4);
+    assert_eq!(slept, vec![500, 1000, 2000]);
+
+    // 客户端错误不重试
+    *attempts.borrow_mut() = 0;
+    let r = policy.run(
+        || {
+            *attempts.borrow_mut() += 1;
+            Err::<(), _>(HttpError::Status {
+                status: 404,
+                body: Bytes(vec![]),
+            })
+        },
+        |_| panic!("should not sleep"),
+    );
+    assert!(r.is_err());
+    assert_eq!(*attempts.borrow(), 1);
+}
+
+#[test]
+fn connectivity_goes_offline_after_consecutive_failures() {
+    let mut tracker = ConnectivityTracker::default();
+    let timeout = HttpError::Timeout;
+    assert_eq!(tracker.record(Some(&timeout)), None);
+    assert_eq!(tracker.record(Some(&timeout)), None);
+    assert_eq!(
+        tracker.record(Some(&timeout)),
+        Some(ConnectivityState::Offline)
+    );
+    assert_eq!(tracker.record(Some(&timeout)), None);
+    assert_eq!(tracker.state(), ConnectivityState::Offline);
+
+    // 服务端返回错误状态码也说明网络已恢复
+    let status = Message::Http(HttpMessage::Error(HttpError::Status {
+        status: 500,
+        body: Bytes(vec![]),
+    }));
+    assert_eq!(
+        tracker.record_message(&status),
+        Some(ConnectivityState::Online)
+    );
+    assert_eq!(tracker.record(Some(&timeout)), None);
+    assert_eq!(tracker.state(), ConnectivityState::Online);
+}
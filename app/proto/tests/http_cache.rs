@@ -0,0 +1,158 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::MemoryStorage;
+use proto::{
+    ipc::StorageClient,
+    storage::{HttpCache, HttpCacheAction, HttpCacheLayer, HTTP_CACHE_HEADER},
+    Bytes, HttpBody, HttpError, HttpMessage, HttpRequest, HttpResponse, Message,
+};
+
+const URL: &str = "https://example.com/weather";
+
+fn response(cache_control: &str) -> Message {
+    Message::Http(HttpMessage::Response(HttpResponse {
+        status: 200,
+        headers: vec![
+            ("cache-control".into(), cache_control.into()),
+            ("etag".into(), "\"v1\"".into()),
+        ],
+        body: HttpBody::Bytes(Bytes(b"sunny".to_vec())),
+    }))
+}
+
+fn error(e: HttpError) -> Message {
+    Message::Http(HttpMessage::Error(e))
+}
+
+fn dispatch(layer: &HttpCacheLayer, stg: &StorageClient, seq: usize) -> HttpRequest {
+    match layer.on_request(stg, seq, HttpRequest::get(URL).cached()) {
+        HttpCacheAction::Dispatch(x) => x,
+        _ => panic!("request should be dispatched"),
+    }
+}
+
+fn cache_kind(msg: &Message) -> Option<&str> {
+    match msg {
+        Message::Http(HttpMessage::Response(x)) => x.header(HTTP_CACHE_HEADER),
+        m => panic!("unexpected message {m:?}"),
+    }
+}
+
+#[test]
+fn fresh_entry_is_served_without_request() {
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    let layer = HttpCacheLayer::default();
+    dispatch(&layer, &stg, 1);
+    layer.on_response(&stg, 1, response("public, max-age=600"));
+
+    match layer.on_request(&stg, 2, HttpRequest::get(URL).cached()) {
+        HttpCacheAction::Respond(x) => assert_eq!(cache_kind(&x), Some("hit")),
+        _ => panic!("fresh entry should be served from cache"),
+    }
+}
+
+#[test]
+fn stale_entry_is_revalidated() {
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    let layer = HttpCacheLayer::default();
+    dispatch(&layer, &stg, 1);
+    layer.on_response(&stg, 1, response("no-cache"));
+
+    let req = dispatch(&layer, &stg, 2);
+    assert!(req
+        .headers
+        .contains(&("If-None-Match".into(), "\"v1\"".into())));
+    let not_modified = error(HttpError::Status {
+        status: 304,
+        body: Bytes(vec![]),
+    });
+    let ret = layer.on_response(&stg, 2, not_modified);
+    assert_eq!(cache_kind(&ret[0].1), Some("revalidated"));
+}
+
+#[test]
+fn stale_entry_is_served_on_error() {
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    let layer = HttpCacheLayer::default();
+    dispatch(&layer, &stg, 1);
+    layer.on_response(&stg, 1, response("max-age=0"));
+
+    dispatch(&layer, &stg, 2);
+    let ret = layer.on_response(&stg, 2, error(HttpError::Timeout));
+    assert_eq!(cache_kind(&ret[0].1), Some("stale"));
+
+    // 客户端错误不使用过期缓存
+    dispatch(&layer, &stg, 3);
+    let not_found = error(HttpError::Status {
+        status: 404,
+        body: Bytes(vec![]),
+    });
+    let ret = layer.on_response(&stg, 3, not_found);
+    assert!(matches!(ret[0].1, Message::Http(HttpMessage::Error(_))));
+}
+
+#[test]
+fn no_store_is_not_cached() {
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    let layer = HttpCacheLayer::default();
+    dispatch(&layer, &stg, 1);
+    layer.on_response(&stg, 1, response("no-store"));
+    assert!(HttpCache(stg).get(URL).unwrap().is_none());
+}
+
+#[test]
+fn identical_requests_are_coalesced() {
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    let layer = HttpCacheLayer::default();
+    dispatch(&layer, &stg, 1);
+    for seq in [2, 3] {
+        assert!(matches!(
+            layer.on_request(&stg, seq, HttpRequest::get(URL)),
+            HttpCacheAction::Wait
+        ));
+    }
+    // 不同的URL不合并
+    assert!(matches!(
+        layer.on_request(&stg, 4, HttpRequest::get(format!("{URL}?day=2"))),
+        HttpCacheAction::Dispatch(_)
+    ));
+
+    let ret = layer.on_response(&stg, 1, response("max-age=600"));
+    assert_eq!(
+        ret.iter().map(|(seq, _)| *seq).collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    // 结果返回后新的请求不再等待
+    assert!(matches!(
+        layer.on_request(&stg, 5, HttpRequest::get(URL)),
+        HttpCacheAction::Dispatch(_)
+    ));
+}
+
+#[test]
+fn api_key_is_not_stored() {
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    let layer = HttpCacheLayer::default();
+    let url = format!("{URL}?location=101010100&key=secret");
+    match layer.on_request(&stg, 1, HttpRequest::get(&url).cached()) {
+        HttpCacheAction::Dispatch(_) => {}
+        _ => panic!("request should be dispatched"),
+    }
+    layer.on_response(&stg, 1, response("max-age=600"));
+
+    let keys = stg.list("http_cache/".into()).unwrap();
+    assert_eq!(keys.len(), 1);
+    for k in keys {
+        let value = stg.get(k).unwrap().as_str().unwrap();
+        assert!(!value.contains("key="));
+        assert!(!value.contains("secret"));
+    }
+    // 不同的key命中同一条缓存
+    let entry = HttpCache(stg).get(&format!("{URL}?key=other&location=101010100"));
+    assert_eq!(
+        entry.unwrap().unwrap().url,
+        format!("{URL}?location=101010100")
+    );
+}
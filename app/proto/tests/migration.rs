@@ -0,0 +1,118 @@
+mod common;
+
+use std::{collections::HashSet, rc::Rc};
+
+use common::MemoryStorage;
+use proto::{
+    ipc::StorageClient,
+    storage::{
+        latest_schema_version, BackupArchive, MusicStorage, StorageBackup, StorageMigrator,
+        UserAlarmStorage, WeatherStorage, BROKEN_SUFFIX, MIGRATIONS, SCHEMA_VERSION_KEY,
+    },
+    StorageValue,
+};
+
+fn load_fixture(name: &str) -> StorageClient {
+    let path = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+    let archive: BackupArchive =
+        serde_json::from_reader(std::fs::File::open(path).unwrap()).unwrap();
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    StorageBackup(stg.clone()).restore(archive).unwrap();
+    stg
+}
+
+#[test]
+fn migrations_are_ordered() {
+    for (i, m) in MIGRATIONS.iter().enumerate() {
+        assert_eq!(m.version as usize, i + 1, "{}", m.description);
+    }
+}
+
+#[test]
+fn migrate_v0_layout() {
+    let stg = load_fixture("v0_layout.json");
+    let migrator = StorageMigrator(stg.clone());
+    assert_eq!(migrator.get_version().unwrap(), 0);
+    assert_eq!(migrator.migrate().unwrap(), (0, latest_schema_version()));
+
+    // 闹钟: 去重，删除悬空id，无法解析与不在列表中的数据移到.broken
+    let alarms = UserAlarmStorage(stg.clone());
+    assert_eq!(alarms.get_id_list().unwrap(), vec![1, 2]);
+    assert_eq!(alarms.get(1).unwrap().comment, "起床");
+    assert_eq!(alarms.get(2).unwrap().time, (8, 0));
+    assert!(alarms.get(9).is_err());
+    assert_eq!(
+        stg.list("useralarm/data/".into()).unwrap(),
+        HashSet::from([
+            "useralarm/data/1".to_string(),
+            "useralarm/data/2".to_string(),
+            format!("useralarm/data/7{BROKEN_SUFFIX}"),
+            format!("useralarm/data/9{BROKEN_SUFFIX}"),
+        ]),
+        "orphan and unreadable alarm data should be kept as .broken"
+    );
+    assert_eq!(
+        stg.get("useralarm/data/9.broken".into())
+            .unwrap()
+            .as_str()
+            .as_deref(),
+        Some("{\"time\":[7,0]}")
+    );
+
+    // 音乐: 去重并移除没有数据的条目，数据保持不变
+    let music = MusicStorage(stg.clone());
+    assert_eq!(music.get_list(), vec!["alarm.mid".to_string()]);
+    assert_eq!(&music.get_data("alarm.mid".into())[..4], b"MThd");
+
+    // 其他数据不受影响
+    assert_eq!(
+        stg.get("wifi/ssid".into()).unwrap().as_str().as_deref(),
+        Some("home")
+    );
+
+    // 天气: 单个位置迁移为位置列表，删除不区分位置的旧缓存
+    let weather = WeatherStorage(stg.clone());
+    let locations = weather.get_locations().unwrap();
+    assert_eq!(locations.len(), 1);
+    assert_eq!(locations[0].location_id, 101010100);
+    assert!(matches!(
+        stg.get("weather/location".into()).unwrap(),
+        StorageValue::None
+    ));
+    assert!(stg.list("weather/cache/".into()).unwrap().is_empty());
+}
+
+#[test]
+fn migrate_keeps_unreadable_location_as_broken() {
+    let stg = load_fixture("v0_broken_location.json");
+    StorageMigrator(stg.clone()).migrate().unwrap();
+    assert!(matches!(
+        stg.get("weather/location".into()).unwrap(),
+        StorageValue::None
+    ));
+    assert_eq!(
+        stg.get("weather/location.broken".into())
+            .unwrap()
+            .as_str()
+            .as_deref(),
+        Some("北京")
+    );
+    assert!(WeatherStorage(stg.clone())
+        .get_locations()
+        .unwrap()
+        .is_empty());
+    assert!(stg.get("wifi/ssid".into()).unwrap().as_str().is_some());
+}
+
+#[test]
+fn migrate_is_idempotent() {
+    let stg = load_fixture("v0_layout.json");
+    let migrator = StorageMigrator(stg.clone());
+    migrator.migrate().unwrap();
+    let latest = latest_schema_version();
+    assert_eq!(migrator.migrate().unwrap(), (latest, latest));
+    assert_eq!(
+        stg.get(SCHEMA_VERSION_KEY.into()).unwrap().as_str(),
+        Some(latest.to_string())
+    );
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::MemoryStorage;
+use proto::{ipc::StorageClient, storage::MusicStorage};
+
+fn chunk(typ: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut ret = typ.to_vec();
+    ret.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    ret.extend_from_slice(data);
+    ret
+}
+
+#[test]
+fn read_first_track_keeps_header_and_first_track() {
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    // 第一个音轨跨越多个分块
+    let track0 = (0..10000).map(|x| x as u8).collect::<Vec<_>>();
+    let mut data = chunk(b"MThd", &[0, 1, 0, 2, 1, 224]);
+    data.extend(chunk(b"XFIH", &[1, 2, 3]));
+    data.extend(chunk(b"MTrk", &track0));
+    data.extend(chunk(b"MTrk", &[4, 5, 6]));
+    let ms = MusicStorage(stg);
+    ms.upload("song.mid".into(), data);
+
+    let mut expected = chunk(b"MThd", &[0, 1, 0, 1, 1, 224]);
+    expected.extend(chunk(b"MTrk", &track0));
+    assert_eq!(ms.read_first_track("song.mid".into()).unwrap(), expected);
+
+    ms.upload("bad.mid".into(), b"not a midi".to_vec());
+    assert!(ms.read_first_track("bad.mid".into()).is_err());
+}
+
+#[test]
+fn aborted_blob_keeps_old_value() {
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    let ms = MusicStorage(stg.clone());
+    ms.upload("song.mid".into(), vec![1, 2, 3]);
+
+    let handle = stg.blob_open("music/data/song.mid".into()).unwrap();
+    stg.blob_write(handle, vec![4, 5, 6, 7]).unwrap();
+    stg.blob_abort(handle).unwrap();
+    assert_eq!(ms.get_data("song.mid".into()), vec![1, 2, 3]);
+}
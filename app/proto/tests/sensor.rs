@@ -0,0 +1,26 @@
+use std::time::Duration;
+
+use proto::{sensor::SensorHistory, SensorReading};
+use time::OffsetDateTime;
+
+fn reading(minutes: i64, temp: f32) -> SensorReading {
+    SensorReading {
+        time: OffsetDateTime::UNIX_EPOCH + time::Duration::minutes(minutes),
+        temp,
+        humidity: 50.0,
+    }
+}
+
+#[test]
+fn history_samples_by_interval_and_wraps() {
+    let mut history = SensorHistory::new(3, Duration::from_secs(60 * 10));
+    assert!(history.sample(reading(0, 20.0)));
+    // 未到采样间隔
+    assert!(!history.sample(reading(5, 21.0)));
+    assert!(history.sample(reading(10, 22.0)));
+    assert!(history.sample(reading(20, 23.0)));
+    assert!(history.sample(reading(30, 24.0)));
+    assert_eq!(history.len(), 3);
+    let temps: Vec<_> = history.to_vec().iter().map(|x| x.temp).collect();
+    assert_eq!(temps, vec![22.0, 23.0, 24.0]);
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use std::rc::Rc;
+
+use common::MemoryStorage;
+use proto::{
+    ipc::StorageClient, storage::WeatherStorage, Coord, Location, NodeName, NowAirQuality,
+    SecretError, StorageValue, WeatherError, WeatherProviderConfig,
+};
+
+#[test]
+fn switch_provider_clears_cache() {
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    let weather = WeatherStorage(stg.clone());
+    assert_eq!(
+        weather.get_provider().unwrap(),
+        WeatherProviderConfig::QWeather
+    );
+
+    weather
+        .set_location(Location {
+            location_id: 2950159,
+            location: "Berlin".into(),
+            coord: None,
+        })
+        .unwrap();
+    stg.set(
+        WeatherStorage::cache_key(2950159, "now_weather"),
+        StorageValue::String("{}".into()),
+    )
+    .unwrap();
+    weather
+        .set_provider(WeatherProviderConfig::Server("http://localhost/api".into()))
+        .unwrap();
+    assert_eq!(
+        weather.get_provider().unwrap(),
+        WeatherProviderConfig::Server("http://localhost/api".into())
+    );
+    let mut keys: Vec<_> = stg.list("weather/".into()).unwrap().into_iter().collect();
+    keys.sort();
+    assert_eq!(keys, ["weather/locations", "weather/provider"]);
+    assert_eq!(weather.get_locations().unwrap().len(), 1);
+
+    // Open-Meteo使用GeoNames id，和风天气的位置需要重新选择
+    weather
+        .set_provider(WeatherProviderConfig::OpenMeteo)
+        .unwrap();
+    assert!(weather.get_locations().unwrap().is_empty());
+}
+
+#[test]
+fn key_errors() {
+    let mem = Rc::new(MemoryStorage::default());
+    let weather = WeatherStorage(StorageClient(mem.clone()));
+    assert!(matches!(weather.get_key(), Err(WeatherError::MissingKey)));
+    weather.set_key("abc".into()).unwrap();
+    assert_eq!(weather.get_key().unwrap(), "abc");
+
+    // 无法解密视为未设置，其他错误原样返回
+    *mem.secret_error.borrow_mut() = Some(SecretError::CryptoError("aead::Error".into()));
+    assert!(matches!(weather.get_key(), Err(WeatherError::MissingKey)));
+    *mem.secret_error.borrow_mut() = Some(SecretError::PermissionDenied(NodeName::Weather));
+    assert!(matches!(
+        weather.get_key(),
+        Err(WeatherError::SecretError(SecretError::PermissionDenied(_)))
+    ));
+}
+
+#[test]
+fn notified_warnings_roundtrip() {
+    let weather = WeatherStorage(StorageClient(Rc::new(MemoryStorage::default())));
+    assert!(weather.get_notified_warnings().unwrap().is_empty());
+    let ids = vec!["10101010020230715150000000".to_string()];
+    weather.set_notified_warnings(&ids).unwrap();
+    assert_eq!(weather.get_notified_warnings().unwrap(), ids);
+}
+
+#[test]
+fn locations_add_move_remove() {
+    let stg = StorageClient(Rc::new(MemoryStorage::default()));
+    let weather = WeatherStorage(stg.clone());
+    let ids = |w: &WeatherStorage| -> Vec<u32> {
+        w.get_locations()
+            .unwrap()
+            .into_iter()
+            .map(|x| x.location_id)
+            .collect()
+    };
+    assert!(ids(&weather).is_empty());
+
+    for (id, name) in [
+        (101010100, "北京"),
+        (101020100, "上海"),
+        (101280101, "广州"),
+    ] {
+        weather
+            .add_location(Location {
+                location_id: id,
+                location: name.into(),
+                coord: None,
+            })
+            .unwrap();
+    }
+    // 重复添加只更新名称
+    weather
+        .add_location(Location {
+            location_id: 101020100,
+            location: "上海市".into(),
+            coord: None,
+        })
+        .unwrap();
+    assert_eq!(ids(&weather), [101010100, 101020100, 101280101]);
+    assert_eq!(weather.get_locations().unwrap()[1].location, "上海市");
+
+    weather.move_location(101280101, 0).unwrap();
+    assert_eq!(ids(&weather), [101280101, 101010100, 101020100]);
+    weather.move_location(101280101, 99).unwrap();
+    assert_eq!(ids(&weather), [101010100, 101020100, 101280101]);
+    // 设置首选位置即移动到第一项
+    weather
+        .set_location(Location {
+            location_id: 101020100,
+            location: "上海".into(),
+            coord: None,
+        })
+        .unwrap();
+    assert_eq!(ids(&weather), [101020100, 101010100, 101280101]);
+
+    // 删除位置时只清除该位置的缓存
+    for id in [101010100, 101020100] {
+        stg.set(
+            WeatherStorage::cache_key(id, "now_weather"),
+            StorageValue::String("{}".into()),
+        )
+        .unwrap();
+    }
+    weather.remove_location(101010100).unwrap();
+    assert_eq!(ids(&weather), [101020100, 101280101]);
+    let keys: Vec<_> = stg
+        .list("weather/cache/".into())
+        .unwrap()
+        .into_iter()
+        .collect();
+    assert_eq!(keys, [WeatherStorage::cache_key(101020100, "now_weather")]);
+    assert!(matches!(
+        weather.remove_location(101010100),
+        Err(WeatherError::MissingLocation)
+    ));
+    assert!(matches!(
+        weather.move_location(101010100, 0),
+        Err(WeatherError::MissingLocation)
+    ));
+}
+
+#[test]
+fn location_coord_is_kept() {
+    let weather = WeatherStorage(StorageClient(Rc::new(MemoryStorage::default())));
+    let beijing = Coord {
+        lat: 39.9,
+        lon: 116.4,
+    };
+    weather
+        .add_location(Location {
+            location_id: 101010100,
+            location: "北京".into(),
+            coord: None,
+        })
+        .unwrap();
+    weather.set_coord(101010100, beijing).unwrap();
+    // 不存在的位置忽略
+    weather.set_coord(101020100, beijing).unwrap();
+    // 更新名称时未提供经纬度，保留已有的
+    weather
+        .add_location(Location {
+            location_id: 101010100,
+            location: "北京市".into(),
+            coord: None,
+        })
+        .unwrap();
+    let locations = weather.get_locations().unwrap();
+    assert_eq!(locations.len(), 1);
+    assert_eq!(locations[0].location, "北京市");
+    assert_eq!(locations[0].coord, Some(beijing));
+}
+
+#[test]
+fn cached_air_quality_without_pollutants() {
+    // 旧版缓存的空气质量没有污染物与健康建议
+    let x: NowAirQuality = serde_json::from_str(
+        r#"{"updated_time":"2024-06-01T08:00:00+08:00","value":46,"category":"Good","color":[0,228,0]}"#,
+    )
+    .unwrap();
+    assert_eq!(x.value, 46);
+    assert!(x.pollutants.is_empty());
+    assert!(x.primary_pollutant.is_none());
+    assert!(x.health.is_none());
+}
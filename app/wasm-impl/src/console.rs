@@ -1,19 +1,31 @@
 use std::sync::{Arc, Mutex, OnceLock};
 
-use app_core::proto::*;
-use log::info;
+use app_core::proto::{
+    gateway::{check_remote_access, AuthError, GatewayAuth},
+    *,
+};
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+/// 与网关相同，需要携带配对得到的令牌
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct ConsoleMessage {
     to: MessageTo,
     body: Message,
+    #[serde(default)]
+    token: Option<String>,
+}
+
+enum ConsoleRequest {
+    Message(ConsoleMessage),
+    /// 没有配对码时开始配对
+    Pair(Option<String>),
 }
 
 struct QueueItem {
-    message: ConsoleMessage,
-    callback: Box<dyn FnOnce(HandleResult) + 'static>,
+    request: ConsoleRequest,
+    callback: Box<dyn FnOnce(String) + 'static>,
 }
 
 unsafe impl Send for QueueItem {}
@@ -26,36 +38,68 @@ fn get_queue() -> Arc<Mutex<Vec<QueueItem>>> {
         .clone()
 }
 
-fn push_queue_item(message: ConsoleMessage, callback: Box<dyn FnOnce(HandleResult)>) {
-    get_queue()
-        .lock()
-        .unwrap()
-        .push(QueueItem { message, callback });
+fn push_queue_item(request: ConsoleRequest, callback: web_sys::js_sys::Function) {
+    get_queue().lock().unwrap().push(QueueItem {
+        request,
+        callback: Box::new(move |s| {
+            info!("js queue ret: {s}");
+            let js_s = JsValue::from_str(&s);
+            let this = JsValue::null();
+            callback.call1(&this, &js_s).unwrap();
+        }),
+    });
 }
 
+/// 回调参数为HandleResult，令牌无效或不允许访问时为`{"error": ...}`
 #[wasm_bindgen::prelude::wasm_bindgen]
 pub fn send_message(message: String, callback: web_sys::js_sys::Function) {
     push_queue_item(
-        serde_json::from_str(&message).unwrap(),
-        Box::new(move |r| {
-            info!("js queue ret: {r:?}");
-            let s = serde_json::to_string(&r).unwrap();
-            let js_s = JsValue::from_str(&s);
-            let this = JsValue::null();
-            callback.call1(&this, &js_s).unwrap();
-        }),
+        ConsoleRequest::Message(serde_json::from_str(&message).unwrap()),
+        callback,
     );
 }
 
+/// 配对码为空时在屏幕上显示配对码，否则回调参数为`{"token": ...}`
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn pair(code: Option<String>, callback: web_sys::js_sys::Function) {
+    push_queue_item(ConsoleRequest::Pair(code), callback);
+}
+
+fn error_json(e: AuthError) -> String {
+    warn!("console request rejected: {e}");
+    serde_json::json!({ "error": e.to_string() }).to_string()
+}
+
+fn random(bs: &mut [u8]) {
+    web_sys::window()
+        .unwrap()
+        .crypto()
+        .and_then(|c| c.get_random_values_with_u8_array(bs))
+        .unwrap();
+}
+
 fn pop_queue_item() -> Option<QueueItem> {
     get_queue().lock().unwrap().pop()
 }
 
-pub struct ConsoleNode {}
+pub struct ConsoleNode {
+    auth: GatewayAuth,
+}
 
 impl ConsoleNode {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            auth: GatewayAuth::new(random),
+        }
+    }
+
+    fn authorize(
+        &self,
+        ctx: std::rc::Rc<dyn Context>,
+        msg: &ConsoleMessage,
+    ) -> Result<(), AuthError> {
+        self.auth.verify(ctx, msg.token.as_deref())?;
+        check_remote_access(&msg.to, &msg.body)
     }
 }
 
@@ -69,20 +113,45 @@ impl Node for ConsoleNode {
         ctx: std::rc::Rc<dyn Context>,
         _msg: MessageWithHeader,
     ) -> HandleResult {
-        if let Some(QueueItem {
-            message: ConsoleMessage { to, body },
-            callback,
-        }) = pop_queue_item()
-        {
-            match to {
-                MessageTo::Broadcast => {
-                    ctx.broadcast_global(body);
-                }
-                MessageTo::Topic(topic) => {
-                    ctx.broadcast_topic(topic, body);
+        let Some(QueueItem { request, callback }) = pop_queue_item() else {
+            return HandleResult::Discard;
+        };
+        match request {
+            ConsoleRequest::Pair(None) => {
+                callback(match self.auth.start_pairing(ctx) {
+                    Ok(_) => "{}".into(),
+                    Err(e) => error_json(e),
+                });
+            }
+            ConsoleRequest::Pair(Some(code)) => {
+                callback(match self.auth.pair(ctx, &code, "web-console") {
+                    Ok(token) => serde_json::json!({ "token": token }).to_string(),
+                    Err(e) => error_json(e),
+                });
+            }
+            ConsoleRequest::Message(msg) => {
+                if let Err(e) = self.authorize(ctx.clone(), &msg) {
+                    callback(error_json(e));
+                    return HandleResult::Discard;
                 }
-                MessageTo::Point(node) => {
-                    ctx.async_call(node, body, callback);
+                let empty =
+                    || serde_json::to_string(&HandleResult::Finish(Message::Empty)).unwrap();
+                match msg.to {
+                    MessageTo::Broadcast => {
+                        ctx.broadcast_global(msg.body);
+                        callback(empty());
+                    }
+                    MessageTo::Topic(topic) => {
+                        ctx.broadcast_topic(topic, msg.body);
+                        callback(empty());
+                    }
+                    MessageTo::Point(node) => {
+                        ctx.async_call(
+                            node,
+                            msg.body,
+                            Box::new(move |r| callback(serde_json::to_string(&r).unwrap())),
+                        );
+                    }
                 }
             }
         }
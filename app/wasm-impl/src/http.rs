@@ -1,22 +1,145 @@
-use std::rc::Rc;
+use std::{cell::RefCell, collections::HashMap, pin::Pin, rc::Rc, time::Duration};
 
 use app_core::proto::{
-    Bytes, Context, HandleResult, HttpBody, HttpError, HttpMessage, HttpRequestMethod,
-    HttpResponse, Message, MessageWithHeader, Node, NodeName,
+    ipc::StorageClient,
+    storage::{HttpCacheAction, HttpCacheLayer},
+    Bytes, ConnectivityTracker, Context, HandleResult, HttpBody, HttpError, HttpMessage,
+    HttpRequest, HttpRequestMethod, HttpResponse, Message, MessageWithHeader, Node, NodeName,
+    TopicName,
 };
+use futures_util::{Stream, StreamExt};
 
 fn convert(method: HttpRequestMethod) -> reqwest::Method {
     use reqwest::Method;
     match method {
         HttpRequestMethod::Get => Method::GET,
+        HttpRequestMethod::Post => Method::POST,
+        HttpRequestMethod::Put => Method::PUT,
+        HttpRequestMethod::Delete => Method::DELETE,
     }
 }
 
-pub struct HttpClient {}
+fn convert_error(e: reqwest::Error) -> HttpError {
+    if e.is_timeout() {
+        HttpError::Timeout
+    } else if e.is_builder() {
+        HttpError::InvalidRequest(e.to_string())
+    } else if e.is_request() {
+        // wasm下fetch失败(网络不可达、CORS等)都表现为请求错误
+        HttpError::Connect(e.to_string())
+    } else if e.is_body() || e.is_decode() {
+        HttpError::Body(e.to_string())
+    } else {
+        HttpError::Other(e.to_string())
+    }
+}
+
+/// 未读完的流式响应体
+struct BodyStream {
+    chunks: Pin<Box<dyn Stream<Item = Result<Vec<u8>, HttpError>>>>,
+    // 上一次拉取到但还未交给消费方的数据
+    buf: Vec<u8>,
+}
+
+async fn read_chunk(
+    streams: Rc<RefCell<HashMap<usize, BodyStream>>>,
+    handle: usize,
+    len: usize,
+) -> Result<Option<Bytes>, HttpError> {
+    // 等待期间不持有借用
+    let mut s = streams
+        .borrow_mut()
+        .remove(&handle)
+        .ok_or_else(|| HttpError::Other(format!("stream {handle} not found")))?;
+    if s.buf.is_empty() {
+        match s.chunks.next().await {
+            Some(chunk) => s.buf = chunk?,
+            None => return Ok(None),
+        }
+    }
+    let rest = s.buf.split_off(len.min(s.buf.len()));
+    let chunk = std::mem::replace(&mut s.buf, rest);
+    streams.borrow_mut().insert(handle, s);
+    Ok(Some(Bytes(chunk)))
+}
+
+async fn execute(
+    streams: Rc<RefCell<HashMap<usize, BodyStream>>>,
+    seq: usize,
+    req: HttpRequest,
+) -> Result<HttpResponse, HttpError> {
+    let stream = req.stream;
+    let client = reqwest::Client::new();
+    let timeout = req.get_timeout();
+    let mut builder = client
+        .request(convert(req.method), req.url)
+        .timeout(timeout);
+    for (k, v) in req.headers.into_iter() {
+        builder = builder.header(k, v);
+    }
+    if let Some(body) = req.body {
+        builder = builder.body(body.0);
+    }
+    let req = builder.build().map_err(convert_error)?;
+    let resp = client.execute(req).await.map_err(convert_error)?;
+    let status = resp.status().as_u16();
+    let headers = resp
+        .headers()
+        .iter()
+        .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into()))
+        .collect();
+    if stream && resp.status().is_success() {
+        let chunks = resp
+            .bytes_stream()
+            .map(|r| r.map(|x| x.to_vec()).map_err(convert_error));
+        streams.borrow_mut().insert(
+            seq,
+            BodyStream {
+                chunks: Box::pin(chunks),
+                buf: Vec::new(),
+            },
+        );
+        return Ok(HttpResponse {
+            status,
+            headers,
+            body: HttpBody::Stream(seq),
+        });
+    }
+    let body = resp.bytes().await.map_err(convert_error)?.to_vec();
+    HttpResponse {
+        status,
+        headers,
+        body: HttpBody::Bytes(Bytes(body)),
+    }
+    .error_for_status(|h| {
+        streams.borrow_mut().remove(&h);
+    })
+}
+
+async fn sleep(d: Duration) {
+    let promise = js_sys::Promise::new(&mut |resolve, _| {
+        web_sys::window()
+            .unwrap()
+            .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, d.as_millis() as i32)
+            .unwrap();
+    });
+    let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+}
+
+pub struct HttpClient {
+    // 流式响应，key为发起请求的消息seq
+    streams: Rc<RefCell<HashMap<usize, BodyStream>>>,
+    connectivity: Rc<RefCell<ConnectivityTracker>>,
+    cache: Rc<HttpCacheLayer>,
+}
 
 impl HttpClient {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            streams: Default::default(),
+            connectivity: Default::default(),
+            cache: Default::default(),
+        }
     }
 }
 
@@ -28,38 +151,69 @@ impl Node for HttpClient {
     fn handle_message(&self, ctx: Rc<dyn Context>, msg: MessageWithHeader) -> HandleResult {
         match msg.body {
             Message::Http(HttpMessage::Request(req)) => {
-                // 否则为新消息
-                let req = req.clone();
+                let req = match self
+                    .cache
+                    .on_request(&StorageClient(ctx.clone()), msg.seq, req)
+                {
+                    HttpCacheAction::Respond(x) => return HandleResult::Finish(x),
+                    HttpCacheAction::Wait => return HandleResult::Pending,
+                    HttpCacheAction::Dispatch(req) => req,
+                };
+                let cache = self.cache.clone();
+                let streams = self.streams.clone();
+                let connectivity = self.connectivity.clone();
+                let seq = msg.seq;
                 wasm_bindgen_futures::spawn_local(async move {
-                    let x = async {
-                        let req = reqwest::Client::new()
-                            .request(convert(req.method.clone()), req.url.clone())
-                            .build()
-                            .map_err(|x| HttpError::Other(x.to_string()))?;
-                        let resp = reqwest::Client::new()
-                            .execute(req)
-                            .await
-                            .map_err(|x| HttpError::Other(x.to_string()))?;
-                        let body = resp
-                            .bytes()
-                            .await
-                            .map_err(|x| HttpError::Other(x.to_string()))?
-                            .to_vec();
-                        Ok(HttpResponse {
-                            body: HttpBody::Bytes(Bytes(body)),
-                        })
+                    let mut attempt = 0;
+                    let x = loop {
+                        match execute(streams.clone(), seq, req.clone()).await {
+                            Err(e) if e.is_retryable() && attempt < req.retry.max_retries => {
+                                sleep(req.retry.backoff(attempt)).await;
+                                attempt += 1;
+                            }
+                            r => break r,
+                        }
+                    };
+                    let ret = Message::Http(match x {
+                        Ok(x) => HttpMessage::Response(x),
+                        Err(e) => HttpMessage::Error(e),
+                    });
+                    if let Some(x) = connectivity.borrow_mut().record_message(&ret) {
+                        ctx.broadcast_topic(
+                            TopicName::Connectivity,
+                            Message::Http(HttpMessage::Connectivity(x)),
+                        );
+                    }
+                    // 合并的请求共享同一结果
+                    for (seq, ret) in cache.on_response(&StorageClient(ctx.clone()), seq, ret) {
+                        ctx.async_ready(seq, ret);
                     }
-                    .await;
+                });
+                return HandleResult::Pending;
+            }
+            Message::Http(HttpMessage::ReadChunkRequest(handle, len)) => {
+                let streams = self.streams.clone();
+                let seq = msg.seq;
+                wasm_bindgen_futures::spawn_local(async move {
+                    let ret = read_chunk(streams, handle, len).await;
                     ctx.async_ready(
-                        msg.seq,
-                        Message::Http(match x {
-                            Ok(x) => HttpMessage::Response(x),
+                        seq,
+                        Message::Http(match ret {
+                            Ok(x) => HttpMessage::ReadChunkResponse(x),
                             Err(e) => HttpMessage::Error(e),
                         }),
                     );
                 });
                 return HandleResult::Pending;
             }
+            Message::Http(HttpMessage::GetConnectivityRequest) => {
+                let state = self.connectivity.borrow().state();
+                return HandleResult::Finish(Message::Http(HttpMessage::Connectivity(state)));
+            }
+            Message::Http(HttpMessage::CloseStreamRequest(handle)) => {
+                self.streams.borrow_mut().remove(&handle);
+                return HandleResult::Finish(Message::Http(HttpMessage::CloseStreamResponse));
+            }
             _ => {}
         }
         HandleResult::Discard
@@ -1,4 +1,4 @@
-use app_core::{get_app_window, get_scheduler};
+use app_core::{get_app_window, get_scheduler, MockSensorService, SecretService};
 use slint::ComponentHandle;
 use std::time::Duration;
 
@@ -13,9 +13,12 @@ pub fn main() {
     let app = get_app_window();
     let sche = get_scheduler();
     sche.register_node(http::HttpClient::new());
-    sche.register_node(storage::LocalStorageService::new());
+    let stg = storage::LocalStorageService::new();
+    sche.register_node(SecretService::new(stg.device_key().unwrap()));
+    sche.register_node(stg);
     sche.register_node(midiplayer::MidiPlayerService::new());
     sche.register_node(console::ConsoleNode::new());
+    sche.register_node(MockSensorService::new());
     let sche_timer = slint::Timer::default();
     sche_timer.start(
         slint::TimerMode::Repeated,
@@ -1,4 +1,7 @@
-use app_core::proto::*;
+use app_core::{
+    proto::{ipc::StorageClient, *},
+    storage::MusicStorage,
+};
 use base64::{prelude::BASE64_STANDARD, Engine};
 
 use wasm_bindgen::prelude::*;
@@ -22,7 +25,7 @@ impl Node for MidiPlayerService {
 
     fn handle_message(
         &self,
-        _ctx: std::rc::Rc<dyn Context>,
+        ctx: std::rc::Rc<dyn Context>,
         msg: MessageWithHeader,
     ) -> HandleResult {
         match msg.body {
@@ -32,6 +35,17 @@ impl Node for MidiPlayerService {
                 loadFile(s);
                 return HandleResult::Finish(Message::Midi(MidiMessage::PlayResponse(false)));
             }
+            // 浏览器播放全部音轨，需要完整的文件
+            Message::Midi(MidiMessage::PlayMusicRequest(filename)) => {
+                let bs = MusicStorage(StorageClient(ctx)).get_data(filename);
+                let mut s = String::new();
+                BASE64_STANDARD.encode_string(bs, &mut s);
+                loadFile(s);
+                return HandleResult::Finish(Message::Midi(MidiMessage::PlayResponse(false)));
+            }
+            Message::Midi(MidiMessage::DownloadRequest { filename, url }) => {
+                return MusicStorage(StorageClient(ctx)).handle_download(msg.seq, filename, url);
+            }
             _ => {}
         }
         HandleResult::Discard
@@ -1,18 +1,55 @@
-use std::{collections::HashSet, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
 
 use app_core::proto::{
-    Context, HandleResult, Message, MessageWithHeader, Node, NodeName, StorageError,
-    StorageMessage, StorageValue,
+    check_storage_quota, Bytes, Context, HandleResult, Message, MessageWithHeader, Node, NodeName,
+    StorageCapacity, StorageError, StorageMessage, StorageUsage, StorageValue,
 };
 
 pub struct LocalStorageService {
     stg: web_sys::Storage,
+    // 未提交的blob写入会话，句柄 -> (key, 已写入内容)
+    blob_sessions: RefCell<HashMap<usize, (String, Vec<u8>)>>,
+    blob_next_handle: RefCell<usize>,
 }
 
 impl LocalStorageService {
     pub fn new() -> Self {
         let stg = web_sys::window().unwrap().local_storage().unwrap().unwrap();
-        Self { stg }
+        Self {
+            stg,
+            blob_sessions: RefCell::new(HashMap::new()),
+            blob_next_handle: RefCell::new(0),
+        }
+    }
+
+    /// 读取localStorage中的设备密钥，不存在时随机生成一个。
+    ///
+    /// 密钥与密文保存在同一个localStorage中，能读取localStorage的脚本或用户可以直接解密，
+    /// 这里的加密只保证secret不会以明文出现在导出的数据中，不提供额外的保护
+    pub fn device_key(&self) -> Result<[u8; 32], StorageError> {
+        if let Some(x) = self.get_raw("device_key")? {
+            let mut v = Vec::new();
+            BASE64_STANDARD
+                .decode_vec(x, &mut v)
+                .map_err(|e| StorageError::TypeError(format!("{e:?}")))?;
+            if let Ok(key) = v.try_into() {
+                return Ok(key);
+            }
+        }
+        let mut key = [0u8; 32];
+        web_sys::window()
+            .unwrap()
+            .crypto()
+            .and_then(|c| c.get_random_values_with_u8_array(&mut key))
+            .map_err(|e| StorageError::Other(format!("{e:?}")))?;
+        self.set_raw("device_key", Some(BASE64_STANDARD.encode(key)))?;
+        Ok(key)
     }
 
     fn get_raw(&self, key: &str) -> Result<Option<String>, StorageError> {
@@ -44,6 +81,9 @@ impl LocalStorageService {
     }
 
     fn set(&self, key: &str, value: StorageValue) -> Result<(), StorageError> {
+        check_storage_quota(key, self.get(key)?.size(), value.size(), |prefix| {
+            self.usage(prefix).map(|x| x.bytes).unwrap_or_default()
+        })?;
         self.set_raw(
             &format!("data/{key}"),
             match value {
@@ -70,6 +110,74 @@ impl LocalStorageService {
             .unwrap_or_default())
     }
 
+    fn usage(&self, prefix: &str) -> Result<StorageUsage, StorageError> {
+        let mut ret = StorageUsage::default();
+        for key in self.list(prefix)?.into_iter() {
+            ret.keys += 1;
+            ret.bytes += self.get(&key)?.size();
+        }
+        Ok(ret)
+    }
+
+    fn delete_prefix(&self, prefix: &str) -> Result<usize, StorageError> {
+        let keys = self.list(prefix)?;
+        for key in keys.iter() {
+            self.set(key, StorageValue::None)?;
+        }
+        Ok(keys.len())
+    }
+
+    fn blob_open(&self, key: String) -> Result<usize, StorageError> {
+        let mut handle = self.blob_next_handle.borrow_mut();
+        *handle += 1;
+        self.blob_sessions
+            .borrow_mut()
+            .insert(*handle, (key, Vec::new()));
+        Ok(*handle)
+    }
+
+    fn blob_write(&self, handle: usize, chunk: Bytes) -> Result<(), StorageError> {
+        match self.blob_sessions.borrow_mut().get_mut(&handle) {
+            Some((_, buf)) => {
+                buf.extend(chunk.0);
+                Ok(())
+            }
+            None => Err(StorageError::Other(format!(
+                "blob session {handle} not found"
+            ))),
+        }
+    }
+
+    fn blob_commit(&self, handle: usize) -> Result<(), StorageError> {
+        let (key, buf) = self
+            .blob_sessions
+            .borrow_mut()
+            .remove(&handle)
+            .ok_or_else(|| StorageError::Other(format!("blob session {handle} not found")))?;
+        self.set(&key, StorageValue::Bytes(Bytes(buf)))
+    }
+
+    fn blob_read(&self, key: &str, offset: usize, len: usize) -> Result<Bytes, StorageError> {
+        match self.get(key)? {
+            StorageValue::Bytes(bs) => {
+                let start = offset.min(bs.0.len());
+                let end = offset.saturating_add(len).min(bs.0.len());
+                Ok(Bytes(bs.0[start..end].to_vec()))
+            }
+            StorageValue::None => Err(StorageError::Other(format!("{key} not found"))),
+            m => Err(StorageError::TypeError(format!(
+                "{key} is not a blob: {m:?}"
+            ))),
+        }
+    }
+
+    fn blob_size(&self, key: &str) -> Result<Option<usize>, StorageError> {
+        Ok(match self.get(key)? {
+            StorageValue::Bytes(bs) => Some(bs.0.len()),
+            _ => None,
+        })
+    }
+
     fn add_list(&self, key: &str) -> Result<(), StorageError> {
         let mut list = self.list("")?;
         list.insert(key.into());
@@ -101,6 +209,38 @@ impl Node for LocalStorageService {
                 StorageMessage::ListKeysRequest(prefix) => {
                     self.list(&prefix).map(StorageMessage::ListKeysResponse)
                 }
+                StorageMessage::BlobOpenRequest(key) => {
+                    self.blob_open(key).map(StorageMessage::BlobOpenResponse)
+                }
+                StorageMessage::BlobWriteRequest(handle, chunk) => self
+                    .blob_write(handle, chunk)
+                    .map(|_| StorageMessage::BlobWriteResponse),
+                StorageMessage::BlobCommitRequest(handle) => self
+                    .blob_commit(handle)
+                    .map(|_| StorageMessage::BlobCommitResponse),
+                StorageMessage::BlobAbortRequest(handle) => {
+                    self.blob_sessions.borrow_mut().remove(&handle);
+                    Ok(StorageMessage::BlobAbortResponse)
+                }
+                StorageMessage::BlobReadRequest(key, offset, len) => self
+                    .blob_read(&key, offset, len)
+                    .map(StorageMessage::BlobReadResponse),
+                StorageMessage::BlobSizeRequest(key) => {
+                    self.blob_size(&key).map(StorageMessage::BlobSizeResponse)
+                }
+                StorageMessage::UsageRequest(prefix) => {
+                    self.usage(&prefix).map(StorageMessage::UsageResponse)
+                }
+                StorageMessage::CapacityRequest => self.usage("").map(|x| {
+                    StorageMessage::CapacityResponse(StorageCapacity {
+                        total: None,
+                        used: x.bytes,
+                        free: None,
+                    })
+                }),
+                StorageMessage::DeletePrefixRequest(prefix) => self
+                    .delete_prefix(&prefix)
+                    .map(StorageMessage::DeletePrefixResponse),
                 m => panic!("unexpected message {:?}", m),
             };
             match resp {
@@ -108,9 +248,7 @@ impl Node for LocalStorageService {
                     return HandleResult::Finish(Message::Storage(v));
                 }
                 Err(e) => {
-                    return HandleResult::Finish(Message::Storage(StorageMessage::Error(
-                        StorageError::Other(format!("{:?}", e)),
-                    )));
+                    return HandleResult::Finish(Message::Storage(StorageMessage::Error(e)));
                 }
             }
         }
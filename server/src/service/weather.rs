@@ -7,7 +7,7 @@ use qweather_http_client::{
 };
 use qweather_service::{CityLookUpInput, GeoApi, LocationInput, Weather};
 use redis::AsyncCommands;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::error::ApiError;
 use client::weather::*;
@@ -18,6 +18,20 @@ pub struct WeatherServiceConfig {
     pub dev_api: bool,
 }
 
+/// 实时天气的缓存key，缓存格式变化时需要更新版本，
+/// v2起附带`obs_time`
+fn now_cache_key(city_id: &str) -> String {
+    format!("weather:now:v2:{city_id}")
+}
+
+/// 实时天气附带观测时间，用于在客户端显示
+#[derive(Debug, Serialize)]
+struct WeatherNowWithObsTime {
+    #[serde(flatten)]
+    now: WeatherNowResponse,
+    obs_time: String,
+}
+
 pub struct WeatherService {
     client: ReqwestHttpAsyncClient,
     redis_cli: redis::Client,
@@ -70,7 +84,7 @@ impl WeatherService {
         ))
     }
 
-    async fn now_remote(&self, city_id: String) -> Result<WeatherNowResponse, ApiError> {
+    async fn now_remote(&self, city_id: String) -> Result<WeatherNowWithObsTime, ApiError> {
         let weather = Weather::new(&self.client);
 
         let ret = weather
@@ -81,11 +95,14 @@ impl WeatherService {
             .await
             .map_err(ApiError::from)?;
         if let Some(data) = ret.now {
-            Ok(WeatherNowResponse {
-                temp: data.temp.take(),
-                humidity: data.humidity.take(),
-                icon: data.icon,
-                text: data.text,
+            Ok(WeatherNowWithObsTime {
+                now: WeatherNowResponse {
+                    temp: data.temp.take(),
+                    humidity: data.humidity.take(),
+                    icon: data.icon,
+                    text: data.text,
+                },
+                obs_time: data.obs_time,
             })
         } else {
             Err(ApiError::NotFound {
@@ -102,21 +119,19 @@ impl WeatherService {
             .get_async_connection()
             .await
             .map_err(ApiError::from)?;
-        let cache: Option<String> = redis_conn
-            .get(&format!("weather:now:{}", &city_id.0))
-            .await
-            .map_err(ApiError::from)?;
+        let key = now_cache_key(&city_id.0);
+        let cache: Option<String> = redis_conn.get(&key).await.map_err(ApiError::from)?;
         if let Some(data) = cache {
-            debug!("cache hit: weather:now:{}", &city_id.0);
+            debug!("cache hit: {key}");
             return Ok(Json(serde_json::from_str(&data).map_err(ApiError::from)?));
         }
 
-        debug!("cache miss: weather:now:{}", &city_id.0);
+        debug!("cache miss: {key}");
         let ret = self.now_remote(city_id.0.clone()).await?;
-        debug!("cache set: weather:now:{} => {:?}", &city_id.0, &ret);
+        debug!("cache set: {key} => {ret:?}");
         redis_conn
             .set_ex(
-                &format!("weather:now:{}", city_id.0),
+                &key,
                 serde_json::to_string(&ret).map_err(ApiError::from)?,
                 3600,
             )
@@ -2,6 +2,34 @@ import { Axios } from 'axios'
 
 const axios = new Axios();
 
+const TOKEN_KEY = 'gateway_token'
+
+export function get_token(): string | null {
+    return localStorage.getItem(TOKEN_KEY)
+}
+
+function auth_headers() {
+    const token = get_token()
+    return token ? { Authorization: `Bearer ${token}` } : {}
+}
+
 export async function send_message(input: any): Promise<any> {
-    await axios.post('/', input)
-}
\ No newline at end of file
+    await axios.post('/', JSON.stringify(input), {
+        headers: { 'Content-Type': 'application/json', ...auth_headers() },
+    })
+}
+
+// 请求设备在屏幕上显示配对码
+export async function request_pairing_code() {
+    await axios.post('/pair', '{}')
+}
+
+// 使用屏幕上的配对码换取令牌并保存
+export async function pair(code: string): Promise<boolean> {
+    const resp = await axios.post('/pair', JSON.stringify({ code, name: 'web-console' }))
+    if (resp.status != 200) {
+        return false
+    }
+    localStorage.setItem(TOKEN_KEY, JSON.parse(resp.data).token)
+    return true
+}
@@ -0,0 +1,66 @@
+// 网关的WebSocket事件流，协议见proto::gateway::GatewayEvents
+
+import { get_token } from './common'
+
+export type ServerFrame =
+    | { Topic: { topic: string, from: any, body: any } }
+    | { Broadcast: { from: any, body: any } }
+    | { Response: { id: number, result: any } }
+    | { Error: { id: number | null, error: string } }
+    | { Unauthorized: string }
+
+export class EventStream {
+    private ws: WebSocket
+    private next_id = 0
+    private pending = new Map<number, { resolve: (result: any) => void, reject: (error: string) => void }>()
+
+    constructor(topics: string[], broadcast: boolean, on_frame: (frame: ServerFrame) => void) {
+        this.ws = new WebSocket(`ws://${location.host}/ws`)
+        this.ws.onopen = () => {
+            this.send({ "Auth": get_token() ?? "" })
+            this.send({ "Subscribe": topics })
+            this.send({ "WatchBroadcast": broadcast })
+        }
+        this.ws.onmessage = (e) => {
+            const frame: ServerFrame = JSON.parse(e.data)
+            if ("Response" in frame) {
+                const cb = this.pending.get(frame.Response.id)
+                this.pending.delete(frame.Response.id)
+                cb?.resolve(frame.Response.result)
+                return
+            }
+            // 被拒绝的请求带有id，需要结束等待
+            if ("Error" in frame && frame.Error.id !== null) {
+                const cb = this.pending.get(frame.Error.id)
+                this.pending.delete(frame.Error.id)
+                cb?.reject(frame.Error.error)
+            }
+            on_frame(frame)
+        }
+    }
+
+    private send(frame: any) {
+        this.ws.send(JSON.stringify(frame))
+    }
+
+    subscribe(topics: string[]) {
+        this.send({ "Subscribe": topics })
+    }
+
+    unsubscribe(topics: string[]) {
+        this.send({ "Unsubscribe": topics })
+    }
+
+    // 向节点发送消息，返回节点的HandleResult
+    request(to: string, body: any, sync = false): Promise<any> {
+        const id = this.next_id++
+        return new Promise((resolve, reject) => {
+            this.pending.set(id, { resolve, reject })
+            this.send({ "Request": { id, to, body, sync } })
+        })
+    }
+
+    close() {
+        this.ws.close()
+    }
+}
@@ -1 +1,4 @@
-export { click, clicks, long_press_holding, long_press_held } from './onebutton'
\ No newline at end of file
+export { click, clicks, long_press_holding, long_press_held } from './onebutton'
+export { get_token, request_pairing_code, pair } from './common'
+export { EventStream } from './events'
+export type { ServerFrame } from './events'
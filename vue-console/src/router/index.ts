@@ -1,6 +1,7 @@
 
 import { createRouter, createWebHistory, RouteRecordRaw } from 'vue-router';
 import Login from '../views/Login.vue';
+import Events from '../views/Events.vue';
  
 const routes: Array<RouteRecordRaw> = [
   {
@@ -8,6 +9,11 @@ const routes: Array<RouteRecordRaw> = [
     name: 'Login',
     component: Login,
   },
+  {
+    path: '/events',
+    name: 'Events',
+    component: Events,
+  },
 ];
  
 const router = createRouter({